@@ -1,9 +1,10 @@
 use crate::data::{Ticket, TicketDraft};
 use crate::store::{TicketId, TicketStore};
-use std::sync::mpsc::{Receiver, Sender};
+use crate::sync::{channel, thread, Receiver, Sender};
 
 pub mod data;
 pub mod store;
+mod sync;
 
 #[derive(Clone)]
 // TODO: flesh out the client implementation.
@@ -18,7 +19,7 @@ impl TicketStoreClient {
 
     // Feel free to panic on all errors, for simplicity.
     pub fn insert(&self, draft: TicketDraft) -> TicketId {
-        let (response_channel, response_receiver) = std::sync::mpsc::channel();
+        let (response_channel, response_receiver) = channel();
 
         let command = Command::Insert {
             draft: draft.clone(),
@@ -35,7 +36,7 @@ impl TicketStoreClient {
     }
 
     pub fn get(&self, id: TicketId) -> Option<Ticket> {
-        let (response_channel, response_receiver) = std::sync::mpsc::channel();
+        let (response_channel, response_receiver) = channel();
         let command = Command::Get {
             id,
             response_channel,
@@ -49,8 +50,8 @@ impl TicketStoreClient {
 }
 
 pub fn launch() -> TicketStoreClient {
-    let (sender, receiver) = std::sync::mpsc::channel();
-    std::thread::spawn(move || server(receiver));
+    let (sender, receiver) = channel();
+    thread::spawn(move || server(receiver));
     TicketStoreClient::new(sender)
 }
 
@@ -0,0 +1,112 @@
+//! The channels and threads the client and the server talk through.
+//!
+//! They're `std`'s, unless the crate is built with `--cfg loom`: then they're
+//! modelled by loom, so `tests/loom.rs` can check every interleaving of the
+//! protocol.
+
+#[cfg(not(loom))]
+pub use std::sync::mpsc::{channel, Receiver, Sender};
+#[cfg(not(loom))]
+pub use std::thread;
+
+#[cfg(loom)]
+pub use loom::thread;
+#[cfg(loom)]
+pub use mpsc::{channel, Receiver, Sender};
+
+/// `std::sync::mpsc` on top of loom's mutex and condition variable.
+///
+/// loom ships its own `mpsc`, but its receivers never notice that every
+/// sender is gone, and that's how the server learns it should shut down.
+#[cfg(loom)]
+mod mpsc {
+    use loom::sync::{Arc, Condvar, Mutex};
+    use std::collections::VecDeque;
+    use std::sync::mpsc::{RecvError, SendError};
+
+    struct State<T> {
+        queue: VecDeque<T>,
+        senders: usize,
+        receiver: bool,
+    }
+
+    struct Channel<T> {
+        state: Mutex<State<T>>,
+        ready: Condvar,
+    }
+
+    pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+        let channel = Arc::new(Channel {
+            state: Mutex::new(State {
+                queue: VecDeque::new(),
+                senders: 1,
+                receiver: true,
+            }),
+            ready: Condvar::new(),
+        });
+        let sender = Sender {
+            channel: channel.clone(),
+        };
+        (sender, Receiver { channel })
+    }
+
+    pub struct Sender<T> {
+        channel: Arc<Channel<T>>,
+    }
+
+    impl<T> Sender<T> {
+        pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+            let mut state = self.channel.state.lock().unwrap();
+            if !state.receiver {
+                return Err(SendError(value));
+            }
+            state.queue.push_back(value);
+            self.channel.ready.notify_one();
+            Ok(())
+        }
+    }
+
+    impl<T> Clone for Sender<T> {
+        fn clone(&self) -> Self {
+            self.channel.state.lock().unwrap().senders += 1;
+            Self {
+                channel: self.channel.clone(),
+            }
+        }
+    }
+
+    impl<T> Drop for Sender<T> {
+        fn drop(&mut self) {
+            let mut state = self.channel.state.lock().unwrap();
+            state.senders -= 1;
+            if state.senders == 0 {
+                self.channel.ready.notify_one();
+            }
+        }
+    }
+
+    pub struct Receiver<T> {
+        channel: Arc<Channel<T>>,
+    }
+
+    impl<T> Receiver<T> {
+        pub fn recv(&self) -> Result<T, RecvError> {
+            let mut state = self.channel.state.lock().unwrap();
+            loop {
+                if let Some(value) = state.queue.pop_front() {
+                    return Ok(value);
+                }
+                if state.senders == 0 {
+                    return Err(RecvError);
+                }
+                state = self.channel.ready.wait(state).unwrap();
+            }
+        }
+    }
+
+    impl<T> Drop for Receiver<T> {
+        fn drop(&mut self) {
+            self.channel.state.lock().unwrap().receiver = false;
+        }
+    }
+}
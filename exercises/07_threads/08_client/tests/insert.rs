@@ -1,3 +1,5 @@
+#![cfg(not(loom))]
+
 use client::data::{Status, TicketDraft};
 use client::launch;
 use ticket_fields::test_helpers::{ticket_description, ticket_title};
@@ -0,0 +1,76 @@
+//! Model-checks the client/server protocol: loom runs each test once for every
+//! way its threads can interleave, and fails on deadlocks and on threads that
+//! never finish.
+//!
+//! These tests only exist when the crate is built for loom:
+//!
+//! ```text
+//! RUSTFLAGS="--cfg loom" cargo test -p client --test loom --release
+//! ```
+//!
+//! Interleavings are bounded to three preemptions per thread, which is where
+//! concurrency bugs almost always show up; set `LOOM_MAX_PREEMPTIONS` to
+//! explore more or fewer.
+#![cfg(loom)]
+
+use client::data::{Status, TicketDraft};
+use client::launch;
+use loom::model::Builder;
+use loom::thread;
+use ticket_fields::test_helpers::{ticket_description, ticket_title};
+
+fn model(f: impl Fn() + Sync + Send + 'static) {
+    let mut builder = Builder::new();
+    builder.preemption_bound.get_or_insert(3);
+    builder.check(f);
+}
+
+fn draft() -> TicketDraft {
+    TicketDraft {
+        title: ticket_title(),
+        description: ticket_description(),
+    }
+}
+
+#[test]
+fn concurrent_inserts_get_distinct_ids() {
+    model(|| {
+        let client = launch();
+        let client2 = client.clone();
+        let handle = thread::spawn(move || client2.insert(draft()));
+        let id1 = client.insert(draft());
+        let id2 = handle.join().unwrap();
+
+        assert_ne!(id1, id2);
+        assert_eq!(client.get(id1).unwrap().id, id1);
+        assert_eq!(client.get(id2).unwrap().id, id2);
+    });
+}
+
+#[test]
+fn inserted_tickets_are_visible_to_other_clients() {
+    model(|| {
+        let client = launch();
+        let client2 = client.clone();
+        let id = client.insert(draft());
+        let handle = thread::spawn(move || client2.get(id));
+        // Racing with the read above.
+        client.insert(draft());
+
+        let ticket = handle.join().unwrap().unwrap();
+        assert_eq!(ticket.id, id);
+        assert_eq!(ticket.status, Status::ToDo);
+    });
+}
+
+#[test]
+fn server_shuts_down_once_every_client_is_gone() {
+    // loom reports the server thread if it's still blocked when the model ends.
+    model(|| {
+        let client = launch();
+        let client2 = client.clone();
+        let handle = thread::spawn(move || drop(client2));
+        drop(client);
+        handle.join().unwrap();
+    });
+}
@@ -1,4 +1,9 @@
 use std::collections::BTreeMap;
+// Under `--cfg loom`, tickets are shared through loom's model of these types,
+// so `tests/loom.rs` can check every interleaving of accesses to them.
+#[cfg(loom)]
+use loom::sync::{Arc, RwLock};
+#[cfg(not(loom))]
 use std::sync::{Arc, RwLock};
 
 use crate::data::{Status, Ticket, TicketDraft};
@@ -1,3 +1,5 @@
+#![cfg(not(loom))]
+
 use std::sync::{Arc, RwLock};
 use std::thread::spawn;
 
@@ -0,0 +1,107 @@
+//! Model-checks the store when it's shared between threads: loom runs each
+//! test once for every way its threads can interleave, and fails on deadlocks
+//! and on threads that never finish.
+//!
+//! These tests only exist when the crate is built for loom:
+//!
+//! ```text
+//! RUSTFLAGS="--cfg loom" cargo test -p without_channels --test loom --release
+//! ```
+//!
+//! Interleavings are bounded to three preemptions per thread, which is where
+//! concurrency bugs almost always show up; set `LOOM_MAX_PREEMPTIONS` to
+//! explore more or fewer.
+#![cfg(loom)]
+
+use loom::model::Builder;
+use loom::sync::{Arc, RwLock};
+use loom::thread;
+use ticket_fields::test_helpers::{ticket_description, ticket_title};
+use ticket_fields::TicketTitle;
+use without_channels::data::{Status, TicketDraft};
+use without_channels::store::{TicketId, TicketStore};
+
+fn model(f: impl Fn() + Sync + Send + 'static) {
+    let mut builder = Builder::new();
+    builder.preemption_bound.get_or_insert(3);
+    builder.check(f);
+}
+
+fn draft() -> TicketDraft {
+    TicketDraft {
+        title: ticket_title(),
+        description: ticket_description(),
+    }
+}
+
+/// A store holding one ticket, and that ticket's id.
+fn store() -> (Arc<RwLock<TicketStore>>, TicketId) {
+    let mut store = TicketStore::new();
+    let id = store.add_ticket(draft());
+    (Arc::new(RwLock::new(store)), id)
+}
+
+/// Updates ticket `id` the way a client would: find it with the store locked
+/// for reading, then lock the ticket alone for writing.
+fn patch(store: &RwLock<TicketStore>, id: TicketId, f: impl FnOnce(&mut Status, &mut TicketTitle)) {
+    let ticket = store.read().unwrap().get(id).unwrap();
+    let mut ticket = ticket.write().unwrap();
+    let ticket = &mut *ticket;
+    f(&mut ticket.status, &mut ticket.title);
+}
+
+#[test]
+fn concurrent_inserts_get_distinct_ids() {
+    model(|| {
+        let store = Arc::new(RwLock::new(TicketStore::new()));
+        let store2 = store.clone();
+        let handle = thread::spawn(move || store2.write().unwrap().add_ticket(draft()));
+        let id1 = store.write().unwrap().add_ticket(draft());
+        let id2 = handle.join().unwrap();
+
+        assert_ne!(id1, id2);
+        let store = store.read().unwrap();
+        assert_eq!(store.get(id1).unwrap().read().unwrap().id, id1);
+        assert_eq!(store.get(id2).unwrap().read().unwrap().id, id2);
+    });
+}
+
+#[test]
+fn concurrent_patches_to_a_ticket_are_not_lost() {
+    model(|| {
+        let (store, id) = store();
+        let store2 = store.clone();
+        let handle = thread::spawn(move || {
+            patch(&store2, id, |status, _| *status = Status::InProgress);
+        });
+        patch(&store, id, |_, title| {
+            *title = TicketTitle::try_from("A new title").unwrap();
+        });
+        handle.join().unwrap();
+
+        let ticket = store.read().unwrap().get(id).unwrap();
+        let ticket = ticket.read().unwrap();
+        assert_eq!(ticket.status, Status::InProgress);
+        assert_eq!(ticket.title, TicketTitle::try_from("A new title").unwrap());
+    });
+}
+
+#[test]
+fn patches_and_inserts_do_not_deadlock() {
+    model(|| {
+        let (store, id) = store();
+        let store2 = store.clone();
+        let handle = thread::spawn(move || {
+            patch(&store2, id, |status, _| *status = Status::Done);
+        });
+        let new_id = store.write().unwrap().add_ticket(draft());
+        handle.join().unwrap();
+
+        let store = store.read().unwrap();
+        assert_eq!(store.get(id).unwrap().read().unwrap().status, Status::Done);
+        assert_eq!(
+            store.get(new_id).unwrap().read().unwrap().status,
+            Status::ToDo
+        );
+    });
+}
@@ -0,0 +1,133 @@
+//! Micro-benchmarks for `TicketStore`, the part of every request that runs
+//! under the server's global lock. Run them before and after changing the
+//! store and compare, on the same machine:
+//!
+//! ```text
+//! cargo bench -p outro_08 --bench store -- --save-baseline before
+//! cargo bench -p outro_08 --bench store -- --baseline before
+//! ```
+//!
+//! `loadgen` measures the whole server instead.
+
+use chrono::Utc;
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use outro_08::auth::{Role, User};
+use outro_08::data::{Status, TicketDraft};
+use outro_08::query::Query;
+use outro_08::reports::Period;
+use outro_08::store::{TicketId, TicketStore};
+use std::hint::black_box;
+
+/// Store sizes each benchmark runs at, to see how operations scale.
+const SIZES: [usize; 3] = [100, 1_000, 10_000];
+
+fn user() -> User {
+    User::new("alice", Role::Maintainer)
+}
+
+fn draft(i: usize) -> TicketDraft {
+    TicketDraft::new(
+        format!("Ticket {}", i),
+        Some(format!("The description of ticket {}", i)),
+    )
+}
+
+/// A store with `size` tickets, a third of them in progress and a third done.
+fn store(size: usize) -> (TicketStore, Vec<TicketId>) {
+    let user = user();
+    let mut store = TicketStore::new();
+    let ids: Vec<TicketId> = (0..size)
+        .map(|i| store.add_ticket_by(draft(i), &user))
+        .collect();
+    for (i, &id) in ids.iter().enumerate() {
+        let status = match i % 3 {
+            0 => continue,
+            1 => Status::InProgress,
+            _ => Status::Done,
+        };
+        move_to(&mut store, id, status, &user);
+    }
+    (store, ids)
+}
+
+/// What `PATCH /tickets/{id}` does to the store.
+fn move_to(store: &mut TicketStore, id: TicketId, status: Status, by: &User) {
+    let ticket = store.get(id).unwrap();
+    let before = ticket.read().unwrap().clone();
+    ticket.write().unwrap().status = status;
+    store.edited(before, by);
+}
+
+fn insert(c: &mut Criterion) {
+    let mut group = c.benchmark_group("insert");
+    let user = user();
+    for size in SIZES {
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, &size| {
+            b.iter_batched_ref(
+                || store(size).0,
+                |store| store.add_ticket_by(draft(size), &user),
+                BatchSize::LargeInput,
+            );
+        });
+    }
+    group.finish();
+}
+
+fn get(c: &mut Criterion) {
+    let mut group = c.benchmark_group("get");
+    for size in SIZES {
+        let (store, ids) = store(size);
+        let mut next = ids.iter().cycle();
+        group.bench_function(BenchmarkId::from_parameter(size), |b| {
+            b.iter(|| {
+                let ticket = store.get(*next.next().unwrap()).unwrap();
+                let ticket = ticket.read().unwrap().clone();
+                black_box(ticket)
+            });
+        });
+    }
+    group.finish();
+}
+
+fn patch(c: &mut Criterion) {
+    let mut group = c.benchmark_group("patch");
+    let user = user();
+    for size in SIZES {
+        let (mut store, ids) = store(size);
+        let mut next = ids.iter().cycle();
+        let mut statuses = [Status::ToDo, Status::InProgress].into_iter().cycle();
+        group.bench_function(BenchmarkId::from_parameter(size), |b| {
+            b.iter(|| {
+                let status = statuses.next().unwrap();
+                move_to(&mut store, *next.next().unwrap(), status, &user);
+            });
+        });
+    }
+    group.finish();
+}
+
+fn query(c: &mut Criterion) {
+    let mut group = c.benchmark_group("query");
+    let query: Query = r#"status:InProgress AND title:~"1""#.parse().unwrap();
+    for size in SIZES {
+        let (store, _) = store(size);
+        group.bench_function(BenchmarkId::from_parameter(size), |b| {
+            b.iter(|| black_box(store.matching(&query)));
+        });
+    }
+    group.finish();
+}
+
+fn summary(c: &mut Criterion) {
+    let mut group = c.benchmark_group("summary");
+    for size in SIZES {
+        let (store, _) = store(size);
+        group.bench_function(BenchmarkId::from_parameter(size), |b| {
+            b.iter(|| black_box(store.summary(Period::default(), Utc::now())));
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, insert, get, patch, query, summary);
+criterion_main!(benches);
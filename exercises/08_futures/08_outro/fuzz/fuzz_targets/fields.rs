@@ -0,0 +1,26 @@
+//! Validates arbitrary text as every kind of ticket field. Validation must
+//! never panic, and whatever it accepts must be accepted again unchanged.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use ticket_fields::{MarkdownDescription, TicketDescription, TicketTitle};
+
+fuzz_target!(|input: &str| {
+    if let Ok(title) = TicketTitle::try_from(input) {
+        assert_eq!(TicketTitle::try_from(title.0.as_str()), Ok(title));
+    }
+    if let Ok(description) = TicketDescription::try_from(input) {
+        assert_eq!(
+            TicketDescription::try_from(description.0.as_str()),
+            Ok(description)
+        );
+    }
+    if let Ok(markdown) = MarkdownDescription::try_from(input) {
+        assert_eq!(
+            MarkdownDescription::try_from(markdown.as_str()).as_ref(),
+            Ok(&markdown)
+        );
+        markdown.to_html();
+        markdown.to_plain_text();
+    }
+});
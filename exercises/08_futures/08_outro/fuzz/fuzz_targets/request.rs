@@ -0,0 +1,63 @@
+//! Feeds arbitrary bytes to the server as one connection: request framing,
+//! routing, authentication and every handler. Whatever the input, the server
+//! must answer with HTTP or hang up, never panic.
+//!
+//! The `alice` token is valid, so inputs can get past authentication;
+//! `request.dict` helps the fuzzer find it.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use outro_08::auth::{Role, TokenStore, User};
+use outro_08::server::{Config, State};
+use outro_08::store::TicketStore;
+use std::sync::OnceLock;
+use tempfile::TempDir;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::runtime::Runtime;
+
+/// Shared across inputs, since starting them is slower than most requests.
+fn runtime() -> &'static Runtime {
+    static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+    RUNTIME.get_or_init(|| {
+        // Paused time, so lingering on refused uploads and idle keep-alive
+        // connections don't slow fuzzing down.
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .start_paused(true)
+            .build()
+            .unwrap()
+    })
+}
+
+fn attachments_dir() -> &'static TempDir {
+    static DIR: OnceLock<TempDir> = OnceLock::new();
+    DIR.get_or_init(|| TempDir::new().unwrap())
+}
+
+fuzz_target!(|input: &[u8]| {
+    runtime().block_on(async {
+        let config = Config {
+            attachments_dir: attachments_dir().path().to_path_buf(),
+            ..Config::default()
+        };
+        let mut tokens = TokenStore::new();
+        tokens.insert("alice".into(), User::new("alice", Role::Maintainer));
+        let state = State::new(&config, TicketStore::new(), tokens);
+
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        let peer = "127.0.0.1:4000".parse().unwrap();
+        let server = tokio::spawn(outro_08::handle_connection(server, peer, state));
+        let (mut reader, mut writer) = tokio::io::split(client);
+        let write = async {
+            let _ = writer.write_all(input).await;
+            let _ = writer.shutdown().await;
+        };
+        let mut output = vec![];
+        let read = reader.read_to_end(&mut output);
+        let _ = tokio::join!(write, read);
+
+        // Errors only mean the connection was dropped.
+        let _ = server.await.expect("The server panicked");
+        assert!(output.is_empty() || output.starts_with(b"HTTP/1.1 "));
+    });
+});
@@ -0,0 +1,186 @@
+//! Files attached to tickets.
+//!
+//! File contents live in a directory, each in a file named after the SHA-256
+//! of its contents, so a file attached twice is only stored once. The store
+//! only keeps each attachment's metadata, see `Attachment`.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io;
+use std::path::{Path, PathBuf};
+use tokio::fs::File;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+
+use crate::store::TicketId;
+
+/// A file attached to a ticket.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Attachment {
+    pub ticket: TicketId,
+    /// Hex SHA-256 of the contents, which identifies the attachment.
+    pub digest: String,
+    /// The file name given on upload.
+    pub name: String,
+    /// Sniffed from the contents, see `sniff`.
+    pub mime: String,
+    pub size: u64,
+    /// Name of the user who uploaded the file.
+    pub uploaded_by: String,
+    pub uploaded_at: DateTime<Utc>,
+}
+
+/// Why an upload was refused or failed.
+#[derive(Debug, thiserror::Error)]
+pub enum AttachmentError {
+    #[error("Attachments can't be larger than {max} bytes, but this one has {size}")]
+    TooLarge { max: u64, size: u64 },
+    #[error("The upload ended after {found} of {expected} bytes")]
+    Truncated { expected: u64, found: u64 },
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// The directory holding attachment contents.
+#[derive(Clone, Debug)]
+pub struct AttachmentDir {
+    path: PathBuf,
+    max_bytes: u64,
+}
+
+/// What `AttachmentDir::write` stored.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Stored {
+    pub digest: String,
+    pub mime: &'static str,
+    pub size: u64,
+}
+
+impl AttachmentDir {
+    pub const DEFAULT_MAX_BYTES: u64 = 10 * 1024 * 1024;
+
+    /// The directory is created on the first upload.
+    pub fn new(path: impl Into<PathBuf>, max_bytes: u64) -> Self {
+        Self {
+            path: path.into(),
+            max_bytes,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn max_bytes(&self) -> u64 {
+        self.max_bytes
+    }
+
+    /// Refuses uploads over the size limit before reading any of them.
+    pub fn check_size(&self, size: u64) -> Result<(), AttachmentError> {
+        if size > self.max_bytes {
+            return Err(AttachmentError::TooLarge {
+                max: self.max_bytes,
+                size,
+            });
+        }
+        Ok(())
+    }
+
+    /// Streams `size` bytes from `contents` into the directory, hashing them on
+    /// the way. Nothing is stored unless all of them arrive.
+    pub async fn write(
+        &self,
+        contents: impl AsyncRead + Unpin,
+        size: u64,
+    ) -> Result<Stored, AttachmentError> {
+        self.check_size(size)?;
+        tokio::fs::create_dir_all(&self.path).await?;
+        // A temporary file in the same directory, so it can be renamed into place.
+        let temp = tempfile::NamedTempFile::new_in(&self.path)?;
+        let mut file = File::from_std(temp.reopen()?);
+
+        let mut contents = contents.take(size);
+        let mut hasher = Sha256::new();
+        let mut head = Vec::with_capacity(SNIFF_BYTES);
+        let mut found = 0;
+        let mut chunk = vec![0; 64 * 1024];
+        loop {
+            let n = contents.read(&mut chunk).await?;
+            if n == 0 {
+                break;
+            }
+            let chunk = &chunk[..n];
+            hasher.update(chunk);
+            let missing = SNIFF_BYTES.saturating_sub(head.len()).min(n);
+            head.extend_from_slice(&chunk[..missing]);
+            file.write_all(chunk).await?;
+            found += n as u64;
+        }
+        if found < size {
+            return Err(AttachmentError::Truncated {
+                expected: size,
+                found,
+            });
+        }
+        file.flush().await?;
+
+        let digest = format!("{:x}", hasher.finalize());
+        temp.persist(self.path.join(&digest)).map_err(|e| e.error)?;
+        Ok(Stored {
+            digest,
+            mime: sniff(&head),
+            size,
+        })
+    }
+
+    /// Opens the contents of an attachment for reading.
+    pub async fn open(&self, digest: &str) -> Result<File, AttachmentError> {
+        // Digests come from URLs, so make sure they can't point elsewhere.
+        if digest.len() != 64 || !digest.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(io::Error::from(io::ErrorKind::NotFound).into());
+        }
+        Ok(File::open(self.path.join(digest)).await?)
+    }
+}
+
+/// How many bytes `sniff` looks at.
+pub const SNIFF_BYTES: usize = 512;
+
+/// Guesses the MIME type of a file from its first bytes, ignoring its name
+/// and whatever type the uploader claims.
+pub fn sniff(head: &[u8]) -> &'static str {
+    const SIGNATURES: &[(&[u8], &str)] = &[
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"GIF87a", "image/gif"),
+        (b"GIF89a", "image/gif"),
+        (b"%PDF-", "application/pdf"),
+        (b"PK\x03\x04", "application/zip"),
+        (b"\x1f\x8b", "application/gzip"),
+    ];
+    if let Some((_, mime)) = SIGNATURES
+        .iter()
+        .find(|(signature, _)| head.starts_with(signature))
+    {
+        return mime;
+    }
+    if head.len() >= 12 && head.starts_with(b"RIFF") && &head[8..12] == b"WEBP" {
+        return "image/webp";
+    }
+
+    // Text if it's UTF-8 without control characters other than whitespace.
+    // The last character may have been cut in half by the sniffing limit.
+    let text = match std::str::from_utf8(head) {
+        Ok(text) => text,
+        Err(e) if e.error_len().is_none() => std::str::from_utf8(&head[..e.valid_up_to()]).unwrap(),
+        Err(_) => return "application/octet-stream",
+    };
+    if text
+        .chars()
+        .all(|c| !c.is_control() || c.is_ascii_whitespace())
+    {
+        "text/plain; charset=utf-8"
+    } else {
+        "application/octet-stream"
+    }
+}
@@ -0,0 +1,207 @@
+use anyhow::{anyhow, Context};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::comments::Comment;
+use crate::data::Ticket;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Role {
+    Viewer,
+    Reporter,
+    Maintainer,
+    Admin,
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl FromStr for Role {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "viewer" => Ok(Role::Viewer),
+            "reporter" => Ok(Role::Reporter),
+            "maintainer" => Ok(Role::Maintainer),
+            "admin" => Ok(Role::Admin),
+            _ => Err(anyhow!("Unknown role: {}", s)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct User {
+    pub name: String,
+    pub role: Role,
+}
+
+impl User {
+    pub fn new(name: &str, role: Role) -> Self {
+        Self {
+            name: name.to_string(),
+            role,
+        }
+    }
+}
+
+/// Something a user may or may not be allowed to do.
+pub enum Action<'a> {
+    Read,
+    Create,
+    /// Change the title or description of a ticket.
+    Edit(&'a Ticket),
+    ChangeStatus,
+    Delete,
+    ManageTokens,
+    /// Create projects and change their settings.
+    ManageProjects,
+    /// Post comments on and attach files to tickets.
+    Comment,
+    /// Change or delete a comment.
+    EditComment(&'a Comment),
+    /// Add and remove ticket templates.
+    ManageTemplates,
+    /// Save and remove named queries.
+    ManageQueries,
+}
+
+impl User {
+    /// - Viewers can only read.
+    /// - Reporters can also create tickets and edit the ones they reported,
+    ///   comment, attach files, edit and delete their own comments, and save
+    ///   queries.
+    /// - Maintainers can edit any ticket or comment, move tickets between
+    ///   statuses and manage ticket templates.
+    /// - Admins can do everything, including deleting tickets, issuing tokens
+    ///   and managing projects.
+    pub fn can(&self, action: Action) -> bool {
+        match action {
+            Action::Read => true,
+            Action::Create => self.role >= Role::Reporter,
+            Action::Edit(ticket) => {
+                self.role >= Role::Maintainer
+                    || (self.role == Role::Reporter
+                        && ticket.reporter.as_deref() == Some(self.name.as_str()))
+            }
+            Action::ChangeStatus | Action::ManageTemplates => self.role >= Role::Maintainer,
+            Action::Comment | Action::ManageQueries => self.role >= Role::Reporter,
+            Action::EditComment(comment) => {
+                self.role >= Role::Maintainer
+                    || (self.role == Role::Reporter && comment.author == self.name)
+            }
+            Action::Delete | Action::ManageTokens | Action::ManageProjects => {
+                self.role == Role::Admin
+            }
+        }
+    }
+}
+
+/// Body of `POST /admin/tokens`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenRequest {
+    pub name: String,
+    pub role: Role,
+}
+
+/// Response to `POST /admin/tokens`. This is the only time the token is shown.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IssuedToken {
+    pub token: String,
+    pub name: String,
+    pub role: Role,
+}
+
+/// Bearer tokens and the users they identify.
+///
+/// Token files have one `<token> <user name> <role>` entry per line.
+/// Blank lines and lines starting with `#` are ignored.
+#[derive(Default)]
+pub struct TokenStore {
+    tokens: HashMap<String, User>,
+    path: Option<PathBuf>,
+}
+
+impl TokenStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads the tokens in `path`. Tokens issued later are appended to the same file.
+    pub fn load(path: &Path) -> Result<Self, anyhow::Error> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read tokens from {}", path.display()))?;
+        let mut store = Self::parse(&content)?;
+        store.path = Some(path.to_path_buf());
+        Ok(store)
+    }
+
+    pub fn parse(content: &str) -> Result<Self, anyhow::Error> {
+        let mut store = Self::new();
+        for (idx, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let [token, name, role] = parts[..] else {
+                return Err(anyhow!(
+                    "Line {}: expected `<token> <name> <role>`",
+                    idx + 1
+                ));
+            };
+            let role = role.parse().with_context(|| format!("Line {}", idx + 1))?;
+            store.insert(token.to_string(), User::new(name, role));
+        }
+        Ok(store)
+    }
+
+    pub fn insert(&mut self, token: String, user: User) {
+        self.tokens.insert(token, user);
+    }
+
+    /// Issues a fresh random token for `user`.
+    pub fn generate(&mut self, user: User) -> Result<String, anyhow::Error> {
+        if user.name.is_empty() || user.name.contains(char::is_whitespace) {
+            return Err(anyhow!("User names cannot be empty or contain whitespace"));
+        }
+
+        let token: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(32)
+            .map(char::from)
+            .collect();
+
+        if let Some(path) = &self.path {
+            let mut file = OpenOptions::new().append(true).create(true).open(path)?;
+            writeln!(file, "{} {} {}", token, user.name, user.role)?;
+        }
+
+        self.insert(token.clone(), user);
+        Ok(token)
+    }
+
+    pub fn authenticate(&self, token: &str) -> Option<User> {
+        self.tokens.get(token).cloned()
+    }
+
+    /// Extracts the bearer token from an `Authorization` header value.
+    pub fn authenticate_header(&self, value: &[u8]) -> Option<User> {
+        let value = std::str::from_utf8(value).ok()?;
+        let (scheme, token) = value.trim().split_once(' ')?;
+        if !scheme.eq_ignore_ascii_case("bearer") {
+            return None;
+        }
+        self.authenticate(token.trim())
+    }
+}
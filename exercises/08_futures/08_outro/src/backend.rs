@@ -0,0 +1,822 @@
+use anyhow::anyhow;
+use chrono::{Duration, Utc};
+use std::env;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use ticket_fields::Policy;
+use tokio::sync::RwLock;
+
+use crate::auth::{Action, Role, User};
+use crate::client::Client;
+use crate::comments::{Comment, CommentDraft};
+use crate::data::{Status, Ticket, TicketDraft, TicketPatch};
+use crate::handlers::{self, PatchError};
+use crate::history::Report;
+use crate::ids::IdStrategy;
+use crate::links::{Link, TicketLinks};
+use crate::projects::{Project, ProjectDraft, ProjectKey};
+use crate::query::{Query, SavedQuery, SavedQueryError};
+use crate::reports::{Period, Summary};
+use crate::snapshot::Snapshot;
+use crate::sqlite::SqliteStore;
+use crate::store::{TicketId, TicketStore};
+use crate::templates::{Template, TemplateError, TemplateRequest};
+use crate::transfer::{self, Ids, ImportOptions, ImportReport, Imported, Parsed};
+use crate::trash::Trashed;
+
+/// Command-line options choosing a `Backend`, shared by the `tickets` and `board` tools.
+#[derive(clap::Args, Debug)]
+pub struct BackendArgs {
+    /// Store file to use when no server is given. Created if it doesn't exist.
+    #[arg(
+        long,
+        env = "TICKETS_STORE",
+        default_value = "tickets.json",
+        global = true
+    )]
+    pub store: PathBuf,
+
+    /// SQLite database to use instead of the store file when no server is given.
+    /// Created if it doesn't exist.
+    #[arg(long, env = "TICKETS_DB", global = true)]
+    pub db: Option<PathBuf>,
+
+    /// Address of an outro_08 server, e.g. `127.0.0.1:8080`.
+    #[arg(long, env = "TICKETS_SERVER", global = true, requires = "token")]
+    pub server: Option<String>,
+
+    /// Bearer token for the server.
+    #[arg(long, env = "TICKETS_TOKEN", global = true, hide_env_values = true)]
+    pub token: Option<String>,
+
+    /// How new tickets get their ids: `sequential`, `ulid` or `uuid7`.
+    /// Servers have their own setting, so it's ignored with `--server`.
+    #[arg(long, env = "TICKETS_IDS", default_value_t, global = true)]
+    pub ids: IdStrategy,
+}
+
+impl BackendArgs {
+    pub fn open(self) -> Result<Backend, anyhow::Error> {
+        match (self.server, self.token, self.db) {
+            (Some(server), Some(token), _) => Ok(Backend::remote(Client::new(server, token))),
+            (_, _, Some(db)) => {
+                let db = SqliteStore::open(&db)?;
+                db.set_id_strategy(self.ids);
+                Ok(Backend::database(db, local_user()))
+            }
+            _ => {
+                let mut store = load_store(&self.store)?;
+                store.set_id_strategy(self.ids);
+                Ok(Backend::Local {
+                    path: Some(self.store),
+                    store: Arc::new(RwLock::new(store)),
+                    user: local_user(),
+                })
+            }
+        }
+    }
+}
+
+/// Where the command-line tools read and write tickets.
+pub enum Backend {
+    /// A store in this process, saved to `path` after every change if there is one.
+    /// Changes are attributed to `user`, checked like on the server, except
+    /// that titles and descriptions only get the built-in rules, not a `Policy`.
+    Local {
+        path: Option<PathBuf>,
+        store: Arc<RwLock<TicketStore>>,
+        user: User,
+    },
+    /// An SQLite database, see `sqlite`. Changes are attributed to `user`, like `Local`.
+    Sqlite { db: SqliteStore, user: User },
+    /// An outro_08 server.
+    Remote(Client),
+}
+
+impl Backend {
+    /// Opens the store at `path`, starting an empty one if the file doesn't exist yet.
+    /// Changes are attributed to the current OS user, who can do anything.
+    pub fn local(path: impl Into<PathBuf>) -> Result<Self, anyhow::Error> {
+        let path = path.into();
+        let store = load_store(&path)?;
+        Ok(Backend::Local {
+            path: Some(path),
+            store: Arc::new(RwLock::new(store)),
+            user: local_user(),
+        })
+    }
+
+    /// Works directly on a store shared with the rest of the process, e.g. a server's.
+    pub fn shared(store: Arc<RwLock<TicketStore>>, user: User) -> Self {
+        Backend::Local {
+            path: None,
+            store,
+            user,
+        }
+    }
+
+    /// Opens the database at `path`, creating it if it doesn't exist yet.
+    /// Changes are attributed to the current OS user, who can do anything.
+    pub fn sqlite(path: impl Into<PathBuf>) -> Result<Self, anyhow::Error> {
+        let db = SqliteStore::open(&path.into())?;
+        Ok(Backend::database(db, local_user()))
+    }
+
+    pub fn database(db: SqliteStore, user: User) -> Self {
+        Backend::Sqlite { db, user }
+    }
+
+    pub fn remote(client: Client) -> Self {
+        Backend::Remote(client)
+    }
+
+    pub async fn create(&self, draft: TicketDraft) -> Result<TicketId, anyhow::Error> {
+        match self {
+            Backend::Local { store, user, .. } => {
+                if !user.can(Action::Create) {
+                    return Err(forbidden());
+                }
+                let id = store.write().await.add_ticket_by(draft, user);
+                self.save().await?;
+                Ok(id)
+            }
+            Backend::Sqlite { db, user } => {
+                if !user.can(Action::Create) {
+                    return Err(forbidden());
+                }
+                db.create(ProjectKey::DEFAULT, draft, user).await
+            }
+            Backend::Remote(client) => Ok(client.create(&draft).await?),
+        }
+    }
+
+    pub async fn get(&self, id: TicketId) -> Result<Ticket, anyhow::Error> {
+        match self {
+            Backend::Local { store, .. } => {
+                let store = store.read().await;
+                let ticket = store.get(id).ok_or_else(|| not_found(id))?;
+                let ticket = ticket.read().unwrap().clone();
+                Ok(ticket)
+            }
+            Backend::Sqlite { db, .. } => db.get(id).await?.ok_or_else(|| not_found(id)),
+            Backend::Remote(client) => Ok(client.get(id).await?),
+        }
+    }
+
+    pub async fn list(&self) -> Result<Vec<Ticket>, anyhow::Error> {
+        match self {
+            Backend::Local { store, .. } => Ok(store
+                .read()
+                .await
+                .tickets
+                .values()
+                .map(|ticket| ticket.read().unwrap().clone())
+                .collect()),
+            Backend::Sqlite { db, .. } => db.list(None).await,
+            Backend::Remote(client) => Ok(client.list().await?),
+        }
+    }
+
+    /// The tickets matching `query`, see `query` for the language.
+    pub async fn search(&self, query: &str) -> Result<Vec<Ticket>, anyhow::Error> {
+        match self {
+            Backend::Local { store, .. } => {
+                let query: Query = query.parse()?;
+                Ok(store
+                    .read()
+                    .await
+                    .matching(&query)
+                    .iter()
+                    .map(|ticket| ticket.read().unwrap().clone())
+                    .collect())
+            }
+            Backend::Sqlite { db, .. } => {
+                let query: Query = query.parse()?;
+                let mut tickets = db.list(None).await?;
+                tickets.retain(|ticket| query.matches(ticket));
+                Ok(tickets)
+            }
+            Backend::Remote(client) => Ok(client.search(query).await?),
+        }
+    }
+
+    pub async fn patch(&self, patch: TicketPatch) -> Result<(), anyhow::Error> {
+        match self {
+            Backend::Local { store, user, .. } => {
+                let id = patch.id;
+                match handlers::update_ticket_endpoint(patch, Arc::clone(store), user).await {
+                    Ok(()) => self.save().await,
+                    Err(PatchError::NotFound) => Err(not_found(id)),
+                    Err(PatchError::Forbidden) => Err(forbidden()),
+                    Err(PatchError::Blocked(blockers)) => {
+                        Err(anyhow!(PatchError::blocked_message(&blockers)))
+                    }
+                }
+            }
+            Backend::Sqlite { db, user } => {
+                let id = patch.id;
+                db.patch(patch, user)
+                    .await
+                    .map_err(|e| match e.downcast_ref::<PatchError>() {
+                        Some(PatchError::NotFound) => not_found(id),
+                        Some(PatchError::Forbidden) => forbidden(),
+                        _ => e,
+                    })
+            }
+            Backend::Remote(client) => Ok(client.patch(&patch).await?),
+        }
+    }
+
+    /// Moves a ticket to the trash, see `trash`.
+    pub async fn delete(&self, id: TicketId) -> Result<(), anyhow::Error> {
+        match self {
+            Backend::Local { store, user, .. } => {
+                if !user.can(Action::Delete) {
+                    return Err(forbidden());
+                }
+                store
+                    .write()
+                    .await
+                    .delete(id, user)
+                    .ok_or_else(|| not_found(id))?;
+                self.save().await
+            }
+            Backend::Sqlite { db, user } => {
+                if !user.can(Action::Delete) {
+                    return Err(forbidden());
+                }
+                if !db.delete(id, user).await? {
+                    return Err(not_found(id));
+                }
+                Ok(())
+            }
+            Backend::Remote(client) => Ok(client.delete(id).await?),
+        }
+    }
+
+    /// The deleted tickets, ordered by id.
+    pub async fn trash(&self) -> Result<Vec<Trashed>, anyhow::Error> {
+        match self {
+            Backend::Local { store, .. } => Ok(store.read().await.trash().cloned().collect()),
+            Backend::Sqlite { db, .. } => db.trash().await,
+            Backend::Remote(client) => Ok(client.trash().await?),
+        }
+    }
+
+    /// Moves a deleted ticket back out of the trash.
+    pub async fn undelete(&self, id: TicketId) -> Result<(), anyhow::Error> {
+        match self {
+            Backend::Local { store, user, .. } => {
+                if !user.can(Action::Delete) {
+                    return Err(forbidden());
+                }
+                store
+                    .write()
+                    .await
+                    .undelete(id)
+                    .ok_or_else(|| not_in_trash(id))?;
+                self.save().await
+            }
+            Backend::Sqlite { db, user } => {
+                if !user.can(Action::Delete) {
+                    return Err(forbidden());
+                }
+                if !db.undelete(id).await? {
+                    return Err(not_in_trash(id));
+                }
+                Ok(())
+            }
+            Backend::Remote(client) => {
+                client.undelete(id).await?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Removes a deleted ticket for good.
+    pub async fn purge(&self, id: TicketId) -> Result<(), anyhow::Error> {
+        match self {
+            Backend::Local { store, user, .. } => {
+                if !user.can(Action::Delete) {
+                    return Err(forbidden());
+                }
+                store
+                    .write()
+                    .await
+                    .purge(id)
+                    .ok_or_else(|| not_in_trash(id))?;
+                self.save().await
+            }
+            Backend::Sqlite { db, user } => {
+                if !user.can(Action::Delete) {
+                    return Err(forbidden());
+                }
+                if !db.purge(id).await? {
+                    return Err(not_in_trash(id));
+                }
+                Ok(())
+            }
+            Backend::Remote(client) => Ok(client.purge(id).await?),
+        }
+    }
+
+    /// Purges every ticket deleted at least `days` days ago. Returns their ids.
+    pub async fn purge_expired(&self, days: u32) -> Result<Vec<TicketId>, anyhow::Error> {
+        let retention = Duration::days(days.into());
+        match self {
+            Backend::Local { store, user, .. } => {
+                if !user.can(Action::Delete) {
+                    return Err(forbidden());
+                }
+                let purged = store.write().await.purge_expired(retention, Utc::now());
+                self.save().await?;
+                Ok(purged)
+            }
+            Backend::Sqlite { db, user } => {
+                if !user.can(Action::Delete) {
+                    return Err(forbidden());
+                }
+                db.purge_expired(retention, Utc::now()).await
+            }
+            Backend::Remote(client) => Ok(client.purge_expired(days).await?),
+        }
+    }
+
+    /// Undoes the last `count` ticket creations, edits and deletions of the
+    /// user, see `TicketStore::undo`.
+    pub async fn undo(&self, count: usize) -> Result<Report, anyhow::Error> {
+        match self {
+            Backend::Local { store, user, .. } => {
+                let report = store.write().await.undo(user, count);
+                self.save().await?;
+                Ok(report)
+            }
+            Backend::Sqlite { .. } => Err(no_history()),
+            Backend::Remote(client) => Ok(client.undo(count).await?),
+        }
+    }
+
+    pub async fn redo(&self, count: usize) -> Result<Report, anyhow::Error> {
+        match self {
+            Backend::Local { store, user, .. } => {
+                let report = store.write().await.redo(user, count);
+                self.save().await?;
+                Ok(report)
+            }
+            Backend::Sqlite { .. } => Err(no_history()),
+            Backend::Remote(client) => Ok(client.redo(count).await?),
+        }
+    }
+
+    pub async fn links(&self, id: TicketId) -> Result<TicketLinks, anyhow::Error> {
+        match self {
+            Backend::Local { store, .. } => {
+                let store = store.read().await;
+                store.get(id).ok_or_else(|| not_found(id))?;
+                Ok(TicketLinks::of(store.links(), id))
+            }
+            Backend::Sqlite { .. } => Err(not_in_database()),
+            Backend::Remote(client) => Ok(client.links(id).await?),
+        }
+    }
+
+    /// Links `link.from` to `link.to`, which needs edit rights on `link.from`.
+    pub async fn link(&self, link: Link) -> Result<(), anyhow::Error> {
+        match self {
+            Backend::Local { store, user, .. } => {
+                let mut store_guard = store.write().await;
+                check_edit(&store_guard, user, link.from)?;
+                store_guard.link(link)?;
+                drop(store_guard);
+                self.save().await
+            }
+            Backend::Sqlite { .. } => Err(not_in_database()),
+            Backend::Remote(client) => Ok(client.link(link).await?),
+        }
+    }
+
+    pub async fn unlink(&self, link: Link) -> Result<(), anyhow::Error> {
+        match self {
+            Backend::Local { store, user, .. } => {
+                let mut store_guard = store.write().await;
+                check_edit(&store_guard, user, link.from)?;
+                if !store_guard.unlink(&link) {
+                    return Err(anyhow!("There is no link {}", link));
+                }
+                drop(store_guard);
+                self.save().await
+            }
+            Backend::Sqlite { .. } => Err(not_in_database()),
+            Backend::Remote(client) => Ok(client.unlink(link).await?),
+        }
+    }
+
+    /// The comment thread of a ticket, oldest first.
+    pub async fn comments(&self, id: TicketId) -> Result<Vec<Comment>, anyhow::Error> {
+        match self {
+            Backend::Local { store, .. } => {
+                let store = store.read().await;
+                store.get(id).ok_or_else(|| not_found(id))?;
+                Ok(store.comments().thread(id))
+            }
+            Backend::Sqlite { .. } => Err(not_in_database()),
+            Backend::Remote(client) => Ok(client.comments(id).await?),
+        }
+    }
+
+    pub async fn comment(
+        &self,
+        id: TicketId,
+        draft: CommentDraft,
+    ) -> Result<Comment, anyhow::Error> {
+        match self {
+            Backend::Local { store, user, .. } => {
+                if !user.can(Action::Comment) {
+                    return Err(forbidden());
+                }
+                let comment = {
+                    let mut store = store.write().await;
+                    let comment = store
+                        .add_comment(id, user, draft.body)
+                        .ok_or_else(|| not_found(id))?;
+                    store.comments().get(comment).cloned().unwrap()
+                };
+                self.save().await?;
+                Ok(comment)
+            }
+            Backend::Sqlite { .. } => Err(not_in_database()),
+            Backend::Remote(client) => Ok(client.comment(id, &draft).await?),
+        }
+    }
+
+    /// Creates a ticket in project `key` rather than the default one.
+    pub async fn create_in(
+        &self,
+        key: ProjectKey,
+        draft: TicketDraft,
+    ) -> Result<TicketId, anyhow::Error> {
+        match self {
+            Backend::Local { store, user, .. } => {
+                if !user.can(Action::Create) {
+                    return Err(forbidden());
+                }
+                let id = store.write().await.add_ticket_to(key, draft, user)?;
+                self.save().await?;
+                Ok(id)
+            }
+            Backend::Sqlite { db, user } => {
+                if !user.can(Action::Create) {
+                    return Err(forbidden());
+                }
+                db.create(key, draft, user).await
+            }
+            Backend::Remote(client) => Ok(client.create_in(key, &draft).await?),
+        }
+    }
+
+    /// The tickets of project `key`, ordered by number.
+    pub async fn list_in(&self, key: ProjectKey) -> Result<Vec<Ticket>, anyhow::Error> {
+        match self {
+            Backend::Local { store, .. } => {
+                let store = store.read().await;
+                store
+                    .project(key)
+                    .ok_or_else(|| anyhow!("No project with key {}", key))?;
+                Ok(store
+                    .tickets_in(key)
+                    .iter()
+                    .map(|ticket| ticket.read().unwrap().clone())
+                    .collect())
+            }
+            Backend::Sqlite { db, .. } => db.list_in(key).await,
+            Backend::Remote(client) => Ok(client.project_tickets(key).await?),
+        }
+    }
+
+    pub async fn projects(&self) -> Result<Vec<Project>, anyhow::Error> {
+        match self {
+            Backend::Local { store, .. } => Ok(store.read().await.projects().cloned().collect()),
+            Backend::Sqlite { db, .. } => db.projects().await,
+            Backend::Remote(client) => Ok(client.projects().await?),
+        }
+    }
+
+    pub async fn create_project(&self, draft: ProjectDraft) -> Result<Project, anyhow::Error> {
+        match self {
+            Backend::Local { store, user, .. } => {
+                if !user.can(Action::ManageProjects) {
+                    return Err(forbidden());
+                }
+                let project = store.write().await.create_project(draft)?.clone();
+                self.save().await?;
+                Ok(project)
+            }
+            Backend::Sqlite { db, user } => {
+                if !user.can(Action::ManageProjects) {
+                    return Err(forbidden());
+                }
+                db.create_project(draft).await
+            }
+            Backend::Remote(client) => Ok(client.create_project(&draft).await?),
+        }
+    }
+
+    pub async fn templates(&self) -> Result<Vec<Template>, anyhow::Error> {
+        match self {
+            Backend::Local { store, .. } => Ok(store.read().await.templates().cloned().collect()),
+            Backend::Sqlite { .. } => Err(no_templates()),
+            Backend::Remote(client) => Ok(client.templates().await?),
+        }
+    }
+
+    pub async fn add_template(&self, template: Template) -> Result<Template, anyhow::Error> {
+        match self {
+            Backend::Local { store, user, .. } => {
+                if !user.can(Action::ManageTemplates) {
+                    return Err(forbidden());
+                }
+                let template = store.write().await.add_template(template)?.clone();
+                self.save().await?;
+                Ok(template)
+            }
+            Backend::Sqlite { .. } => Err(no_templates()),
+            Backend::Remote(client) => Ok(client.create_template(&template).await?),
+        }
+    }
+
+    pub async fn remove_template(&self, name: &str) -> Result<(), anyhow::Error> {
+        match self {
+            Backend::Local { store, user, .. } => {
+                if !user.can(Action::ManageTemplates) {
+                    return Err(forbidden());
+                }
+                if store.write().await.remove_template(name).is_none() {
+                    return Err(TemplateError::NotFound(name.to_string()).into());
+                }
+                self.save().await
+            }
+            Backend::Sqlite { .. } => Err(no_templates()),
+            Backend::Remote(client) => Ok(client.delete_template(name).await?),
+        }
+    }
+
+    /// Creates every ticket of template `name`, see `TicketStore::create_from_template`.
+    pub async fn create_from_template(
+        &self,
+        name: &str,
+        request: TemplateRequest,
+    ) -> Result<Vec<TicketId>, anyhow::Error> {
+        match self {
+            Backend::Local { store, user, .. } => {
+                if !user.can(Action::Create) {
+                    return Err(forbidden());
+                }
+                let project = request.project.unwrap_or(ProjectKey::DEFAULT);
+                let ids = store.write().await.create_from_template(
+                    name,
+                    project,
+                    &request.values,
+                    user,
+                    &Policy::default(),
+                )?;
+                self.save().await?;
+                Ok(ids)
+            }
+            Backend::Sqlite { .. } => Err(no_templates()),
+            Backend::Remote(client) => Ok(client.create_from_template(name, &request).await?),
+        }
+    }
+
+    pub async fn saved_queries(&self) -> Result<Vec<SavedQuery>, anyhow::Error> {
+        match self {
+            Backend::Local { store, .. } => {
+                Ok(store.read().await.saved_queries().cloned().collect())
+            }
+            Backend::Sqlite { .. } => Err(no_saved_queries()),
+            Backend::Remote(client) => Ok(client.saved_queries().await?),
+        }
+    }
+
+    pub async fn save_query(&self, query: SavedQuery) -> Result<SavedQuery, anyhow::Error> {
+        match self {
+            Backend::Local { store, user, .. } => {
+                if !user.can(Action::ManageQueries) {
+                    return Err(forbidden());
+                }
+                let query = store.write().await.save_query(query)?.clone();
+                self.save().await?;
+                Ok(query)
+            }
+            Backend::Sqlite { .. } => Err(no_saved_queries()),
+            Backend::Remote(client) => Ok(client.save_query(&query).await?),
+        }
+    }
+
+    pub async fn remove_query(&self, name: &str) -> Result<(), anyhow::Error> {
+        match self {
+            Backend::Local { store, user, .. } => {
+                if !user.can(Action::ManageQueries) {
+                    return Err(forbidden());
+                }
+                if store.write().await.remove_query(name).is_none() {
+                    return Err(SavedQueryError::NotFound(name.to_string()).into());
+                }
+                self.save().await
+            }
+            Backend::Sqlite { .. } => Err(no_saved_queries()),
+            Backend::Remote(client) => Ok(client.delete_query(name).await?),
+        }
+    }
+
+    /// The tickets matching saved query `name`.
+    pub async fn run_query(&self, name: &str) -> Result<Vec<Ticket>, anyhow::Error> {
+        match self {
+            Backend::Local { store, .. } => Ok(store
+                .read()
+                .await
+                .run_query(name)?
+                .iter()
+                .map(|ticket| ticket.read().unwrap().clone())
+                .collect()),
+            Backend::Sqlite { .. } => Err(no_saved_queries()),
+            Backend::Remote(client) => Ok(client.run_query(name).await?),
+        }
+    }
+
+    /// See `reports`.
+    pub async fn summary(&self, period: Period) -> Result<Summary, anyhow::Error> {
+        match self {
+            Backend::Local { store, .. } => Ok(store.read().await.summary(period, Utc::now())),
+            Backend::Sqlite { .. } => Err(no_status_changes()),
+            Backend::Remote(client) => Ok(client.summary(period).await?),
+        }
+    }
+
+    /// Imports every row of `parsed`, or none if any of them is invalid.
+    /// A server gives imported tickets new ids, so `Ids::Preserve` only works locally.
+    pub async fn import(
+        &self,
+        parsed: Parsed,
+        options: ImportOptions,
+    ) -> Result<ImportReport, anyhow::Error> {
+        match self {
+            Backend::Local { store, user, .. } => {
+                if !user.can(Action::Create) {
+                    return Err(forbidden());
+                }
+                let policy = Policy::default();
+                let report = transfer::import(&mut *store.write().await, parsed, options, &policy);
+                if report.is_ok() && !options.dry_run {
+                    self.save().await?;
+                }
+                Ok(report)
+            }
+            Backend::Sqlite { .. } => Err(not_in_database()),
+            Backend::Remote(_) if options.ids == Ids::Preserve => {
+                Err(anyhow!("Ids can only be preserved when importing into a store file"))
+            }
+            Backend::Remote(client) => {
+                let mut report = ImportReport {
+                    errors: parsed.errors,
+                    dry_run: options.dry_run,
+                    ..ImportReport::default()
+                };
+                if !report.is_ok() || options.dry_run {
+                    return Ok(report);
+                }
+                for row in parsed.rows {
+                    let draft = TicketDraft {
+                        title: row.title,
+                        description: row.description,
+                    };
+                    let id = client.create(&draft).await?;
+                    if row.status != Status::ToDo {
+                        let patch = TicketPatch {
+                            id,
+                            title: None,
+                            description: None,
+                            status: Some(row.status),
+                        };
+                        client.patch(&patch).await?;
+                    }
+                    report.imported.push(Imported {
+                        line: row.line,
+                        source: row.id,
+                        id,
+                    });
+                }
+                Ok(report)
+            }
+        }
+    }
+
+    /// A consistent copy of every ticket in a local store.
+    pub async fn snapshot(&self) -> Result<Snapshot, anyhow::Error> {
+        match self {
+            Backend::Local { store, .. } => Ok(Snapshot::capture_shared(store).await),
+            Backend::Sqlite { .. } | Backend::Remote(_) => Err(local_only()),
+        }
+    }
+
+    /// Replaces every ticket in a local store with the snapshot's.
+    /// Templates and saved queries aren't part of snapshots, so they're kept.
+    pub async fn restore(&self, snapshot: Snapshot) -> Result<(), anyhow::Error> {
+        match self {
+            Backend::Local { store, user, .. } => {
+                if !user.can(Action::Delete) {
+                    return Err(forbidden());
+                }
+                let mut restored = snapshot.restore();
+                let mut store = store.write().await;
+                for template in store.templates() {
+                    restored.add_template(template.clone())?;
+                }
+                for query in store.saved_queries() {
+                    restored.save_query(query.clone())?;
+                }
+                *store = restored;
+                drop(store);
+                self.save().await
+            }
+            Backend::Sqlite { .. } | Backend::Remote(_) => Err(local_only()),
+        }
+    }
+
+    async fn save(&self) -> Result<(), anyhow::Error> {
+        if let Backend::Local {
+            path: Some(path),
+            store,
+            ..
+        } = self
+        {
+            store.read().await.save(path)?;
+        }
+        Ok(())
+    }
+}
+
+fn check_edit(store: &TicketStore, user: &User, id: TicketId) -> Result<(), anyhow::Error> {
+    let ticket = store.get(id).ok_or_else(|| not_found(id))?;
+    if !user.can(Action::Edit(&ticket.read().unwrap())) {
+        return Err(forbidden());
+    }
+    Ok(())
+}
+
+fn not_found(id: TicketId) -> anyhow::Error {
+    anyhow!("No ticket with id {}", id)
+}
+
+/// Loads the store file at `path`, or starts an empty store if there's none yet.
+fn load_store(path: &Path) -> Result<TicketStore, anyhow::Error> {
+    match TicketStore::load(path) {
+        Ok(store) => Ok(store),
+        Err(e) if is_not_found(&e) => Ok(TicketStore::new()),
+        Err(e) => Err(e),
+    }
+}
+
+/// The current OS user, who can do anything with their own files.
+fn local_user() -> User {
+    let name = env::var("USER").unwrap_or_else(|_| String::from("local"));
+    User::new(&name, Role::Admin)
+}
+
+fn not_in_trash(id: TicketId) -> anyhow::Error {
+    anyhow!("Ticket {} isn't in the trash", id)
+}
+
+fn forbidden() -> anyhow::Error {
+    anyhow!("Not allowed")
+}
+
+fn local_only() -> anyhow::Error {
+    anyhow!("Snapshots only work with store files, not servers or databases")
+}
+
+fn not_in_database() -> anyhow::Error {
+    anyhow!("SQLite databases don't support links, comments or imports yet")
+}
+
+fn no_history() -> anyhow::Error {
+    anyhow!("SQLite databases don't keep a history to undo yet")
+}
+
+fn no_templates() -> anyhow::Error {
+    anyhow!("SQLite databases don't keep ticket templates yet")
+}
+
+fn no_saved_queries() -> anyhow::Error {
+    anyhow!("SQLite databases don't keep saved queries yet")
+}
+
+fn no_status_changes() -> anyhow::Error {
+    anyhow!("SQLite databases don't keep status changes for reports yet")
+}
+
+fn is_not_found(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<io::Error>()
+        .is_some_and(|e| e.kind() == io::ErrorKind::NotFound)
+}
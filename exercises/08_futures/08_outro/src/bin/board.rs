@@ -0,0 +1,37 @@
+use clap::Parser;
+use outro_08::backend::BackendArgs;
+use outro_08::board::Board;
+use ratatui::crossterm::event::{self, Event, KeyEventKind};
+use ratatui::DefaultTerminal;
+
+/// A kanban board of tickets, from a local store file or an outro_08 server.
+#[derive(Parser, Debug)]
+#[command(name = "board", version, about)]
+struct Cli {
+    #[command(flatten)]
+    backend: BackendArgs,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), anyhow::Error> {
+    let cli = Cli::parse();
+    // Fail before taking over the terminal if the tickets can't be loaded.
+    let mut board = Board::new(cli.backend.open()?).await?;
+
+    let mut terminal = ratatui::init();
+    let result = run(&mut terminal, &mut board).await;
+    ratatui::restore();
+    result
+}
+
+async fn run(terminal: &mut DefaultTerminal, board: &mut Board) -> Result<(), anyhow::Error> {
+    while !board.should_quit() {
+        terminal.draw(|frame| board.draw(frame))?;
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press {
+                board.handle_key(key).await;
+            }
+        }
+    }
+    Ok(())
+}
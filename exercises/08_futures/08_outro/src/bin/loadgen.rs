@@ -0,0 +1,60 @@
+use clap::Parser;
+use outro_08::loadgen::{self, LoadConfig, Mix};
+use std::time::Duration;
+
+/// Load test an outro_08 server and report throughput, latency percentiles
+/// and errors. Start the server with `TICKETS_NO_RATE_LIMITS=1`, or most
+/// requests will be rate limited.
+#[derive(Parser, Debug)]
+#[command(name = "loadgen", version, about)]
+struct Cli {
+    /// Address of the server, e.g. `127.0.0.1:8080`.
+    #[arg(long, env = "TICKETS_SERVER", default_value = "127.0.0.1:8080")]
+    server: String,
+
+    /// Bearer token of a user who can create and edit tickets.
+    #[arg(long, env = "TICKETS_TOKEN", hide_env_values = true)]
+    token: String,
+
+    /// How many requests are in flight at the same time.
+    #[arg(long, short, default_value_t = 16)]
+    concurrency: usize,
+
+    /// How long to send requests for, in seconds.
+    #[arg(long, short, default_value_t = 10)]
+    duration: u64,
+
+    /// Relative weights of the requests sent, e.g. `create=1,get=8,patch=1`.
+    #[arg(long, default_value = "create=1,get=8,patch=1")]
+    mix: Mix,
+
+    /// Open a connection per request instead of reusing one per worker.
+    #[arg(long)]
+    no_keep_alive: bool,
+
+    /// Seconds after which a request counts as failed.
+    #[arg(long, default_value_t = 5)]
+    timeout: u64,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), anyhow::Error> {
+    let cli = Cli::parse();
+    let config = LoadConfig {
+        addr: cli.server,
+        token: cli.token,
+        concurrency: cli.concurrency,
+        duration: Duration::from_secs(cli.duration),
+        mix: cli.mix,
+        keep_alive: !cli.no_keep_alive,
+        timeout: Duration::from_secs(cli.timeout),
+    };
+    let keep_alive = if config.keep_alive { "on" } else { "off" };
+    eprintln!(
+        "Sending requests to {} from {} workers for {}s, keep-alive {}",
+        config.addr, config.concurrency, cli.duration, keep_alive
+    );
+    let report = loadgen::run(config).await?;
+    print!("{}", report);
+    Ok(())
+}
@@ -0,0 +1,754 @@
+use anyhow::bail;
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use outro_08::backend::BackendArgs;
+use outro_08::comments::{Comment, CommentDraft};
+use outro_08::data::{Status, Ticket, TicketDraft, TicketPatch};
+use outro_08::history::Report;
+use outro_08::links::{Link, LinkKind, TicketLinks};
+use outro_08::projects::{Project, ProjectDraft, ProjectKey, ProjectSettings};
+use outro_08::query::SavedQuery;
+use outro_08::reports::Period;
+use outro_08::snapshot::Snapshot;
+use outro_08::store::TicketId;
+use outro_08::templates::{Template, TemplateRequest};
+use outro_08::transfer::{self, CsvMapping, Ids, ImportOptions, Parsed};
+use outro_08::trash::Trashed;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use ticket_fields::{MarkdownDescription, TicketTitle};
+
+/// Manage tickets in a local store file or on an outro_08 server.
+#[derive(Parser, Debug)]
+#[command(name = "tickets", version, about)]
+struct Cli {
+    #[command(flatten)]
+    backend: BackendArgs,
+
+    /// Output format. Defaults to `table`, except for `export`, which defaults to `json`.
+    #[arg(long, value_enum, global = true)]
+    format: Option<Format>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Create a ticket and print its id.
+    Create {
+        #[arg(long, value_parser = parse_title)]
+        title: TicketTitle,
+        #[arg(long, value_parser = parse_description)]
+        description: MarkdownDescription,
+        /// Create it in this project instead of the default one.
+        #[arg(long)]
+        project: Option<ProjectKey>,
+    },
+    /// Show a single ticket.
+    Show { id: TicketId },
+    /// List tickets, ordered by id.
+    List {
+        /// Only list tickets with this status.
+        #[arg(long)]
+        status: Option<Status>,
+        /// Only list the tickets of this project.
+        #[arg(long)]
+        project: Option<ProjectKey>,
+        /// Only list the tickets matching this query, e.g.
+        /// `status:InProgress AND title:~"login" AND NOT reporter:bob`.
+        #[arg(long, short = 'q', conflicts_with = "saved")]
+        query: Option<String>,
+        /// Only list the tickets matching this saved query.
+        #[arg(long)]
+        saved: Option<String>,
+    },
+    /// Change the title and/or description of a ticket.
+    #[command(arg_required_else_help = true)]
+    Edit {
+        id: TicketId,
+        #[arg(long, value_parser = parse_title, required_unless_present = "description")]
+        title: Option<TicketTitle>,
+        #[arg(long, value_parser = parse_description)]
+        description: Option<MarkdownDescription>,
+    },
+    /// Change the status of a ticket, e.g. `tickets move 3 in-progress`.
+    Move { id: TicketId, status: Status },
+    /// Move a ticket to the trash. It can be restored with `undelete` until it's purged.
+    Delete { id: TicketId },
+    /// List deleted tickets, ordered by id.
+    Trash,
+    /// Restore a deleted ticket, with its links and comments.
+    Undelete { id: TicketId },
+    /// Permanently delete a ticket from the trash, along with its links.
+    /// Its subtasks are kept.
+    #[command(arg_required_else_help = true)]
+    Purge {
+        #[arg(required_unless_present = "older_than_days")]
+        id: Option<TicketId>,
+        /// Purge every ticket deleted at least this many days ago instead,
+        /// and print their ids. `0` empties the trash.
+        #[arg(long, conflicts_with = "id")]
+        older_than_days: Option<u32>,
+    },
+    /// Undo your last ticket creations, edits and deletions, most recent
+    /// first, and print the ids of their tickets. Stops at the first ticket
+    /// that changed since.
+    Undo {
+        #[arg(long, default_value_t = 1)]
+        count: usize,
+    },
+    /// Redo the last operations undone with `undo`.
+    Redo {
+        #[arg(long, default_value_t = 1)]
+        count: usize,
+    },
+    /// Link a ticket to another, e.g. `tickets link 3 blocks 5` or `tickets link 4 child-of 1`.
+    Link {
+        id: TicketId,
+        kind: LinkKind,
+        other: TicketId,
+    },
+    /// Remove a link added with `link`.
+    Unlink {
+        id: TicketId,
+        kind: LinkKind,
+        other: TicketId,
+    },
+    /// Show the tickets linked to a ticket.
+    Links { id: TicketId },
+    /// Add a comment to a ticket and print its id.
+    Comment {
+        id: TicketId,
+        #[arg(value_parser = parse_description)]
+        body: MarkdownDescription,
+    },
+    /// Show the comment thread of a ticket, oldest first.
+    Comments { id: TicketId },
+    /// List projects, ordered by key.
+    Projects,
+    /// Create a project, e.g. `tickets create-project API --name "Public API"`.
+    CreateProject {
+        key: ProjectKey,
+        #[arg(long)]
+        name: String,
+        #[arg(long, default_value = "")]
+        description: String,
+    },
+    /// List ticket templates, ordered by name.
+    Templates,
+    /// Add a ticket template from a JSON file and print its name, e.g.
+    /// `{"name": "release", "tickets": [{"title": "Release {{version}}", "description": "…"}]}`.
+    AddTemplate {
+        /// `-` reads from stdin.
+        file: PathBuf,
+    },
+    /// Remove a ticket template. Tickets created from it are kept.
+    RemoveTemplate { name: String },
+    /// Create every ticket of a template and print their ids, e.g.
+    /// `tickets from-template release --set version=1.2.0`.
+    /// If any of them is invalid, none are created.
+    FromTemplate {
+        name: String,
+        /// The value of a placeholder.
+        #[arg(long = "set", value_name = "NAME=VALUE", value_parser = parse_value)]
+        values: Vec<(String, String)>,
+        /// Create them in this project instead of the default one.
+        #[arg(long)]
+        project: Option<ProjectKey>,
+    },
+    /// List saved queries, ordered by name.
+    Queries,
+    /// Save a query to run later with `list --saved NAME`.
+    SaveQuery { name: String, query: String },
+    /// Remove a saved query.
+    RemoveQuery { name: String },
+    /// Show ticket counts per status, throughput, cycle time and the age of
+    /// open tickets.
+    Report {
+        /// How long each throughput window is.
+        #[arg(long, default_value_t = Period::default().days())]
+        days: u32,
+        /// How many throughput windows, the last one ending now.
+        #[arg(long, default_value_t = Period::default().windows())]
+        windows: u32,
+    },
+    /// Create tickets from a file written by `export`, or by hand.
+    /// Every ticket is validated first: if any is invalid, none are created.
+    /// The format comes from `--format` or the file extension, and defaults to JSON.
+    Import {
+        /// `-` reads from stdin.
+        file: PathBuf,
+        /// Only validate the file and print the ids tickets would get.
+        #[arg(long)]
+        dry_run: bool,
+        /// Keep the ids in the file instead of giving tickets new ones.
+        #[arg(long)]
+        keep_ids: bool,
+        /// Read a field from a differently named CSV column, e.g. `title=Summary`.
+        #[arg(long = "map", value_name = "FIELD=COLUMN")]
+        mappings: Vec<String>,
+    },
+    /// Write every ticket to a file, or to stdout.
+    Export {
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Write a binary snapshot of the store file, to restore later.
+    Snapshot { file: PathBuf },
+    /// Replace every ticket in the store file with the ones in a snapshot.
+    Restore { file: PathBuf },
+    /// Print a shell completion script.
+    Completions { shell: Shell },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Format {
+    Table,
+    Json,
+    /// JSON Lines: one ticket per line.
+    Jsonl,
+    Csv,
+    /// A checklist grouped by status.
+    Markdown,
+}
+
+fn parse_title(title: &str) -> Result<TicketTitle, anyhow::Error> {
+    Ok(TicketTitle::try_from(title)?)
+}
+
+fn parse_description(description: &str) -> Result<MarkdownDescription, anyhow::Error> {
+    Ok(MarkdownDescription::try_from(description)?)
+}
+
+fn parse_value(value: &str) -> Result<(String, String), anyhow::Error> {
+    match value.split_once('=') {
+        Some((name, value)) => Ok((name.trim().to_string(), value.to_string())),
+        None => bail!("Expected NAME=VALUE, e.g. `version=1.2.0`"),
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), anyhow::Error> {
+    let cli = Cli::parse();
+
+    if let Command::Completions { shell } = cli.command {
+        clap_complete::generate(shell, &mut Cli::command(), "tickets", &mut io::stdout());
+        return Ok(());
+    }
+
+    let backend = cli.backend.open()?;
+    let format = cli.format;
+    let mut stdout = io::stdout().lock();
+
+    match cli.command {
+        Command::Create {
+            title,
+            description,
+            project,
+        } => {
+            let draft = TicketDraft { title, description };
+            let id = match project {
+                Some(project) => backend.create_in(project, draft).await?,
+                None => backend.create(draft).await?,
+            };
+            writeln!(stdout, "{}", id)?;
+        }
+        Command::Show { id } => {
+            let ticket = backend.get(id).await?;
+            match format.unwrap_or(Format::Table) {
+                Format::Table => write_details(&mut stdout, &ticket)?,
+                format => write_tickets(&mut stdout, format, &[ticket])?,
+            }
+        }
+        Command::List {
+            status,
+            project,
+            query,
+            saved,
+        } => {
+            let mut tickets = match (query, saved, project) {
+                (Some(query), _, _) => backend.search(&query).await?,
+                (None, Some(name), _) => backend.run_query(&name).await?,
+                (None, None, Some(project)) => backend.list_in(project).await?,
+                (None, None, None) => backend.list().await?,
+            };
+            if let Some(project) = project {
+                tickets.retain(|ticket| ticket.id.project == project);
+            }
+            if let Some(status) = status {
+                tickets.retain(|ticket| ticket.status == status);
+            }
+            write_tickets(&mut stdout, format.unwrap_or(Format::Table), &tickets)?;
+        }
+        Command::Edit {
+            id,
+            title,
+            description,
+        } => {
+            let patch = TicketPatch {
+                id,
+                title,
+                description,
+                status: None,
+            };
+            backend.patch(patch).await?;
+        }
+        Command::Move { id, status } => {
+            let patch = TicketPatch {
+                id,
+                title: None,
+                description: None,
+                status: Some(status),
+            };
+            backend.patch(patch).await?;
+        }
+        Command::Delete { id } => backend.delete(id).await?,
+        Command::Trash => {
+            let trash = backend.trash().await?;
+            match format.unwrap_or(Format::Table) {
+                Format::Table => write_trash(&mut stdout, &trash)?,
+                Format::Json => {
+                    serde_json::to_writer_pretty(&mut stdout, &trash)?;
+                    writeln!(stdout)?;
+                }
+                format => bail!("The trash can't be shown as {:?}", format),
+            }
+        }
+        Command::Undelete { id } => backend.undelete(id).await?,
+        Command::Purge {
+            id,
+            older_than_days,
+        } => match (id, older_than_days) {
+            (_, Some(days)) => {
+                for id in backend.purge_expired(days).await? {
+                    writeln!(stdout, "{}", id)?;
+                }
+            }
+            (Some(id), None) => backend.purge(id).await?,
+            (None, None) => unreachable!("clap requires one of them"),
+        },
+        Command::Undo { count } => write_report(&mut stdout, backend.undo(count).await?)?,
+        Command::Redo { count } => write_report(&mut stdout, backend.redo(count).await?)?,
+        Command::Link { id, kind, other } => {
+            let link = Link {
+                from: id,
+                kind,
+                to: other,
+            };
+            backend.link(link).await?;
+        }
+        Command::Unlink { id, kind, other } => {
+            let link = Link {
+                from: id,
+                kind,
+                to: other,
+            };
+            backend.unlink(link).await?;
+        }
+        Command::Links { id } => {
+            let links = backend.links(id).await?;
+            match format.unwrap_or(Format::Table) {
+                Format::Table => write_links(&mut stdout, &links)?,
+                Format::Json => {
+                    serde_json::to_writer_pretty(&mut stdout, &links)?;
+                    writeln!(stdout)?;
+                }
+                format => bail!("Links can't be shown as {:?}", format),
+            }
+        }
+        Command::Comment { id, body } => {
+            let comment = backend.comment(id, CommentDraft { body }).await?;
+            writeln!(stdout, "{}", comment.id)?;
+        }
+        Command::Comments { id } => {
+            let comments = backend.comments(id).await?;
+            match format.unwrap_or(Format::Table) {
+                Format::Table => write_comments(&mut stdout, &comments)?,
+                Format::Json => {
+                    serde_json::to_writer_pretty(&mut stdout, &comments)?;
+                    writeln!(stdout)?;
+                }
+                format => bail!("Comments can't be shown as {:?}", format),
+            }
+        }
+        Command::Projects => {
+            let projects = backend.projects().await?;
+            match format.unwrap_or(Format::Table) {
+                Format::Table => write_projects(&mut stdout, &projects)?,
+                Format::Json => {
+                    serde_json::to_writer_pretty(&mut stdout, &projects)?;
+                    writeln!(stdout)?;
+                }
+                format => bail!("Projects can't be shown as {:?}", format),
+            }
+        }
+        Command::CreateProject {
+            key,
+            name,
+            description,
+        } => {
+            let draft = ProjectDraft {
+                key,
+                settings: ProjectSettings {
+                    description,
+                    ..ProjectSettings::new(&name)
+                },
+            };
+            let project = backend.create_project(draft).await?;
+            writeln!(stdout, "{}", project.key)?;
+        }
+        Command::Templates => {
+            let templates = backend.templates().await?;
+            match format.unwrap_or(Format::Table) {
+                Format::Table => write_templates(&mut stdout, &templates)?,
+                Format::Json => {
+                    serde_json::to_writer_pretty(&mut stdout, &templates)?;
+                    writeln!(stdout)?;
+                }
+                format => bail!("Templates can't be shown as {:?}", format),
+            }
+        }
+        Command::AddTemplate { file } => {
+            let template: Template = if file == Path::new("-") {
+                serde_json::from_reader(io::stdin())?
+            } else {
+                serde_json::from_reader(BufReader::new(File::open(&file)?))?
+            };
+            let template = backend.add_template(template).await?;
+            writeln!(stdout, "{}", template.name)?;
+        }
+        Command::RemoveTemplate { name } => backend.remove_template(&name).await?,
+        Command::FromTemplate {
+            name,
+            values,
+            project,
+        } => {
+            let request = TemplateRequest {
+                project,
+                values: values.into_iter().collect(),
+            };
+            for id in backend.create_from_template(&name, request).await? {
+                writeln!(stdout, "{}", id)?;
+            }
+        }
+        Command::Queries => {
+            let queries = backend.saved_queries().await?;
+            match format.unwrap_or(Format::Table) {
+                Format::Table => write_queries(&mut stdout, &queries)?,
+                Format::Json => {
+                    serde_json::to_writer_pretty(&mut stdout, &queries)?;
+                    writeln!(stdout)?;
+                }
+                format => bail!("Saved queries can't be shown as {:?}", format),
+            }
+        }
+        Command::SaveQuery { name, query } => {
+            backend.save_query(SavedQuery { name, query }).await?;
+        }
+        Command::RemoveQuery { name } => backend.remove_query(&name).await?,
+        Command::Report { days, windows } => {
+            let summary = backend.summary(Period::new(days, windows)?).await?;
+            match format.unwrap_or(Format::Table) {
+                Format::Table => write!(stdout, "{}", summary)?,
+                Format::Json => {
+                    serde_json::to_writer_pretty(&mut stdout, &summary)?;
+                    writeln!(stdout)?;
+                }
+                format => bail!("Reports can't be shown as {:?}", format),
+            }
+        }
+        Command::Import {
+            file,
+            dry_run,
+            keep_ids,
+            mappings,
+        } => {
+            let mut mapping = CsvMapping::default();
+            for pair in &mappings {
+                mapping.set(pair)?;
+            }
+            let parsed = read_tickets(&file, format, &mapping)?;
+            let valid = parsed.rows.len();
+            let options = ImportOptions {
+                ids: if keep_ids { Ids::Preserve } else { Ids::Remap },
+                dry_run,
+            };
+
+            let report = backend.import(parsed, options).await?;
+            if !report.is_ok() {
+                for error in &report.errors {
+                    eprintln!("{}", error);
+                }
+                bail!("{} errors, nothing was imported", report.errors.len());
+            }
+            for imported in &report.imported {
+                writeln!(stdout, "{}", imported.id)?;
+            }
+            if dry_run {
+                eprintln!("{} tickets are valid, nothing was imported", valid);
+            }
+        }
+        Command::Export { output } => {
+            let tickets = backend.list().await?;
+            let format = format.unwrap_or(Format::Json);
+            match output {
+                Some(path) => write_tickets(&mut File::create(path)?, format, &tickets)?,
+                None => write_tickets(&mut stdout, format, &tickets)?,
+            }
+        }
+        Command::Snapshot { file } => backend.snapshot().await?.save(&file)?,
+        Command::Restore { file } => backend.restore(Snapshot::load(&file)?).await?,
+        Command::Completions { .. } => unreachable!("handled before connecting"),
+    }
+
+    Ok(())
+}
+
+fn write_tickets(
+    out: &mut impl Write,
+    format: Format,
+    tickets: &[Ticket],
+) -> Result<(), anyhow::Error> {
+    let format = match format {
+        Format::Table => return Ok(write_table(out, tickets)?),
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut *out, tickets)?;
+            writeln!(out)?;
+            return Ok(());
+        }
+        Format::Jsonl => transfer::Format::Jsonl,
+        Format::Csv => transfer::Format::Csv,
+        Format::Markdown => transfer::Format::Markdown,
+    };
+    transfer::export(tickets.iter().cloned(), format, &CsvMapping::default(), out)
+}
+
+fn write_table(out: &mut impl Write, tickets: &[Ticket]) -> io::Result<()> {
+    let rows: Vec<[String; 4]> = tickets
+        .iter()
+        .map(|ticket| {
+            [
+                ticket.id.to_string(),
+                ticket.status.to_string(),
+                ticket.reporter.clone().unwrap_or_default(),
+                ticket.title.0.clone(),
+            ]
+        })
+        .collect();
+    let header = ["ID", "STATUS", "REPORTER", "TITLE"].map(String::from);
+
+    let mut widths = header.clone().map(|cell| cell.chars().count());
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    for row in std::iter::once(&header).chain(&rows) {
+        let [id, status, reporter, title] = row;
+        writeln!(
+            out,
+            "{:>id_width$}  {:<status_width$}  {:<reporter_width$}  {}",
+            id,
+            status,
+            reporter,
+            title,
+            id_width = widths[0],
+            status_width = widths[1],
+            reporter_width = widths[2],
+        )?;
+    }
+    Ok(())
+}
+
+fn write_details(out: &mut impl Write, ticket: &Ticket) -> io::Result<()> {
+    writeln!(out, "id:          {}", ticket.id)?;
+    writeln!(out, "title:       {}", ticket.title.0)?;
+    writeln!(out, "status:      {}", ticket.status)?;
+    if let Some(reporter) = &ticket.reporter {
+        writeln!(out, "reporter:    {}", reporter)?;
+    }
+    if let Some(editor) = &ticket.last_editor {
+        writeln!(out, "last editor: {}", editor)?;
+    }
+    writeln!(out)?;
+    writeln!(out, "{}", ticket.description)
+}
+
+fn write_links(out: &mut impl Write, links: &TicketLinks) -> io::Result<()> {
+    let ids = |ids: &[TicketId]| -> String {
+        let ids: Vec<String> = ids.iter().map(ToString::to_string).collect();
+        ids.join(", ")
+    };
+    let rows = [
+        ("parent", ids(links.parent.as_slice())),
+        ("children", ids(&links.children)),
+        ("blocks", ids(&links.blocks)),
+        ("blocked by", ids(&links.blocked_by)),
+        ("all blockers", ids(&links.all_blockers)),
+        ("duplicate of", ids(links.duplicate_of.as_slice())),
+        ("duplicates", ids(&links.duplicates)),
+        ("descendants", ids(&links.descendants)),
+    ];
+    for (name, ids) in rows {
+        if !ids.is_empty() {
+            writeln!(out, "{:<13} {}", format!("{}:", name), ids)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_comments(out: &mut impl Write, comments: &[Comment]) -> io::Result<()> {
+    for (i, comment) in comments.iter().enumerate() {
+        if i > 0 {
+            writeln!(out)?;
+        }
+        let edited = if comment.edited_at.is_some() {
+            " (edited)"
+        } else {
+            ""
+        };
+        writeln!(
+            out,
+            "#{} {} at {}{}",
+            comment.id,
+            comment.author,
+            comment.created_at.format("%Y-%m-%d %H:%M"),
+            edited
+        )?;
+        writeln!(out, "{}", comment.body)?;
+    }
+    Ok(())
+}
+
+fn write_projects(out: &mut impl Write, projects: &[Project]) -> io::Result<()> {
+    let width = projects
+        .iter()
+        .map(|project| project.key.as_str().len())
+        .chain(["KEY".len()])
+        .max()
+        .unwrap_or_default();
+    writeln!(out, "{:<width$}  {:>7}  NAME", "KEY", "NEXT")?;
+    for project in projects {
+        let archived = if project.settings.archived {
+            " (archived)"
+        } else {
+            ""
+        };
+        writeln!(
+            out,
+            "{:<width$}  {:>7}  {}{}",
+            project.key, project.next_number, project.settings.name, archived
+        )?;
+    }
+    Ok(())
+}
+
+fn write_trash(out: &mut impl Write, trash: &[Trashed]) -> io::Result<()> {
+    let width = trash
+        .iter()
+        .map(|trashed| trashed.ticket.id.to_string().len())
+        .chain(["ID".len()])
+        .max()
+        .unwrap_or_default();
+    writeln!(
+        out,
+        "{:<width$}  {:<16}  {:<12}  TITLE",
+        "ID", "DELETED", "BY"
+    )?;
+    for trashed in trash {
+        writeln!(
+            out,
+            "{:<width$}  {:<16}  {:<12}  {}",
+            trashed.ticket.id.to_string(),
+            trashed.deleted_at.format("%Y-%m-%d %H:%M"),
+            trashed.deleted_by,
+            trashed.ticket.title.0
+        )?;
+    }
+    Ok(())
+}
+
+fn write_templates(out: &mut impl Write, templates: &[Template]) -> io::Result<()> {
+    let width = templates
+        .iter()
+        .map(|template| template.name.len())
+        .chain(["NAME".len()])
+        .max()
+        .unwrap_or_default();
+    writeln!(out, "{:<width$}  {:>7}  PLACEHOLDERS", "NAME", "TICKETS")?;
+    for template in templates {
+        let placeholders: Vec<&str> = template.placeholders().into_iter().collect();
+        writeln!(
+            out,
+            "{:<width$}  {:>7}  {}",
+            template.name,
+            template.tickets.len(),
+            placeholders.join(", ")
+        )?;
+    }
+    Ok(())
+}
+
+fn write_queries(out: &mut impl Write, queries: &[SavedQuery]) -> io::Result<()> {
+    let width = queries
+        .iter()
+        .map(|query| query.name.len())
+        .chain(["NAME".len()])
+        .max()
+        .unwrap_or_default();
+    writeln!(out, "{:<width$}  QUERY", "NAME")?;
+    for query in queries {
+        writeln!(out, "{:<width$}  {}", query.name, query.query)?;
+    }
+    Ok(())
+}
+
+/// Prints the tickets changed, then fails if there was a conflict.
+fn write_report(out: &mut impl Write, report: Report) -> Result<(), anyhow::Error> {
+    for id in report.tickets {
+        writeln!(out, "{}", id)?;
+    }
+    match report.conflict {
+        Some(conflict) => bail!(conflict),
+        None => Ok(()),
+    }
+}
+
+/// Reads and validates tickets in `format`, or in the format matching the file extension.
+/// JSON arrays, as written by `export --format json`, are read as one ticket per element.
+fn read_tickets(
+    path: &Path,
+    format: Option<Format>,
+    mapping: &CsvMapping,
+) -> Result<Parsed, anyhow::Error> {
+    let format = match format {
+        Some(Format::Table) => bail!("Tables can't be imported"),
+        Some(Format::Json) => None,
+        Some(Format::Jsonl) => Some(transfer::Format::Jsonl),
+        Some(Format::Csv) => Some(transfer::Format::Csv),
+        Some(Format::Markdown) => Some(transfer::Format::Markdown),
+        None => transfer::Format::from_path(path),
+    };
+    let reader: Box<dyn Read> = if path == Path::new("-") {
+        Box::new(io::stdin())
+    } else {
+        Box::new(File::open(path)?)
+    };
+    let reader = BufReader::new(reader);
+
+    match format {
+        Some(format) => transfer::parse(reader, format, mapping),
+        None => {
+            let tickets: Vec<serde_json::Value> = serde_json::from_reader(reader)?;
+            let mut lines = vec![];
+            for ticket in tickets {
+                serde_json::to_writer(&mut lines, &ticket)?;
+                lines.push(b'\n');
+            }
+            transfer::parse(&lines[..], transfer::Format::Jsonl, mapping)
+        }
+    }
+}
@@ -0,0 +1,409 @@
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph};
+use ratatui::Frame;
+use ticket_fields::{MarkdownDescription, TicketTitle};
+
+use crate::backend::Backend;
+use crate::data::{Status, Ticket, TicketPatch};
+use crate::store::TicketId;
+
+/// One board column per status, in this order.
+pub const COLUMNS: [Status; 3] = [Status::ToDo, Status::InProgress, Status::Done];
+
+/// A kanban board over a `Backend`: the state of the `board` tool, updated by
+/// `handle_key` and rendered by `draw`.
+pub struct Board {
+    backend: Backend,
+    tickets: Vec<Ticket>,
+    column: usize,
+    /// The selected row of each column.
+    rows: [usize; COLUMNS.len()],
+    filter: String,
+    mode: Mode,
+    message: Option<Message>,
+    quit: bool,
+}
+
+enum Mode {
+    Browse,
+    Filter,
+    Edit(Editor),
+}
+
+/// Shown in the status bar until the next key press.
+pub enum Message {
+    Info(String),
+    Error(String),
+}
+
+/// The title and description of a ticket being edited.
+pub struct Editor {
+    pub id: TicketId,
+    pub field: Field,
+    pub title: String,
+    pub description: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Field {
+    Title,
+    Description,
+}
+
+impl Editor {
+    fn new(ticket: &Ticket) -> Self {
+        Self {
+            id: ticket.id,
+            field: Field::Title,
+            title: ticket.title.0.clone(),
+            description: ticket.description.to_string(),
+        }
+    }
+
+    pub fn title(&self) -> Result<TicketTitle, String> {
+        TicketTitle::try_from(self.title.as_str()).map_err(|e| e.to_string())
+    }
+
+    pub fn description(&self) -> Result<MarkdownDescription, String> {
+        MarkdownDescription::try_from(self.description.as_str()).map_err(|e| e.to_string())
+    }
+
+    fn input(&mut self) -> &mut String {
+        match self.field {
+            Field::Title => &mut self.title,
+            Field::Description => &mut self.description,
+        }
+    }
+}
+
+impl Board {
+    pub async fn new(backend: Backend) -> Result<Self, anyhow::Error> {
+        let mut board = Self {
+            backend,
+            tickets: vec![],
+            column: 0,
+            rows: [0; COLUMNS.len()],
+            filter: String::new(),
+            mode: Mode::Browse,
+            message: None,
+            quit: false,
+        };
+        board.refresh().await?;
+        Ok(board)
+    }
+
+    /// Reloads every ticket from the backend.
+    pub async fn refresh(&mut self) -> Result<(), anyhow::Error> {
+        self.tickets = self.backend.list().await?;
+        self.clamp_rows();
+        Ok(())
+    }
+
+    /// The tickets shown in a column: those with its status that match the filter.
+    pub fn column(&self, index: usize) -> Vec<&Ticket> {
+        let filter = self.filter.to_lowercase();
+        self.tickets
+            .iter()
+            .filter(|ticket| ticket.status == COLUMNS[index])
+            .filter(|ticket| {
+                ticket.title.0.to_lowercase().contains(&filter)
+                    || ticket.description.as_str().to_lowercase().contains(&filter)
+            })
+            .collect()
+    }
+
+    pub fn focused_column(&self) -> usize {
+        self.column
+    }
+
+    pub fn selected(&self) -> Option<&Ticket> {
+        self.column(self.column)
+            .get(self.rows[self.column])
+            .copied()
+    }
+
+    pub fn filter(&self) -> &str {
+        &self.filter
+    }
+
+    pub fn editor(&self) -> Option<&Editor> {
+        match &self.mode {
+            Mode::Edit(editor) => Some(editor),
+            _ => None,
+        }
+    }
+
+    pub fn message(&self) -> Option<&Message> {
+        self.message.as_ref()
+    }
+
+    pub fn should_quit(&self) -> bool {
+        self.quit
+    }
+
+    pub async fn handle_key(&mut self, key: KeyEvent) {
+        self.message = None;
+        if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            self.quit = true;
+            return;
+        }
+
+        let result = match self.mode {
+            Mode::Browse => self.browse(key).await,
+            Mode::Filter => {
+                self.edit_filter(key);
+                Ok(())
+            }
+            Mode::Edit(_) => self.edit_ticket(key).await,
+        };
+        if let Err(e) = result {
+            self.message = Some(Message::Error(e.to_string()));
+        }
+    }
+
+    async fn browse(&mut self, key: KeyEvent) -> Result<(), anyhow::Error> {
+        match key.code {
+            KeyCode::Char('q') => self.quit = true,
+            KeyCode::Esc if self.filter.is_empty() => self.quit = true,
+            KeyCode::Esc => {
+                self.filter.clear();
+                self.clamp_rows();
+            }
+            KeyCode::Left | KeyCode::Char('h') => self.column = self.column.saturating_sub(1),
+            KeyCode::Right | KeyCode::Char('l') => {
+                self.column = (self.column + 1).min(COLUMNS.len() - 1)
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.rows[self.column] = self.rows[self.column].saturating_sub(1)
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                let last = self.column(self.column).len().saturating_sub(1);
+                self.rows[self.column] = (self.rows[self.column] + 1).min(last);
+            }
+            KeyCode::Char('<') | KeyCode::Char('H') => self.move_selected(-1).await?,
+            KeyCode::Char('>') | KeyCode::Char('L') => self.move_selected(1).await?,
+            KeyCode::Char('e') | KeyCode::Enter => {
+                if let Some(ticket) = self.selected() {
+                    self.mode = Mode::Edit(Editor::new(ticket));
+                }
+            }
+            KeyCode::Char('/') => self.mode = Mode::Filter,
+            KeyCode::Char('r') => {
+                self.refresh().await?;
+                self.message = Some(Message::Info(String::from("Refreshed")));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Moves the selected ticket `offset` columns over, and follows it there.
+    async fn move_selected(&mut self, offset: isize) -> Result<(), anyhow::Error> {
+        let Some(ticket) = self.selected() else {
+            return Ok(());
+        };
+        let Some(target) = self.column.checked_add_signed(offset) else {
+            return Ok(());
+        };
+        if target >= COLUMNS.len() {
+            return Ok(());
+        }
+
+        let id = ticket.id;
+        let patch = TicketPatch {
+            id,
+            title: None,
+            description: None,
+            status: Some(COLUMNS[target]),
+        };
+        self.backend.patch(patch).await?;
+        self.refresh().await?;
+
+        self.column = target;
+        if let Some(row) = self
+            .column(target)
+            .iter()
+            .position(|ticket| ticket.id == id)
+        {
+            self.rows[target] = row;
+        }
+        Ok(())
+    }
+
+    fn edit_filter(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Enter => self.mode = Mode::Browse,
+            KeyCode::Esc => {
+                self.filter.clear();
+                self.mode = Mode::Browse;
+            }
+            KeyCode::Backspace => {
+                self.filter.pop();
+            }
+            KeyCode::Char(c) => self.filter.push(c),
+            _ => {}
+        }
+        self.clamp_rows();
+    }
+
+    async fn edit_ticket(&mut self, key: KeyEvent) -> Result<(), anyhow::Error> {
+        let Mode::Edit(editor) = &mut self.mode else {
+            return Ok(());
+        };
+        match key.code {
+            KeyCode::Esc => self.mode = Mode::Browse,
+            KeyCode::Tab | KeyCode::BackTab | KeyCode::Up | KeyCode::Down => {
+                editor.field = match editor.field {
+                    Field::Title => Field::Description,
+                    Field::Description => Field::Title,
+                };
+            }
+            KeyCode::Backspace => {
+                editor.input().pop();
+            }
+            KeyCode::Char(c) => editor.input().push(c),
+            KeyCode::Enter => {
+                let (Ok(title), Ok(description)) = (editor.title(), editor.description()) else {
+                    self.message = Some(Message::Error(String::from("Fix the errors first")));
+                    return Ok(());
+                };
+                let patch = TicketPatch {
+                    id: editor.id,
+                    title: Some(title),
+                    description: Some(description),
+                    status: None,
+                };
+                // Stay in the editor if saving fails, so nothing typed is lost.
+                self.backend.patch(patch).await?;
+                self.mode = Mode::Browse;
+                self.refresh().await?;
+                self.message = Some(Message::Info(String::from("Saved")));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn clamp_rows(&mut self) {
+        for index in 0..COLUMNS.len() {
+            let last = self.column(index).len().saturating_sub(1);
+            self.rows[index] = self.rows[index].min(last);
+        }
+    }
+
+    pub fn draw(&self, frame: &mut Frame) {
+        let [board, status] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+        let columns = Layout::horizontal([Constraint::Ratio(1, 3); COLUMNS.len()]).split(board);
+
+        for (index, area) in columns.iter().enumerate() {
+            self.draw_column(frame, index, *area);
+        }
+        frame.render_widget(self.status_line(), status);
+
+        if let Mode::Edit(editor) = &self.mode {
+            draw_editor(frame, editor);
+        }
+    }
+
+    fn draw_column(&self, frame: &mut Frame, index: usize, area: Rect) {
+        let tickets = self.column(index);
+        let focused = index == self.column;
+
+        let items: Vec<ListItem> = tickets
+            .iter()
+            .map(|ticket| ListItem::new(format!("{} {}", ticket.id, ticket.title.0)))
+            .collect();
+        let border = if focused {
+            Style::default().fg(Color::Yellow)
+        } else {
+            Style::default()
+        };
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_style(border)
+            .title(format!(" {} ({}) ", COLUMNS[index], tickets.len()));
+        let list = List::new(items)
+            .block(block)
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+
+        let mut state = ListState::default();
+        if focused && !tickets.is_empty() {
+            state.select(Some(self.rows[index]));
+        }
+        frame.render_stateful_widget(list, area, &mut state);
+    }
+
+    fn status_line(&self) -> Line<'_> {
+        match (&self.message, &self.mode) {
+            (Some(Message::Error(error)), _) => {
+                Line::styled(error.as_str(), Style::default().fg(Color::Red))
+            }
+            (Some(Message::Info(info)), _) => Line::raw(info.as_str()),
+            (None, Mode::Filter) => Line::raw(format!("/{}", self.filter)),
+            (None, Mode::Edit(_)) => Line::raw("tab: switch field  enter: save  esc: cancel"),
+            (None, Mode::Browse) => {
+                let mut spans = vec![Span::raw(
+                    "←→ column  ↑↓ ticket  <> move  e edit  / filter  r refresh  q quit",
+                )];
+                if !self.filter.is_empty() {
+                    spans.push(Span::styled(
+                        format!("  filter: {}", self.filter),
+                        Style::default().fg(Color::Cyan),
+                    ));
+                }
+                Line::from(spans)
+            }
+        }
+    }
+}
+
+fn draw_editor(frame: &mut Frame, editor: &Editor) {
+    let area = centered(frame.area(), 60, 10);
+    frame.render_widget(Clear, area);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(" Edit {} ", editor.id));
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let fields = [
+        (Field::Title, "Title", &editor.title, editor.title().err()),
+        (
+            Field::Description,
+            "Description",
+            &editor.description,
+            editor.description().err(),
+        ),
+    ];
+    let mut lines = vec![];
+    for (field, label, value, error) in fields {
+        let label_style = if field == editor.field {
+            Style::default().add_modifier(Modifier::BOLD)
+        } else {
+            Style::default()
+        };
+        lines.push(Line::styled(label, label_style));
+        lines.push(Line::raw(format!("> {}", value)));
+        lines.push(match error {
+            Some(error) => Line::styled(error, Style::default().fg(Color::Red)),
+            None => Line::styled("✓", Style::default().fg(Color::Green)),
+        });
+    }
+    frame.render_widget(Paragraph::new(lines), inner);
+}
+
+/// A `width` by `height` rectangle in the middle of `area`, shrunk to fit.
+fn centered(area: Rect, width: u16, height: u16) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    }
+}
@@ -0,0 +1,638 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::io;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::attachments::Attachment;
+use crate::comments::{Comment, CommentDraft, CommentId};
+use crate::data::{DescriptionFormat, ErrorBody, Ticket, TicketDraft, TicketPatch};
+use crate::helpers;
+use crate::history::Report;
+use crate::links::{Link, LinkRequest, TicketLinks};
+use crate::projects::{Project, ProjectDraft, ProjectKey, ProjectPatch};
+use crate::query::SavedQuery;
+use crate::reports::{Period, Summary};
+use crate::store::TicketId;
+use crate::templates::{Template, TemplateRequest};
+use crate::trash::Trashed;
+
+/// Everything that can go wrong when calling the ticket API.
+/// Error responses from the server map to the variant for their status.
+#[derive(Debug, thiserror::Error)]
+pub enum ClientError {
+    #[error("Bad request: {0}")]
+    BadRequest(String),
+    #[error("Missing or invalid bearer token")]
+    Unauthorized,
+    #[error("Not allowed")]
+    Forbidden,
+    #[error("Not found")]
+    NotFound,
+    /// The request conflicts with the state of the store,
+    /// e.g. a ticket that's still blocked was moved to `Done`.
+    #[error("{0}")]
+    Conflict(String),
+    /// An upload is over the server's size limit.
+    #[error("{0}")]
+    TooLarge(String),
+    #[error("Rate limited, retry in {0:?}")]
+    RateLimited(Duration),
+    #[error("Unexpected {status} response: {message}")]
+    Unexpected { status: u16, message: String },
+    #[error("Malformed response: {0}")]
+    Protocol(String),
+    #[error("No response within {0:?}")]
+    Timeout(Duration),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+#[derive(Clone, Debug)]
+pub struct ClientConfig {
+    /// Limit for a single attempt: connecting, sending the request and
+    /// reading the whole response.
+    pub timeout: Duration,
+    /// How many times a request is retried. Idempotent requests are retried
+    /// after timeouts and I/O errors; any request is retried after a 429,
+    /// since the server rejected it before doing anything.
+    pub retries: u32,
+    /// Wait before the first retry, doubled for each one after it.
+    pub backoff: Duration,
+    /// Most idle connections kept open for reuse.
+    pub max_idle: usize,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(10),
+            retries: 3,
+            backoff: Duration::from_millis(100),
+            max_idle: 8,
+        }
+    }
+}
+
+/// A client for the ticket API, authenticated with a bearer token.
+/// Connections are kept alive and reused between calls.
+pub struct Client {
+    addr: String,
+    token: String,
+    config: ClientConfig,
+    idle: Mutex<Vec<TcpStream>>,
+}
+
+impl Client {
+    pub fn new(addr: impl Into<String>, token: impl Into<String>) -> Self {
+        Self::with_config(addr, token, ClientConfig::default())
+    }
+
+    pub fn with_config(
+        addr: impl Into<String>,
+        token: impl Into<String>,
+        config: ClientConfig,
+    ) -> Self {
+        Self {
+            addr: addr.into(),
+            token: token.into(),
+            config,
+            idle: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub async fn create(&self, draft: &TicketDraft) -> Result<TicketId, ClientError> {
+        self.send("POST", "/tickets", Some(draft), false)
+            .await?
+            .decode(201)
+    }
+
+    pub async fn get(&self, id: TicketId) -> Result<Ticket, ClientError> {
+        let path = format!("/tickets/{}", id);
+        self.send::<()>("GET", &path, None, true).await?.decode(200)
+    }
+
+    /// A ticket's description in `format`, rendered by the server.
+    pub async fn description(
+        &self,
+        id: TicketId,
+        format: DescriptionFormat,
+    ) -> Result<String, ClientError> {
+        let path = format!("/tickets/{}?description={}", id, format);
+        let ticket: serde_json::Value = self
+            .send::<()>("GET", &path, None, true)
+            .await?
+            .decode(200)?;
+        match ticket["description"].as_str() {
+            Some(description) => Ok(description.to_string()),
+            None => Err(ClientError::Protocol(String::from("Missing description"))),
+        }
+    }
+
+    pub async fn list(&self) -> Result<Vec<Ticket>, ClientError> {
+        self.send::<()>("GET", "/tickets", None, true)
+            .await?
+            .decode(200)
+    }
+
+    /// The tickets matching `query`, in the language described in `query`.
+    pub async fn search(&self, query: &str) -> Result<Vec<Ticket>, ClientError> {
+        let path = format!("/tickets?q={}", encode_query(query));
+        self.send::<()>("GET", &path, None, true).await?.decode(200)
+    }
+
+    /// Patches are idempotent: applying the same one twice has the same effect as once.
+    pub async fn patch(&self, patch: &TicketPatch) -> Result<(), ClientError> {
+        let path = format!("/tickets/{}", patch.id);
+        self.send("PATCH", &path, Some(patch), true)
+            .await?
+            .expect(204)
+    }
+
+    /// Retried like the other idempotent calls, so if the response to a
+    /// successful delete is lost, the retry fails with `NotFound`.
+    pub async fn delete(&self, id: TicketId) -> Result<(), ClientError> {
+        let path = format!("/tickets/{}", id);
+        self.send::<()>("DELETE", &path, None, true)
+            .await?
+            .expect(204)
+    }
+
+    /// Sends a request, retrying as described on `ClientConfig::retries`.
+    /// Returns the first successful response.
+    pub async fn links(&self, id: TicketId) -> Result<TicketLinks, ClientError> {
+        let path = format!("/tickets/{}/links", id);
+        self.send::<()>("GET", &path, None, true).await?.decode(200)
+    }
+
+    pub async fn link(&self, link: Link) -> Result<(), ClientError> {
+        let path = format!("/tickets/{}/links", link.from);
+        let body = LinkRequest {
+            kind: link.kind,
+            to: link.to,
+        };
+        self.send("POST", &path, Some(&body), true)
+            .await?
+            .expect(204)
+    }
+
+    pub async fn unlink(&self, link: Link) -> Result<(), ClientError> {
+        let path = format!("/tickets/{}/links", link.from);
+        let body = LinkRequest {
+            kind: link.kind,
+            to: link.to,
+        };
+        self.send("DELETE", &path, Some(&body), true)
+            .await?
+            .expect(204)
+    }
+
+    pub async fn comments(&self, id: TicketId) -> Result<Vec<Comment>, ClientError> {
+        let path = format!("/tickets/{}/comments", id);
+        self.send::<()>("GET", &path, None, true).await?.decode(200)
+    }
+
+    pub async fn comment(
+        &self,
+        id: TicketId,
+        draft: &CommentDraft,
+    ) -> Result<Comment, ClientError> {
+        let path = format!("/tickets/{}/comments", id);
+        self.send("POST", &path, Some(draft), false)
+            .await?
+            .decode(201)
+    }
+
+    pub async fn edit_comment(
+        &self,
+        id: TicketId,
+        comment: CommentId,
+        draft: &CommentDraft,
+    ) -> Result<(), ClientError> {
+        let path = format!("/tickets/{}/comments/{}", id, comment);
+        self.send("PATCH", &path, Some(draft), true)
+            .await?
+            .expect(204)
+    }
+
+    pub async fn delete_comment(
+        &self,
+        id: TicketId,
+        comment: CommentId,
+    ) -> Result<(), ClientError> {
+        let path = format!("/tickets/{}/comments/{}", id, comment);
+        self.send::<()>("DELETE", &path, None, true)
+            .await?
+            .expect(204)
+    }
+
+    pub async fn attachments(&self, id: TicketId) -> Result<Vec<Attachment>, ClientError> {
+        let path = format!("/tickets/{}/attachments", id);
+        self.send::<()>("GET", &path, None, true).await?.decode(200)
+    }
+
+    /// Attachments are stored by their contents, so uploading the same file
+    /// twice is harmless and uploads are retried like idempotent calls.
+    pub async fn upload(
+        &self,
+        id: TicketId,
+        name: &str,
+        contents: &[u8],
+    ) -> Result<Attachment, ClientError> {
+        let path = format!("/tickets/{}/attachments?name={}", id, encode_query(name));
+        let body = Body {
+            bytes: contents.to_vec(),
+            content_type: "application/octet-stream",
+        };
+        self.send_body("POST", &path, body, true).await?.decode(201)
+    }
+
+    pub async fn download(&self, id: TicketId, digest: &str) -> Result<Vec<u8>, ClientError> {
+        let path = format!("/tickets/{}/attachments/{}", id, digest);
+        let reply = self.send::<()>("GET", &path, None, true).await?;
+        reply.expect(200)?;
+        Ok(reply.body)
+    }
+
+    pub async fn projects(&self) -> Result<Vec<Project>, ClientError> {
+        self.send::<()>("GET", "/projects", None, true)
+            .await?
+            .decode(200)
+    }
+
+    pub async fn project(&self, key: ProjectKey) -> Result<Project, ClientError> {
+        let path = format!("/projects/{}", key);
+        self.send::<()>("GET", &path, None, true).await?.decode(200)
+    }
+
+    /// Not retried after timeouts: a retry of a successful call would fail
+    /// with `Conflict`, as the project exists by then.
+    pub async fn create_project(&self, draft: &ProjectDraft) -> Result<Project, ClientError> {
+        self.send("POST", "/projects", Some(draft), false)
+            .await?
+            .decode(201)
+    }
+
+    pub async fn update_project(
+        &self,
+        key: ProjectKey,
+        patch: &ProjectPatch,
+    ) -> Result<Project, ClientError> {
+        let path = format!("/projects/{}", key);
+        self.send("PATCH", &path, Some(patch), true)
+            .await?
+            .decode(200)
+    }
+
+    pub async fn project_tickets(&self, key: ProjectKey) -> Result<Vec<Ticket>, ClientError> {
+        let path = format!("/projects/{}/tickets", key);
+        self.send::<()>("GET", &path, None, true).await?.decode(200)
+    }
+
+    /// Creates a ticket in project `key` rather than the default one.
+    pub async fn create_in(
+        &self,
+        key: ProjectKey,
+        draft: &TicketDraft,
+    ) -> Result<TicketId, ClientError> {
+        let path = format!("/projects/{}/tickets", key);
+        self.send("POST", &path, Some(draft), false)
+            .await?
+            .decode(201)
+    }
+
+    pub async fn trash(&self) -> Result<Vec<Trashed>, ClientError> {
+        self.send::<()>("GET", "/trash", None, true)
+            .await?
+            .decode(200)
+    }
+
+    /// Returns the restored ticket. Retried like `delete`, with the same caveat.
+    pub async fn undelete(&self, id: TicketId) -> Result<Ticket, ClientError> {
+        let path = format!("/trash/{}/restore", id);
+        self.send::<()>("POST", &path, None, true)
+            .await?
+            .decode(200)
+    }
+
+    pub async fn purge(&self, id: TicketId) -> Result<(), ClientError> {
+        let path = format!("/trash/{}", id);
+        self.send::<()>("DELETE", &path, None, true)
+            .await?
+            .expect(204)
+    }
+
+    /// Purges every ticket deleted at least `days` days ago, and returns their ids.
+    pub async fn purge_expired(&self, days: u32) -> Result<Vec<TicketId>, ClientError> {
+        let path = format!("/trash?older_than_days={}", days);
+        self.send::<()>("DELETE", &path, None, true)
+            .await?
+            .decode(200)
+    }
+
+    /// Undoes the last `count` operations of the token's user.
+    /// Not retried, as that could undo more than `count`.
+    pub async fn undo(&self, count: usize) -> Result<Report, ClientError> {
+        let path = format!("/undo?count={}", count);
+        self.send::<()>("POST", &path, None, false)
+            .await?
+            .decode(200)
+    }
+
+    /// Redoes the last `count` operations undone, like `undo`.
+    pub async fn redo(&self, count: usize) -> Result<Report, ClientError> {
+        let path = format!("/redo?count={}", count);
+        self.send::<()>("POST", &path, None, false)
+            .await?
+            .decode(200)
+    }
+
+    pub async fn templates(&self) -> Result<Vec<Template>, ClientError> {
+        self.send::<()>("GET", "/templates", None, true)
+            .await?
+            .decode(200)
+    }
+
+    /// Not retried after timeouts, like `create_project`.
+    pub async fn create_template(&self, template: &Template) -> Result<Template, ClientError> {
+        self.send("POST", "/templates", Some(template), false)
+            .await?
+            .decode(201)
+    }
+
+    pub async fn template(&self, name: &str) -> Result<Template, ClientError> {
+        let path = format!("/templates/{}", name);
+        self.send::<()>("GET", &path, None, true).await?.decode(200)
+    }
+
+    pub async fn delete_template(&self, name: &str) -> Result<(), ClientError> {
+        let path = format!("/templates/{}", name);
+        self.send::<()>("DELETE", &path, None, true)
+            .await?
+            .expect(204)
+    }
+
+    /// Creates every ticket of template `name` and returns their ids, in order.
+    pub async fn create_from_template(
+        &self,
+        name: &str,
+        request: &TemplateRequest,
+    ) -> Result<Vec<TicketId>, ClientError> {
+        let path = format!("/templates/{}/tickets", name);
+        self.send("POST", &path, Some(request), false)
+            .await?
+            .decode(201)
+    }
+
+    pub async fn saved_queries(&self) -> Result<Vec<SavedQuery>, ClientError> {
+        self.send::<()>("GET", "/queries", None, true)
+            .await?
+            .decode(200)
+    }
+
+    /// Not retried after timeouts, like `create_template`.
+    pub async fn save_query(&self, query: &SavedQuery) -> Result<SavedQuery, ClientError> {
+        self.send("POST", "/queries", Some(query), false)
+            .await?
+            .decode(201)
+    }
+
+    pub async fn delete_query(&self, name: &str) -> Result<(), ClientError> {
+        let path = format!("/queries/{}", name);
+        self.send::<()>("DELETE", &path, None, true)
+            .await?
+            .expect(204)
+    }
+
+    /// The tickets matching saved query `name`.
+    pub async fn run_query(&self, name: &str) -> Result<Vec<Ticket>, ClientError> {
+        let path = format!("/queries/{}/tickets", name);
+        self.send::<()>("GET", &path, None, true).await?.decode(200)
+    }
+
+    pub async fn summary(&self, period: Period) -> Result<Summary, ClientError> {
+        let path = format!(
+            "/reports/summary?days={}&windows={}",
+            period.days(),
+            period.windows()
+        );
+        self.send::<()>("GET", &path, None, true).await?.decode(200)
+    }
+
+    async fn send<T: Serialize>(
+        &self,
+        method: &str,
+        path: &str,
+        body: Option<&T>,
+        idempotent: bool,
+    ) -> Result<Reply, ClientError> {
+        let body = Body {
+            bytes: match body {
+                Some(body) => serde_json::to_vec(body).expect("API types always serialize"),
+                None => Vec::new(),
+            },
+            content_type: "application/json",
+        };
+        self.send_body(method, path, body, idempotent).await
+    }
+
+    async fn send_body(
+        &self,
+        method: &str,
+        path: &str,
+        body: Body,
+        idempotent: bool,
+    ) -> Result<Reply, ClientError> {
+        let request = self.request(method, path, &body);
+
+        let mut backoff = self.config.backoff;
+        let mut attempt = 0;
+        loop {
+            let result = match self.attempt(&request).await {
+                Ok(reply) if reply.status >= 400 => Err(reply.into_error()),
+                result => result,
+            };
+
+            let wait = match &result {
+                Err(ClientError::RateLimited(retry_after)) => Some(backoff.max(*retry_after)),
+                Err(ClientError::Io(_) | ClientError::Timeout(_)) if idempotent => Some(backoff),
+                _ => None,
+            };
+            match wait {
+                Some(wait) if attempt < self.config.retries => {
+                    tokio::time::sleep(wait).await;
+                    attempt += 1;
+                    backoff *= 2;
+                }
+                _ => return result,
+            }
+        }
+    }
+
+    fn request(&self, method: &str, path: &str, body: &Body) -> Vec<u8> {
+        let mut request = format!(
+            "{} {} HTTP/1.1\r\nHost: {}\r\nAuthorization: Bearer {}\r\nContent-Length: {}\r\n",
+            method,
+            path,
+            self.addr,
+            self.token,
+            body.bytes.len()
+        );
+        if !body.bytes.is_empty() {
+            request.push_str(&format!("Content-Type: {}\r\n", body.content_type));
+        }
+        request.push_str("\r\n");
+
+        let mut request = request.into_bytes();
+        request.extend_from_slice(&body.bytes);
+        request
+    }
+
+    async fn attempt(&self, request: &[u8]) -> Result<Reply, ClientError> {
+        let attempt = async {
+            // The server may have closed an idle connection since we last used it.
+            // It can't have seen the request then, so resending it is safe.
+            let pooled = self.idle.lock().unwrap().pop();
+            if let Some(mut stream) = pooled {
+                if let Some(reply) = exchange(&mut stream, request).await? {
+                    self.release(stream, &reply);
+                    return Ok(reply);
+                }
+            }
+
+            let mut stream = TcpStream::connect(&self.addr).await?;
+            match exchange(&mut stream, request).await? {
+                Some(reply) => {
+                    self.release(stream, &reply);
+                    Ok(reply)
+                }
+                None => Err(ClientError::Io(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "The server closed the connection without responding",
+                ))),
+            }
+        };
+
+        tokio::time::timeout(self.config.timeout, attempt)
+            .await
+            .map_err(|_| ClientError::Timeout(self.config.timeout))?
+    }
+
+    fn release(&self, stream: TcpStream, reply: &Reply) {
+        let mut idle = self.idle.lock().unwrap();
+        if reply.reusable && idle.len() < self.config.max_idle {
+            idle.push(stream);
+        }
+    }
+}
+
+/// A response of any status.
+struct Body {
+    bytes: Vec<u8>,
+    content_type: &'static str,
+}
+
+/// Percent-encodes everything but unreserved characters, for query strings.
+fn encode_query(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+            encoded.push(char::from(byte));
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+struct Reply {
+    status: u16,
+    body: Vec<u8>,
+    retry_after: Option<Duration>,
+    /// Whether the connection can carry another request.
+    reusable: bool,
+}
+
+impl Reply {
+    fn into_error(self) -> ClientError {
+        let message = serde_json::from_slice::<ErrorBody>(&self.body)
+            .map(|body| body.error)
+            .unwrap_or_else(|_| String::from_utf8_lossy(&self.body).into_owned());
+        match self.status {
+            400 => ClientError::BadRequest(message),
+            401 => ClientError::Unauthorized,
+            403 => ClientError::Forbidden,
+            404 => ClientError::NotFound,
+            409 => ClientError::Conflict(message),
+            413 => ClientError::TooLarge(message),
+            429 => ClientError::RateLimited(self.retry_after.unwrap_or_default()),
+            status => ClientError::Unexpected { status, message },
+        }
+    }
+
+    fn expect(&self, status: u16) -> Result<(), ClientError> {
+        if self.status == status {
+            Ok(())
+        } else {
+            Err(ClientError::Unexpected {
+                status: self.status,
+                message: format!("Expected {}", status),
+            })
+        }
+    }
+
+    fn decode<T: DeserializeOwned>(self, status: u16) -> Result<T, ClientError> {
+        self.expect(status)?;
+        serde_json::from_slice(&self.body).map_err(|e| ClientError::Protocol(e.to_string()))
+    }
+}
+
+/// Writes `request` and reads the response to it.
+/// `None` means the connection was closed before any of the response arrived.
+async fn exchange(stream: &mut TcpStream, request: &[u8]) -> Result<Option<Reply>, ClientError> {
+    if stream.write_all(request).await.is_err() {
+        return Ok(None);
+    }
+
+    let mut buffer = Vec::new();
+    loop {
+        let mut chunk = [0; 4096];
+        let n = match stream.read(&mut chunk).await {
+            Ok(0) | Err(_) if buffer.is_empty() => return Ok(None),
+            Ok(0) => {
+                let message = "The connection closed in the middle of a response";
+                return Err(ClientError::Protocol(message.into()));
+            }
+            Ok(n) => n,
+            Err(e) => return Err(e.into()),
+        };
+        buffer.extend_from_slice(&chunk[..n]);
+
+        let mut headers = [httparse::EMPTY_HEADER; helpers::MAX_HEADERS];
+        let mut response = httparse::Response::new(&mut headers);
+        let head = match response.parse(&buffer) {
+            Ok(httparse::Status::Complete(head)) => head,
+            Ok(httparse::Status::Partial) => continue,
+            Err(e) => return Err(ClientError::Protocol(e.to_string())),
+        };
+        let length = helpers::content_length(response.headers)
+            .map_err(|e| ClientError::Protocol(e.to_string()))?;
+        if buffer.len() < head + length {
+            continue;
+        }
+
+        let retry_after = helpers::header(response.headers, "retry-after")
+            .and_then(|value| std::str::from_utf8(value).ok()?.trim().parse().ok())
+            .map(Duration::from_secs);
+        let close = helpers::header(response.headers, "connection")
+            .is_some_and(|value| value.eq_ignore_ascii_case(b"close"));
+        return Ok(Some(Reply {
+            status: response.code.unwrap_or_default(),
+            body: buffer[head..head + length].to_vec(),
+            retry_after,
+            reusable: !close && buffer.len() == head + length,
+        }));
+    }
+}
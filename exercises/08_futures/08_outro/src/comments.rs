@@ -0,0 +1,134 @@
+use anyhow::anyhow;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use ticket_fields::MarkdownDescription;
+
+use crate::store::TicketId;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct CommentId(pub u64);
+
+impl fmt::Display for CommentId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for CommentId {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let id = s.parse::<u64>().map_err(|_| anyhow!("Failed parsing"))?;
+
+        Ok(CommentId(id))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Comment {
+    pub id: CommentId,
+    pub ticket: TicketId,
+    /// Name of the user who posted the comment.
+    pub author: String,
+    /// Markdown, checked like ticket descriptions.
+    pub body: MarkdownDescription,
+    pub created_at: DateTime<Utc>,
+    /// When the body was last changed, if ever.
+    pub edited_at: Option<DateTime<Utc>>,
+}
+
+/// The body of `POST /tickets/{id}/comments` and `PATCH /tickets/{id}/comments/{comment}`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommentDraft {
+    pub body: MarkdownDescription,
+}
+
+/// The comment threads of every ticket in a store.
+/// Comment ids are unique across tickets and never reused.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Comments {
+    comments: BTreeMap<CommentId, Comment>,
+    counter: u64,
+}
+
+impl Comments {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a comment to `ticket`'s thread. Doesn't check that the ticket exists,
+    /// see `TicketStore::add_comment`.
+    pub fn add(&mut self, ticket: TicketId, author: &str, body: MarkdownDescription) -> CommentId {
+        let id = CommentId(self.counter);
+        self.counter += 1;
+        let comment = Comment {
+            id,
+            ticket,
+            author: author.to_string(),
+            body,
+            created_at: Utc::now(),
+            edited_at: None,
+        };
+        self.comments.insert(id, comment);
+        id
+    }
+
+    pub fn get(&self, id: CommentId) -> Option<&Comment> {
+        self.comments.get(&id)
+    }
+
+    /// The thread of `ticket`, oldest first.
+    pub fn thread(&self, ticket: TicketId) -> Vec<Comment> {
+        self.comments
+            .values()
+            .filter(|comment| comment.ticket == ticket)
+            .cloned()
+            .collect()
+    }
+
+    /// Replaces the body of a comment. Returns `false` if there is no such comment.
+    pub fn edit(&mut self, id: CommentId, body: MarkdownDescription) -> bool {
+        match self.comments.get_mut(&id) {
+            Some(comment) => {
+                comment.body = body;
+                comment.edited_at = Some(Utc::now());
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn remove(&mut self, id: CommentId) -> Option<Comment> {
+        self.comments.remove(&id)
+    }
+
+    /// Removes the whole thread of `ticket`.
+    pub fn remove_ticket(&mut self, ticket: TicketId) {
+        self.comments.retain(|_, comment| comment.ticket != ticket);
+    }
+
+    /// Every comment, ordered by id.
+    pub fn iter(&self) -> impl Iterator<Item = &Comment> {
+        self.comments.values()
+    }
+
+    /// The id the next added comment will get.
+    pub fn next_id(&self) -> CommentId {
+        CommentId(self.counter)
+    }
+
+    /// Inserts a comment with its id as is, e.g. from a backup.
+    /// Later comments get higher ids.
+    pub fn restore(&mut self, comment: Comment) {
+        self.counter = self.counter.max(comment.id.0 + 1);
+        self.comments.insert(comment.id, comment);
+    }
+
+    /// Makes sure comments added from now on get `next` or a higher id.
+    pub fn reserve_ids(&mut self, next: CommentId) {
+        self.counter = self.counter.max(next.0);
+    }
+}
@@ -1,19 +1,28 @@
 use crate::store::TicketId;
+use anyhow::anyhow;
 use serde::{Deserialize, Serialize};
-use ticket_fields::{TicketDescription, TicketTitle};
+use std::fmt;
+use std::str::FromStr;
+use ticket_fields::{MarkdownDescription, Policy, TicketDescription, TicketTitle};
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Ticket {
     pub id: TicketId,
     pub title: TicketTitle,
-    pub description: TicketDescription,
+    pub description: MarkdownDescription,
     pub status: Status,
+    /// Name of the user who created the ticket.
+    #[serde(default)]
+    pub reporter: Option<String>,
+    /// Name of the user who last patched the ticket.
+    #[serde(default)]
+    pub last_editor: Option<String>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct TicketDraft {
     pub title: TicketTitle,
-    pub description: TicketDescription,
+    pub description: MarkdownDescription,
 }
 
 impl TicketDraft {
@@ -22,22 +31,146 @@ impl TicketDraft {
             title: TicketTitle(title),
             description: TicketDescription(
                 description.unwrap_or_else(|| String::from("Default description"))
-            ),
+            )
+            .into(),
         }
     }
+
+    /// Checks the draft against `policy`, on top of the built-in rules it
+    /// was built with, reporting every rule it breaks.
+    pub fn validate_with(self, policy: &Policy) -> Result<Self, anyhow::Error> {
+        Ok(Self {
+            title: TicketTitle::validate_with(&self.title.0, policy)?,
+            description: MarkdownDescription::validate_with(self.description.as_str(), policy)?,
+        })
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct TicketPatch {
     pub id: TicketId,
     pub title: Option<TicketTitle>,
-    pub description: Option<TicketDescription>,
+    pub description: Option<MarkdownDescription>,
     pub status: Option<Status>,
 }
 
+impl TicketPatch {
+    /// Checks the new title and description against `policy`, like
+    /// `TicketDraft::validate_with`.
+    pub fn validate_with(self, policy: &Policy) -> Result<Self, anyhow::Error> {
+        let title = self
+            .title
+            .map(|title| TicketTitle::validate_with(&title.0, policy))
+            .transpose()?;
+        let description = self
+            .description
+            .map(|description| MarkdownDescription::validate_with(description.as_str(), policy))
+            .transpose()?;
+        Ok(Self {
+            title,
+            description,
+            ..self
+        })
+    }
+}
+
+/// How the API returns descriptions, chosen with `?description=` on `GET` requests.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DescriptionFormat {
+    /// The Markdown source, as stored.
+    #[default]
+    Markdown,
+    /// Sanitized HTML, see `MarkdownDescription::to_html`.
+    Html,
+    /// Plain text, without formatting.
+    Text,
+}
+
+impl DescriptionFormat {
+    pub const ALL: [DescriptionFormat; 3] = [
+        DescriptionFormat::Markdown,
+        DescriptionFormat::Html,
+        DescriptionFormat::Text,
+    ];
+
+    pub fn render(self, description: &MarkdownDescription) -> String {
+        match self {
+            DescriptionFormat::Markdown => description.to_string(),
+            DescriptionFormat::Html => description.to_html(),
+            DescriptionFormat::Text => description.to_plain_text(),
+        }
+    }
+}
+
+impl fmt::Display for DescriptionFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let format = match self {
+            DescriptionFormat::Markdown => "markdown",
+            DescriptionFormat::Html => "html",
+            DescriptionFormat::Text => "text",
+        };
+        f.write_str(format)
+    }
+}
+
+impl FromStr for DescriptionFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|format| format.to_string().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                anyhow!(
+                    "Unknown description format `{}`, expected markdown, html or text",
+                    s
+                )
+            })
+    }
+}
+
 #[derive(Clone, Debug, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Status {
     ToDo,
     InProgress,
     Done,
 }
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = match self {
+            Status::ToDo => "ToDo",
+            Status::InProgress => "InProgress",
+            Status::Done => "Done",
+        };
+        f.write_str(status)
+    }
+}
+
+impl FromStr for Status {
+    type Err = anyhow::Error;
+
+    /// Case-insensitive, ignoring `-`, `_` and spaces: `todo`, `in-progress` and `Done` all parse.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let normalized: String = s
+            .chars()
+            .filter(|c| !matches!(c, '-' | '_' | ' '))
+            .collect::<String>()
+            .to_lowercase();
+        match normalized.as_str() {
+            "todo" => Ok(Status::ToDo),
+            "inprogress" => Ok(Status::InProgress),
+            "done" => Ok(Status::Done),
+            _ => Err(anyhow!(
+                "Unknown status `{}`, expected one of ToDo, InProgress or Done",
+                s
+            )),
+        }
+    }
+}
+
+/// The body of every error response.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorBody {
+    pub error: String,
+}
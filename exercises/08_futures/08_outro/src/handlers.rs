@@ -1,56 +1,161 @@
-use anyhow::Ok;
+use chrono::Utc;
+use serde::Serialize;
 use std::sync::Arc;
-use tokio::io::AsyncWriteExt;
-use tokio::net::TcpStream;
+use ticket_fields::Policy;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::sync::RwLock;
 
-use crate::data::{Ticket, TicketDraft, TicketPatch};
+use crate::attachments::{Attachment, AttachmentDir, AttachmentError};
+use crate::auth::{Action, IssuedToken, TokenRequest, TokenStore, User};
+use crate::comments::{CommentDraft, CommentId};
+use crate::data::{DescriptionFormat, Ticket, TicketDraft, TicketPatch};
 use crate::helpers;
+use crate::links::{Blocked, Link, LinkError, LinkRequest, TicketLinks};
+use crate::openapi;
+use crate::projects::{ProjectDraft, ProjectError, ProjectKey, ProjectPatch};
+use crate::query::{Query, SavedQuery, SavedQueryError};
+use crate::reports::{InvalidPeriod, Period};
+use crate::server::State;
+use crate::Stream;
 use crate::store::{TicketId, TicketStore};
-
+use crate::templates::{Template, TemplateError, TemplateRequest};
+use crate::trash::Trashed;
 
 pub async fn create_ticket<'a>(
-    socket: &mut TcpStream,
+    socket: &mut impl Stream,
     store: Arc<RwLock<TicketStore>>,
+    policy: &Policy,
+    user: &User,
     buffer: &'a [u8],
     request: &mut httparse::Request<'a, 'a>,
     parse_result: Option<httparse::Status<usize>>,
 ) -> Result<(), anyhow::Error> {
+    if !user.can(Action::Create) {
+        return respond(socket, helpers::Response::<()>::Forbidden).await;
+    }
+
     let body = helpers::parse_body(socket, request, buffer, parse_result).await?;
-    let draft: TicketDraft = serde_json::from_str(&body)?;
+    let draft = match serde_json::from_str::<TicketDraft>(&body) {
+        Ok(draft) => draft.validate_with(policy),
+        Err(e) => Err(e.into()),
+    };
+    let draft = match draft {
+        Ok(draft) => draft,
+        Err(e) => return respond(socket, helpers::Response::<()>::BadRequest(e.to_string())).await,
+    };
 
-    let id: TicketId = store.write().await.add_ticket(draft);
+    let id: TicketId = store.write().await.add_ticket_by(draft, user);
     let response = helpers::build_response(helpers::Response::Created(id)).await;
 
     socket.write_all(&response).await?;
     Ok(())
 }
 
+/// Every ticket, or only those matching `query` (`?q=`) if there is one.
+pub async fn list_tickets(
+    socket: &mut impl Stream,
+    store: Arc<RwLock<TicketStore>>,
+    user: &User,
+    query: Option<String>,
+    format: DescriptionFormat,
+) -> Result<(), anyhow::Error> {
+    if !user.can(Action::Read) {
+        return respond(socket, helpers::Response::<()>::Forbidden).await;
+    }
+    let query = match query.map(|query| query.parse::<Query>()).transpose() {
+        Ok(query) => query,
+        Err(e) => return respond(socket, helpers::Response::<()>::BadRequest(e.to_string())).await,
+    };
+
+    let store = store.read().await;
+    let tickets: Vec<serde_json::Value> = match &query {
+        Some(query) => store
+            .matching(query)
+            .iter()
+            .map(|ticket_lock| render(&ticket_lock.read().unwrap(), format))
+            .collect(),
+        None => store
+            .tickets
+            .values()
+            .map(|ticket_lock| render(&ticket_lock.read().unwrap(), format))
+            .collect(),
+    };
+    drop(store);
+    respond(socket, helpers::Response::Ok(tickets)).await
+}
+
 pub async fn get_ticket(
-    socket: &mut TcpStream,
+    socket: &mut impl Stream,
     store: Arc<RwLock<TicketStore>>,
+    user: &User,
     id: TicketId,
+    format: DescriptionFormat,
 ) -> Result<(), anyhow::Error> {
-    let ticket: Ticket = {
+    if !user.can(Action::Read) {
+        return respond(socket, helpers::Response::<()>::Forbidden).await;
+    }
+
+    let ticket: Option<Ticket> = {
         let store_guard = store.read().await;
-        let ticket_lock = store_guard.get(id).unwrap();
-        let ticket_guard = ticket_lock.read().ok().unwrap();
-        ticket_guard.clone()
+        store_guard
+            .get(id)
+            .map(|ticket_lock| ticket_lock.read().unwrap().clone())
     };
 
-    let response = helpers::build_response(helpers::Response::Ok(ticket)).await;
-    socket.write_all(&response).await?;
-    Ok(())
+    match ticket {
+        Some(ticket) => respond(socket, helpers::Response::Ok(render(&ticket, format))).await,
+        None => respond(socket, helpers::Response::<()>::NotFound).await,
+    }
+}
+
+/// Why a patch was not applied.
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum PatchError {
+    #[error("No such ticket")]
+    NotFound,
+    #[error("Not allowed")]
+    Forbidden,
+    /// The ticket can't be done while these tickets blocking it are open.
+    #[error("{}", PatchError::blocked_message(.0))]
+    Blocked(Vec<TicketId>),
+}
+
+impl PatchError {
+    /// The message for `Blocked`.
+    pub fn blocked_message(blockers: &[TicketId]) -> String {
+        Blocked(blockers.to_vec()).to_string()
+    }
+}
+
+impl From<Blocked> for PatchError {
+    fn from(Blocked(blockers): Blocked) -> Self {
+        PatchError::Blocked(blockers)
+    }
 }
 
 pub async fn update_ticket_endpoint(
     patch: TicketPatch,
     store: Arc<RwLock<TicketStore>>,
-) -> Option<()> {
-    let store_guard = store.write().await;
-    let ticket_lock = store_guard.get(patch.id)?;
-    let mut ticket_guard = ticket_lock.write().ok()?;
+    user: &User,
+) -> Result<(), PatchError> {
+    let mut store_guard = store.write().await;
+    let ticket_lock = store_guard.get(patch.id).ok_or(PatchError::NotFound)?;
+    let blocked = match patch.status {
+        Some(status) => store_guard.check_status(patch.id, status),
+        None => Ok(()),
+    };
+    let mut ticket_guard = ticket_lock.write().unwrap();
+
+    let edits_content = patch.title.is_some() || patch.description.is_some();
+    if edits_content && !user.can(Action::Edit(&ticket_guard)) {
+        return Err(PatchError::Forbidden);
+    }
+    if patch.status.is_some() && !user.can(Action::ChangeStatus) {
+        return Err(PatchError::Forbidden);
+    }
+    blocked?;
 
+    let before = ticket_guard.clone();
     if let Some(title) = patch.title {
         ticket_guard.title = title;
     }
@@ -63,21 +168,965 @@ pub async fn update_ticket_endpoint(
         ticket_guard.status = status;
     }
 
-    Some(())
+    ticket_guard.last_editor = Some(user.name.clone());
+    drop(ticket_guard);
+    store_guard.edited(before, user);
+
+    Ok(())
+}
+
+pub async fn patch_ticket(
+    socket: &mut impl Stream,
+    store: Arc<RwLock<TicketStore>>,
+    policy: &Policy,
+    user: &User,
+    id: TicketId,
+    body: &str,
+) -> Result<(), anyhow::Error> {
+    let patch = match serde_json::from_str::<TicketPatch>(body) {
+        Ok(patch) => patch.validate_with(policy),
+        Err(e) => Err(e.into()),
+    };
+    let patch = match patch {
+        Ok(patch) => patch,
+        Err(e) => return respond(socket, helpers::Response::<()>::BadRequest(e.to_string())).await,
+    };
+    if patch.id != id {
+        let message = String::from("The id in the body doesn't match the one in the path");
+        return respond(socket, helpers::Response::<()>::BadRequest(message)).await;
+    }
+    match update_ticket_endpoint(patch, store, user).await {
+        Ok(()) => respond(socket, helpers::Response::NO_CONTENT).await,
+        Err(PatchError::NotFound) => respond(socket, helpers::Response::<()>::NotFound).await,
+        Err(PatchError::Forbidden) => respond(socket, helpers::Response::<()>::Forbidden).await,
+        Err(PatchError::Blocked(blockers)) => {
+            let message = PatchError::blocked_message(&blockers);
+            respond(socket, helpers::Response::<()>::Conflict(message)).await
+        }
+    }
+}
+
+pub async fn delete_ticket(
+    socket: &mut impl Stream,
+    store: Arc<RwLock<TicketStore>>,
+    user: &User,
+    id: TicketId,
+) -> Result<(), anyhow::Error> {
+    if !user.can(Action::Delete) {
+        return respond(socket, helpers::Response::<()>::Forbidden).await;
+    }
+
+    match store.write().await.delete(id, user) {
+        Some(_) => respond(socket, helpers::Response::NO_CONTENT).await,
+        None => respond(socket, helpers::Response::<()>::NotFound).await,
+    }
+}
+
+pub async fn get_links(
+    socket: &mut impl Stream,
+    store: Arc<RwLock<TicketStore>>,
+    user: &User,
+    id: TicketId,
+) -> Result<(), anyhow::Error> {
+    if !user.can(Action::Read) {
+        return respond(socket, helpers::Response::<()>::Forbidden).await;
+    }
+
+    let links = {
+        let store_guard = store.read().await;
+        store_guard
+            .get(id)
+            .map(|_| TicketLinks::of(store_guard.links(), id))
+    };
+    match links {
+        Some(links) => respond(socket, helpers::Response::Ok(links)).await,
+        None => respond(socket, helpers::Response::<()>::NotFound).await,
+    }
+}
+
+pub async fn add_link<'a>(
+    socket: &mut impl Stream,
+    store: Arc<RwLock<TicketStore>>,
+    user: &User,
+    id: TicketId,
+    buffer: &'a [u8],
+    request: &mut httparse::Request<'a, 'a>,
+    parse_result: Option<httparse::Status<usize>>,
+) -> Result<(), anyhow::Error> {
+    let body = helpers::parse_body(socket, request, buffer, parse_result).await?;
+    change_link(socket, store, user, id, &body, true).await
+}
+
+pub async fn remove_link<'a>(
+    socket: &mut impl Stream,
+    store: Arc<RwLock<TicketStore>>,
+    user: &User,
+    id: TicketId,
+    buffer: &'a [u8],
+    request: &mut httparse::Request<'a, 'a>,
+    parse_result: Option<httparse::Status<usize>>,
+) -> Result<(), anyhow::Error> {
+    let body = helpers::parse_body(socket, request, buffer, parse_result).await?;
+    change_link(socket, store, user, id, &body, false).await
+}
+
+/// Adds (`add`) or removes the link from the ticket `id` described by `body`.
+/// Both need edit rights on that ticket.
+async fn change_link(
+    socket: &mut impl Stream,
+    store: Arc<RwLock<TicketStore>>,
+    user: &User,
+    id: TicketId,
+    body: &str,
+    add: bool,
+) -> Result<(), anyhow::Error> {
+    let LinkRequest { kind, to } = match serde_json::from_str(body) {
+        Ok(request) => request,
+        Err(e) => return respond(socket, helpers::Response::<()>::BadRequest(e.to_string())).await,
+    };
+    let link = Link { from: id, kind, to };
+
+    let mut store_guard = store.write().await;
+    let allowed = match store_guard.get(id) {
+        Some(ticket) => user.can(Action::Edit(&ticket.read().unwrap())),
+        None => return respond(socket, helpers::Response::<()>::NotFound).await,
+    };
+    if !allowed {
+        return respond(socket, helpers::Response::<()>::Forbidden).await;
+    }
+
+    if !add {
+        return match store_guard.unlink(&link) {
+            true => respond(socket, helpers::Response::NO_CONTENT).await,
+            false => respond(socket, helpers::Response::<()>::NotFound).await,
+        };
+    }
+    match store_guard.link(link) {
+        Ok(_) => respond(socket, helpers::Response::NO_CONTENT).await,
+        Err(LinkError::NotFound(_)) => respond(socket, helpers::Response::<()>::NotFound).await,
+        Err(e) => respond(socket, helpers::Response::<()>::Conflict(e.to_string())).await,
+    }
+}
+
+pub async fn list_comments(
+    socket: &mut impl Stream,
+    store: Arc<RwLock<TicketStore>>,
+    user: &User,
+    id: TicketId,
+) -> Result<(), anyhow::Error> {
+    if !user.can(Action::Read) {
+        return respond(socket, helpers::Response::<()>::Forbidden).await;
+    }
+
+    let thread = {
+        let store_guard = store.read().await;
+        store_guard
+            .get(id)
+            .map(|_| store_guard.comments().thread(id))
+    };
+    match thread {
+        Some(thread) => respond(socket, helpers::Response::Ok(thread)).await,
+        None => respond(socket, helpers::Response::<()>::NotFound).await,
+    }
+}
+
+pub async fn add_comment<'a>(
+    socket: &mut impl Stream,
+    store: Arc<RwLock<TicketStore>>,
+    user: &User,
+    id: TicketId,
+    buffer: &'a [u8],
+    request: &mut httparse::Request<'a, 'a>,
+    parse_result: Option<httparse::Status<usize>>,
+) -> Result<(), anyhow::Error> {
+    if !user.can(Action::Comment) {
+        return respond(socket, helpers::Response::<()>::Forbidden).await;
+    }
+
+    let body = helpers::parse_body(socket, request, buffer, parse_result).await?;
+    let draft: CommentDraft = match serde_json::from_str(&body) {
+        Ok(draft) => draft,
+        Err(e) => return respond(socket, helpers::Response::<()>::BadRequest(e.to_string())).await,
+    };
+
+    let comment = {
+        let mut store_guard = store.write().await;
+        store_guard
+            .add_comment(id, user, draft.body)
+            .and_then(|comment| store_guard.comments().get(comment).cloned())
+    };
+    match comment {
+        Some(comment) => respond(socket, helpers::Response::Created(comment)).await,
+        None => respond(socket, helpers::Response::<()>::NotFound).await,
+    }
 }
 
-pub async fn patch_ticket<'a>(
-    socket: &mut TcpStream,
+/// Replaces the body of comment `comment` on ticket `id` with the one in `body`.
+pub async fn edit_comment(
+    socket: &mut impl Stream,
     store: Arc<RwLock<TicketStore>>,
+    user: &User,
+    id: TicketId,
+    comment: CommentId,
+    body: &str,
+) -> Result<(), anyhow::Error> {
+    let draft: CommentDraft = match serde_json::from_str(body) {
+        Ok(draft) => draft,
+        Err(e) => return respond(socket, helpers::Response::<()>::BadRequest(e.to_string())).await,
+    };
+
+    let mut store_guard = store.write().await;
+    let allowed = match store_guard.comments().get(comment) {
+        Some(existing) if existing.ticket == id => user.can(Action::EditComment(existing)),
+        _ => return respond(socket, helpers::Response::<()>::NotFound).await,
+    };
+    if !allowed {
+        return respond(socket, helpers::Response::<()>::Forbidden).await;
+    }
+    store_guard.comments_mut().edit(comment, draft.body);
+    respond(socket, helpers::Response::NO_CONTENT).await
+}
+
+pub async fn delete_comment(
+    socket: &mut impl Stream,
+    store: Arc<RwLock<TicketStore>>,
+    user: &User,
+    id: TicketId,
+    comment: CommentId,
+) -> Result<(), anyhow::Error> {
+    let mut store_guard = store.write().await;
+    let allowed = match store_guard.comments().get(comment) {
+        Some(existing) if existing.ticket == id => user.can(Action::EditComment(existing)),
+        _ => return respond(socket, helpers::Response::<()>::NotFound).await,
+    };
+    if !allowed {
+        return respond(socket, helpers::Response::<()>::Forbidden).await;
+    }
+    store_guard.comments_mut().remove(comment);
+    respond(socket, helpers::Response::NO_CONTENT).await
+}
+
+pub async fn list_attachments(
+    socket: &mut impl Stream,
+    store: Arc<RwLock<TicketStore>>,
+    user: &User,
+    id: TicketId,
+) -> Result<(), anyhow::Error> {
+    if !user.can(Action::Read) {
+        return respond(socket, helpers::Response::<()>::Forbidden).await;
+    }
+
+    let attachments = {
+        let store_guard = store.read().await;
+        store_guard.get(id).map(|_| store_guard.attachments(id))
+    };
+    match attachments {
+        Some(attachments) => respond(socket, helpers::Response::Ok(attachments)).await,
+        None => respond(socket, helpers::Response::<()>::NotFound).await,
+    }
+}
+
+/// Streams the request body into `state.attachments`, then records it as
+/// attached to ticket `id` under `name`. Everything is checked before reading
+/// the body, which has to keep arriving within `state.keep_alive`. Only
+/// recording it waits for the database, see `Database::lock`.
+pub async fn upload_attachment(
+    socket: &mut impl Stream,
+    state: &State,
+    user: &User,
+    id: TicketId,
+    name: Result<Option<String>, anyhow::Error>,
+    body: helpers::BodyStart<'_>,
+) -> Result<(), anyhow::Error> {
+    if !user.can(Action::Comment) {
+        return respond_closing(socket, helpers::Response::<()>::Forbidden).await;
+    }
+    let name = name.map_err(|e| e.to_string()).and_then(|name| {
+        name.map(|name| check_file_name(&name).map(|()| name))
+            .transpose()
+    });
+    let name = match name {
+        Ok(Some(name)) => name,
+        Err(message) => {
+            return respond_closing(socket, helpers::Response::<()>::BadRequest(message)).await
+        }
+        Ok(None) => {
+            let message = String::from("Missing the `name` query parameter");
+            return respond_closing(socket, helpers::Response::<()>::BadRequest(message)).await;
+        }
+    };
+    if state.store.read().await.get(id).is_none() {
+        return respond_closing(socket, helpers::Response::<()>::NotFound).await;
+    }
+    if let Err(e) = state.attachments.check_size(body.length) {
+        return respond_closing(
+            socket,
+            helpers::Response::<()>::PayloadTooLarge(e.to_string()),
+        )
+        .await;
+    }
+
+    let contents = helpers::ReadTimeout::new(body.buffered.chain(&mut *socket), state.keep_alive);
+    let stored = match state.attachments.write(contents, body.length).await {
+        Ok(stored) => stored,
+        Err(e @ AttachmentError::Truncated { .. }) => {
+            return respond_closing(socket, helpers::Response::<()>::BadRequest(e.to_string()))
+                .await
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let attachment = Attachment {
+        ticket: id,
+        digest: stored.digest,
+        name,
+        mime: stored.mime.to_string(),
+        size: stored.size,
+        uploaded_by: user.name.clone(),
+        uploaded_at: Utc::now(),
+    };
+    let lock = match &state.database {
+        Some(database) => Some(database.lock().await),
+        None => None,
+    };
+    // The ticket may have been deleted while the upload was in progress.
+    if !state.store.write().await.attach(attachment.clone()) {
+        return respond_closing(socket, helpers::Response::<()>::NotFound).await;
+    }
+    if let Some(database) = &state.database {
+        if let Err(e) = database.write(&state.store).await {
+            eprintln!("Failed to write to the database; err = {:?}", e);
+            return respond_closing(socket, helpers::Response::<()>::InternalServerError).await;
+        }
+    }
+    drop(lock);
+    respond_closing(socket, helpers::Response::Created(attachment)).await
+}
+
+/// Like `respond`, but tells the client the connection closes afterwards,
+/// since the rest of a refused upload can't be told apart from a next request.
+async fn respond_closing<T: Serialize>(
+    socket: &mut impl Stream,
+    response: helpers::Response<T>,
+) -> Result<(), anyhow::Error> {
+    let mut response = helpers::build_response(response).await;
+    let status_line = response
+        .windows(2)
+        .position(|window| window == b"\r\n")
+        .map_or(0, |end| end + 2);
+    response.splice(status_line..status_line, *b"Connection: close\r\n");
+    socket.write_all(&response).await?;
+    Ok(())
+}
+
+/// File names are shown to users and sent back in headers, so they have to be
+/// a single path component without control characters.
+fn check_file_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.len() > 255 {
+        return Err(String::from("File names must be 1 to 255 bytes long"));
+    }
+    if name == "." || name == ".." || name.contains(['/', '\\']) {
+        return Err(String::from("File names can't contain path separators"));
+    }
+    if name.chars().any(char::is_control) {
+        return Err(String::from("File names can't contain control characters"));
+    }
+    Ok(())
+}
+
+/// Streams the contents of an attachment, as the type it was sniffed as.
+pub async fn download_attachment(
+    socket: &mut impl Stream,
+    store: Arc<RwLock<TicketStore>>,
+    attachments: &AttachmentDir,
+    user: &User,
+    id: TicketId,
+    digest: &str,
+) -> Result<(), anyhow::Error> {
+    if !user.can(Action::Read) {
+        return respond(socket, helpers::Response::<()>::Forbidden).await;
+    }
+
+    let attachment = store.read().await.attachment(id, digest).cloned();
+    let Some(attachment) = attachment else {
+        return respond(socket, helpers::Response::<()>::NotFound).await;
+    };
+    let mut file = match attachments.open(&attachment.digest).await {
+        Ok(file) => file,
+        Err(_) => return respond(socket, helpers::Response::<()>::NotFound).await,
+    };
+
+    let file_name = attachment.name.replace(['"', '\\'], "_");
+    let head = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\n\
+         Content-Disposition: attachment; filename=\"{}\"\r\n\
+         X-Content-Type-Options: nosniff\r\n\r\n",
+        attachment.mime, attachment.size, file_name
+    );
+    socket.write_all(head.as_bytes()).await?;
+    tokio::io::copy(&mut file, socket).await?;
+    Ok(())
+}
+
+pub async fn issue_token<'a>(
+    socket: &mut impl Stream,
+    tokens: Arc<RwLock<TokenStore>>,
+    user: &User,
     buffer: &'a [u8],
     request: &mut httparse::Request<'a, 'a>,
     parse_result: Option<httparse::Status<usize>>,
 ) -> Result<(), anyhow::Error> {
-    let body = helpers::parse_body(socket, request, &buffer, parse_result).await?;
-    let patch: TicketPatch = serde_json::from_str(&body)?;
-    if let Some(()) = update_ticket_endpoint(patch, store).await {
-        let response = helpers::build_response(helpers::Response::NO_CONTENT).await;
-        socket.write_all(&response).await?;
+    if !user.can(Action::ManageTokens) {
+        return respond(socket, helpers::Response::<()>::Forbidden).await;
+    }
+
+    let body = helpers::parse_body(socket, request, buffer, parse_result).await?;
+    let TokenRequest { name, role } = match serde_json::from_str(&body) {
+        Ok(request) => request,
+        Err(e) => return respond(socket, helpers::Response::<()>::BadRequest(e.to_string())).await,
     };
+
+    let token = tokens.write().await.generate(User::new(&name, role));
+    match token {
+        Ok(token) => {
+            let issued = IssuedToken { token, name, role };
+            respond(socket, helpers::Response::Created(issued)).await
+        }
+        Err(e) => respond(socket, helpers::Response::<()>::BadRequest(e.to_string())).await,
+    }
+}
+
+pub async fn list_projects(
+    socket: &mut impl Stream,
+    store: Arc<RwLock<TicketStore>>,
+    user: &User,
+) -> Result<(), anyhow::Error> {
+    if !user.can(Action::Read) {
+        return respond(socket, helpers::Response::<()>::Forbidden).await;
+    }
+
+    let projects: Vec<_> = store.read().await.projects().cloned().collect();
+    respond(socket, helpers::Response::Ok(projects)).await
+}
+
+pub async fn create_project<'a>(
+    socket: &mut impl Stream,
+    store: Arc<RwLock<TicketStore>>,
+    user: &User,
+    buffer: &'a [u8],
+    request: &mut httparse::Request<'a, 'a>,
+    parse_result: Option<httparse::Status<usize>>,
+) -> Result<(), anyhow::Error> {
+    if !user.can(Action::ManageProjects) {
+        return respond(socket, helpers::Response::<()>::Forbidden).await;
+    }
+
+    let body = helpers::parse_body(socket, request, buffer, parse_result).await?;
+    let draft: ProjectDraft = match serde_json::from_str(&body) {
+        Ok(draft) => draft,
+        Err(e) => return respond(socket, helpers::Response::<()>::BadRequest(e.to_string())).await,
+    };
+
+    let project = store.write().await.create_project(draft).cloned();
+    match project {
+        Ok(project) => respond(socket, helpers::Response::Created(project)).await,
+        Err(e) => respond_project_error(socket, e).await,
+    }
+}
+
+pub async fn get_project(
+    socket: &mut impl Stream,
+    store: Arc<RwLock<TicketStore>>,
+    user: &User,
+    key: ProjectKey,
+) -> Result<(), anyhow::Error> {
+    if !user.can(Action::Read) {
+        return respond(socket, helpers::Response::<()>::Forbidden).await;
+    }
+
+    let project = store.read().await.project(key).cloned();
+    match project {
+        Some(project) => respond(socket, helpers::Response::Ok(project)).await,
+        None => respond(socket, helpers::Response::<()>::NotFound).await,
+    }
+}
+
+pub async fn patch_project<'a>(
+    socket: &mut impl Stream,
+    store: Arc<RwLock<TicketStore>>,
+    user: &User,
+    key: ProjectKey,
+    buffer: &'a [u8],
+    request: &mut httparse::Request<'a, 'a>,
+    parse_result: Option<httparse::Status<usize>>,
+) -> Result<(), anyhow::Error> {
+    if !user.can(Action::ManageProjects) {
+        return respond(socket, helpers::Response::<()>::Forbidden).await;
+    }
+
+    let body = helpers::parse_body(socket, request, buffer, parse_result).await?;
+    let patch: ProjectPatch = match serde_json::from_str(&body) {
+        Ok(patch) => patch,
+        Err(e) => return respond(socket, helpers::Response::<()>::BadRequest(e.to_string())).await,
+    };
+
+    let project = store.write().await.update_project(key, patch).cloned();
+    match project {
+        Ok(project) => respond(socket, helpers::Response::Ok(project)).await,
+        Err(e) => respond_project_error(socket, e).await,
+    }
+}
+
+pub async fn list_project_tickets(
+    socket: &mut impl Stream,
+    store: Arc<RwLock<TicketStore>>,
+    user: &User,
+    key: ProjectKey,
+    format: DescriptionFormat,
+) -> Result<(), anyhow::Error> {
+    if !user.can(Action::Read) {
+        return respond(socket, helpers::Response::<()>::Forbidden).await;
+    }
+
+    let tickets: Option<Vec<serde_json::Value>> = {
+        let store_guard = store.read().await;
+        store_guard.project(key).map(|_| {
+            store_guard
+                .tickets_in(key)
+                .iter()
+                .map(|ticket_lock| render(&ticket_lock.read().unwrap(), format))
+                .collect()
+        })
+    };
+    match tickets {
+        Some(tickets) => respond(socket, helpers::Response::Ok(tickets)).await,
+        None => respond(socket, helpers::Response::<()>::NotFound).await,
+    }
+}
+
+pub async fn create_project_ticket(
+    socket: &mut impl Stream,
+    store: Arc<RwLock<TicketStore>>,
+    policy: &Policy,
+    user: &User,
+    key: ProjectKey,
+    body: &str,
+) -> Result<(), anyhow::Error> {
+    if !user.can(Action::Create) {
+        return respond(socket, helpers::Response::<()>::Forbidden).await;
+    }
+
+    let draft = match serde_json::from_str::<TicketDraft>(body) {
+        Ok(draft) => draft.validate_with(policy),
+        Err(e) => Err(e.into()),
+    };
+    let draft = match draft {
+        Ok(draft) => draft,
+        Err(e) => return respond(socket, helpers::Response::<()>::BadRequest(e.to_string())).await,
+    };
+
+    let id = store.write().await.add_ticket_to(key, draft, user);
+    match id {
+        Ok(id) => respond(socket, helpers::Response::Created(id)).await,
+        Err(e) => respond_project_error(socket, e).await,
+    }
+}
+
+/// Unknown projects are 404s, and attempts that clash with existing ones are 409s.
+async fn respond_project_error(
+    socket: &mut impl Stream,
+    error: ProjectError,
+) -> Result<(), anyhow::Error> {
+    let response = match error {
+        ProjectError::NotFound(_) => helpers::Response::<()>::NotFound,
+        ProjectError::Exists(_) | ProjectError::Archived(_) => {
+            helpers::Response::Conflict(error.to_string())
+        }
+        ProjectError::ArchiveDefault | ProjectError::InvalidName => {
+            helpers::Response::BadRequest(error.to_string())
+        }
+    };
+    respond(socket, response).await
+}
+
+pub async fn list_trash(
+    socket: &mut impl Stream,
+    store: Arc<RwLock<TicketStore>>,
+    user: &User,
+) -> Result<(), anyhow::Error> {
+    if !user.can(Action::Read) {
+        return respond(socket, helpers::Response::<()>::Forbidden).await;
+    }
+
+    let trash: Vec<Trashed> = store.read().await.trash().cloned().collect();
+    respond(socket, helpers::Response::Ok(trash)).await
+}
+
+pub async fn restore_ticket(
+    socket: &mut impl Stream,
+    store: Arc<RwLock<TicketStore>>,
+    user: &User,
+    id: TicketId,
+    format: DescriptionFormat,
+) -> Result<(), anyhow::Error> {
+    if !user.can(Action::Delete) {
+        return respond(socket, helpers::Response::<()>::Forbidden).await;
+    }
+
+    let ticket = store
+        .write()
+        .await
+        .undelete(id)
+        .map(|ticket_lock| render(&ticket_lock.read().unwrap(), format));
+    match ticket {
+        Some(ticket) => respond(socket, helpers::Response::Ok(ticket)).await,
+        None => respond(socket, helpers::Response::<()>::NotFound).await,
+    }
+}
+
+pub async fn purge_ticket(
+    socket: &mut impl Stream,
+    store: Arc<RwLock<TicketStore>>,
+    user: &User,
+    id: TicketId,
+) -> Result<(), anyhow::Error> {
+    if !user.can(Action::Delete) {
+        return respond(socket, helpers::Response::<()>::Forbidden).await;
+    }
+
+    match store.write().await.purge(id) {
+        Some(_) => respond(socket, helpers::Response::NO_CONTENT).await,
+        None => respond(socket, helpers::Response::<()>::NotFound).await,
+    }
+}
+
+/// Purges the tickets deleted at least `days` days ago, or all of them
+/// without `days`. Responds with their ids.
+pub async fn empty_trash(
+    socket: &mut impl Stream,
+    store: Arc<RwLock<TicketStore>>,
+    user: &User,
+    days: Option<String>,
+) -> Result<(), anyhow::Error> {
+    if !user.can(Action::Delete) {
+        return respond(socket, helpers::Response::<()>::Forbidden).await;
+    }
+    let days: u32 = match days.map(|days| days.parse()) {
+        Some(Ok(days)) => days,
+        Some(Err(_)) => {
+            let message = String::from("`older_than_days` must be a whole number of days");
+            return respond(socket, helpers::Response::<()>::BadRequest(message)).await;
+        }
+        None => 0,
+    };
+
+    let retention = chrono::Duration::days(days.into());
+    let purged = store.write().await.purge_expired(retention, Utc::now());
+    respond(socket, helpers::Response::Ok(purged)).await
+}
+
+pub async fn undo(
+    socket: &mut impl Stream,
+    store: Arc<RwLock<TicketStore>>,
+    user: &User,
+    count: Option<String>,
+) -> Result<(), anyhow::Error> {
+    let Some(count) = parse_count(count) else {
+        return respond(socket, helpers::Response::<()>::BadRequest(count_message())).await;
+    };
+    let report = store.write().await.undo(user, count);
+    respond(socket, helpers::Response::Ok(report)).await
+}
+
+pub async fn redo(
+    socket: &mut impl Stream,
+    store: Arc<RwLock<TicketStore>>,
+    user: &User,
+    count: Option<String>,
+) -> Result<(), anyhow::Error> {
+    let Some(count) = parse_count(count) else {
+        return respond(socket, helpers::Response::<()>::BadRequest(count_message())).await;
+    };
+    let report = store.write().await.redo(user, count);
+    respond(socket, helpers::Response::Ok(report)).await
+}
+
+/// The `count` of `POST /undo` and `POST /redo`, 1 if it's missing.
+fn parse_count(count: Option<String>) -> Option<usize> {
+    match count {
+        Some(count) => count.parse().ok().filter(|count| *count > 0),
+        None => Some(1),
+    }
+}
+
+fn count_message() -> String {
+    String::from("`count` must be a positive whole number")
+}
+
+pub async fn list_templates(
+    socket: &mut impl Stream,
+    store: Arc<RwLock<TicketStore>>,
+    user: &User,
+) -> Result<(), anyhow::Error> {
+    if !user.can(Action::Read) {
+        return respond(socket, helpers::Response::<()>::Forbidden).await;
+    }
+
+    let templates: Vec<Template> = store.read().await.templates().cloned().collect();
+    respond(socket, helpers::Response::Ok(templates)).await
+}
+
+pub async fn create_template<'a>(
+    socket: &mut impl Stream,
+    store: Arc<RwLock<TicketStore>>,
+    user: &User,
+    buffer: &'a [u8],
+    request: &mut httparse::Request<'a, 'a>,
+    parse_result: Option<httparse::Status<usize>>,
+) -> Result<(), anyhow::Error> {
+    if !user.can(Action::ManageTemplates) {
+        return respond(socket, helpers::Response::<()>::Forbidden).await;
+    }
+
+    let body = helpers::parse_body(socket, request, buffer, parse_result).await?;
+    let template: Template = match serde_json::from_str(&body) {
+        Ok(template) => template,
+        Err(e) => return respond(socket, helpers::Response::<()>::BadRequest(e.to_string())).await,
+    };
+
+    let template = store.write().await.add_template(template).cloned();
+    match template {
+        Ok(template) => respond(socket, helpers::Response::Created(template)).await,
+        Err(e) => respond_template_error(socket, e).await,
+    }
+}
+
+pub async fn get_template(
+    socket: &mut impl Stream,
+    store: Arc<RwLock<TicketStore>>,
+    user: &User,
+    name: &str,
+) -> Result<(), anyhow::Error> {
+    if !user.can(Action::Read) {
+        return respond(socket, helpers::Response::<()>::Forbidden).await;
+    }
+
+    let template = store.read().await.template(name).cloned();
+    match template {
+        Some(template) => respond(socket, helpers::Response::Ok(template)).await,
+        None => respond(socket, helpers::Response::<()>::NotFound).await,
+    }
+}
+
+pub async fn delete_template(
+    socket: &mut impl Stream,
+    store: Arc<RwLock<TicketStore>>,
+    user: &User,
+    name: &str,
+) -> Result<(), anyhow::Error> {
+    if !user.can(Action::ManageTemplates) {
+        return respond(socket, helpers::Response::<()>::Forbidden).await;
+    }
+
+    match store.write().await.remove_template(name) {
+        Some(_) => respond(socket, helpers::Response::NO_CONTENT).await,
+        None => respond(socket, helpers::Response::<()>::NotFound).await,
+    }
+}
+
+/// Creates every ticket of a template at once, or none of them.
+pub async fn create_template_tickets(
+    socket: &mut impl Stream,
+    store: Arc<RwLock<TicketStore>>,
+    policy: &Policy,
+    user: &User,
+    name: &str,
+    body: &str,
+) -> Result<(), anyhow::Error> {
+    if !user.can(Action::Create) {
+        return respond(socket, helpers::Response::<()>::Forbidden).await;
+    }
+
+    let request: TemplateRequest = match serde_json::from_str(body) {
+        Ok(request) => request,
+        Err(e) => return respond(socket, helpers::Response::<()>::BadRequest(e.to_string())).await,
+    };
+
+    let project = request.project.unwrap_or(ProjectKey::DEFAULT);
+    let mut store = store.write().await;
+    let ids = store.create_from_template(name, project, &request.values, user, policy);
+    drop(store);
+    match ids {
+        Ok(ids) => respond(socket, helpers::Response::Created(ids)).await,
+        Err(e) => respond_template_error(socket, e).await,
+    }
+}
+
+/// Unknown templates are 404s, and existing names 409s. Anything wrong with
+/// the template or its values is a 400.
+async fn respond_template_error(
+    socket: &mut impl Stream,
+    error: TemplateError,
+) -> Result<(), anyhow::Error> {
+    let response = match error {
+        TemplateError::Project(error) => return respond_project_error(socket, error).await,
+        TemplateError::NotFound(_) => helpers::Response::<()>::NotFound,
+        TemplateError::Exists(_) => helpers::Response::Conflict(error.to_string()),
+        TemplateError::InvalidName
+        | TemplateError::TicketCount
+        | TemplateError::Unclosed { .. }
+        | TemplateError::InvalidPlaceholder { .. }
+        | TemplateError::MissingValue(_)
+        | TemplateError::UnknownValue(_)
+        | TemplateError::InvalidTitle { .. }
+        | TemplateError::InvalidDescription { .. } => {
+            helpers::Response::BadRequest(error.to_string())
+        }
+    };
+    respond(socket, response).await
+}
+
+pub async fn list_queries(
+    socket: &mut impl Stream,
+    store: Arc<RwLock<TicketStore>>,
+    user: &User,
+) -> Result<(), anyhow::Error> {
+    if !user.can(Action::Read) {
+        return respond(socket, helpers::Response::<()>::Forbidden).await;
+    }
+
+    let queries: Vec<SavedQuery> = store.read().await.saved_queries().cloned().collect();
+    respond(socket, helpers::Response::Ok(queries)).await
+}
+
+pub async fn save_query<'a>(
+    socket: &mut impl Stream,
+    store: Arc<RwLock<TicketStore>>,
+    user: &User,
+    buffer: &'a [u8],
+    request: &mut httparse::Request<'a, 'a>,
+    parse_result: Option<httparse::Status<usize>>,
+) -> Result<(), anyhow::Error> {
+    if !user.can(Action::ManageQueries) {
+        return respond(socket, helpers::Response::<()>::Forbidden).await;
+    }
+
+    let body = helpers::parse_body(socket, request, buffer, parse_result).await?;
+    let query: SavedQuery = match serde_json::from_str(&body) {
+        Ok(query) => query,
+        Err(e) => return respond(socket, helpers::Response::<()>::BadRequest(e.to_string())).await,
+    };
+
+    let query = store.write().await.save_query(query).cloned();
+    match query {
+        Ok(query) => respond(socket, helpers::Response::Created(query)).await,
+        Err(e) => respond_query_error(socket, e).await,
+    }
+}
+
+pub async fn delete_query(
+    socket: &mut impl Stream,
+    store: Arc<RwLock<TicketStore>>,
+    user: &User,
+    name: &str,
+) -> Result<(), anyhow::Error> {
+    if !user.can(Action::ManageQueries) {
+        return respond(socket, helpers::Response::<()>::Forbidden).await;
+    }
+
+    match store.write().await.remove_query(name) {
+        Some(_) => respond(socket, helpers::Response::NO_CONTENT).await,
+        None => respond(socket, helpers::Response::<()>::NotFound).await,
+    }
+}
+
+/// The tickets matching a saved query, as of now.
+pub async fn run_query(
+    socket: &mut impl Stream,
+    store: Arc<RwLock<TicketStore>>,
+    user: &User,
+    name: &str,
+    format: DescriptionFormat,
+) -> Result<(), anyhow::Error> {
+    if !user.can(Action::Read) {
+        return respond(socket, helpers::Response::<()>::Forbidden).await;
+    }
+
+    let tickets: Result<Vec<serde_json::Value>, SavedQueryError> =
+        store.read().await.run_query(name).map(|tickets| {
+            tickets
+                .iter()
+                .map(|ticket_lock| render(&ticket_lock.read().unwrap(), format))
+                .collect()
+        });
+    match tickets {
+        Ok(tickets) => respond(socket, helpers::Response::Ok(tickets)).await,
+        Err(e) => respond_query_error(socket, e).await,
+    }
+}
+
+/// Unknown queries are 404s, existing names 409s and anything else a 400.
+async fn respond_query_error(
+    socket: &mut impl Stream,
+    error: SavedQueryError,
+) -> Result<(), anyhow::Error> {
+    let response = match error {
+        SavedQueryError::NotFound(_) => helpers::Response::<()>::NotFound,
+        SavedQueryError::Exists(_) => helpers::Response::Conflict(error.to_string()),
+        SavedQueryError::InvalidName | SavedQueryError::Invalid(_) => {
+            helpers::Response::BadRequest(error.to_string())
+        }
+    };
+    respond(socket, response).await
+}
+
+/// Counts, throughput, cycle time and open ticket ages, see `reports`.
+pub async fn report_summary(
+    socket: &mut impl Stream,
+    store: Arc<RwLock<TicketStore>>,
+    user: &User,
+    days: Option<String>,
+    windows: Option<String>,
+) -> Result<(), anyhow::Error> {
+    if !user.can(Action::Read) {
+        return respond(socket, helpers::Response::<()>::Forbidden).await;
+    }
+    let default = Period::default();
+    let parse = |value: Option<String>, default: u32| match value {
+        Some(value) => value.parse().map_err(|_| InvalidPeriod),
+        None => Ok(default),
+    };
+    let period = parse(days, default.days()).and_then(|days| {
+        let windows = parse(windows, default.windows())?;
+        Period::new(days, windows)
+    });
+    let period = match period {
+        Ok(period) => period,
+        Err(e) => return respond(socket, helpers::Response::<()>::BadRequest(e.to_string())).await,
+    };
+
+    let summary = store.read().await.summary(period, Utc::now());
+    respond(socket, helpers::Response::Ok(summary)).await
+}
+
+pub async fn openapi(socket: &mut impl Stream) -> Result<(), anyhow::Error> {
+    respond(socket, helpers::Response::Ok(openapi::spec())).await
+}
+
+/// `ticket` as JSON, with its description in `format`.
+fn render(ticket: &Ticket, format: DescriptionFormat) -> serde_json::Value {
+    let mut value = serde_json::to_value(ticket).expect("Tickets always serialize");
+    if format != DescriptionFormat::default() {
+        value["description"] = format.render(&ticket.description).into();
+    }
+    value
+}
+
+async fn respond<T: Serialize>(
+    socket: &mut impl Stream,
+    response: helpers::Response<T>,
+) -> Result<(), anyhow::Error> {
+    let response = helpers::build_response(response).await;
+    socket.write_all(&response).await?;
     Ok(())
 }
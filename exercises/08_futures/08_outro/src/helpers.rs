@@ -1,39 +1,236 @@
-use anyhow::anyhow;
+use anyhow::{anyhow, bail};
+use crate::data::ErrorBody;
+use crate::Stream;
 use serde::Serialize;
-use tokio::io::AsyncReadExt;
-use tokio::net::TcpStream;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, ReadBuf};
+use tokio::time::{Instant, Sleep};
+
+/// Most headers a request may have.
+pub const MAX_HEADERS: usize = 32;
+/// Largest request line plus headers the server accepts.
+pub const MAX_HEAD_BYTES: usize = 8 * 1024;
+/// Largest request body the server accepts.
+pub const MAX_BODY_BYTES: usize = 64 * 1024;
+
+/// Where a request in the buffer ends, see `read_request`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Framing {
+    /// Length of the request line and headers.
+    pub head: usize,
+    /// The `Content-Length`.
+    pub body: usize,
+    /// Whether the body is left on the socket for the handler to stream,
+    /// rather than read into the buffer.
+    pub streamed: bool,
+}
+
+impl Framing {
+    /// How much of the buffer the request takes: all of it, or for streamed
+    /// requests, the head and as much of the body as was read along with it.
+    pub fn buffered(&self, buffer: &[u8]) -> usize {
+        (self.head + self.body).min(buffer.len())
+    }
+}
+
+/// Reads from `socket` until `buffer` starts with a complete request.
+/// Anything after it is the start of the next request on the same connection.
+///
+/// Requests for which `streamed` returns true are returned as soon as their
+/// head is complete, and their body isn't limited to `MAX_BODY_BYTES`.
+/// `None` means the client closed the connection cleanly between requests.
+pub async fn read_request(
+    socket: &mut impl Stream,
+    buffer: &mut Vec<u8>,
+    streamed: impl Fn(&httparse::Request) -> bool,
+) -> Result<Option<Framing>, anyhow::Error> {
+    loop {
+        if !buffer.is_empty() {
+            let mut headers = [httparse::EMPTY_HEADER; MAX_HEADERS];
+            let mut request = httparse::Request::new(&mut headers);
+            match request.parse(buffer)? {
+                httparse::Status::Complete(head) => {
+                    if header(request.headers, "transfer-encoding").is_some() {
+                        bail!("Chunked request bodies aren't supported");
+                    }
+                    let body = content_length(request.headers)?;
+                    if streamed(&request) {
+                        return Ok(Some(Framing {
+                            head,
+                            body,
+                            streamed: true,
+                        }));
+                    }
+                    if body > MAX_BODY_BYTES {
+                        bail!("The request body is over {} bytes", MAX_BODY_BYTES);
+                    }
+                    if buffer.len() >= head + body {
+                        return Ok(Some(Framing {
+                            head,
+                            body,
+                            streamed: false,
+                        }));
+                    }
+                }
+                httparse::Status::Partial if buffer.len() > MAX_HEAD_BYTES => {
+                    bail!("The request headers are over {} bytes", MAX_HEAD_BYTES);
+                }
+                httparse::Status::Partial => {}
+            }
+        }
+
+        let mut chunk = [0; 4096];
+        let n = socket.read(&mut chunk).await?;
+        if n == 0 {
+            if buffer.is_empty() {
+                return Ok(None);
+            }
+            bail!("Connection closed in the middle of a request");
+        }
+        buffer.extend_from_slice(&chunk[..n]);
+    }
+}
+
+/// The value of the first header called `name`, ignoring case.
+pub fn header<'a>(headers: &[httparse::Header<'a>], name: &str) -> Option<&'a [u8]> {
+    headers
+        .iter()
+        .find(|header| header.name.eq_ignore_ascii_case(name))
+        .map(|header| header.value)
+}
+
+/// The `Content-Length` header, or 0 if there is none.
+pub fn content_length(headers: &[httparse::Header]) -> Result<usize, anyhow::Error> {
+    match header(headers, "content-length") {
+        Some(value) => std::str::from_utf8(value)?
+            .trim()
+            .parse()
+            .map_err(|_| anyhow!("Invalid Content-Length")),
+        None => Ok(0),
+    }
+}
+
+/// The value of the first `name=value` pair called `name` in a query string,
+/// with `+` and `%XX` escapes decoded. `None` if it's missing; an error if it
+/// has a broken escape or isn't valid UTF-8.
+pub fn query_param(query: &str, name: &str) -> Result<Option<String>, anyhow::Error> {
+    let Some(value) = query.split('&').find_map(|pair| {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        (key == name).then_some(value)
+    }) else {
+        return Ok(None);
+    };
+    let malformed = || anyhow!("The `{}` query parameter is malformed", name);
+
+    let mut decoded = Vec::with_capacity(value.len());
+    let mut bytes = value.bytes();
+    while let Some(byte) = bytes.next() {
+        match byte {
+            b'+' => decoded.push(b' '),
+            b'%' => {
+                let (Some(high), Some(low)) = (bytes.next(), bytes.next()) else {
+                    return Err(malformed());
+                };
+                let hex = [high, low];
+                let hex = std::str::from_utf8(&hex).map_err(|_| malformed())?;
+                decoded.push(u8::from_str_radix(hex, 16).map_err(|_| malformed())?);
+            }
+            byte => decoded.push(byte),
+        }
+    }
+    String::from_utf8(decoded)
+        .map(Some)
+        .map_err(|_| malformed())
+}
+
+/// The part of a streamed request body that was read along with its head,
+/// see `Framing::streamed`. The rest is still on the socket.
+pub struct BodyStart<'a> {
+    pub buffered: &'a [u8],
+    /// The length of the whole body.
+    pub length: u64,
+}
+
+/// Fails reads that make no progress for `timeout`, e.g. from a client that
+/// stopped sending the body it announced.
+pub struct ReadTimeout<R> {
+    inner: R,
+    timeout: Duration,
+    deadline: Pin<Box<Sleep>>,
+}
+
+impl<R> ReadTimeout<R> {
+    pub fn new(inner: R, timeout: Duration) -> Self {
+        Self {
+            inner,
+            timeout,
+            deadline: Box::pin(tokio::time::sleep(timeout)),
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for ReadTimeout<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        match Pin::new(&mut this.inner).poll_read(cx, buf) {
+            Poll::Ready(result) => {
+                this.deadline.as_mut().reset(Instant::now() + this.timeout);
+                Poll::Ready(result)
+            }
+            Poll::Pending => match this.deadline.as_mut().poll(cx) {
+                Poll::Ready(()) => Poll::Ready(Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "The body stopped arriving",
+                ))),
+                Poll::Pending => Poll::Pending,
+            },
+        }
+    }
+}
+
+pub fn body_start<'a>(
+    request: &httparse::Request,
+    buffer: &'a [u8],
+    parse_result: Option<httparse::Status<usize>>,
+) -> Result<BodyStart<'a>, anyhow::Error> {
+    match parse_result {
+        Some(httparse::Status::Complete(head)) => Ok(BodyStart {
+            buffered: &buffer[head..],
+            length: content_length(request.headers)? as u64,
+        }),
+        _ => Err(anyhow!("Failed to parse body")),
+    }
+}
 
 pub async fn parse_body<'a>(
-    socket: &mut TcpStream,
+    socket: &mut impl Stream,
     request: &mut httparse::Request<'a, 'a>,
     buffer: &'a [u8],
     parse_result: Option<httparse::Status<usize>>,
 ) -> Result<String, anyhow::Error> {
-    let content_length = request
-        .headers
-        .iter()
-        .find(|header| header.name.eq_ignore_ascii_case("content-length"))
-        .and_then(|header| {
-            std::str::from_utf8(header.value)
-                .ok()?
-                .parse::<usize>()
-                .ok()
-        })
-        .unwrap_or(0);
+    let content_length = content_length(request.headers).unwrap_or(0);
 
     if let Some(httparse::Status::Complete(idx)) = parse_result {
         let body = &buffer[idx..];
 
         let body = if body.len() < content_length {
             let mut remaining_body = vec![0; content_length - body.len()];
-            socket.read_exact(&mut remaining_body).await.unwrap();
+            socket.read_exact(&mut remaining_body).await?;
             let complete_body = [body, &remaining_body].concat();
             String::from_utf8_lossy(&complete_body)
                 .into_owned()
                 .trim_matches(char::from(0))
                 .to_string()
         } else {
-            String::from_utf8_lossy(&body)
+            String::from_utf8_lossy(body)
                 .into_owned()
                 .trim_matches(char::from(0))
                 .to_string()
@@ -52,6 +249,16 @@ where
     Ok(T),
     Created(T),
     NoContent,
+    BadRequest(String),
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    /// The request conflicts with the state of the store.
+    Conflict(String),
+    PayloadTooLarge(String),
+    TooManyRequests(Duration),
+    /// The request couldn't be completed, e.g. its changes couldn't be saved.
+    InternalServerError,
 }
 
 impl Response<()> {
@@ -79,7 +286,43 @@ where
                 body
             )
         }
-        Response::NoContent => format!("HTTP/1.1 204 No Content\r\n"),
+        Response::NoContent => "HTTP/1.1 204 No Content\r\n\r\n".to_string(),
+        Response::BadRequest(message) => error("400 Bad Request", "", &message),
+        Response::Unauthorized => error(
+            "401 Unauthorized",
+            "WWW-Authenticate: Bearer\r\n",
+            "Missing or invalid bearer token",
+        ),
+        Response::Forbidden => error("403 Forbidden", "", "Not allowed"),
+        Response::NotFound => error("404 Not Found", "", "Not found"),
+        Response::Conflict(message) => error("409 Conflict", "", &message),
+        Response::PayloadTooLarge(message) => error("413 Payload Too Large", "", &message),
+        Response::TooManyRequests(retry_after) => {
+            // `Retry-After` only takes whole seconds, so round up.
+            let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+            error(
+                "429 Too Many Requests",
+                &format!("Retry-After: {}\r\n", seconds),
+                "Too many requests",
+            )
+        }
+        Response::InternalServerError => {
+            error("500 Internal Server Error", "", "Internal server error")
+        }
     }
     .into_bytes()
 }
+
+fn error(status: &str, headers: &str, message: &str) -> String {
+    let body = serde_json::to_string(&ErrorBody {
+        error: message.to_string(),
+    })
+    .unwrap_or_else(|_| "{}".to_string());
+    format!(
+        "HTTP/1.1 {}\r\n{}Content-Length: {}\r\n\r\n{}",
+        status,
+        headers,
+        body.len(),
+        body
+    )
+}
@@ -0,0 +1,88 @@
+//! Undo and redo for changes to tickets.
+//!
+//! Every user has their own history of the tickets they created, edited and
+//! deleted, so undoing only reverts their own changes, however many other
+//! tickets changed in between. An operation can't be undone once its ticket
+//! has changed again since, e.g. by someone else: that's a conflict, and the
+//! operation is dropped from the history. Operations are checked like any
+//! other change too: undoing needs the same permissions as making the change
+//! by hand, e.g. deleting the ticket to undo creating it, or the operation
+//! is dropped. And a ticket can't be done again while it's blocked: the
+//! operation stays in the history then, to be tried again later.
+
+use serde::{Deserialize, Serialize};
+
+use crate::data::Ticket;
+use crate::links::format_ids;
+use crate::store::TicketId;
+
+/// A change to one ticket: the ticket before and after it.
+/// `None` means it wasn't live: not created yet, or in the trash.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Operation {
+    pub id: TicketId,
+    pub before: Option<Ticket>,
+    pub after: Option<Ticket>,
+}
+
+/// The operations of one user that can be undone and redone, oldest first.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct History {
+    pub undo: Vec<Operation>,
+    pub redo: Vec<Operation>,
+}
+
+impl History {
+    /// Older operations are forgotten beyond this many.
+    pub const MAX_OPERATIONS: usize = 100;
+
+    /// Adds a new operation. It can't be followed by what was undone before it,
+    /// so that can't be redone anymore.
+    pub fn push(&mut self, operation: Operation) {
+        self.redo.clear();
+        self.undo.push(operation);
+        if self.undo.len() > History::MAX_OPERATIONS {
+            self.undo.remove(0);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.undo.is_empty() && self.redo.is_empty()
+    }
+}
+
+/// Why an operation couldn't be undone or redone.
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum Conflict {
+    #[error("Ticket {0} changed since, so the operation was dropped")]
+    Changed(TicketId),
+    #[error("Ticket {0} was purged, so the operation was dropped")]
+    Purged(TicketId),
+    /// The user isn't allowed to make the change, e.g. to delete the ticket.
+    #[error("Not allowed to change ticket {0} that way, so the operation was dropped")]
+    Forbidden(TicketId),
+    #[error(
+        "Ticket {id} can't be done while it's blocked by {}, so the operation was kept",
+        format_ids(.blockers)
+    )]
+    Blocked {
+        id: TicketId,
+        blockers: Vec<TicketId>,
+    },
+}
+
+impl Conflict {
+    /// Whether the operation stays in the history, to be tried again later.
+    pub fn keeps_operation(&self) -> bool {
+        matches!(self, Conflict::Blocked { .. })
+    }
+}
+
+/// What `TicketStore::undo` or `TicketStore::redo` did.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Report {
+    /// The tickets of the operations undone or redone, in that order.
+    pub tickets: Vec<TicketId>,
+    /// The conflict that stopped it before `count` operations, if any.
+    pub conflict: Option<String>,
+}
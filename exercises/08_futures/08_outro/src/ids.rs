@@ -0,0 +1,236 @@
+//! How tickets get their ids.
+//!
+//! A ticket id is a project key followed by a serial, e.g. `API-42`. By
+//! default the serial is the ticket's number within its project, which is
+//! short but only unique within one store. To merge stores from several
+//! instances, ids can be generated from the time instead, as ULIDs
+//! (`API-01J9ZB3X4N5Q8W7RYV2T6KDMCE`) or UUIDv7s
+//! (`API-01927c3e-5a61-7d3f-9e24-6b8f0c1a2d3e`). Both are unique without
+//! coordination and sort by creation time.
+//!
+//! Every strategy can read the ids of the others, so a store can switch
+//! strategies without renaming its tickets.
+
+use anyhow::anyhow;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use ulid::Ulid;
+use uuid::Uuid;
+
+use crate::projects::Project;
+
+/// The part of a ticket id after the project key.
+///
+/// Serials of the same kind sort in the order they were generated.
+/// Numbers sort before ULIDs, which sort before UUIDs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Serial {
+    /// The ticket's number within its project, e.g. `42`.
+    Number(u64),
+    /// 26 characters of Crockford's base 32, upper case.
+    Ulid(Ulid),
+    /// A UUIDv7, hyphenated and lower case.
+    Uuid(Uuid),
+}
+
+impl From<u64> for Serial {
+    fn from(number: u64) -> Self {
+        Serial::Number(number)
+    }
+}
+
+impl From<Ulid> for Serial {
+    fn from(ulid: Ulid) -> Self {
+        Serial::Ulid(ulid)
+    }
+}
+
+impl From<Uuid> for Serial {
+    fn from(uuid: Uuid) -> Self {
+        Serial::Uuid(uuid)
+    }
+}
+
+impl fmt::Display for Serial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Serial::Number(number) => write!(f, "{}", number),
+            Serial::Ulid(ulid) => write!(f, "{}", ulid),
+            Serial::Uuid(uuid) => write!(f, "{}", uuid.hyphenated()),
+        }
+    }
+}
+
+impl FromStr for Serial {
+    type Err = anyhow::Error;
+
+    /// Parses the forms written by `Display`. ULIDs and UUIDs are case-insensitive.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || anyhow!("`{}` is not a number, a ULID or a UUID", s);
+        if !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()) {
+            return s.parse().map(Serial::Number).map_err(|_| invalid());
+        }
+        match s.len() {
+            ulid::ULID_LEN => Ulid::from_string(s)
+                .map(Serial::Ulid)
+                .map_err(|_| invalid()),
+            // Only the hyphenated form: `Uuid::parse_str` also takes others.
+            36 => Uuid::try_parse(s).map(Serial::Uuid).map_err(|_| invalid()),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// How new tickets get their serial.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IdStrategy {
+    /// The next number of the ticket's project.
+    #[default]
+    Sequential,
+    Ulid,
+    Uuid7,
+}
+
+impl IdStrategy {
+    pub const ALL: [IdStrategy; 3] = [IdStrategy::Sequential, IdStrategy::Ulid, IdStrategy::Uuid7];
+}
+
+impl fmt::Display for IdStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let strategy = match self {
+            IdStrategy::Sequential => "sequential",
+            IdStrategy::Ulid => "ulid",
+            IdStrategy::Uuid7 => "uuid7",
+        };
+        f.write_str(strategy)
+    }
+}
+
+impl FromStr for IdStrategy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|strategy| strategy.to_string().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                anyhow!(
+                    "Unknown id strategy `{}`, expected sequential, ulid or uuid7",
+                    s
+                )
+            })
+    }
+}
+
+/// Bits after the timestamp that ULIDs and UUIDv7s fill at random.
+const ULID_RANDOM_BITS: u32 = 80;
+const UUID7_RANDOM_BITS: u32 = 74;
+
+/// Generates serials with an `IdStrategy`.
+///
+/// Time-based serials are strictly increasing, even when several are
+/// generated in the same millisecond or the clock goes back: the generator
+/// remembers the last one, and adds one to it when the clock hasn't moved
+/// past it. Serials from elsewhere, e.g. restored tickets, can be made to
+/// come before every new one with `observe`.
+#[derive(Clone, Debug, Default)]
+pub struct IdGenerator {
+    strategy: IdStrategy,
+    /// Milliseconds since the Unix epoch and random bits of the last ULID.
+    last_ulid: Option<(u64, u128)>,
+    /// Same for UUIDv7s.
+    last_uuid: Option<(u64, u128)>,
+}
+
+impl IdGenerator {
+    pub fn new(strategy: IdStrategy) -> Self {
+        Self {
+            strategy,
+            ..Self::default()
+        }
+    }
+
+    pub fn strategy(&self) -> IdStrategy {
+        self.strategy
+    }
+
+    /// Switches strategies, still generating serials after the ones so far.
+    pub fn set_strategy(&mut self, strategy: IdStrategy) {
+        self.strategy = strategy;
+    }
+
+    /// The serial of the next ticket of `project`. Sequential serials come
+    /// from the project's counter, which is advanced.
+    pub fn generate(&mut self, project: &mut Project) -> Serial {
+        match self.strategy {
+            IdStrategy::Sequential => {
+                project.next_number += 1;
+                Serial::Number(project.next_number - 1)
+            }
+            IdStrategy::Ulid => {
+                let random = rand::random::<u128>() >> (128 - ULID_RANDOM_BITS);
+                let (millis, random) = after(self.last_ulid, (now(), random), ULID_RANDOM_BITS);
+                self.last_ulid = Some((millis, random));
+                Serial::Ulid(Ulid::from_parts(millis, random))
+            }
+            IdStrategy::Uuid7 => {
+                let random = rand::random::<u128>() >> (128 - UUID7_RANDOM_BITS);
+                let (millis, random) = after(self.last_uuid, (now(), random), UUID7_RANDOM_BITS);
+                self.last_uuid = Some((millis, random));
+                Serial::Uuid(uuid7(millis, random))
+            }
+        }
+    }
+
+    /// Makes sure serials generated from now on come after `serial`.
+    /// Numbers are tracked by their project, so they're ignored.
+    pub fn observe(&mut self, serial: Serial) {
+        let (last, parts) = match serial {
+            Serial::Number(_) => return,
+            Serial::Ulid(ulid) => (&mut self.last_ulid, (ulid.timestamp_ms(), ulid.random())),
+            Serial::Uuid(uuid) => (&mut self.last_uuid, uuid7_parts(uuid)),
+        };
+        if last.is_none_or(|last| last < parts) {
+            *last = Some(parts);
+        }
+    }
+}
+
+fn now() -> u64 {
+    Utc::now().timestamp_millis().max(0) as u64
+}
+
+/// `next` if it comes after `last`, otherwise the pair right after `last`.
+/// Pairs are milliseconds and `bits` random bits.
+fn after(last: Option<(u64, u128)>, next: (u64, u128), bits: u32) -> (u64, u128) {
+    match last {
+        Some((millis, random)) if next <= (millis, random) => {
+            if random + 1 < 1 << bits {
+                (millis, random + 1)
+            } else {
+                (millis + 1, 0)
+            }
+        }
+        _ => next,
+    }
+}
+
+/// Lays out a UUIDv7: 48 bits of milliseconds, the version, 12 random bits,
+/// the variant and 62 more random bits.
+fn uuid7(millis: u64, random: u128) -> Uuid {
+    let millis = (millis as u128 & ((1 << 48) - 1)) << 80;
+    let version = 0x7 << 76;
+    let random_a = (random >> 62) << 64;
+    let variant = 0b10 << 62;
+    let random_b = random & ((1 << 62) - 1);
+    Uuid::from_u128(millis | version | random_a | variant | random_b)
+}
+
+fn uuid7_parts(uuid: Uuid) -> (u64, u128) {
+    let value = uuid.as_u128();
+    let random_a = (value >> 64) & 0xfff;
+    let random_b = value & ((1 << 62) - 1);
+    ((value >> 80) as u64, random_a << 62 | random_b)
+}
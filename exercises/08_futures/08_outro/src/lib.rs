@@ -9,79 +9,489 @@
 // Use Rust's package registry, crates.io, to find the dependencies you need
 // (if any) to build this system.
 
-use regex::Regex;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
 use std::sync::Arc;
-use tokio::io::AsyncReadExt;
-use tokio::net::TcpStream;
-use tokio::sync::RwLock;
-use anyhow::anyhow;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
 
-lazy_static::lazy_static! {
-    static ref TICKET_PATH_RE: Regex = Regex::new(r"^/tickets/(\d+)$").unwrap();
-}
-
-use crate::store::{TicketId, TicketStore};
+use crate::auth::User;
+use crate::comments::CommentId;
+use crate::data::DescriptionFormat;
+use crate::projects::ProjectKey;
+use crate::routes::Route;
+use crate::server::State;
+use crate::store::TicketId;
 
+pub mod attachments;
+pub mod auth;
+pub mod backend;
+pub mod board;
+pub mod client;
+pub mod comments;
 pub mod data;
 pub mod handlers;
 pub mod helpers;
+pub mod history;
+pub mod ids;
+pub mod limits;
+pub mod links;
+pub mod loadgen;
+pub mod openapi;
+pub mod projects;
+pub mod query;
+pub mod reports;
+pub mod routes;
 pub mod server;
+pub mod snapshot;
+pub mod sqlite;
 pub mod store;
+pub mod templates;
+pub mod tls;
+pub mod transfer;
+pub mod trash;
+
+/// How long to wait for the rest of a refused upload before closing the connection.
+const LINGER: Duration = Duration::from_secs(1);
+
+/// Anything requests can be read from and responses written to:
+/// a plain `TcpStream`, or a TLS stream wrapping one.
+pub trait Stream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Stream for T {}
+
+/// Reads the request from `socket`, but holds back the response until it's
+/// taken, e.g. until the request's changes are saved.
+struct HeldResponse<'a, S> {
+    socket: &'a mut S,
+    response: Vec<u8>,
+}
+
+impl<S: Stream> AsyncRead for HeldResponse<'_, S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut *self.socket).poll_read(cx, buf)
+    }
+}
+
+impl<S: Stream> AsyncWrite for HeldResponse<'_, S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.response.extend_from_slice(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
 
+/// Serves requests from `socket` until the client closes it, asks for it to be
+/// closed with `Connection: close`, or sends nothing for `state.keep_alive`.
 pub async fn handle_connection(
-    mut socket: TcpStream,
-    store: Arc<RwLock<TicketStore>>,
+    mut socket: impl Stream,
+    peer: SocketAddr,
+    state: State,
 ) -> Result<(), anyhow::Error> {
-    let mut buffer = [0; 1024];
-    match socket.read(&mut buffer).await {
-        Ok(n) if n == 0 => return Ok(()), // connection closed
-        Ok(_n) => {
-            let mut headers = [httparse::EMPTY_HEADER; 16];
-            let mut request = httparse::Request::new(&mut headers);
-            let res = request.parse(&buffer).ok();
-            handle_request(request, &mut socket, &buffer, store, res).await?;
-        }
-        Err(e) => eprintln!("Failed to read from socket; err = {:?}", e),
+    let mut buffer = Vec::new();
+    let mut streamed = false;
+    loop {
+        let read = helpers::read_request(&mut socket, &mut buffer, streams_body);
+        let framing = match tokio::time::timeout(state.keep_alive, read).await {
+            Err(_) => break, // idle for too long
+            Ok(Ok(None)) => break, // connection closed
+            Ok(Ok(Some(framing))) => framing,
+            Ok(Err(e)) => {
+                // We can't tell where the next request would start, so give up on the connection.
+                let response = helpers::build_response(helpers::Response::<()>::BadRequest(
+                    e.to_string(),
+                ))
+                .await;
+                let _ = socket.write_all(&response).await;
+                break;
+            }
+        };
+
+        let len = framing.buffered(&buffer);
+        let keep_alive = serve_request(&mut socket, peer, &state, &buffer[..len]).await?;
+        buffer.drain(..len);
+        // A streamed body may have been refused without being read,
+        // so we can't tell where the next request would start.
+        if framing.streamed {
+            streamed = true;
+            break;
+        }
+        if !keep_alive {
+            break;
+        }
     }
 
+    // Lets TLS clients know the response is complete.
+    // This fails if the client is already gone, which is fine.
+    let _ = socket.shutdown().await;
+    if streamed {
+        // Closing with unread data resets the connection, which can make the
+        // client lose the response, e.g. a 413, before it reads it. Reading
+        // what's left for a moment gives it time to.
+        let mut rest = (&mut socket).take(state.attachments.max_bytes());
+        let mut sink = tokio::io::sink();
+        let _ = tokio::time::timeout(LINGER, tokio::io::copy(&mut rest, &mut sink)).await;
+    }
     Ok(())
 }
 
+/// Uploads are streamed to disk rather than read into memory first.
+fn streams_body(request: &httparse::Request) -> bool {
+    let (Some(method), Some(target)) = (request.method, request.path) else {
+        return false;
+    };
+    let path = target.split_once('?').map_or(target, |(path, _)| path);
+    matches!(
+        routes::route(method, path),
+        Some((Route::UploadAttachment, _))
+    )
+}
+
+/// Handles the single complete request in `buffer`.
+/// For streamed requests, `buffer` only holds the start of the body.
+/// Returns whether the client wants to keep the connection open.
+async fn serve_request(
+    socket: &mut impl Stream,
+    peer: SocketAddr,
+    state: &State,
+    buffer: &[u8],
+) -> Result<bool, anyhow::Error> {
+    let mut headers = [httparse::EMPTY_HEADER; helpers::MAX_HEADERS];
+    let mut request = httparse::Request::new(&mut headers);
+    let res = request.parse(buffer).ok();
+
+    let keep_alive = !helpers::header(request.headers, "connection")
+        .is_some_and(|value| value.eq_ignore_ascii_case(b"close"));
+
+    let method = request.method.unwrap_or_default();
+    let target = request.path.unwrap_or_default();
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let response = if let Err(retry_after) = state.limiter.check(peer.ip(), method, path) {
+        Some(helpers::Response::<()>::TooManyRequests(retry_after))
+    } else {
+        match routes::route(method, path) {
+            None => Some(helpers::Response::NotFound),
+            Some((route, _)) if !route.requires_auth() => {
+                handlers::openapi(socket).await?;
+                None
+            }
+            Some((route, params)) => match authenticate(&request, state).await {
+                Some(user) => {
+                    let target = Target {
+                        route,
+                        project: params.project,
+                        id: params.id,
+                        item: params.item,
+                        name: params.name,
+                        query,
+                    };
+                    match &state.database {
+                        // Only changes have to be written before the response.
+                        // Uploads lock it themselves, once their body is on disk.
+                        Some(database)
+                            if route.method() != "GET" && route != Route::UploadAttachment =>
+                        {
+                            let _lock = database.lock().await;
+                            let mut held = HeldResponse {
+                                socket: &mut *socket,
+                                response: vec![],
+                            };
+                            let handled = handle_request(
+                                target, request, &mut held, buffer, state, &user, res,
+                            )
+                            .await;
+                            let response = held.response;
+                            match database.write(&state.store).await {
+                                Ok(()) => {
+                                    socket.write_all(&response).await?;
+                                    handled?;
+                                    None
+                                }
+                                Err(e) => {
+                                    eprintln!("Failed to write to the database; err = {:?}", e);
+                                    Some(helpers::Response::InternalServerError)
+                                }
+                            }
+                        }
+                        _ => {
+                            handle_request(target, request, socket, buffer, state, &user, res)
+                                .await?;
+                            None
+                        }
+                    }
+                }
+                None => Some(helpers::Response::Unauthorized),
+            },
+        }
+    };
+
+    if let Some(response) = response {
+        let response = helpers::build_response(response).await;
+        socket.write_all(&response).await?;
+    }
+    Ok(keep_alive)
+}
+
+async fn authenticate(request: &httparse::Request<'_, '_>, state: &State) -> Option<User> {
+    let header = helpers::header(request.headers, "authorization")?;
+    state.tokens.read().await.authenticate_header(header)
+}
+
+/// What a request is for, from its method and path.
+pub struct Target<'a> {
+    pub route: Route,
+    /// The project key in the path, if any.
+    pub project: Option<ProjectKey>,
+    /// The ticket id in the path, if any.
+    pub id: Option<TicketId>,
+    /// The comment id or attachment digest in the path, if any.
+    pub item: Option<&'a str>,
+    /// The template or saved query name in the path, if any.
+    pub name: Option<&'a str>,
+    /// Everything after the `?` in the path, or an empty string.
+    pub query: &'a str,
+}
+
 pub async fn handle_request<'a>(
+    target: Target<'_>,
     mut request: httparse::Request<'a, 'a>,
-    socket: &mut TcpStream,
+    socket: &mut impl Stream,
     buffer: &'a [u8],
-    store: Arc<RwLock<TicketStore>>,
+    state: &State,
+    user: &User,
     parse_result: Option<httparse::Status<usize>>,
 ) -> Result<(), anyhow::Error> {
-    match request {
-        httparse::Request {
-            method: Some("POST"),
-            path: Some("/tickets"),
-            ..
-        } => handlers::create_ticket(socket, store, &buffer, &mut request, parse_result).await,
-        httparse::Request {
-            method: Some("PATCH"),
-            path: Some(path),
-            ..
-        } if TICKET_PATH_RE.is_match(path) => {
-            if let Some(_) = TICKET_PATH_RE.captures(path) {
-                handlers::patch_ticket(socket, store, &buffer, &mut request, parse_result).await?;
+    let store = Arc::clone(&state.store);
+    let format = helpers::query_param(target.query, "description").and_then(|format| {
+        Ok(match format {
+            Some(format) => format.parse::<DescriptionFormat>()?,
+            None => DescriptionFormat::default(),
+        })
+    });
+    let format = match format {
+        Ok(format) => format,
+        Err(e) => return bad_request(socket, e).await,
+    };
+    match (target.route, target.id) {
+        (Route::CreateTicket, _) => {
+            handlers::create_ticket(
+                socket,
+                store,
+                &state.policy,
+                user,
+                buffer,
+                &mut request,
+                parse_result,
+            )
+            .await
+        }
+        (Route::IssueToken, _) => {
+            let tokens = Arc::clone(&state.tokens);
+            handlers::issue_token(socket, tokens, user, buffer, &mut request, parse_result).await
+        }
+        (Route::PatchTicket, Some(id)) => {
+            let body = helpers::parse_body(socket, &mut request, buffer, parse_result).await?;
+            handlers::patch_ticket(socket, store, &state.policy, user, id, &body).await
+        }
+        (Route::ListTickets, _) => {
+            let query = match helpers::query_param(target.query, "q") {
+                Ok(query) => query,
+                Err(e) => return bad_request(socket, e).await,
+            };
+            handlers::list_tickets(socket, store, user, query, format).await
+        }
+        (Route::GetTicket, Some(id)) => {
+            handlers::get_ticket(socket, store, user, id, format).await
+        }
+        (Route::DeleteTicket, Some(id)) => handlers::delete_ticket(socket, store, user, id).await,
+        (Route::GetLinks, Some(id)) => handlers::get_links(socket, store, user, id).await,
+        (Route::AddLink, Some(id)) => {
+            handlers::add_link(socket, store, user, id, buffer, &mut request, parse_result).await
+        }
+        (Route::RemoveLink, Some(id)) => {
+            handlers::remove_link(socket, store, user, id, buffer, &mut request, parse_result)
+                .await
+        }
+        (Route::ListComments, Some(id)) => {
+            handlers::list_comments(socket, store, user, id).await
+        }
+        (Route::AddComment, Some(id)) => {
+            handlers::add_comment(socket, store, user, id, buffer, &mut request, parse_result)
+                .await
+        }
+        (Route::EditComment, Some(id)) => match comment_id(&target) {
+            Some(comment) => {
+                let body =
+                    helpers::parse_body(socket, &mut request, buffer, parse_result).await?;
+                handlers::edit_comment(socket, store, user, id, comment, &body).await
             }
-            Ok(())
+            None => not_found(socket).await,
+        },
+        (Route::DeleteComment, Some(id)) => match comment_id(&target) {
+            Some(comment) => handlers::delete_comment(socket, store, user, id, comment).await,
+            None => not_found(socket).await,
+        },
+        (Route::ListAttachments, Some(id)) => {
+            handlers::list_attachments(socket, store, user, id).await
         }
-        httparse::Request {
-            method: Some("GET"),
-            path: Some(path),
-            ..
-        } if TICKET_PATH_RE.is_match(path) => {
-            if let Some(caps) = TICKET_PATH_RE.captures(path) {
-                let id: TicketId = caps.get(1).map(|m| m.as_str()).unwrap().parse()?;
-
-                handlers::get_ticket(socket, store, id).await?;
+        (Route::UploadAttachment, Some(id)) => {
+            let name = helpers::query_param(target.query, "name");
+            let body = helpers::body_start(&request, buffer, parse_result)?;
+            handlers::upload_attachment(socket, state, user, id, name, body).await
+        }
+        (Route::DownloadAttachment, Some(id)) => {
+            let digest = target.item.unwrap_or_default();
+            let attachments = &state.attachments;
+            handlers::download_attachment(socket, store, attachments, user, id, digest).await
+        }
+        (Route::ListProjects, _) => handlers::list_projects(socket, store, user).await,
+        (Route::CreateProject, _) => {
+            handlers::create_project(socket, store, user, buffer, &mut request, parse_result).await
+        }
+        (Route::GetProject, _) => match target.project {
+            Some(key) => handlers::get_project(socket, store, user, key).await,
+            None => not_found(socket).await,
+        },
+        (Route::PatchProject, _) => match target.project {
+            Some(key) => {
+                let request = &mut request;
+                handlers::patch_project(socket, store, user, key, buffer, request, parse_result)
+                    .await
             }
-            Ok(())
+            None => not_found(socket).await,
+        },
+        (Route::ListProjectTickets, _) => match target.project {
+            Some(key) => handlers::list_project_tickets(socket, store, user, key, format).await,
+            None => not_found(socket).await,
+        },
+        (Route::CreateProjectTicket, _) => match target.project {
+            Some(key) => {
+                let body = helpers::parse_body(socket, &mut request, buffer, parse_result).await?;
+                let policy = &state.policy;
+                handlers::create_project_ticket(socket, store, policy, user, key, &body).await
+            }
+            None => not_found(socket).await,
+        },
+        (Route::ListTrash, _) => handlers::list_trash(socket, store, user).await,
+        (Route::RestoreTicket, Some(id)) => {
+            handlers::restore_ticket(socket, store, user, id, format).await
+        }
+        (Route::PurgeTicket, Some(id)) => handlers::purge_ticket(socket, store, user, id).await,
+        (Route::EmptyTrash, _) => {
+            let days = match helpers::query_param(target.query, "older_than_days") {
+                Ok(days) => days,
+                Err(e) => return bad_request(socket, e).await,
+            };
+            handlers::empty_trash(socket, store, user, days).await
+        }
+        (Route::Undo, _) => {
+            let count = match helpers::query_param(target.query, "count") {
+                Ok(count) => count,
+                Err(e) => return bad_request(socket, e).await,
+            };
+            handlers::undo(socket, store, user, count).await
+        }
+        (Route::Redo, _) => {
+            let count = match helpers::query_param(target.query, "count") {
+                Ok(count) => count,
+                Err(e) => return bad_request(socket, e).await,
+            };
+            handlers::redo(socket, store, user, count).await
+        }
+        (Route::ListTemplates, _) => handlers::list_templates(socket, store, user).await,
+        (Route::CreateTemplate, _) => {
+            handlers::create_template(socket, store, user, buffer, &mut request, parse_result)
+                .await
+        }
+        (Route::GetTemplate, _) => match target.name {
+            Some(name) => handlers::get_template(socket, store, user, name).await,
+            None => not_found(socket).await,
+        },
+        (Route::DeleteTemplate, _) => match target.name {
+            Some(name) => handlers::delete_template(socket, store, user, name).await,
+            None => not_found(socket).await,
+        },
+        (Route::CreateTemplateTickets, _) => match target.name {
+            Some(name) => {
+                let body = helpers::parse_body(socket, &mut request, buffer, parse_result).await?;
+                let policy = &state.policy;
+                handlers::create_template_tickets(socket, store, policy, user, name, &body).await
+            }
+            None => not_found(socket).await,
+        },
+        (Route::ListQueries, _) => handlers::list_queries(socket, store, user).await,
+        (Route::SaveQuery, _) => {
+            handlers::save_query(socket, store, user, buffer, &mut request, parse_result).await
+        }
+        (Route::DeleteQuery, _) => match target.name {
+            Some(name) => handlers::delete_query(socket, store, user, name).await,
+            None => not_found(socket).await,
+        },
+        (Route::RunQuery, _) => match target.name {
+            Some(name) => handlers::run_query(socket, store, user, name, format).await,
+            None => not_found(socket).await,
+        },
+        (Route::ReportSummary, _) => {
+            let period = helpers::query_param(target.query, "days")
+                .and_then(|days| Ok((days, helpers::query_param(target.query, "windows")?)));
+            let (days, windows) = match period {
+                Ok(period) => period,
+                Err(e) => return bad_request(socket, e).await,
+            };
+            handlers::report_summary(socket, store, user, days, windows).await
         }
-        _ => Err(anyhow!("Got something else!")),
+        (Route::OpenApi, _) => handlers::openapi(socket).await,
+        (
+            Route::PatchTicket
+            | Route::GetTicket
+            | Route::DeleteTicket
+            | Route::GetLinks
+            | Route::AddLink
+            | Route::RemoveLink
+            | Route::ListComments
+            | Route::AddComment
+            | Route::EditComment
+            | Route::DeleteComment
+            | Route::ListAttachments
+            | Route::UploadAttachment
+            | Route::DownloadAttachment
+            | Route::RestoreTicket
+            | Route::PurgeTicket,
+            None,
+        ) => not_found(socket).await,
     }
 }
+
+fn comment_id(target: &Target) -> Option<CommentId> {
+    target.item?.parse().ok()
+}
+
+async fn not_found(socket: &mut impl Stream) -> Result<(), anyhow::Error> {
+    let response = helpers::build_response(helpers::Response::<()>::NotFound).await;
+    socket.write_all(&response).await?;
+    Ok(())
+}
+
+async fn bad_request(socket: &mut impl Stream, e: anyhow::Error) -> Result<(), anyhow::Error> {
+    let response =
+        helpers::build_response(helpers::Response::<()>::BadRequest(e.to_string())).await;
+    socket.write_all(&response).await?;
+    Ok(())
+}
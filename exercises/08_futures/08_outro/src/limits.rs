@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How many requests a client may burst, and how fast its allowance refills.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quota {
+    pub burst: u32,
+    pub per_second: f64,
+}
+
+impl Quota {
+    pub fn new(burst: u32, per_second: f64) -> Self {
+        Self { burst, per_second }
+    }
+}
+
+/// A quota that only applies to requests matching `method` and `path`.
+/// Paths are compared segment by segment, and a `*` segment matches any one
+/// segment: `/projects/*/tickets` covers `/projects/API/tickets`, but
+/// `/tickets` doesn't cover `/tickets/TKT-1/comments`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RouteQuota {
+    pub method: String,
+    pub path: String,
+    pub quota: Quota,
+}
+
+impl RouteQuota {
+    pub fn new(method: &str, path: &str, quota: Quota) -> Self {
+        Self {
+            method: method.to_string(),
+            path: path.to_string(),
+            quota,
+        }
+    }
+
+    fn matches(&self, method: &str, path: &str) -> bool {
+        let mut pattern = self.path.split('/');
+        let mut segments = path.split('/');
+        self.method.eq_ignore_ascii_case(method)
+            && loop {
+                match (pattern.next(), segments.next()) {
+                    (None, None) => break true,
+                    (Some("*"), Some(_)) => {}
+                    (Some(expected), Some(segment)) if expected == segment => {}
+                    _ => break false,
+                }
+            }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RateLimitConfig {
+    /// Applied to every request that isn't covered by one of `routes`.
+    pub default: Quota,
+    /// Checked in order, the first match wins.
+    pub routes: Vec<RouteQuota>,
+    /// Clients that haven't been seen for this long are forgotten.
+    pub idle_timeout: Duration,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            default: Quota::new(50, 20.0),
+            routes: vec![
+                RouteQuota::new("POST", "/tickets", Quota::new(5, 1.0)),
+                // Creating projects and the tickets in them.
+                RouteQuota::new("POST", "/projects", Quota::new(5, 1.0)),
+                RouteQuota::new("POST", "/projects/*/tickets", Quota::new(5, 1.0)),
+            ],
+            idle_timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+impl RateLimitConfig {
+    /// Quotas no client can use up, for load tests from a single machine.
+    pub fn unlimited() -> Self {
+        let quota = Quota::new(u32::MAX, f64::from(u32::MAX));
+        Self {
+            default: quota,
+            routes: vec![],
+            ..Self::default()
+        }
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    last_seen: Instant,
+}
+
+/// Per-IP token bucket limiter. Each client gets one bucket per route quota,
+/// plus one for the default quota.
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    buckets: Mutex<HashMap<(IpAddr, Option<usize>), Bucket>>,
+    last_sweep: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            buckets: Mutex::new(HashMap::new()),
+            last_sweep: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Takes a token from the client's bucket for this route.
+    /// On failure, returns how long the client should wait before retrying.
+    pub fn check(&self, ip: IpAddr, method: &str, path: &str) -> Result<(), Duration> {
+        self.check_at(ip, method, path, Instant::now())
+    }
+
+    pub fn check_at(
+        &self,
+        ip: IpAddr,
+        method: &str,
+        path: &str,
+        now: Instant,
+    ) -> Result<(), Duration> {
+        self.sweep(now);
+
+        let route = self
+            .config
+            .routes
+            .iter()
+            .position(|route| route.matches(method, path));
+        let quota = route.map_or(self.config.default, |idx| self.config.routes[idx].quota);
+
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets.entry((ip, route)).or_insert(Bucket {
+            tokens: f64::from(quota.burst),
+            last_seen: now,
+        });
+
+        let elapsed = now
+            .saturating_duration_since(bucket.last_seen)
+            .as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * quota.per_second).min(f64::from(quota.burst));
+        bucket.last_seen = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else if quota.per_second > 0.0 {
+            Err(Duration::from_secs_f64(
+                (1.0 - bucket.tokens) / quota.per_second,
+            ))
+        } else {
+            Err(self.config.idle_timeout)
+        }
+    }
+
+    /// Number of (client, route) pairs currently tracked.
+    pub fn tracked_clients(&self) -> usize {
+        self.buckets.lock().unwrap().len()
+    }
+
+    fn sweep(&self, now: Instant) {
+        let mut last_sweep = self.last_sweep.lock().unwrap();
+        if now.saturating_duration_since(*last_sweep) < self.config.idle_timeout {
+            return;
+        }
+        *last_sweep = now;
+
+        let idle_timeout = self.config.idle_timeout;
+        self.buckets
+            .lock()
+            .unwrap()
+            .retain(|_, bucket| now.saturating_duration_since(bucket.last_seen) < idle_timeout);
+    }
+}
@@ -0,0 +1,349 @@
+//! Typed links between tickets.
+//!
+//! - `ChildOf` makes a ticket a subtask of another. A ticket has at most one parent.
+//! - `Blocks` means the first ticket has to be done before the second can be.
+//! - `DuplicateOf` marks a ticket as reporting the same thing as another.
+//!   A ticket is a duplicate of at most one other ticket.
+//!
+//! None of the kinds can form a cycle: a ticket can't be its own ancestor,
+//! (transitively) block itself, or be a duplicate of its own duplicate.
+
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::data::Status;
+use crate::store::TicketId;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum LinkKind {
+    ChildOf,
+    Blocks,
+    DuplicateOf,
+}
+
+impl LinkKind {
+    pub const ALL: [LinkKind; 3] = [LinkKind::ChildOf, LinkKind::Blocks, LinkKind::DuplicateOf];
+}
+
+impl fmt::Display for LinkKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            LinkKind::ChildOf => "child-of",
+            LinkKind::Blocks => "blocks",
+            LinkKind::DuplicateOf => "duplicate-of",
+        };
+        f.write_str(kind)
+    }
+}
+
+impl FromStr for LinkKind {
+    type Err = anyhow::Error;
+
+    /// Case-insensitive, ignoring `-`, `_` and spaces, like `Status`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let normalized: String = s
+            .chars()
+            .filter(|c| !matches!(c, '-' | '_' | ' '))
+            .collect::<String>()
+            .to_lowercase();
+        match normalized.as_str() {
+            "childof" => Ok(LinkKind::ChildOf),
+            "blocks" => Ok(LinkKind::Blocks),
+            "duplicateof" => Ok(LinkKind::DuplicateOf),
+            _ => Err(anyhow!(
+                "Unknown link kind `{}`, expected child-of, blocks or duplicate-of",
+                s
+            )),
+        }
+    }
+}
+
+/// `from` is a child of, blocks, or is a duplicate of `to`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Link {
+    pub from: TicketId,
+    pub kind: LinkKind,
+    pub to: TicketId,
+}
+
+impl fmt::Display for Link {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.from, self.kind, self.to)
+    }
+}
+
+/// Why a link was refused.
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum LinkError {
+    #[error("No ticket with id {0}")]
+    NotFound(TicketId),
+    #[error("A ticket can't be linked to itself")]
+    SelfLink,
+    #[error("Ticket {child} is already a child of {parent}")]
+    HasParent { child: TicketId, parent: TicketId },
+    #[error("Ticket {duplicate} is already a duplicate of {original}")]
+    AlreadyDuplicate {
+        duplicate: TicketId,
+        original: TicketId,
+    },
+    /// `path` goes from the link's `to` back to its `from`.
+    #[error("Linking would create a cycle: {}", format_path(.path))]
+    Cycle { kind: LinkKind, path: Vec<TicketId> },
+}
+
+fn format_path(path: &[TicketId]) -> String {
+    let ids: Vec<String> = path.iter().map(ToString::to_string).collect();
+    ids.join(" -> ")
+}
+
+/// Why a ticket can't be moved to `Done`: the tickets blocking it that
+/// aren't done yet. See `Links::check_status`.
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+#[error("The ticket can't be done while it's blocked by {}", format_ids(.0))]
+pub struct Blocked(pub Vec<TicketId>);
+
+pub(crate) fn format_ids(ids: &[TicketId]) -> String {
+    let ids: Vec<String> = ids.iter().map(ToString::to_string).collect();
+    ids.join(", ")
+}
+
+/// Every link between the tickets of a store.
+///
+/// Links are kept in one ordered set, which is small enough for the number
+/// of tickets a store holds that lookups scan it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Links {
+    links: BTreeSet<Link>,
+}
+
+impl Links {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `link` after checking it doesn't break the rules in the module docs.
+    /// Doesn't check that the tickets exist, see `TicketStore::link`.
+    /// Returns `false` if the link was already there.
+    pub fn add(&mut self, link: Link) -> Result<bool, LinkError> {
+        if link.from == link.to {
+            return Err(LinkError::SelfLink);
+        }
+        if self.links.contains(&link) {
+            return Ok(false);
+        }
+        match link.kind {
+            LinkKind::ChildOf => {
+                if let Some(parent) = self.parent(link.from) {
+                    return Err(LinkError::HasParent {
+                        child: link.from,
+                        parent,
+                    });
+                }
+            }
+            LinkKind::DuplicateOf => {
+                if let Some(original) = self.duplicate_of(link.from) {
+                    return Err(LinkError::AlreadyDuplicate {
+                        duplicate: link.from,
+                        original,
+                    });
+                }
+            }
+            LinkKind::Blocks => {}
+        }
+        if let Some(path) = self.path(link.to, link.from, link.kind) {
+            return Err(LinkError::Cycle {
+                kind: link.kind,
+                path,
+            });
+        }
+        Ok(self.links.insert(link))
+    }
+
+    /// Returns whether the link was there.
+    pub fn remove(&mut self, link: &Link) -> bool {
+        self.links.remove(link)
+    }
+
+    /// Removes every link from or to `id`. This is what happens when a ticket
+    /// is deleted: its children lose their parent, the tickets it blocked are
+    /// no longer blocked by it, and its duplicates are no longer marked as such.
+    pub fn remove_ticket(&mut self, id: TicketId) {
+        self.links.retain(|link| link.from != id && link.to != id);
+    }
+
+    /// Every link, ordered by `from`, then kind, then `to`.
+    pub fn iter(&self) -> impl Iterator<Item = &Link> {
+        self.links.iter()
+    }
+
+    /// The tickets `id` links to with `kind`.
+    pub fn outgoing(&self, id: TicketId, kind: LinkKind) -> Vec<TicketId> {
+        self.links
+            .iter()
+            .filter(|link| link.from == id && link.kind == kind)
+            .map(|link| link.to)
+            .collect()
+    }
+
+    /// The tickets linking to `id` with `kind`.
+    pub fn incoming(&self, id: TicketId, kind: LinkKind) -> Vec<TicketId> {
+        self.links
+            .iter()
+            .filter(|link| link.to == id && link.kind == kind)
+            .map(|link| link.from)
+            .collect()
+    }
+
+    pub fn parent(&self, id: TicketId) -> Option<TicketId> {
+        self.outgoing(id, LinkKind::ChildOf).first().copied()
+    }
+
+    pub fn children(&self, id: TicketId) -> Vec<TicketId> {
+        self.incoming(id, LinkKind::ChildOf)
+    }
+
+    /// What blocks `id` directly.
+    pub fn blockers(&self, id: TicketId) -> Vec<TicketId> {
+        self.incoming(id, LinkKind::Blocks)
+    }
+
+    /// Checks that `id` can be moved to `status`: it can't be done while any
+    /// ticket blocking it is open. `status_of` gives the status of a ticket
+    /// blocking it, or `None` if that ticket is gone.
+    pub fn check_status(
+        &self,
+        id: TicketId,
+        status: Status,
+        status_of: impl Fn(TicketId) -> Option<Status>,
+    ) -> Result<(), Blocked> {
+        if status != Status::Done {
+            return Ok(());
+        }
+        let open: Vec<TicketId> = self
+            .blockers(id)
+            .into_iter()
+            .filter(|blocker| status_of(*blocker).is_some_and(|status| status != Status::Done))
+            .collect();
+        if open.is_empty() {
+            Ok(())
+        } else {
+            Err(Blocked(open))
+        }
+    }
+
+    /// What `id` blocks directly.
+    pub fn blocking(&self, id: TicketId) -> Vec<TicketId> {
+        self.outgoing(id, LinkKind::Blocks)
+    }
+
+    pub fn duplicate_of(&self, id: TicketId) -> Option<TicketId> {
+        self.outgoing(id, LinkKind::DuplicateOf).first().copied()
+    }
+
+    pub fn duplicates(&self, id: TicketId) -> Vec<TicketId> {
+        self.incoming(id, LinkKind::DuplicateOf)
+    }
+
+    /// Everything that blocks `id`, directly or through other tickets,
+    /// nearest first.
+    pub fn all_blockers(&self, id: TicketId) -> Vec<TicketId> {
+        self.closure(id, |id| self.blockers(id))
+    }
+
+    /// Every subtask of `id`, and their subtasks, nearest first.
+    pub fn descendants(&self, id: TicketId) -> Vec<TicketId> {
+        self.closure(id, |id| self.children(id))
+    }
+
+    /// The tickets reachable from `start` through `next`, breadth first,
+    /// without `start` itself.
+    fn closure(&self, start: TicketId, next: impl Fn(TicketId) -> Vec<TicketId>) -> Vec<TicketId> {
+        let mut seen = BTreeSet::from([start]);
+        let mut found = vec![];
+        let mut queue = VecDeque::from([start]);
+        while let Some(id) = queue.pop_front() {
+            for next in next(id) {
+                if seen.insert(next) {
+                    found.push(next);
+                    queue.push_back(next);
+                }
+            }
+        }
+        found
+    }
+
+    /// A shortest path of `kind` links from `start` to `end`, both included.
+    fn path(&self, start: TicketId, end: TicketId, kind: LinkKind) -> Option<Vec<TicketId>> {
+        let mut previous = BTreeMap::new();
+        let mut queue = VecDeque::from([start]);
+        while let Some(id) = queue.pop_front() {
+            if id == end {
+                let mut path = vec![end];
+                let mut current = end;
+                while let Some(&before) = previous.get(&current) {
+                    path.push(before);
+                    current = before;
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for next in self.outgoing(id, kind) {
+                if next != start && !previous.contains_key(&next) {
+                    previous.insert(next, id);
+                    queue.push_back(next);
+                }
+            }
+        }
+        None
+    }
+}
+
+impl FromIterator<Link> for Links {
+    /// Collects links as is, without checking them.
+    fn from_iter<I: IntoIterator<Item = Link>>(iter: I) -> Self {
+        Self {
+            links: iter.into_iter().collect(),
+        }
+    }
+}
+
+/// Every link of one ticket, as returned by `GET /tickets/{id}/links`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TicketLinks {
+    pub parent: Option<TicketId>,
+    pub children: Vec<TicketId>,
+    pub blocks: Vec<TicketId>,
+    pub blocked_by: Vec<TicketId>,
+    pub duplicate_of: Option<TicketId>,
+    pub duplicates: Vec<TicketId>,
+    /// Everything that has to be done before this ticket, transitively.
+    pub all_blockers: Vec<TicketId>,
+    /// Every subtask, transitively.
+    pub descendants: Vec<TicketId>,
+}
+
+impl TicketLinks {
+    pub fn of(links: &Links, id: TicketId) -> Self {
+        Self {
+            parent: links.parent(id),
+            children: links.children(id),
+            blocks: links.blocking(id),
+            blocked_by: links.blockers(id),
+            duplicate_of: links.duplicate_of(id),
+            duplicates: links.duplicates(id),
+            all_blockers: links.all_blockers(id),
+            descendants: links.descendants(id),
+        }
+    }
+}
+
+/// The body of `POST` and `DELETE /tickets/{id}/links`,
+/// for a link from the ticket in the path.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LinkRequest {
+    pub kind: LinkKind,
+    pub to: TicketId,
+}
@@ -0,0 +1,421 @@
+//! A load generator for the server: `concurrency` workers send a mix of
+//! creates, gets and patches for a fixed duration, and the latency of every
+//! request is recorded.
+//!
+//! Requests aren't retried, so the report shows what the server actually did,
+//! rate limiting included: start the server with `TICKETS_NO_RATE_LIMITS=1`
+//! to measure anything else.
+
+use anyhow::{anyhow, bail};
+use rand::Rng;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use crate::client::{Client, ClientConfig, ClientError};
+use crate::data::{Status, TicketDraft, TicketPatch};
+use crate::store::TicketId;
+
+#[derive(Clone, Debug)]
+pub struct LoadConfig {
+    /// Address of the server, e.g. `127.0.0.1:8080`.
+    pub addr: String,
+    /// Bearer token of a user who can create and edit tickets.
+    pub token: String,
+    /// How many workers send requests at the same time, each waiting for
+    /// a response before sending its next request.
+    pub concurrency: usize,
+    pub duration: Duration,
+    pub mix: Mix,
+    /// Whether workers reuse their connection, or open one per request.
+    pub keep_alive: bool,
+    /// Requests taking longer than this count as errors.
+    pub timeout: Duration,
+}
+
+impl Default for LoadConfig {
+    fn default() -> Self {
+        Self {
+            addr: String::from("127.0.0.1:8080"),
+            token: String::new(),
+            concurrency: 16,
+            duration: Duration::from_secs(10),
+            mix: Mix::default(),
+            keep_alive: true,
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+/// A request the load generator sends.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Operation {
+    /// `POST /tickets`
+    Create,
+    /// `GET /tickets/{id}`
+    Get,
+    /// `PATCH /tickets/{id}`, changing the status.
+    Patch,
+}
+
+impl Operation {
+    pub const ALL: [Operation; 3] = [Operation::Create, Operation::Get, Operation::Patch];
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Operation::Create => "create",
+            Operation::Get => "get",
+            Operation::Patch => "patch",
+        };
+        f.write_str(name)
+    }
+}
+
+/// How often each operation is picked, relative to the others.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Mix {
+    pub create: u32,
+    pub get: u32,
+    pub patch: u32,
+}
+
+impl Mix {
+    fn total(&self) -> u64 {
+        u64::from(self.create) + u64::from(self.get) + u64::from(self.patch)
+    }
+
+    fn weight(&self, operation: Operation) -> u64 {
+        match operation {
+            Operation::Create => self.create,
+            Operation::Get => self.get,
+            Operation::Patch => self.patch,
+        }
+        .into()
+    }
+
+    fn pick(&self, rng: &mut impl Rng) -> Operation {
+        let mut n = rng.gen_range(0..self.total());
+        for operation in Operation::ALL {
+            let weight = self.weight(operation);
+            if n < weight {
+                return operation;
+            }
+            n -= weight;
+        }
+        unreachable!("`n` is below the total weight")
+    }
+}
+
+/// Mostly reads: 1 create and 1 patch for every 8 gets.
+impl Default for Mix {
+    fn default() -> Self {
+        Self {
+            create: 1,
+            get: 8,
+            patch: 1,
+        }
+    }
+}
+
+/// Parses weights like `create=1,get=8,patch=1`. Operations left out get 0.
+impl FromStr for Mix {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut mix = Mix {
+            create: 0,
+            get: 0,
+            patch: 0,
+        };
+        for part in s.split(',') {
+            let (name, weight) = part
+                .split_once('=')
+                .ok_or_else(|| anyhow!("Expected `operation=weight`, got `{}`", part))?;
+            let weight = weight
+                .trim()
+                .parse()
+                .map_err(|_| anyhow!("`{}` is not a valid weight", weight))?;
+            match name.trim() {
+                "create" => mix.create = weight,
+                "get" => mix.get = weight,
+                "patch" => mix.patch = weight,
+                name => bail!(
+                    "Unknown operation `{}`, expected create, get or patch",
+                    name
+                ),
+            }
+        }
+        if mix.total() == 0 {
+            bail!("At least one operation needs a weight above 0");
+        }
+        Ok(mix)
+    }
+}
+
+/// What a load test measured.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Report {
+    /// How long the workers ran, which is a little over the configured duration
+    /// since requests in flight are waited for.
+    pub elapsed: Duration,
+    /// Operations that were picked at least once, in order.
+    pub operations: Vec<OperationReport>,
+    /// How many requests failed, by what went wrong, e.g. `429` or `timeout`.
+    pub errors: BTreeMap<String, usize>,
+}
+
+impl Report {
+    pub fn requests(&self) -> usize {
+        self.operations.iter().map(|report| report.requests).sum()
+    }
+
+    pub fn requests_per_second(&self) -> f64 {
+        self.requests() as f64 / self.elapsed.as_secs_f64()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct OperationReport {
+    pub operation: Operation,
+    /// Every request sent, including failed ones.
+    pub requests: usize,
+    pub errors: usize,
+    /// Over successful requests only, since failures are often much faster.
+    pub latency: Latency,
+}
+
+/// Latency percentiles, all zero without samples.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Latency {
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+impl Latency {
+    /// Nearest-rank percentiles of `samples`.
+    pub fn new(samples: &mut [Duration]) -> Self {
+        samples.sort_unstable();
+        let percentile = |p: usize| match samples.len() {
+            0 => Duration::ZERO,
+            n => samples[(n * p).div_ceil(100).max(1) - 1],
+        };
+        Self {
+            p50: percentile(50),
+            p90: percentile(90),
+            p99: percentile(99),
+            max: percentile(100),
+        }
+    }
+}
+
+/// What one worker saw.
+#[derive(Default)]
+struct Samples {
+    latencies: BTreeMap<Operation, Vec<Duration>>,
+    requests: BTreeMap<Operation, usize>,
+    errors: BTreeMap<Operation, usize>,
+    kinds: BTreeMap<String, usize>,
+}
+
+/// Runs a load test against the server at `config.addr`.
+///
+/// Each worker creates a ticket before the clock starts, so gets and patches
+/// always have something to hit. After that, they only touch tickets they
+/// created themselves.
+pub async fn run(config: LoadConfig) -> Result<Report, anyhow::Error> {
+    if config.concurrency == 0 {
+        bail!("`concurrency` must be at least 1");
+    }
+    let client_config = ClientConfig {
+        timeout: config.timeout,
+        retries: 0,
+        max_idle: usize::from(config.keep_alive),
+        ..ClientConfig::default()
+    };
+
+    let mut workers = Vec::with_capacity(config.concurrency);
+    for _ in 0..config.concurrency {
+        let client = Client::with_config(&config.addr, &config.token, client_config.clone());
+        let id = client
+            .create(&draft())
+            .await
+            .map_err(|e| anyhow!("Failed to create the first ticket: {}", e))?;
+        workers.push((client, id));
+    }
+
+    let start = Instant::now();
+    let deadline = start + config.duration;
+    let tasks: Vec<_> = workers
+        .into_iter()
+        .map(|(client, id)| tokio::spawn(work(client, id, config.mix, deadline)))
+        .collect();
+    let mut samples = Vec::with_capacity(tasks.len());
+    for task in tasks {
+        samples.push(task.await?);
+    }
+    Ok(report(samples, start.elapsed()))
+}
+
+async fn work(client: Client, first: TicketId, mix: Mix, deadline: Instant) -> Samples {
+    let mut samples = Samples::default();
+    let mut ids = vec![first];
+    let mut status = Status::ToDo;
+    while Instant::now() < deadline {
+        // `ThreadRng` can't be held across an `.await`.
+        let (operation, id) = {
+            let mut rng = rand::thread_rng();
+            (mix.pick(&mut rng), ids[rng.gen_range(0..ids.len())])
+        };
+
+        let sent = Instant::now();
+        let result = match operation {
+            Operation::Create => client.create(&draft()).await.map(|id| ids.push(id)),
+            Operation::Get => client.get(id).await.map(|_| ()),
+            Operation::Patch => {
+                status = match status {
+                    Status::ToDo => Status::InProgress,
+                    _ => Status::ToDo,
+                };
+                let patch = TicketPatch {
+                    id,
+                    title: None,
+                    description: None,
+                    status: Some(status),
+                };
+                client.patch(&patch).await.map(|_| ())
+            }
+        };
+        let latency = sent.elapsed();
+
+        *samples.requests.entry(operation).or_default() += 1;
+        match result {
+            Ok(()) => samples
+                .latencies
+                .entry(operation)
+                .or_default()
+                .push(latency),
+            Err(e) => {
+                *samples.errors.entry(operation).or_default() += 1;
+                *samples.kinds.entry(error_kind(&e)).or_default() += 1;
+            }
+        }
+    }
+    samples
+}
+
+fn draft() -> TicketDraft {
+    TicketDraft::new(
+        "Load test".into(),
+        Some("Created by the load generator".into()),
+    )
+}
+
+/// Groups errors by status code, or by what went wrong if there's none.
+fn error_kind(error: &ClientError) -> String {
+    let kind = match error {
+        ClientError::BadRequest(_) => "400",
+        ClientError::Unauthorized => "401",
+        ClientError::Forbidden => "403",
+        ClientError::NotFound => "404",
+        ClientError::Conflict(_) => "409",
+        ClientError::TooLarge(_) => "413",
+        ClientError::RateLimited(_) => "429",
+        ClientError::Unexpected { status, .. } => return status.to_string(),
+        ClientError::Protocol(_) => "malformed response",
+        ClientError::Timeout(_) => "timeout",
+        ClientError::Io(_) => "connection error",
+    };
+    kind.to_string()
+}
+
+fn report(samples: Vec<Samples>, elapsed: Duration) -> Report {
+    let mut total = Samples::default();
+    for worker in samples {
+        for (operation, mut latencies) in worker.latencies {
+            total
+                .latencies
+                .entry(operation)
+                .or_default()
+                .append(&mut latencies);
+        }
+        for (operation, requests) in worker.requests {
+            *total.requests.entry(operation).or_default() += requests;
+        }
+        for (operation, errors) in worker.errors {
+            *total.errors.entry(operation).or_default() += errors;
+        }
+        for (kind, errors) in worker.kinds {
+            *total.kinds.entry(kind).or_default() += errors;
+        }
+    }
+
+    let operations = total
+        .requests
+        .iter()
+        .map(|(&operation, &requests)| OperationReport {
+            operation,
+            requests,
+            errors: total.errors.get(&operation).copied().unwrap_or(0),
+            latency: Latency::new(total.latencies.entry(operation).or_default()),
+        })
+        .collect();
+    Report {
+        elapsed,
+        operations,
+        errors: total.kinds,
+    }
+}
+
+/// A table with a row per operation and one for all of them, then errors.
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let seconds = self.elapsed.as_secs_f64();
+        writeln!(
+            f,
+            "{:<9}  {:>8}  {:>6}  {:>9}  {:>8}  {:>8}  {:>8}  {:>8}",
+            "OPERATION", "REQUESTS", "ERRORS", "REQ/S", "P50", "P90", "P99", "MAX"
+        )?;
+        for report in &self.operations {
+            let latency = report.latency;
+            writeln!(
+                f,
+                "{:<9}  {:>8}  {:>6}  {:>9.1}  {:>8}  {:>8}  {:>8}  {:>8}",
+                report.operation.to_string(),
+                report.requests,
+                report.errors,
+                report.requests as f64 / seconds,
+                millis(latency.p50),
+                millis(latency.p90),
+                millis(latency.p99),
+                millis(latency.max)
+            )?;
+        }
+        let errors: usize = self.errors.values().sum();
+        writeln!(
+            f,
+            "{:<9}  {:>8}  {:>6}  {:>9.1}",
+            "total",
+            self.requests(),
+            errors,
+            self.requests_per_second()
+        )?;
+
+        if !self.errors.is_empty() {
+            writeln!(f)?;
+            writeln!(f, "{:<20}  {:>6}", "ERROR", "COUNT")?;
+            for (kind, count) in &self.errors {
+                writeln!(f, "{:<20}  {:>6}", kind, count)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn millis(duration: Duration) -> String {
+    format!("{:.2}ms", duration.as_secs_f64() * 1000.0)
+}
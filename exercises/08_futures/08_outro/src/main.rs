@@ -1,11 +1,9 @@
-use std::sync::Arc;
+use outro_08::server::{self, Config};
 
 #[tokio::main]
 async fn main() -> Result<(), anyhow::Error> {
-    let (listener, store) = outro_08::server::init().await?;
+    let config = Config::from_env()?;
+    let (listeners, state) = server::init(&config).await?;
 
-    loop {
-        let (socket, _) = listener.accept().await?;
-        tokio::spawn(outro_08::handle_connection(socket, Arc::clone(&store)));
-    }
+    server::serve(listeners, state).await
 }
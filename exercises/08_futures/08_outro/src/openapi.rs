@@ -0,0 +1,1452 @@
+use serde_json::{json, Map, Value};
+use ticket_fields::{MarkdownDescription, TicketTitle};
+
+use crate::attachments::{Attachment, AttachmentDir};
+use crate::auth::{IssuedToken, Role, TokenRequest};
+use crate::comments::{Comment, CommentDraft, CommentId};
+use crate::data::{DescriptionFormat, ErrorBody, Status, Ticket, TicketDraft, TicketPatch};
+use crate::history::Report;
+use crate::links::{LinkKind, LinkRequest, TicketLinks};
+use crate::projects::{Project, ProjectDraft, ProjectKey, ProjectPatch, ProjectSettings};
+use crate::query::SavedQuery;
+use crate::reports::{AgeBucket, Durations, Period, StatusCount, Summary, Window};
+use crate::routes::Route;
+use crate::store::TicketId;
+use crate::templates::{Template, TemplateRequest, TicketTemplate};
+use crate::trash::Trashed;
+
+/// A type that can describe its JSON form as an OpenAPI schema.
+/// The schema is registered under `components/schemas/{NAME}`.
+pub trait ApiSchema {
+    const NAME: &'static str;
+
+    fn schema() -> Value;
+
+    fn reference() -> Value {
+        json!({ "$ref": format!("#/components/schemas/{}", Self::NAME) })
+    }
+}
+
+impl ApiSchema for TicketId {
+    const NAME: &'static str = "TicketId";
+
+    fn schema() -> Value {
+        json!({
+            "type": "string",
+            "pattern": "^[A-Z][A-Z0-9]{1,9}-([0-9]+|[0-9A-HJKMNP-TV-Z]{26}|[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12})$",
+            "example": "API-42",
+            "description": format!(
+                "The project key and the ticket's serial: its number within the project, \
+                 or a ULID or UUIDv7 if the server is set up to generate those. \
+                 In requests, a bare serial means a ticket of the default project, {}.",
+                ProjectKey::DEFAULT
+            ),
+        })
+    }
+}
+
+impl ApiSchema for ProjectKey {
+    const NAME: &'static str = "ProjectKey";
+
+    fn schema() -> Value {
+        json!({
+            "type": "string",
+            "pattern": "^[A-Za-z][A-Za-z0-9]{1,9}$",
+            "example": "API",
+            "description": "Case-insensitive, returned in upper case.",
+        })
+    }
+}
+
+impl ApiSchema for Project {
+    const NAME: &'static str = "Project";
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["key", "name", "description", "archived", "next_number"],
+            "properties": {
+                "key": ProjectKey::reference(),
+                "name": project_name(),
+                "description": { "type": "string" },
+                "archived": {
+                    "type": "boolean",
+                    "description": "No tickets can be created in archived projects.",
+                },
+                "next_number": {
+                    "type": "integer",
+                    "format": "uint64",
+                    "description": "The number the next ticket of the project gets.",
+                },
+            },
+        })
+    }
+}
+
+impl ApiSchema for ProjectDraft {
+    const NAME: &'static str = "ProjectDraft";
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["key", "name"],
+            "properties": {
+                "key": ProjectKey::reference(),
+                "name": project_name(),
+                "description": { "type": "string", "default": "" },
+                "archived": { "type": "boolean", "default": false },
+            },
+        })
+    }
+}
+
+impl ApiSchema for ProjectPatch {
+    const NAME: &'static str = "ProjectPatch";
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "description": "Fields left out or set to null are not changed.",
+            "properties": {
+                "name": nullable(project_name()),
+                "description": { "type": "string", "nullable": true },
+                "archived": { "type": "boolean", "nullable": true },
+            },
+        })
+    }
+}
+
+fn project_name() -> Value {
+    json!({ "type": "string", "minLength": 1, "maxLength": ProjectSettings::MAX_NAME_LEN })
+}
+
+impl ApiSchema for TicketTitle {
+    const NAME: &'static str = "TicketTitle";
+
+    fn schema() -> Value {
+        json!({
+            "type": "string",
+            "minLength": 1,
+            "x-maxGraphemes": TicketTitle::MAX_GRAPHEMES,
+            "description": format!(
+                "One line of 1 to {} characters, counted as grapheme clusters. \
+                 Stored NFC-normalized, without leading or trailing whitespace.",
+                TicketTitle::MAX_GRAPHEMES
+            ),
+        })
+    }
+}
+
+impl ApiSchema for MarkdownDescription {
+    const NAME: &'static str = "TicketDescription";
+
+    fn schema() -> Value {
+        json!({
+            "type": "string",
+            "minLength": 1,
+            "x-maxGraphemes": MarkdownDescription::MAX_GRAPHEMES,
+            "description": format!(
+                "Markdown, 1 to {} characters, counted as grapheme clusters. Newlines and \
+                 tabs are allowed. Stored NFC-normalized, without leading or trailing whitespace.",
+                MarkdownDescription::MAX_GRAPHEMES
+            ),
+        })
+    }
+}
+
+impl ApiSchema for Status {
+    const NAME: &'static str = "Status";
+
+    fn schema() -> Value {
+        json!({ "type": "string", "enum": ["ToDo", "InProgress", "Done"] })
+    }
+}
+
+impl ApiSchema for Ticket {
+    const NAME: &'static str = "Ticket";
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["id", "title", "description", "status"],
+            "properties": {
+                "id": TicketId::reference(),
+                "title": TicketTitle::reference(),
+                "description": MarkdownDescription::reference(),
+                "status": Status::reference(),
+                "reporter": { "type": "string", "nullable": true },
+                "last_editor": { "type": "string", "nullable": true },
+            },
+        })
+    }
+}
+
+impl ApiSchema for TicketDraft {
+    const NAME: &'static str = "TicketDraft";
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["title", "description"],
+            "properties": {
+                "title": TicketTitle::reference(),
+                "description": MarkdownDescription::reference(),
+            },
+        })
+    }
+}
+
+impl ApiSchema for TicketPatch {
+    const NAME: &'static str = "TicketPatch";
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["id"],
+            "description": "Fields left out or set to null are not changed.",
+            "properties": {
+                "id": TicketId::reference(),
+                "title": nullable(TicketTitle::reference()),
+                "description": nullable(MarkdownDescription::reference()),
+                "status": nullable(Status::reference()),
+            },
+        })
+    }
+}
+
+impl ApiSchema for LinkKind {
+    const NAME: &'static str = "LinkKind";
+
+    fn schema() -> Value {
+        json!({ "type": "string", "enum": ["ChildOf", "Blocks", "DuplicateOf"] })
+    }
+}
+
+impl ApiSchema for LinkRequest {
+    const NAME: &'static str = "LinkRequest";
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["kind", "to"],
+            "description": "A link from the ticket in the path to `to`: \
+                            the ticket is a child of, blocks, or is a duplicate of `to`.",
+            "properties": {
+                "kind": LinkKind::reference(),
+                "to": TicketId::reference(),
+            },
+        })
+    }
+}
+
+impl ApiSchema for TicketLinks {
+    const NAME: &'static str = "TicketLinks";
+
+    fn schema() -> Value {
+        let ids = json!({ "type": "array", "items": TicketId::reference() });
+        json!({
+            "type": "object",
+            "required": [
+                "parent", "children", "blocks", "blocked_by", "duplicate_of",
+                "duplicates", "all_blockers", "descendants",
+            ],
+            "properties": {
+                "parent": nullable(TicketId::reference()),
+                "children": ids,
+                "blocks": ids,
+                "blocked_by": ids,
+                "duplicate_of": nullable(TicketId::reference()),
+                "duplicates": ids,
+                "all_blockers": {
+                    "type": "array",
+                    "items": TicketId::reference(),
+                    "description": "Every ticket blocking this one, directly or not, nearest first.",
+                },
+                "descendants": {
+                    "type": "array",
+                    "items": TicketId::reference(),
+                    "description": "Every subtask, and their subtasks, nearest first.",
+                },
+            },
+        })
+    }
+}
+
+impl ApiSchema for CommentId {
+    const NAME: &'static str = "CommentId";
+
+    fn schema() -> Value {
+        json!({ "type": "integer", "format": "uint64", "minimum": 0 })
+    }
+}
+
+impl ApiSchema for Comment {
+    const NAME: &'static str = "Comment";
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["id", "ticket", "author", "body", "created_at"],
+            "properties": {
+                "id": CommentId::reference(),
+                "ticket": TicketId::reference(),
+                "author": { "type": "string" },
+                "body": MarkdownDescription::reference(),
+                "created_at": { "type": "string", "format": "date-time" },
+                "edited_at": { "type": "string", "format": "date-time", "nullable": true },
+            },
+        })
+    }
+}
+
+impl ApiSchema for Trashed {
+    const NAME: &'static str = "Trashed";
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["ticket", "deleted_by", "deleted_at"],
+            "properties": {
+                "ticket": Ticket::reference(),
+                "deleted_by": { "type": "string" },
+                "deleted_at": { "type": "string", "format": "date-time" },
+            },
+        })
+    }
+}
+
+impl ApiSchema for Report {
+    const NAME: &'static str = "HistoryReport";
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["tickets", "conflict"],
+            "properties": {
+                "tickets": { "type": "array", "items": TicketId::reference() },
+                "conflict": nullable(json!({ "type": "string" })),
+            },
+        })
+    }
+}
+
+impl ApiSchema for TicketTemplate {
+    const NAME: &'static str = "TicketTemplate";
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["title", "description"],
+            "description": "Validated like a `TicketDraft` once placeholders are replaced.",
+            "properties": {
+                "title": { "type": "string" },
+                "description": { "type": "string" },
+            },
+        })
+    }
+}
+
+impl ApiSchema for Template {
+    const NAME: &'static str = "Template";
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["name", "description", "tickets"],
+            "description": "Tickets created together. Their titles and descriptions can \
+                            hold placeholders like `{{version}}`, replaced with values \
+                            given when the template is used.",
+            "properties": {
+                "name": {
+                    "type": "string",
+                    "pattern": format!("^[A-Za-z0-9_-]{{1,{}}}$", Template::MAX_NAME_LEN),
+                },
+                "description": { "type": "string", "default": "" },
+                "tickets": {
+                    "type": "array",
+                    "items": TicketTemplate::reference(),
+                    "minItems": 1,
+                    "maxItems": Template::MAX_TICKETS,
+                },
+            },
+        })
+    }
+}
+
+impl ApiSchema for TemplateRequest {
+    const NAME: &'static str = "TemplateRequest";
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "project": nullable(ProjectKey::reference()),
+                "values": {
+                    "type": "object",
+                    "additionalProperties": { "type": "string" },
+                    "description": "The value of every placeholder of the template, by name.",
+                },
+            },
+        })
+    }
+}
+
+impl ApiSchema for SavedQuery {
+    const NAME: &'static str = "SavedQuery";
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["name", "query"],
+            "properties": {
+                "name": {
+                    "type": "string",
+                    "pattern": format!("^[A-Za-z0-9_-]{{1,{}}}$", SavedQuery::MAX_NAME_LEN),
+                },
+                "query": {
+                    "type": "string",
+                    "description": "Like the `q` parameter of `GET /tickets`.",
+                },
+            },
+        })
+    }
+}
+
+impl ApiSchema for StatusCount {
+    const NAME: &'static str = "StatusCount";
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["status", "tickets"],
+            "properties": {
+                "status": Status::reference(),
+                "tickets": { "type": "integer", "format": "uint64" },
+            },
+        })
+    }
+}
+
+impl ApiSchema for Window {
+    const NAME: &'static str = "Window";
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["start", "end", "created", "done"],
+            "description": "What happened after `start` and up to `end`.",
+            "properties": {
+                "start": { "type": "string", "format": "date-time" },
+                "end": { "type": "string", "format": "date-time" },
+                "created": { "type": "integer", "format": "uint64" },
+                "done": {
+                    "type": "integer",
+                    "format": "uint64",
+                    "description": "Moves to `Done`, so a ticket done twice counts twice.",
+                },
+            },
+        })
+    }
+}
+
+impl ApiSchema for Durations {
+    const NAME: &'static str = "Durations";
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["tickets", "average_hours", "max_hours", "longest"],
+            "description": "One duration per ticket. Everything but `tickets` is null \
+                            without tickets.",
+            "properties": {
+                "tickets": { "type": "integer", "format": "uint64" },
+                "average_hours": nullable(json!({ "type": "number" })),
+                "max_hours": nullable(json!({ "type": "number" })),
+                "longest": nullable(TicketId::reference()),
+            },
+        })
+    }
+}
+
+impl ApiSchema for AgeBucket {
+    const NAME: &'static str = "AgeBucket";
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["min_days", "max_days", "tickets"],
+            "description": "Open tickets created `min_days` to `max_days` days ago.",
+            "properties": {
+                "min_days": { "type": "integer", "format": "uint32" },
+                "max_days": nullable(json!({ "type": "integer", "format": "uint32" })),
+                "tickets": { "type": "integer", "format": "uint64" },
+            },
+        })
+    }
+}
+
+impl ApiSchema for Summary {
+    const NAME: &'static str = "Summary";
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": [
+                "generated_at",
+                "counts",
+                "throughput",
+                "cycle_time",
+                "open_age",
+                "age_buckets",
+            ],
+            "description": "Only tickets created through the API since status changes \
+                            are recorded count towards anything but `counts`. \
+                            Deleted tickets don't count.",
+            "properties": {
+                "generated_at": { "type": "string", "format": "date-time" },
+                "counts": { "type": "array", "items": StatusCount::reference() },
+                "throughput": {
+                    "type": "array",
+                    "items": Window::reference(),
+                    "description": "Oldest window first.",
+                },
+                "cycle_time": {
+                    "allOf": [Durations::reference()],
+                    "description": "From creation to the last move to `Done`, \
+                                    for tickets that are done.",
+                },
+                "open_age": {
+                    "allOf": [Durations::reference()],
+                    "description": "Since creation, for tickets that aren't done.",
+                },
+                "age_buckets": { "type": "array", "items": AgeBucket::reference() },
+            },
+        })
+    }
+}
+
+impl ApiSchema for CommentDraft {
+    const NAME: &'static str = "CommentDraft";
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["body"],
+            "properties": { "body": MarkdownDescription::reference() },
+        })
+    }
+}
+
+impl ApiSchema for Attachment {
+    const NAME: &'static str = "Attachment";
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["ticket", "digest", "name", "mime", "size", "uploaded_by", "uploaded_at"],
+            "properties": {
+                "ticket": TicketId::reference(),
+                "digest": {
+                    "type": "string",
+                    "pattern": "^[0-9a-f]{64}$",
+                    "description": "Hex SHA-256 of the contents.",
+                },
+                "name": { "type": "string" },
+                "mime": {
+                    "type": "string",
+                    "description": "Sniffed from the contents, not taken from the upload.",
+                },
+                "size": { "type": "integer", "format": "uint64" },
+                "uploaded_by": { "type": "string" },
+                "uploaded_at": { "type": "string", "format": "date-time" },
+            },
+        })
+    }
+}
+
+impl ApiSchema for Role {
+    const NAME: &'static str = "Role";
+
+    fn schema() -> Value {
+        json!({ "type": "string", "enum": ["Viewer", "Reporter", "Maintainer", "Admin"] })
+    }
+}
+
+impl ApiSchema for TokenRequest {
+    const NAME: &'static str = "TokenRequest";
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["name", "role"],
+            "properties": {
+                "name": { "type": "string", "pattern": "^\\S+$" },
+                "role": Role::reference(),
+            },
+        })
+    }
+}
+
+impl ApiSchema for IssuedToken {
+    const NAME: &'static str = "IssuedToken";
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["token", "name", "role"],
+            "properties": {
+                "token": { "type": "string" },
+                "name": { "type": "string" },
+                "role": Role::reference(),
+            },
+        })
+    }
+}
+
+impl ApiSchema for ErrorBody {
+    const NAME: &'static str = "ErrorBody";
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["error"],
+            "properties": { "error": { "type": "string" } },
+        })
+    }
+}
+
+/// `allOf` is needed because siblings of `$ref` are ignored in OpenAPI 3.0.
+fn nullable(schema: Value) -> Value {
+    json!({ "allOf": [schema], "nullable": true })
+}
+
+fn component<T: ApiSchema>(schemas: &mut Map<String, Value>) {
+    schemas.insert(T::NAME.to_string(), T::schema());
+}
+
+/// The OpenAPI 3 document describing every `Route`.
+pub fn spec() -> Value {
+    let mut paths = Map::new();
+    for route in Route::ALL {
+        let item = paths
+            .entry(route.path())
+            .or_insert_with(|| json!({}))
+            .as_object_mut()
+            .unwrap();
+        item.insert(route.method().to_lowercase(), operation(route));
+    }
+
+    let mut schemas = Map::new();
+    component::<TicketId>(&mut schemas);
+    component::<ProjectKey>(&mut schemas);
+    component::<Project>(&mut schemas);
+    component::<ProjectDraft>(&mut schemas);
+    component::<ProjectPatch>(&mut schemas);
+    component::<TicketTitle>(&mut schemas);
+    component::<MarkdownDescription>(&mut schemas);
+    component::<Status>(&mut schemas);
+    component::<Ticket>(&mut schemas);
+    component::<TicketDraft>(&mut schemas);
+    component::<TicketPatch>(&mut schemas);
+    component::<Trashed>(&mut schemas);
+    component::<Report>(&mut schemas);
+    component::<TicketTemplate>(&mut schemas);
+    component::<Template>(&mut schemas);
+    component::<TemplateRequest>(&mut schemas);
+    component::<SavedQuery>(&mut schemas);
+    component::<StatusCount>(&mut schemas);
+    component::<Window>(&mut schemas);
+    component::<Durations>(&mut schemas);
+    component::<AgeBucket>(&mut schemas);
+    component::<Summary>(&mut schemas);
+    component::<LinkKind>(&mut schemas);
+    component::<LinkRequest>(&mut schemas);
+    component::<TicketLinks>(&mut schemas);
+    component::<CommentId>(&mut schemas);
+    component::<Comment>(&mut schemas);
+    component::<CommentDraft>(&mut schemas);
+    component::<Attachment>(&mut schemas);
+    component::<Role>(&mut schemas);
+    component::<TokenRequest>(&mut schemas);
+    component::<IssuedToken>(&mut schemas);
+    component::<ErrorBody>(&mut schemas);
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Ticket API",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "security": [{ "bearer": [] }],
+        "paths": paths,
+        "components": {
+            "schemas": schemas,
+            "securitySchemes": {
+                "bearer": { "type": "http", "scheme": "bearer" },
+            },
+        },
+    })
+}
+
+/// The request body schema and an example of it.
+type RequestBody = Option<(Value, Value)>;
+
+fn operation(route: Route) -> Value {
+    let (summary, request, responses): (&str, RequestBody, Vec<(u16, Value)>) = match route {
+        Route::CreateTicket => (
+            "Create a ticket in the default project. New tickets start in the `ToDo` status.",
+            Some((
+                TicketDraft::reference(),
+                json!({ "title": "A title", "description": "A description" }),
+            )),
+            vec![
+                (
+                    201,
+                    content("The id of the new ticket", TicketId::reference()),
+                ),
+                error(400),
+                error(401),
+                error(403),
+            ],
+        ),
+        Route::ListTickets => (
+            "List every ticket, or those matching `q`, ordered by id.",
+            None,
+            vec![
+                (
+                    200,
+                    content(
+                        "The tickets",
+                        json!({ "type": "array", "items": Ticket::reference() }),
+                    ),
+                ),
+                error(400),
+                error(401),
+            ],
+        ),
+        Route::GetTicket => (
+            "Retrieve a ticket.",
+            None,
+            vec![
+                (200, content("The ticket", Ticket::reference())),
+                error(400),
+                error(401),
+                error(404),
+            ],
+        ),
+        Route::PatchTicket => (
+            "Change some of the fields of a ticket. \
+                 Changing the title or description requires edit rights on the ticket, \
+                 changing the status requires the maintainer role. \
+                 A ticket can't be moved to `Done` while a ticket blocking it is open.",
+            Some((
+                TicketPatch::reference(),
+                json!({ "id": "TKT-0", "status": "InProgress" }),
+            )),
+            vec![
+                (204, json!({ "description": "The ticket was updated" })),
+                error(400),
+                error(401),
+                error(403),
+                error(404),
+                error(409),
+            ],
+        ),
+        Route::DeleteTicket => (
+            "Move a ticket to the trash. It's hidden everywhere else until it's \
+                 restored, and its links, comments and attachments are kept until \
+                 it's purged. Requires the admin role.",
+            None,
+            vec![
+                (204, json!({ "description": "The ticket is in the trash" })),
+                error(401),
+                error(403),
+                error(404),
+            ],
+        ),
+        Route::GetLinks => (
+            "List the tickets linked to a ticket.",
+            None,
+            vec![
+                (200, content("The links", TicketLinks::reference())),
+                error(401),
+                error(404),
+            ],
+        ),
+        Route::AddLink => (
+            "Link a ticket to another. Requires edit rights on the ticket. \
+                 A ticket has at most one parent and is a duplicate of at most one ticket, \
+                 and links can't form cycles.",
+            Some((
+                LinkRequest::reference(),
+                json!({ "kind": "Blocks", "to": "TKT-1" }),
+            )),
+            vec![
+                (204, json!({ "description": "The tickets are linked" })),
+                error(400),
+                error(401),
+                error(403),
+                error(404),
+                error(409),
+            ],
+        ),
+        Route::RemoveLink => (
+            "Remove a link from a ticket. Requires edit rights on the ticket.",
+            Some((
+                LinkRequest::reference(),
+                json!({ "kind": "Blocks", "to": "TKT-1" }),
+            )),
+            vec![
+                (204, json!({ "description": "The link was removed" })),
+                error(400),
+                error(401),
+                error(403),
+                error(404),
+            ],
+        ),
+        Route::ListComments => (
+            "List the comments on a ticket, oldest first.",
+            None,
+            vec![
+                (
+                    200,
+                    content(
+                        "The comments",
+                        json!({ "type": "array", "items": Comment::reference() }),
+                    ),
+                ),
+                error(401),
+                error(404),
+            ],
+        ),
+        Route::AddComment => (
+            "Comment on a ticket. Requires the reporter role.",
+            Some((
+                CommentDraft::reference(),
+                json!({ "body": "Also happens on **Linux**." }),
+            )),
+            vec![
+                (201, content("The new comment", Comment::reference())),
+                error(400),
+                error(401),
+                error(403),
+                error(404),
+            ],
+        ),
+        Route::EditComment => (
+            "Change the body of a comment. \
+                 Reporters can only edit their own comments, maintainers can edit any.",
+            Some((
+                CommentDraft::reference(),
+                json!({ "body": "Also happens on Linux and macOS." }),
+            )),
+            vec![
+                (204, json!({ "description": "The comment was updated" })),
+                error(400),
+                error(401),
+                error(403),
+                error(404),
+            ],
+        ),
+        Route::DeleteComment => (
+            "Delete a comment. \
+                 Reporters can only delete their own comments, maintainers can delete any.",
+            None,
+            vec![
+                (204, json!({ "description": "The comment was deleted" })),
+                error(401),
+                error(403),
+                error(404),
+            ],
+        ),
+        Route::ListAttachments => (
+            "List the files attached to a ticket.",
+            None,
+            vec![
+                (
+                    200,
+                    content(
+                        "The attachments",
+                        json!({ "type": "array", "items": Attachment::reference() }),
+                    ),
+                ),
+                error(401),
+                error(404),
+            ],
+        ),
+        Route::UploadAttachment => (
+            "Attach a file to a ticket. The body is the file's contents, \
+                 and its type is sniffed from them. Requires the reporter role. \
+                 The server closes the connection after responding.",
+            // The body isn't JSON, see below.
+            None,
+            vec![
+                (201, content("The attachment", Attachment::reference())),
+                error(400),
+                error(401),
+                error(403),
+                error(404),
+                error(413),
+            ],
+        ),
+        Route::DownloadAttachment => (
+            "Download a file attached to a ticket.",
+            None,
+            vec![
+                (
+                    200,
+                    json!({
+                        "description": "The file's contents, with the sniffed `Content-Type`",
+                        "content": {
+                            "*/*": { "schema": { "type": "string", "format": "binary" } },
+                        },
+                    }),
+                ),
+                error(401),
+                error(404),
+            ],
+        ),
+        Route::ListProjects => (
+            "List every project, ordered by key.",
+            None,
+            vec![
+                (
+                    200,
+                    content(
+                        "The projects",
+                        json!({ "type": "array", "items": Project::reference() }),
+                    ),
+                ),
+                error(401),
+            ],
+        ),
+        Route::CreateProject => (
+            "Create a project. Requires the admin role.",
+            Some((
+                ProjectDraft::reference(),
+                json!({ "key": "API", "name": "Public API" }),
+            )),
+            vec![
+                (201, content("The new project", Project::reference())),
+                error(400),
+                error(401),
+                error(403),
+                error(409),
+            ],
+        ),
+        Route::GetProject => (
+            "Retrieve a project.",
+            None,
+            vec![
+                (200, content("The project", Project::reference())),
+                error(401),
+                error(404),
+            ],
+        ),
+        Route::PatchProject => (
+            "Change the settings of a project. Requires the admin role. \
+                 The default project can't be archived.",
+            Some((
+                ProjectPatch::reference(),
+                json!({ "description": "Everything behind `/v1`" }),
+            )),
+            vec![
+                (200, content("The updated project", Project::reference())),
+                error(400),
+                error(401),
+                error(403),
+                error(404),
+            ],
+        ),
+        Route::ListProjectTickets => (
+            "List the tickets of a project, ordered by number.",
+            None,
+            vec![
+                (
+                    200,
+                    content(
+                        "The tickets",
+                        json!({ "type": "array", "items": Ticket::reference() }),
+                    ),
+                ),
+                error(400),
+                error(401),
+                error(404),
+            ],
+        ),
+        Route::CreateProjectTicket => (
+            "Create a ticket in a project. New tickets start in the `ToDo` status. \
+                 No tickets can be created in archived projects.",
+            Some((
+                TicketDraft::reference(),
+                json!({ "title": "A title", "description": "A description" }),
+            )),
+            vec![
+                (
+                    201,
+                    content("The id of the new ticket", TicketId::reference()),
+                ),
+                error(400),
+                error(401),
+                error(403),
+                error(404),
+                error(409),
+            ],
+        ),
+        Route::ListTrash => (
+            "List the deleted tickets, ordered by id.",
+            None,
+            vec![
+                (
+                    200,
+                    content(
+                        "The deleted tickets",
+                        json!({ "type": "array", "items": Trashed::reference() }),
+                    ),
+                ),
+                error(401),
+            ],
+        ),
+        Route::RestoreTicket => (
+            "Move a deleted ticket back out of the trash, with its id, links, \
+                 comments and attachments. Requires the admin role.",
+            None,
+            vec![
+                (200, content("The restored ticket", Ticket::reference())),
+                error(400),
+                error(401),
+                error(403),
+                error(404),
+            ],
+        ),
+        Route::PurgeTicket => (
+            "Permanently delete a ticket from the trash, along with every link from \
+                 or to it, its comments and its attachments. Its subtasks are kept, \
+                 without a parent. Its id is never given to another ticket. \
+                 Requires the admin role.",
+            None,
+            vec![
+                (204, json!({ "description": "The ticket was purged" })),
+                error(401),
+                error(403),
+                error(404),
+            ],
+        ),
+        Route::EmptyTrash => (
+            "Purge every ticket deleted at least `older_than_days` days ago, \
+                 like `DELETE /trash/{id}`. Requires the admin role.",
+            None,
+            vec![
+                (
+                    200,
+                    content(
+                        "The ids of the purged tickets",
+                        json!({ "type": "array", "items": TicketId::reference() }),
+                    ),
+                ),
+                error(400),
+                error(401),
+                error(403),
+            ],
+        ),
+        Route::Undo => (
+            "Undo your last `count` ticket creations, edits and deletions, most recent \
+                 first, even if other tickets changed since. It stops at the first \
+                 ticket that changed since its operation, which is then dropped.",
+            None,
+            vec![
+                (200, content("What was undone", Report::reference())),
+                error(400),
+                error(401),
+            ],
+        ),
+        Route::Redo => (
+            "Redo the last `count` operations you undid, like `POST /undo`. \
+                 They can't be redone anymore once you change a ticket.",
+            None,
+            vec![
+                (200, content("What was redone", Report::reference())),
+                error(400),
+                error(401),
+            ],
+        ),
+        Route::ListTemplates => (
+            "List every ticket template, ordered by name.",
+            None,
+            vec![
+                (
+                    200,
+                    content(
+                        "The templates",
+                        json!({ "type": "array", "items": Template::reference() }),
+                    ),
+                ),
+                error(401),
+            ],
+        ),
+        Route::CreateTemplate => (
+            "Add a ticket template. Requires the maintainer role.",
+            Some((
+                Template::reference(),
+                json!({
+                    "name": "release",
+                    "tickets": [
+                        { "title": "Release {{version}}", "description": "Publish {{version}}" },
+                        { "title": "Announce {{version}}", "description": "Write the post" },
+                    ],
+                }),
+            )),
+            vec![
+                (201, content("The new template", Template::reference())),
+                error(400),
+                error(401),
+                error(403),
+                error(409),
+            ],
+        ),
+        Route::GetTemplate => (
+            "Retrieve a ticket template.",
+            None,
+            vec![
+                (200, content("The template", Template::reference())),
+                error(401),
+                error(404),
+            ],
+        ),
+        Route::DeleteTemplate => (
+            "Remove a ticket template. Tickets created from it are kept. \
+                 Requires the maintainer role.",
+            None,
+            vec![
+                (204, json!({ "description": "The template was removed" })),
+                error(401),
+                error(403),
+                error(404),
+            ],
+        ),
+        Route::CreateTemplateTickets => (
+            "Create every ticket of a template, with its placeholders replaced, \
+                 in the template's order. Either every ticket is created or, if any \
+                 of them is invalid, none are.",
+            Some((
+                TemplateRequest::reference(),
+                json!({ "values": { "version": "1.2.0" } }),
+            )),
+            vec![
+                (
+                    201,
+                    content(
+                        "The ids of the new tickets",
+                        json!({ "type": "array", "items": TicketId::reference() }),
+                    ),
+                ),
+                error(400),
+                error(401),
+                error(403),
+                error(404),
+                error(409),
+            ],
+        ),
+        Route::ListQueries => (
+            "List every saved query, ordered by name.",
+            None,
+            vec![
+                (
+                    200,
+                    content(
+                        "The saved queries",
+                        json!({ "type": "array", "items": SavedQuery::reference() }),
+                    ),
+                ),
+                error(401),
+            ],
+        ),
+        Route::SaveQuery => (
+            "Save a query under a name, for everyone to run. \
+                 Requires the reporter role.",
+            Some((
+                SavedQuery::reference(),
+                json!({ "name": "in-progress", "query": "status:InProgress" }),
+            )),
+            vec![
+                (201, content("The saved query", SavedQuery::reference())),
+                error(400),
+                error(401),
+                error(403),
+                error(409),
+            ],
+        ),
+        Route::DeleteQuery => (
+            "Remove a saved query. Requires the reporter role.",
+            None,
+            vec![
+                (204, json!({ "description": "The query was removed" })),
+                error(401),
+                error(403),
+                error(404),
+            ],
+        ),
+        Route::RunQuery => (
+            "List the tickets matching a saved query, ordered by id.",
+            None,
+            vec![
+                (
+                    200,
+                    content(
+                        "The tickets",
+                        json!({ "type": "array", "items": Ticket::reference() }),
+                    ),
+                ),
+                error(400),
+                error(401),
+                error(404),
+            ],
+        ),
+        Route::ReportSummary => (
+            "Count tickets per status, and measure throughput, the time tickets take \
+                 to get done and how long open ones have been waiting.",
+            None,
+            vec![
+                (200, content("The summary", Summary::reference())),
+                error(400),
+                error(401),
+            ],
+        ),
+        Route::IssueToken => (
+            "Issue a bearer token for a new user. Requires the admin role.",
+            Some((
+                TokenRequest::reference(),
+                json!({ "name": "carol", "role": "Reporter" }),
+            )),
+            vec![
+                (201, content("The new token", IssuedToken::reference())),
+                error(400),
+                error(401),
+                error(403),
+            ],
+        ),
+        Route::OpenApi => (
+            "This document.",
+            None,
+            vec![(
+                200,
+                content("The OpenAPI document", json!({ "type": "object" })),
+            )],
+        ),
+    };
+
+    let mut operation = json!({
+        "operationId": format!("{:?}", route),
+        "summary": summary,
+    });
+
+    let mut parameters = vec![];
+    if route.path().contains("{id}") {
+        parameters.push(json!({
+            "name": "id",
+            "in": "path",
+            "required": true,
+            "schema": TicketId::reference(),
+        }));
+    }
+    if route.path().contains("{key}") {
+        parameters.push(json!({
+            "name": "key",
+            "in": "path",
+            "required": true,
+            "schema": ProjectKey::reference(),
+        }));
+    }
+    if route.path().contains("{comment}") {
+        parameters.push(json!({
+            "name": "comment",
+            "in": "path",
+            "required": true,
+            "schema": CommentId::reference(),
+        }));
+    }
+    if route.path().contains("{attachment}") {
+        parameters.push(json!({
+            "name": "attachment",
+            "in": "path",
+            "required": true,
+            "description": "The attachment's digest.",
+            "schema": { "type": "string", "pattern": "^[0-9a-f]{64}$" },
+        }));
+    }
+    if route.path().contains("{name}") {
+        parameters.push(json!({
+            "name": "name",
+            "in": "path",
+            "required": true,
+            "description": if route.path().starts_with("/queries") {
+                "The saved query's name."
+            } else {
+                "The template's name."
+            },
+            "schema": { "type": "string" },
+        }));
+    }
+    if route == Route::UploadAttachment {
+        parameters.push(json!({
+            "name": "name",
+            "in": "query",
+            "required": true,
+            "description": "The file name, without any path.",
+            "schema": { "type": "string", "minLength": 1, "maxLength": 255 },
+            "example": "screenshot.png",
+        }));
+        operation["requestBody"] = json!({
+            "required": true,
+            "content": {
+                "application/octet-stream": {
+                    "schema": {
+                        "type": "string",
+                        "format": "binary",
+                        "description": format!(
+                            "At most {} bytes, unless the server is configured otherwise.",
+                            AttachmentDir::DEFAULT_MAX_BYTES
+                        ),
+                    },
+                },
+            },
+        });
+    }
+    if route == Route::EmptyTrash {
+        parameters.push(json!({
+            "name": "older_than_days",
+            "in": "query",
+            "description": "Without it, the whole trash is emptied.",
+            "schema": { "type": "integer", "format": "uint32", "default": 0 },
+            "example": 30,
+        }));
+    }
+    if route == Route::ListTickets {
+        parameters.push(json!({
+            "name": "q",
+            "in": "query",
+            "description": "Only list the tickets matching this query. Conditions are \
+                            written `field:value`, or `field:~value` to match part of a \
+                            text field, both ignoring case. The fields are `id`, `project`, \
+                            `status`, `title`, `description`, `reporter` and `editor`. \
+                            Values with spaces go in double quotes. Conditions are combined \
+                            with `NOT`, `AND` (or nothing) and `OR`, and grouped with \
+                            parentheses.",
+            "schema": { "type": "string" },
+            "example": "status:InProgress AND title:~\"login\" AND NOT reporter:bob",
+        }));
+    }
+    if route == Route::ReportSummary {
+        parameters.push(json!({
+            "name": "days",
+            "in": "query",
+            "description": "How long each throughput window is.",
+            "schema": {
+                "type": "integer",
+                "format": "uint32",
+                "minimum": 1,
+                "maximum": Period::MAX_DAYS,
+                "default": Period::default().days(),
+            },
+        }));
+        parameters.push(json!({
+            "name": "windows",
+            "in": "query",
+            "description": "How many throughput windows, the last one ending now.",
+            "schema": {
+                "type": "integer",
+                "format": "uint32",
+                "minimum": 1,
+                "maximum": Period::MAX_WINDOWS,
+                "default": Period::default().windows(),
+            },
+        }));
+    }
+    if matches!(route, Route::Undo | Route::Redo) {
+        parameters.push(json!({
+            "name": "count",
+            "in": "query",
+            "description": "How many operations.",
+            "schema": { "type": "integer", "format": "uint32", "minimum": 1, "default": 1 },
+        }));
+    }
+    if matches!(
+        route,
+        Route::ListTickets
+            | Route::GetTicket
+            | Route::ListProjectTickets
+            | Route::RestoreTicket
+            | Route::RunQuery
+    ) {
+        let formats: Vec<String> = DescriptionFormat::ALL
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        parameters.push(json!({
+            "name": "description",
+            "in": "query",
+            "description": "How to return descriptions: as the Markdown source, \
+                            as sanitized HTML or as plain text.",
+            "schema": {
+                "type": "string",
+                "enum": formats,
+                "default": DescriptionFormat::default().to_string(),
+            },
+        }));
+    }
+    if !parameters.is_empty() {
+        operation["parameters"] = Value::Array(parameters);
+    }
+
+    if let Some((schema, example)) = request {
+        operation["requestBody"] = json!({
+            "required": true,
+            "content": { "application/json": { "schema": schema, "example": example } },
+        });
+    }
+
+    if !route.requires_auth() {
+        operation["security"] = json!([]);
+    }
+
+    // Every request goes through the rate limiter.
+    let mut responses: Map<String, Value> = responses
+        .into_iter()
+        .map(|(status, response)| (status.to_string(), response))
+        .collect();
+    let (status, response) = error(429);
+    responses.insert(status.to_string(), response);
+    operation["responses"] = Value::Object(responses);
+
+    operation
+}
+
+fn content(description: &str, schema: Value) -> Value {
+    json!({
+        "description": description,
+        "content": { "application/json": { "schema": schema } },
+    })
+}
+
+fn error(status: u16) -> (u16, Value) {
+    let description = match status {
+        400 => "The request body is malformed or invalid",
+        401 => "Missing or unknown bearer token",
+        403 => "The user isn't allowed to do this",
+        404 => "No such ticket, project, template or saved query",
+        409 => "The change conflicts with the ticket's links, the project or an existing name",
+        413 => "The upload is over the size limit",
+        429 => "Rate limited, see the `Retry-After` header",
+        _ => "Error",
+    };
+    let mut response = content(description, ErrorBody::reference());
+    if status == 429 {
+        response["headers"] = json!({
+            "Retry-After": {
+                "description": "Seconds to wait before retrying",
+                "schema": { "type": "integer" },
+            },
+        });
+    }
+    (status, response)
+}
@@ -0,0 +1,209 @@
+//! Projects group tickets, each with its own key and counter, so tickets
+//! get ids like `API-42`.
+//!
+//! Every store has the default project, `TKT`. It holds the tickets created
+//! without naming a project, including every ticket from before projects
+//! existed, and can't be archived.
+
+use anyhow::anyhow;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+/// The short, unique name of a project: 2 to 10 ASCII letters and digits,
+/// starting with a letter, e.g. `API`. Keys are case-insensitive and stored
+/// in upper case.
+///
+/// The key is stored inline, so `ProjectKey` and `TicketId` are `Copy`.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ProjectKey {
+    // Padded with zeros, which sort before any letter or digit,
+    // so keys compare like their strings.
+    bytes: [u8; ProjectKey::MAX_LEN],
+    len: u8,
+}
+
+impl ProjectKey {
+    pub const MIN_LEN: usize = 2;
+    pub const MAX_LEN: usize = 10;
+    pub const DEFAULT: ProjectKey = ProjectKey::from_static("TKT");
+
+    /// Only for keys known to be valid.
+    const fn from_static(key: &str) -> Self {
+        let key = key.as_bytes();
+        let mut bytes = [0; ProjectKey::MAX_LEN];
+        let mut i = 0;
+        while i < key.len() {
+            bytes[i] = key[i];
+            i += 1;
+        }
+        Self {
+            bytes,
+            len: key.len() as u8,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        std::str::from_utf8(&self.bytes[..self.len as usize]).expect("Keys are ASCII")
+    }
+}
+
+impl fmt::Display for ProjectKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Debug for ProjectKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ProjectKey({})", self.as_str())
+    }
+}
+
+impl FromStr for ProjectKey {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let valid = (ProjectKey::MIN_LEN..=ProjectKey::MAX_LEN).contains(&s.len())
+            && s.starts_with(|c: char| c.is_ascii_alphabetic())
+            && s.chars().all(|c| c.is_ascii_alphanumeric());
+        if !valid {
+            return Err(anyhow!(
+                "`{}` is not a project key: expected {} to {} letters and digits, \
+                 starting with a letter",
+                s,
+                ProjectKey::MIN_LEN,
+                ProjectKey::MAX_LEN
+            ));
+        }
+        Ok(ProjectKey::from_static(&s.to_ascii_uppercase()))
+    }
+}
+
+impl Serialize for ProjectKey {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for ProjectKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let key = String::deserialize(deserializer)?;
+        key.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// What can be changed about a project after creating it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProjectSettings {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Archived projects keep their tickets, but no new ones can be created in them.
+    #[serde(default)]
+    pub archived: bool,
+}
+
+impl ProjectSettings {
+    pub const MAX_NAME_LEN: usize = 100;
+
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            description: String::new(),
+            archived: false,
+        }
+    }
+
+    fn check(&self, key: ProjectKey) -> Result<(), ProjectError> {
+        let name = self.name.trim();
+        if name.is_empty() || name.chars().count() > ProjectSettings::MAX_NAME_LEN {
+            return Err(ProjectError::InvalidName);
+        }
+        if key == ProjectKey::DEFAULT && self.archived {
+            return Err(ProjectError::ArchiveDefault);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Project {
+    pub key: ProjectKey,
+    #[serde(flatten)]
+    pub settings: ProjectSettings,
+    /// The number the next ticket created in the project gets.
+    pub next_number: u64,
+}
+
+impl Project {
+    /// The default project of a new store.
+    pub fn default_project() -> Self {
+        Self {
+            key: ProjectKey::DEFAULT,
+            settings: ProjectSettings::new("Tickets"),
+            next_number: 0,
+        }
+    }
+
+    /// Checks `settings` and creates a project without tickets.
+    pub fn new(key: ProjectKey, settings: ProjectSettings) -> Result<Self, ProjectError> {
+        settings.check(key)?;
+        Ok(Self {
+            key,
+            settings,
+            next_number: 0,
+        })
+    }
+
+    /// Applies `patch`, or leaves the project unchanged if the result isn't valid.
+    pub fn update(&mut self, patch: ProjectPatch) -> Result<(), ProjectError> {
+        let mut settings = self.settings.clone();
+        if let Some(name) = patch.name {
+            settings.name = name;
+        }
+        if let Some(description) = patch.description {
+            settings.description = description;
+        }
+        if let Some(archived) = patch.archived {
+            settings.archived = archived;
+        }
+        settings.check(self.key)?;
+        self.settings = settings;
+        Ok(())
+    }
+}
+
+/// The body of `POST /projects`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProjectDraft {
+    pub key: ProjectKey,
+    #[serde(flatten)]
+    pub settings: ProjectSettings,
+}
+
+/// The body of `PATCH /projects/{key}`. Missing fields are left as they are.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProjectPatch {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub archived: Option<bool>,
+}
+
+/// Why a project couldn't be created or changed, or a ticket created in it.
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum ProjectError {
+    #[error("No project with key {0}")]
+    NotFound(ProjectKey),
+    #[error("There is already a project with key {0}")]
+    Exists(ProjectKey),
+    #[error("Project {0} is archived, so no tickets can be created in it")]
+    Archived(ProjectKey),
+    #[error("The default project can't be archived")]
+    ArchiveDefault,
+    #[error(
+        "Project names must be 1 to {} characters long",
+        ProjectSettings::MAX_NAME_LEN
+    )]
+    InvalidName,
+}
@@ -0,0 +1,446 @@
+//! A small language for filtering tickets, e.g.
+//! `status:InProgress AND title:~"login" AND NOT reporter:bob`.
+//!
+//! A condition is a field, an operator and a value. `:` matches the whole
+//! value and `:~` any part of it, both ignoring case; only text fields can be
+//! matched with `:~`. Values with spaces, parentheses or `:` go in double
+//! quotes, with `\"` and `\\` escapes. Conditions are combined with `NOT`,
+//! `AND` and `OR`, in that order of precedence, and grouped with parentheses.
+//! Conditions next to each other without an operator are `AND`ed.
+//!
+//! The fields are `id`, `project`, `status`, `title`, `description`,
+//! `reporter` and `editor`, the last user who edited the ticket.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::data::{Status, Ticket};
+use crate::projects::ProjectKey;
+use crate::store::TicketId;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Field {
+    Id,
+    Project,
+    Status,
+    Title,
+    Description,
+    Reporter,
+    Editor,
+}
+
+impl Field {
+    pub const ALL: [Field; 7] = [
+        Field::Id,
+        Field::Project,
+        Field::Status,
+        Field::Title,
+        Field::Description,
+        Field::Reporter,
+        Field::Editor,
+    ];
+
+    fn is_text(self) -> bool {
+        matches!(
+            self,
+            Field::Title | Field::Description | Field::Reporter | Field::Editor
+        )
+    }
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let field = match self {
+            Field::Id => "id",
+            Field::Project => "project",
+            Field::Status => "status",
+            Field::Title => "title",
+            Field::Description => "description",
+            Field::Reporter => "reporter",
+            Field::Editor => "editor",
+        };
+        f.write_str(field)
+    }
+}
+
+/// One field compared with one value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Condition {
+    Id(TicketId),
+    Project(ProjectKey),
+    Status(Status),
+    /// Matches the whole text, or any part of it if `contains`, ignoring case.
+    /// The value is stored in lower case.
+    Text {
+        field: Field,
+        value: String,
+        contains: bool,
+    },
+}
+
+impl Condition {
+    pub fn matches(&self, ticket: &Ticket) -> bool {
+        match self {
+            Condition::Id(id) => ticket.id == *id,
+            Condition::Project(key) => ticket.id.project == *key,
+            Condition::Status(status) => ticket.status == *status,
+            Condition::Text {
+                field,
+                value,
+                contains,
+            } => {
+                let text = match field {
+                    Field::Title => Some(ticket.title.0.as_str()),
+                    Field::Description => Some(ticket.description.as_str()),
+                    Field::Reporter => ticket.reporter.as_deref(),
+                    Field::Editor => ticket.last_editor.as_deref(),
+                    Field::Id | Field::Project | Field::Status => None,
+                };
+                let Some(text) = text.map(str::to_lowercase) else {
+                    return false;
+                };
+                if *contains {
+                    text.contains(value.as_str())
+                } else {
+                    text == *value
+                }
+            }
+        }
+    }
+}
+
+/// A parsed query. Parse one with `str::parse`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Query {
+    Condition(Condition),
+    Not(Box<Query>),
+    And(Vec<Query>),
+    Or(Vec<Query>),
+}
+
+impl Query {
+    pub fn matches(&self, ticket: &Ticket) -> bool {
+        match self {
+            Query::Condition(condition) => condition.matches(ticket),
+            Query::Not(query) => !query.matches(ticket),
+            Query::And(queries) => queries.iter().all(|query| query.matches(ticket)),
+            Query::Or(queries) => queries.iter().any(|query| query.matches(ticket)),
+        }
+    }
+}
+
+impl FromStr for Query {
+    type Err = QueryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?,
+            next: 0,
+            depth: 0,
+        };
+        let query = parser.or()?;
+        match parser.peek() {
+            Token::End => Ok(query),
+            _ => Err(parser.expected("`AND`, `OR` or the end of the query")),
+        }
+    }
+}
+
+/// Why a query couldn't be parsed.
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+#[error("Invalid query at character {position}: {message}")]
+pub struct QueryError {
+    /// Where the problem is, counting characters from 1.
+    pub position: usize,
+    pub message: String,
+}
+
+/// A query saved under a name, to run again later.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedQuery {
+    /// 1 to 50 letters, digits, `-` and `_`, e.g. `my-open-bugs`.
+    pub name: String,
+    pub query: String,
+}
+
+impl SavedQuery {
+    pub const MAX_NAME_LEN: usize = 50;
+
+    /// Checks the name and parses the query.
+    pub fn parse(&self) -> Result<Query, SavedQueryError> {
+        let valid_name = (1..=SavedQuery::MAX_NAME_LEN).contains(&self.name.len())
+            && self
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid_name {
+            return Err(SavedQueryError::InvalidName);
+        }
+        Ok(self.query.parse()?)
+    }
+}
+
+/// Why a query couldn't be saved or run.
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum SavedQueryError {
+    #[error("No saved query named {0}")]
+    NotFound(String),
+    #[error("There is already a saved query named {0}")]
+    Exists(String),
+    #[error(
+        "Saved query names must be 1 to {} letters, digits, `-` and `_`",
+        SavedQuery::MAX_NAME_LEN
+    )]
+    InvalidName,
+    #[error(transparent)]
+    Invalid(#[from] QueryError),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    /// A field, keyword or unquoted value.
+    Word(String),
+    /// A quoted value, without the quotes.
+    Quoted(String),
+    Is,
+    Contains,
+    Open,
+    Close,
+    End,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Word(word) => write!(f, "`{}`", word),
+            Token::Quoted(value) => write!(f, "{:?}", value),
+            Token::Is => f.write_str("`:`"),
+            Token::Contains => f.write_str("`:~`"),
+            Token::Open => f.write_str("`(`"),
+            Token::Close => f.write_str("`)`"),
+            Token::End => f.write_str("the end of the query"),
+        }
+    }
+}
+
+/// Splits a query into tokens, each with its position.
+fn tokenize(query: &str) -> Result<Vec<(Token, usize)>, QueryError> {
+    let mut tokens = vec![];
+    let mut chars = query.chars().zip(1..).peekable();
+    while let Some((c, position)) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            ':' if chars.next_if(|(c, _)| *c == '~').is_some() => Token::Contains,
+            ':' => Token::Is,
+            '"' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some(('"', _)) => break,
+                        Some(('\\', _)) => match chars.next() {
+                            Some((c @ ('"' | '\\'), _)) => value.push(c),
+                            Some((c, position)) => {
+                                return Err(QueryError {
+                                    position,
+                                    message: format!(
+                                        "Unknown escape `\\{}`, expected `\\\"` or `\\\\`",
+                                        c
+                                    ),
+                                })
+                            }
+                            None => break,
+                        },
+                        Some((c, _)) => value.push(c),
+                        None => {
+                            return Err(QueryError {
+                                position,
+                                message: String::from("This quote is never closed"),
+                            })
+                        }
+                    }
+                }
+                Token::Quoted(value)
+            }
+            c => {
+                let mut word = String::from(c);
+                while let Some((c, _)) = chars
+                    .next_if(|(c, _)| !c.is_whitespace() && !matches!(c, '(' | ')' | ':' | '"'))
+                {
+                    word.push(c);
+                }
+                Token::Word(word)
+            }
+        };
+        tokens.push((token, position));
+    }
+    tokens.push((Token::End, query.chars().count() + 1));
+    Ok(tokens)
+}
+
+/// How deeply `NOT`s and parentheses can nest, so parsing (and matching)
+/// a hostile query can't overflow the stack.
+const MAX_DEPTH: usize = 64;
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    next: usize,
+    /// How many `NOT`s and parentheses enclose the next token.
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.next].0
+    }
+
+    fn position(&self) -> usize {
+        self.tokens[self.next].1
+    }
+
+    fn advance(&mut self) -> (Token, usize) {
+        let token = self.tokens[self.next].clone();
+        if token.0 != Token::End {
+            self.next += 1;
+        }
+        token
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Token::Word(word) if word.eq_ignore_ascii_case(keyword))
+    }
+
+    fn expected(&self, expected: &str) -> QueryError {
+        QueryError {
+            position: self.position(),
+            message: format!("Expected {}, found {}", expected, self.peek()),
+        }
+    }
+
+    /// Consumes the `NOT` or `(` at the next token and parses what it
+    /// encloses with `parse`, one level deeper.
+    fn nested(
+        &mut self,
+        parse: fn(&mut Self) -> Result<Query, QueryError>,
+    ) -> Result<Query, QueryError> {
+        if self.depth == MAX_DEPTH {
+            return Err(QueryError {
+                position: self.position(),
+                message: format!("Queries can't nest more than {} levels deep", MAX_DEPTH),
+            });
+        }
+        self.advance();
+        self.depth += 1;
+        let query = parse(self);
+        self.depth -= 1;
+        query
+    }
+
+    fn or(&mut self) -> Result<Query, QueryError> {
+        let mut queries = vec![self.and()?];
+        while self.is_keyword("OR") {
+            self.advance();
+            queries.push(self.and()?);
+        }
+        Ok(flatten(queries, Query::Or))
+    }
+
+    fn and(&mut self) -> Result<Query, QueryError> {
+        let mut queries = vec![self.not()?];
+        loop {
+            if self.is_keyword("AND") {
+                self.advance();
+            } else if self.is_keyword("OR") || !matches!(self.peek(), Token::Word(_) | Token::Open)
+            {
+                break;
+            }
+            queries.push(self.not()?);
+        }
+        Ok(flatten(queries, Query::And))
+    }
+
+    fn not(&mut self) -> Result<Query, QueryError> {
+        if self.is_keyword("NOT") {
+            return Ok(Query::Not(Box::new(self.nested(Self::not)?)));
+        }
+        match self.peek() {
+            Token::Open => {
+                let query = self.nested(Self::or)?;
+                match self.peek() {
+                    Token::Close => {
+                        self.advance();
+                        Ok(query)
+                    }
+                    _ => Err(self.expected("`AND`, `OR` or `)`")),
+                }
+            }
+            Token::Word(_) => self.condition(),
+            _ => Err(self.expected("a field, `NOT` or `(`")),
+        }
+    }
+
+    fn condition(&mut self) -> Result<Query, QueryError> {
+        let (Token::Word(name), position) = self.advance() else {
+            unreachable!("Only called on words");
+        };
+        let field = Field::ALL
+            .into_iter()
+            .find(|field| field.to_string().eq_ignore_ascii_case(&name))
+            .ok_or_else(|| {
+                let fields: Vec<String> = Field::ALL.iter().map(ToString::to_string).collect();
+                QueryError {
+                    position,
+                    message: format!(
+                        "Unknown field `{}`, expected one of {}",
+                        name,
+                        fields.join(", ")
+                    ),
+                }
+            })?;
+
+        let contains = match self.peek() {
+            Token::Is => false,
+            Token::Contains if field.is_text() => true,
+            Token::Contains => {
+                return Err(QueryError {
+                    position: self.position(),
+                    message: format!("`{}` can only be matched exactly, with `:`", field),
+                })
+            }
+            _ => return Err(self.expected(&format!("`:` or `:~` after `{}`", field))),
+        };
+        self.advance();
+
+        let value = match self.peek() {
+            Token::Word(value) | Token::Quoted(value) => value.clone(),
+            _ => return Err(self.expected("a value")),
+        };
+        let (_, position) = self.advance();
+        let invalid = |e: anyhow::Error| QueryError {
+            position,
+            message: e.to_string(),
+        };
+        let condition = match field {
+            Field::Id => Condition::Id(value.parse().map_err(invalid)?),
+            Field::Project => Condition::Project(value.parse().map_err(invalid)?),
+            Field::Status => Condition::Status(value.parse().map_err(invalid)?),
+            field => Condition::Text {
+                field,
+                value: value.to_lowercase(),
+                contains,
+            },
+        };
+        Ok(Query::Condition(condition))
+    }
+}
+
+/// A single query as is, more of them combined with `combine`.
+fn flatten(mut queries: Vec<Query>, combine: fn(Vec<Query>) -> Query) -> Query {
+    if queries.len() == 1 {
+        queries.remove(0)
+    } else {
+        combine(queries)
+    }
+}
@@ -0,0 +1,290 @@
+//! Reports on the tickets of a store: how many are in each status, how many
+//! get created and done over time, how long they take to get done and how
+//! long open ones have been waiting.
+//!
+//! Everything but the counts is built on when tickets changed status, which
+//! the store records from the moment a ticket is created. Tickets restored
+//! from backups, imported or created before status changes were recorded
+//! have no such timeline, so they only show up in the counts.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::data::{Status, Ticket};
+use crate::store::TicketId;
+
+/// A ticket moving to `status`, or being created if `status` is `ToDo` and
+/// it's the ticket's first change.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatusChange {
+    pub status: Status,
+    pub at: DateTime<Utc>,
+}
+
+/// How throughput is split: `windows` back-to-back windows of `days` days
+/// each, the last one ending now.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Period {
+    days: u32,
+    windows: u32,
+}
+
+impl Period {
+    pub const MAX_DAYS: u32 = 365;
+    pub const MAX_WINDOWS: u32 = 52;
+
+    pub fn new(days: u32, windows: u32) -> Result<Self, InvalidPeriod> {
+        if (1..=Period::MAX_DAYS).contains(&days) && (1..=Period::MAX_WINDOWS).contains(&windows) {
+            Ok(Self { days, windows })
+        } else {
+            Err(InvalidPeriod)
+        }
+    }
+
+    pub fn days(&self) -> u32 {
+        self.days
+    }
+
+    pub fn windows(&self) -> u32 {
+        self.windows
+    }
+}
+
+/// The last four weeks.
+impl Default for Period {
+    fn default() -> Self {
+        Self {
+            days: 7,
+            windows: 4,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+#[error(
+    "`days` must be 1 to {} and `windows` 1 to {}",
+    Period::MAX_DAYS,
+    Period::MAX_WINDOWS
+)]
+pub struct InvalidPeriod;
+
+/// The body of `GET /reports/summary`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Summary {
+    pub generated_at: DateTime<Utc>,
+    /// Every status, in order, even those without tickets.
+    pub counts: Vec<StatusCount>,
+    /// Oldest window first.
+    pub throughput: Vec<Window>,
+    /// From creation to the last move to `Done`, for tickets that are done.
+    pub cycle_time: Durations,
+    /// Since creation, for tickets that aren't done.
+    pub open_age: Durations,
+    /// How many open tickets have been waiting for how long.
+    pub age_buckets: Vec<AgeBucket>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatusCount {
+    pub status: Status,
+    pub tickets: usize,
+}
+
+/// What happened from `start`, excluded, to `end`, included.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Window {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub created: usize,
+    /// Moves to `Done`, so a ticket that's reopened and done again counts twice.
+    pub done: usize,
+}
+
+/// Statistics over one duration per ticket, in hours.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Durations {
+    pub tickets: usize,
+    /// `None` without tickets, like `max_hours`.
+    pub average_hours: Option<f64>,
+    pub max_hours: Option<f64>,
+    /// The ticket that took, or has been waiting, the longest.
+    pub longest: Option<TicketId>,
+}
+
+impl Durations {
+    fn new(durations: &[(TicketId, Duration)]) -> Self {
+        let hours = |duration: Duration| duration.num_seconds() as f64 / 3600.0;
+        let Some(&(longest, max)) = durations.iter().max_by_key(|(_, duration)| *duration) else {
+            return Self::default();
+        };
+        let total: f64 = durations.iter().map(|(_, duration)| hours(*duration)).sum();
+        Self {
+            tickets: durations.len(),
+            average_hours: Some(total / durations.len() as f64),
+            max_hours: Some(hours(max)),
+            longest: Some(longest),
+        }
+    }
+}
+
+/// Open tickets created `min_days` to `max_days` days ago.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AgeBucket {
+    pub min_days: u32,
+    /// `None` for the last bucket.
+    pub max_days: Option<u32>,
+    pub tickets: usize,
+}
+
+impl AgeBucket {
+    /// Where the buckets start, in days.
+    pub const BOUNDS: [u32; 4] = [0, 1, 7, 30];
+}
+
+/// Summarizes `tickets`, each with the status changes recorded for it, oldest first.
+pub fn summarize<'a>(
+    tickets: impl IntoIterator<Item = (&'a Ticket, &'a [StatusChange])>,
+    period: Period,
+    now: DateTime<Utc>,
+) -> Summary {
+    let statuses = [Status::ToDo, Status::InProgress, Status::Done];
+    let mut counts = statuses.map(|status| StatusCount { status, tickets: 0 });
+    let length = Duration::days(period.days.into());
+    let mut throughput: Vec<Window> = (0..period.windows)
+        .rev()
+        .map(|i| {
+            let end = now - length * i as i32;
+            Window {
+                start: end - length,
+                end,
+                created: 0,
+                done: 0,
+            }
+        })
+        .collect();
+    let mut cycle_times = vec![];
+    let mut open_ages = vec![];
+
+    for (ticket, changes) in tickets {
+        let count = counts
+            .iter_mut()
+            .find(|count| count.status == ticket.status)
+            .expect("Every status is counted");
+        count.tickets += 1;
+
+        let Some(created) = changes.first() else {
+            continue;
+        };
+        for window in &mut throughput {
+            let contains = |at: DateTime<Utc>| window.start < at && at <= window.end;
+            if contains(created.at) {
+                window.created += 1;
+            }
+            window.done += changes
+                .iter()
+                .filter(|change| change.status == Status::Done && contains(change.at))
+                .count();
+        }
+        if ticket.status == Status::Done {
+            if let Some(done) = changes.iter().rfind(|change| change.status == Status::Done) {
+                cycle_times.push((ticket.id, done.at - created.at));
+            }
+        } else {
+            open_ages.push((ticket.id, now - created.at));
+        }
+    }
+
+    let age_buckets = AgeBucket::BOUNDS
+        .iter()
+        .enumerate()
+        .map(|(i, &min_days)| {
+            let max_days = AgeBucket::BOUNDS.get(i + 1).copied();
+            let tickets = open_ages
+                .iter()
+                .filter(|(_, age)| {
+                    *age >= Duration::days(min_days.into())
+                        && max_days.is_none_or(|max| *age < Duration::days(max.into()))
+                })
+                .count();
+            AgeBucket {
+                min_days,
+                max_days,
+                tickets,
+            }
+        })
+        .collect();
+
+    Summary {
+        generated_at: now,
+        counts: counts.to_vec(),
+        throughput,
+        cycle_time: Durations::new(&cycle_times),
+        open_age: Durations::new(&open_ages),
+        age_buckets,
+    }
+}
+
+/// Plain-text tables, one per part of the summary.
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:<10}  {:>7}", "STATUS", "TICKETS")?;
+        for count in &self.counts {
+            writeln!(f, "{:<10}  {:>7}", count.status.to_string(), count.tickets)?;
+        }
+
+        writeln!(f)?;
+        writeln!(f, "{:<23}  {:>7}  {:>7}", "WINDOW", "CREATED", "DONE")?;
+        for window in &self.throughput {
+            let dates = format!(
+                "{} - {}",
+                window.start.format("%Y-%m-%d"),
+                window.end.format("%Y-%m-%d")
+            );
+            writeln!(
+                f,
+                "{:<23}  {:>7}  {:>7}",
+                dates, window.created, window.done
+            )?;
+        }
+
+        writeln!(f)?;
+        writeln!(
+            f,
+            "{:<10}  {:>7}  {:>7}  {:>7}  LONGEST",
+            "TIME", "TICKETS", "AVERAGE", "MAX"
+        )?;
+        for (name, durations) in [("to done", &self.cycle_time), ("open", &self.open_age)] {
+            let longest = durations.longest.map(|id| id.to_string());
+            writeln!(
+                f,
+                "{:<10}  {:>7}  {:>7}  {:>7}  {}",
+                name,
+                durations.tickets,
+                hours(durations.average_hours),
+                hours(durations.max_hours),
+                longest.as_deref().unwrap_or("-")
+            )?;
+        }
+
+        writeln!(f)?;
+        writeln!(f, "{:<10}  {:>7}", "OPEN FOR", "TICKETS")?;
+        for bucket in &self.age_buckets {
+            let days = match bucket.max_days {
+                Some(max_days) => format!("{}-{}d", bucket.min_days, max_days),
+                None => format!("{}d+", bucket.min_days),
+            };
+            writeln!(f, "{:<10}  {:>7}", days, bucket.tickets)?;
+        }
+        Ok(())
+    }
+}
+
+/// Hours up to two days, days beyond.
+fn hours(hours: Option<f64>) -> String {
+    match hours {
+        Some(hours) if hours < 48.0 => format!("{:.1}h", hours),
+        Some(hours) => format!("{:.1}d", hours / 24.0),
+        None => String::from("-"),
+    }
+}
@@ -0,0 +1,265 @@
+use regex::Regex;
+
+use crate::projects::ProjectKey;
+use crate::store::TicketId;
+
+lazy_static::lazy_static! {
+    static ref TICKET_PATH_RE: Regex =
+        Regex::new(r"^/tickets/([^/]+)(?:/(links|comments|attachments)(?:/([^/]+))?)?$").unwrap();
+    static ref PROJECT_PATH_RE: Regex = Regex::new(r"^/projects/([^/]+)(/tickets)?$").unwrap();
+    static ref TRASH_PATH_RE: Regex = Regex::new(r"^/trash/([^/]+)(/restore)?$").unwrap();
+    static ref NAMED_PATH_RE: Regex =
+        Regex::new(r"^/(templates|queries)/([^/]+)(/tickets)?$").unwrap();
+}
+
+/// Every endpoint the server handles. `openapi::spec` documents exactly these.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Route {
+    CreateTicket,
+    ListTickets,
+    GetTicket,
+    PatchTicket,
+    DeleteTicket,
+    GetLinks,
+    AddLink,
+    RemoveLink,
+    ListComments,
+    AddComment,
+    EditComment,
+    DeleteComment,
+    ListAttachments,
+    UploadAttachment,
+    DownloadAttachment,
+    ListProjects,
+    CreateProject,
+    GetProject,
+    PatchProject,
+    ListProjectTickets,
+    CreateProjectTicket,
+    ListTrash,
+    RestoreTicket,
+    PurgeTicket,
+    EmptyTrash,
+    Undo,
+    Redo,
+    ListTemplates,
+    CreateTemplate,
+    GetTemplate,
+    DeleteTemplate,
+    CreateTemplateTickets,
+    ListQueries,
+    SaveQuery,
+    DeleteQuery,
+    RunQuery,
+    ReportSummary,
+    IssueToken,
+    OpenApi,
+}
+
+impl Route {
+    pub const ALL: [Route; 39] = [
+        Route::CreateTicket,
+        Route::ListTickets,
+        Route::GetTicket,
+        Route::PatchTicket,
+        Route::DeleteTicket,
+        Route::GetLinks,
+        Route::AddLink,
+        Route::RemoveLink,
+        Route::ListComments,
+        Route::AddComment,
+        Route::EditComment,
+        Route::DeleteComment,
+        Route::ListAttachments,
+        Route::UploadAttachment,
+        Route::DownloadAttachment,
+        Route::ListProjects,
+        Route::CreateProject,
+        Route::GetProject,
+        Route::PatchProject,
+        Route::ListProjectTickets,
+        Route::CreateProjectTicket,
+        Route::ListTrash,
+        Route::RestoreTicket,
+        Route::PurgeTicket,
+        Route::EmptyTrash,
+        Route::Undo,
+        Route::Redo,
+        Route::ListTemplates,
+        Route::CreateTemplate,
+        Route::GetTemplate,
+        Route::DeleteTemplate,
+        Route::CreateTemplateTickets,
+        Route::ListQueries,
+        Route::SaveQuery,
+        Route::DeleteQuery,
+        Route::RunQuery,
+        Route::ReportSummary,
+        Route::IssueToken,
+        Route::OpenApi,
+    ];
+
+    pub fn method(self) -> &'static str {
+        match self {
+            Route::CreateTicket
+            | Route::AddLink
+            | Route::AddComment
+            | Route::UploadAttachment
+            | Route::CreateProject
+            | Route::CreateProjectTicket
+            | Route::RestoreTicket
+            | Route::Undo
+            | Route::Redo
+            | Route::CreateTemplate
+            | Route::CreateTemplateTickets
+            | Route::SaveQuery
+            | Route::IssueToken => "POST",
+            Route::ListTickets
+            | Route::GetTicket
+            | Route::GetLinks
+            | Route::ListComments
+            | Route::ListAttachments
+            | Route::DownloadAttachment
+            | Route::ListProjects
+            | Route::GetProject
+            | Route::ListProjectTickets
+            | Route::ListTrash
+            | Route::ListTemplates
+            | Route::GetTemplate
+            | Route::ListQueries
+            | Route::RunQuery
+            | Route::ReportSummary
+            | Route::OpenApi => "GET",
+            Route::PatchTicket | Route::EditComment | Route::PatchProject => "PATCH",
+            Route::DeleteTicket
+            | Route::RemoveLink
+            | Route::DeleteComment
+            | Route::PurgeTicket
+            | Route::EmptyTrash
+            | Route::DeleteTemplate
+            | Route::DeleteQuery => "DELETE",
+        }
+    }
+
+    /// The path, with parameters written the OpenAPI way (`{id}`).
+    pub fn path(self) -> &'static str {
+        match self {
+            Route::CreateTicket | Route::ListTickets => "/tickets",
+            Route::GetTicket | Route::PatchTicket | Route::DeleteTicket => "/tickets/{id}",
+            Route::GetLinks | Route::AddLink | Route::RemoveLink => "/tickets/{id}/links",
+            Route::ListComments | Route::AddComment => "/tickets/{id}/comments",
+            Route::EditComment | Route::DeleteComment => "/tickets/{id}/comments/{comment}",
+            Route::ListAttachments | Route::UploadAttachment => "/tickets/{id}/attachments",
+            Route::DownloadAttachment => "/tickets/{id}/attachments/{attachment}",
+            Route::ListProjects | Route::CreateProject => "/projects",
+            Route::GetProject | Route::PatchProject => "/projects/{key}",
+            Route::ListProjectTickets | Route::CreateProjectTicket => "/projects/{key}/tickets",
+            Route::ListTrash | Route::EmptyTrash => "/trash",
+            Route::PurgeTicket => "/trash/{id}",
+            Route::RestoreTicket => "/trash/{id}/restore",
+            Route::Undo => "/undo",
+            Route::Redo => "/redo",
+            Route::ListTemplates | Route::CreateTemplate => "/templates",
+            Route::GetTemplate | Route::DeleteTemplate => "/templates/{name}",
+            Route::CreateTemplateTickets => "/templates/{name}/tickets",
+            Route::ListQueries | Route::SaveQuery => "/queries",
+            Route::DeleteQuery => "/queries/{name}",
+            Route::RunQuery => "/queries/{name}/tickets",
+            Route::ReportSummary => "/reports/summary",
+            Route::IssueToken => "/admin/tokens",
+            Route::OpenApi => "/openapi.json",
+        }
+    }
+
+    pub fn requires_auth(self) -> bool {
+        self != Route::OpenApi
+    }
+}
+
+/// The parameters in a request's path.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Params<'a> {
+    /// The project key.
+    pub project: Option<ProjectKey>,
+    /// The ticket id.
+    pub id: Option<TicketId>,
+    /// The comment id or attachment digest after the ticket id, unparsed.
+    pub item: Option<&'a str>,
+    /// The template or saved query name, unchecked.
+    pub name: Option<&'a str>,
+}
+
+/// Finds the route for a request, along with the parameters in its path.
+pub fn route<'a>(method: &str, path: &'a str) -> Option<(Route, Params<'a>)> {
+    let (template, params) = match TICKET_PATH_RE.captures(path) {
+        Some(caps) => {
+            let id = caps[1].parse::<TicketId>().ok()?;
+            let collection = caps.get(2).map(|collection| collection.as_str());
+            let item = caps.get(3).map(|item| item.as_str());
+            let template = match (collection, item) {
+                (None, _) => "/tickets/{id}",
+                (Some("links"), None) => "/tickets/{id}/links",
+                (Some("comments"), None) => "/tickets/{id}/comments",
+                (Some("comments"), Some(_)) => "/tickets/{id}/comments/{comment}",
+                (Some("attachments"), None) => "/tickets/{id}/attachments",
+                (Some("attachments"), Some(_)) => "/tickets/{id}/attachments/{attachment}",
+                _ => return None,
+            };
+            let params = Params {
+                id: Some(id),
+                item,
+                ..Params::default()
+            };
+            (template, params)
+        }
+        None => match PROJECT_PATH_RE.captures(path) {
+            Some(caps) => {
+                let project = caps[1].parse::<ProjectKey>().ok()?;
+                let template = match caps.get(2) {
+                    None => "/projects/{key}",
+                    Some(_) => "/projects/{key}/tickets",
+                };
+                let params = Params {
+                    project: Some(project),
+                    ..Params::default()
+                };
+                (template, params)
+            }
+            None => match TRASH_PATH_RE.captures(path) {
+                Some(caps) => {
+                    let id = caps[1].parse::<TicketId>().ok()?;
+                    let template = match caps.get(2) {
+                        None => "/trash/{id}",
+                        Some(_) => "/trash/{id}/restore",
+                    };
+                    let params = Params {
+                        id: Some(id),
+                        ..Params::default()
+                    };
+                    (template, params)
+                }
+                None => match NAMED_PATH_RE.captures(path) {
+                    Some(caps) => {
+                        let template = match (&caps[1], caps.get(3)) {
+                            ("templates", None) => "/templates/{name}",
+                            ("templates", Some(_)) => "/templates/{name}/tickets",
+                            ("queries", None) => "/queries/{name}",
+                            _ => "/queries/{name}/tickets",
+                        };
+                        let params = Params {
+                            name: caps.get(2).map(|name| name.as_str()),
+                            ..Params::default()
+                        };
+                        (template, params)
+                    }
+                    None => (path, Params::default()),
+                },
+            },
+        },
+    };
+
+    Route::ALL
+        .into_iter()
+        .find(|route| route.method() == method && route.path() == template)
+        .map(|route| (route, params))
+}
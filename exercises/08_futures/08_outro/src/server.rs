@@ -1,11 +1,407 @@
+use crate::attachments::AttachmentDir;
+use crate::auth::{Role, TokenStore, User};
+use crate::ids::IdStrategy;
+use crate::limits::{RateLimitConfig, RateLimiter};
+use crate::sqlite::SqliteStore;
+use crate::store::{Journal, TicketStore};
+use crate::tls::{self, TlsConfig};
+use anyhow::{anyhow, Context};
+use std::env;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::net::TcpListener;
-use tokio::sync::RwLock;
-use crate::store::TicketStore;
-
-pub async fn init() -> Result<(TcpListener, Arc<RwLock<TicketStore>>), anyhow::Error> {
-    let listener = TcpListener::bind("127.0.0.1:8080").await?;
-    let store = Arc::new(RwLock::new(TicketStore::new()));
-    println!("Server running on 127.0.0.1:8080");
-    Ok((listener, store))
+use tokio::sync::{Mutex, MutexGuard, RwLock, Semaphore};
+use ticket_fields::Policy;
+use tokio_rustls::TlsAcceptor;
+
+pub struct Config {
+    /// Plain HTTP address.
+    pub addr: String,
+    /// Don't listen for plain HTTP, only on `tls.addr`.
+    pub tls_only: bool,
+    pub tls: Option<TlsConfig>,
+    /// How long a TLS client may take to finish its handshake. It holds a
+    /// connection slot until then.
+    pub handshake_timeout: Duration,
+    /// Connections beyond this many wait in the listener backlog.
+    /// Plain and TLS connections share the same limit.
+    pub max_connections: usize,
+    /// How long an open connection may sit idle before the server closes it.
+    pub keep_alive: Duration,
+    pub rate_limits: RateLimitConfig,
+    /// Where bearer tokens are loaded from. Without one, a single admin
+    /// token is generated at startup and printed.
+    pub tokens_file: Option<PathBuf>,
+    /// Extra rules for the titles and descriptions of new and edited
+    /// tickets. Tickets already in the store are kept as they are.
+    pub policy: Option<Policy>,
+    /// Where attachment contents are stored. Created on the first upload.
+    pub attachments_dir: PathBuf,
+    pub max_attachment_bytes: u64,
+    /// SQLite database the store is loaded from at startup. Requests that
+    /// change the store are answered once their changes are written to it,
+    /// see `Database`. Without one, tickets only live in memory.
+    pub database: Option<PathBuf>,
+    /// How long deleted tickets stay in the trash before they're purged.
+    /// Without one, they stay until they're purged through the API.
+    pub trash_retention: Option<Duration>,
+    /// How new tickets get their ids.
+    pub ids: IdStrategy,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            addr: String::from("127.0.0.1:8080"),
+            tls_only: false,
+            tls: None,
+            handshake_timeout: Duration::from_secs(10),
+            max_connections: 1024,
+            keep_alive: Duration::from_secs(5),
+            rate_limits: RateLimitConfig::default(),
+            tokens_file: None,
+            policy: None,
+            attachments_dir: PathBuf::from("attachments"),
+            max_attachment_bytes: AttachmentDir::DEFAULT_MAX_BYTES,
+            database: None,
+            trash_retention: None,
+            ids: IdStrategy::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Defaults, overridden by any of these environment variables:
+    ///  - `TICKETS_ADDR`: plain HTTP address
+    ///  - `TICKETS_TOKENS_FILE`: bearer tokens file
+    ///  - `TICKETS_POLICY_FILE`: JSON validation policy for ticket fields, see `ticket_fields::Policy`
+    ///  - `TICKETS_ATTACHMENTS_DIR`: where attachment contents are stored
+    ///  - `TICKETS_MAX_ATTACHMENT_BYTES`: size limit for attachments
+    ///  - `TICKETS_DATABASE`: SQLite database to keep tickets in
+    ///  - `TICKETS_TRASH_RETENTION_DAYS`: days before deleted tickets are purged
+    ///  - `TICKETS_IDS`: how new tickets get their ids: `sequential`, `ulid` or `uuid7`
+    ///  - `TICKETS_TLS_ADDR`, `TICKETS_TLS_CERT`, `TICKETS_TLS_KEY`: enable HTTPS.
+    ///    The address defaults to `127.0.0.1:8443`, the cert and key are required.
+    ///  - `TICKETS_TLS_ONLY=1`: disable plain HTTP
+    ///  - `TICKETS_NO_RATE_LIMITS=1`: don't rate limit clients, e.g. for load tests
+    pub fn from_env() -> Result<Self, anyhow::Error> {
+        let mut config = Self::default();
+        if let Ok(addr) = env::var("TICKETS_ADDR") {
+            config.addr = addr;
+        }
+        if let Ok(path) = env::var("TICKETS_TOKENS_FILE") {
+            config.tokens_file = Some(path.into());
+        }
+        if let Ok(path) = env::var("TICKETS_POLICY_FILE") {
+            let policy = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read the policy at {}", path))?;
+            let policy = serde_json::from_str(&policy)
+                .with_context(|| format!("{} is not a valid policy", path))?;
+            config.policy = Some(policy);
+        }
+        if let Ok(path) = env::var("TICKETS_ATTACHMENTS_DIR") {
+            config.attachments_dir = path.into();
+        }
+        if let Ok(max) = env::var("TICKETS_MAX_ATTACHMENT_BYTES") {
+            config.max_attachment_bytes = max
+                .parse()
+                .with_context(|| format!("Invalid TICKETS_MAX_ATTACHMENT_BYTES: {}", max))?;
+        }
+        if let Ok(path) = env::var("TICKETS_DATABASE") {
+            config.database = Some(path.into());
+        }
+        if let Ok(days) = env::var("TICKETS_TRASH_RETENTION_DAYS") {
+            let days: u64 = days
+                .parse()
+                .with_context(|| format!("Invalid TICKETS_TRASH_RETENTION_DAYS: {}", days))?;
+            config.trash_retention = Some(Duration::from_secs(days * 24 * 60 * 60));
+        }
+        if let Ok(ids) = env::var("TICKETS_IDS") {
+            config.ids = ids.parse().context("Invalid TICKETS_IDS")?;
+        }
+
+        match (env::var("TICKETS_TLS_CERT"), env::var("TICKETS_TLS_KEY")) {
+            (Ok(cert_path), Ok(key_path)) => {
+                config.tls = Some(TlsConfig {
+                    addr: env::var("TICKETS_TLS_ADDR")
+                        .unwrap_or_else(|_| String::from("127.0.0.1:8443")),
+                    cert_path: cert_path.into(),
+                    key_path: key_path.into(),
+                });
+            }
+            (Err(_), Err(_)) => {}
+            _ => {
+                return Err(anyhow!(
+                    "TICKETS_TLS_CERT and TICKETS_TLS_KEY must be set together"
+                ))
+            }
+        }
+        config.tls_only = env::var("TICKETS_TLS_ONLY").is_ok_and(|value| value == "1");
+        if env::var("TICKETS_NO_RATE_LIMITS").is_ok_and(|value| value == "1") {
+            config.rate_limits = RateLimitConfig::unlimited();
+        }
+
+        Ok(config)
+    }
+}
+
+/// Everything a connection handler needs access to.
+#[derive(Clone)]
+pub struct State {
+    pub store: Arc<RwLock<TicketStore>>,
+    pub tokens: Arc<RwLock<TokenStore>>,
+    pub limiter: Arc<RateLimiter>,
+    pub attachments: AttachmentDir,
+    pub connections: Arc<Semaphore>,
+    pub handshake_timeout: Duration,
+    pub keep_alive: Duration,
+    /// Where changes to `store` are written, if anywhere.
+    pub database: Option<Arc<Database>>,
+    /// Checked on top of the built-in rules for new titles and descriptions.
+    pub policy: Arc<Policy>,
+}
+
+impl State {
+    /// `config`'s limits around `store` and `tokens`. `init` builds one for
+    /// its listeners; anything else can feed it connections directly,
+    /// e.g. in-memory ones.
+    pub fn new(config: &Config, store: TicketStore, tokens: TokenStore) -> Self {
+        Self {
+            store: Arc::new(RwLock::new(store)),
+            tokens: Arc::new(RwLock::new(tokens)),
+            limiter: Arc::new(RateLimiter::new(config.rate_limits.clone())),
+            attachments: AttachmentDir::new(&config.attachments_dir, config.max_attachment_bytes),
+            connections: Arc::new(Semaphore::new(config.max_connections)),
+            handshake_timeout: config.handshake_timeout,
+            keep_alive: config.keep_alive,
+            database: None,
+            policy: Arc::new(config.policy.clone().unwrap_or_default()),
+        }
+    }
+}
+
+/// The database a server keeps its store in, see `Config::database`.
+///
+/// The store stays in memory, and journals its changes. Whoever changes it
+/// holds `lock` until they've called `write`, so each write holds the
+/// changes of exactly one request, which only succeeds if they're written.
+pub struct Database {
+    db: SqliteStore,
+    journal: Journal,
+    ids: IdStrategy,
+    writing: Mutex<()>,
+}
+
+impl Database {
+    /// Opens the database at `path`, with the store loaded from it.
+    pub async fn open(path: &Path, ids: IdStrategy) -> Result<(Self, TicketStore), anyhow::Error> {
+        let database = Self {
+            db: SqliteStore::open(path)?,
+            journal: Journal::new(),
+            ids,
+            writing: Mutex::new(()),
+        };
+        let store = database.load().await?;
+        Ok((database, store))
+    }
+
+    async fn load(&self) -> Result<TicketStore, anyhow::Error> {
+        let mut store = self.db.load().await?;
+        store.set_id_strategy(self.ids);
+        // Loading journaled nothing, as it started afterwards.
+        self.journal.take();
+        store.journal_to(self.journal.clone());
+        Ok(store)
+    }
+
+    /// Hold it from the first change to the store until `write`.
+    pub async fn lock(&self) -> MutexGuard<'_, ()> {
+        self.writing.lock().await
+    }
+
+    /// Writes every change to `store` since the last write. If that fails,
+    /// `store` is loaded again from the database, dropping those changes.
+    pub async fn write(&self, store: &RwLock<TicketStore>) -> Result<(), anyhow::Error> {
+        let changes = self.journal.take();
+        if changes.is_empty() {
+            return Ok(());
+        }
+        let result = self.db.write_changes(&*store.read().await, changes).await;
+        if let Err(e) = result {
+            match self.load().await {
+                Ok(loaded) => *store.write().await = loaded,
+                Err(e) => eprintln!("Failed to reload the database; err = {:?}", e),
+            }
+            return Err(e);
+        }
+        Ok(())
+    }
+}
+
+pub struct Listeners {
+    pub http: Option<TcpListener>,
+    pub https: Option<(TcpListener, TlsAcceptor)>,
+}
+
+impl Listeners {
+    pub fn http_addr(&self) -> Option<SocketAddr> {
+        self.http.as_ref()?.local_addr().ok()
+    }
+
+    pub fn https_addr(&self) -> Option<SocketAddr> {
+        self.https.as_ref()?.0.local_addr().ok()
+    }
+}
+
+pub async fn init(config: &Config) -> Result<(Listeners, State), anyhow::Error> {
+    if config.tls_only && config.tls.is_none() {
+        return Err(anyhow!("`tls_only` is set but no TLS config was given"));
+    }
+
+    let tokens = match &config.tokens_file {
+        Some(path) => TokenStore::load(path)?,
+        None => {
+            let mut tokens = TokenStore::new();
+            let token = tokens.generate(User::new("admin", Role::Admin))?;
+            println!("Admin token: {}", token);
+            tokens
+        }
+    };
+
+    let (store, database) = match &config.database {
+        Some(path) => {
+            let (database, store) = Database::open(path, config.ids).await?;
+            (store, Some(Arc::new(database)))
+        }
+        None => {
+            let mut store = TicketStore::new();
+            store.set_id_strategy(config.ids);
+            (store, None)
+        }
+    };
+
+    let http = if config.tls_only {
+        None
+    } else {
+        let listener = TcpListener::bind(&config.addr).await?;
+        println!("Server running on http://{}", listener.local_addr()?);
+        Some(listener)
+    };
+
+    let https = match &config.tls {
+        Some(tls_config) => {
+            let acceptor = tls::acceptor(tls_config)?;
+            let listener = TcpListener::bind(&tls_config.addr).await?;
+            println!("Server running on https://{}", listener.local_addr()?);
+            Some((listener, acceptor))
+        }
+        None => None,
+    };
+
+    let state = State {
+        database,
+        ..State::new(config, store, tokens)
+    };
+    if let Some(retention) = config.trash_retention {
+        let retention = chrono::Duration::from_std(retention)?;
+        tokio::spawn(purge_trash(state.clone(), retention));
+    }
+    Ok((Listeners { http, https }, state))
+}
+
+/// How often `purge_trash` looks for expired tickets.
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Purges tickets once they've been in the trash for `retention`, forever.
+async fn purge_trash(state: State, retention: chrono::Duration) {
+    let mut interval = tokio::time::interval(PURGE_INTERVAL);
+    loop {
+        interval.tick().await;
+        let _lock = match &state.database {
+            Some(database) => Some(database.lock().await),
+            None => None,
+        };
+        let purged = state
+            .store
+            .write()
+            .await
+            .purge_expired(retention, chrono::Utc::now());
+        if let Some(database) = &state.database {
+            if let Err(e) = database.write(&state.store).await {
+                eprintln!("Failed to purge the trash; err = {:?}", e);
+                continue;
+            }
+        }
+        if !purged.is_empty() {
+            println!("Purged {} tickets from the trash", purged.len());
+        }
+    }
+}
+
+pub async fn serve(listeners: Listeners, state: State) -> Result<(), anyhow::Error> {
+    let http = listeners
+        .http
+        .map(|listener| serve_http(listener, state.clone()));
+    let https = listeners
+        .https
+        .map(|(listener, acceptor)| serve_https(listener, acceptor, state.clone()));
+
+    match (http, https) {
+        (Some(http), Some(https)) => tokio::try_join!(http, https).map(|_| ()),
+        (Some(http), None) => http.await,
+        (None, Some(https)) => https.await,
+        (None, None) => Ok(()),
+    }
+}
+
+async fn serve_http(listener: TcpListener, state: State) -> Result<(), anyhow::Error> {
+    loop {
+        // Wait for a free slot before accepting, so excess clients queue up
+        // in the kernel instead of each getting a task and a buffer.
+        let permit = Arc::clone(&state.connections).acquire_owned().await?;
+        let (socket, peer) = listener.accept().await?;
+        let state = state.clone();
+        tokio::spawn(async move {
+            let _permit = permit;
+            if let Err(e) = crate::handle_connection(socket, peer, state).await {
+                eprintln!("Failed to handle connection from {}; err = {:?}", peer, e);
+            }
+        });
+    }
+}
+
+async fn serve_https(
+    listener: TcpListener,
+    acceptor: TlsAcceptor,
+    state: State,
+) -> Result<(), anyhow::Error> {
+    loop {
+        let permit = Arc::clone(&state.connections).acquire_owned().await?;
+        let (socket, peer) = listener.accept().await?;
+        let acceptor = acceptor.clone();
+        let state = state.clone();
+        tokio::spawn(async move {
+            let _permit = permit;
+            // The handshake happens in the task so a slow client can't stall the accept loop,
+            // and it's limited so it can't hold on to its permit.
+            let handshake = tokio::time::timeout(state.handshake_timeout, acceptor.accept(socket));
+            let socket = match handshake.await {
+                Ok(Ok(socket)) => socket,
+                Ok(Err(e)) => {
+                    eprintln!("TLS handshake with {} failed; err = {:?}", peer, e);
+                    return;
+                }
+                Err(_) => {
+                    eprintln!("TLS handshake with {} timed out", peer);
+                    return;
+                }
+            };
+            if let Err(e) = crate::handle_connection(socket, peer, state).await {
+                eprintln!("Failed to handle connection from {}; err = {:?}", peer, e);
+            }
+        });
+    }
 }
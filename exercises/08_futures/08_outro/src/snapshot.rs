@@ -0,0 +1,974 @@
+//! Point-in-time copies of a `TicketStore` in a compact binary format.
+//!
+//! A snapshot file is a fixed header followed by a bincode payload:
+//!
+//! | bytes | contents                                  |
+//! |-------|-------------------------------------------|
+//! | 4     | magic, `TKTS`                             |
+//! | 2     | format version, little-endian             |
+//! | 4     | CRC-32 of the payload, little-endian      |
+//! | 8     | payload length in bytes, little-endian    |
+//! | n     | payload                                   |
+//!
+//! Version 1 stored tickets without their reporter and last editor.
+//! Version 2 adds them, version 3 adds links between tickets, version 4 adds
+//! comments and attachment metadata, version 5 adds projects, with ticket ids
+//! made of a project key and a number, version 6 adds deleted tickets,
+//! version 7 adds ids with a ULID or a UUID instead of a number, and version 8,
+//! the current one, adds when tickets changed status and every user's undo and
+//! redo history. Tickets from versions before 5 go to the default project, and
+//! tickets from versions before 8 have no status changes, so reports only
+//! count them. Attachment contents, templates and saved queries aren't part of
+//! snapshots. Older versions are migrated on read.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use ticket_fields::{MarkdownDescription, TicketTitle};
+use tokio::sync::RwLock;
+use ulid::Ulid;
+use uuid::Uuid;
+
+use crate::attachments::Attachment;
+use crate::comments::{Comment, CommentId};
+use crate::data::{Status, Ticket};
+use crate::history::{History, Operation};
+use crate::ids::Serial;
+use crate::links::{Link, LinkKind};
+use crate::projects::{Project, ProjectKey, ProjectSettings};
+use crate::reports::StatusChange;
+use crate::store::{TicketId, TicketStore};
+use crate::trash::Trashed;
+
+pub const MAGIC: [u8; 4] = *b"TKTS";
+/// The version written by `Snapshot::encode`.
+pub const VERSION: u16 = 8;
+const HEADER_LEN: usize = 4 + 2 + 4 + 8;
+
+/// Why a snapshot couldn't be read.
+#[derive(Debug, thiserror::Error)]
+pub enum SnapshotError {
+    #[error("Not a ticket store snapshot")]
+    NotASnapshot,
+    #[error("The snapshot is truncated: expected {expected} bytes, found {found}")]
+    Truncated { expected: u64, found: u64 },
+    #[error("The snapshot has format version {found}, but only versions up to {VERSION} are supported. Is it from a newer release?")]
+    UnsupportedVersion { found: u16 },
+    #[error("The snapshot is corrupt: checksum {found:08x} doesn't match {expected:08x}")]
+    ChecksumMismatch { expected: u32, found: u32 },
+    #[error("The snapshot is corrupt: {0}")]
+    Corrupt(String),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// Every ticket in a store at one point in time.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Snapshot {
+    /// Ordered by key, each with the number its next ticket gets.
+    pub projects: Vec<Project>,
+    /// Ordered by id.
+    pub tickets: Vec<Ticket>,
+    /// Deleted tickets, ordered by id.
+    pub trash: Vec<Trashed>,
+    pub links: Vec<Link>,
+    pub next_comment_id: CommentId,
+    /// Ordered by id.
+    pub comments: Vec<Comment>,
+    pub attachments: Vec<Attachment>,
+    /// When each ticket was created and changed status, oldest first.
+    pub status_changes: BTreeMap<TicketId, Vec<StatusChange>>,
+    /// What each user can undo and redo, keyed by user name.
+    pub history: BTreeMap<String, History>,
+}
+
+impl Snapshot {
+    /// Copies every ticket in `store`.
+    pub fn capture(store: &TicketStore) -> Self {
+        Self {
+            projects: store.projects().cloned().collect(),
+            tickets: store
+                .tickets
+                .values()
+                .map(|ticket| ticket.read().unwrap().clone())
+                .collect(),
+            trash: store.trash().cloned().collect(),
+            links: store.links().iter().copied().collect(),
+            next_comment_id: store.comments().next_id(),
+            comments: store.comments().iter().cloned().collect(),
+            attachments: store.all_attachments().cloned().collect(),
+            status_changes: store
+                .all_status_changes()
+                .map(|(id, changes)| (id, changes.to_vec()))
+                .collect(),
+            history: store
+                .histories()
+                .map(|(user, history)| (user.to_owned(), history.clone()))
+                .collect(),
+        }
+    }
+
+    /// Copies a shared store. Every change to a store takes its write lock,
+    /// so holding the read lock while copying gives a consistent view.
+    /// Writers only wait for the copy, not for encoding or writing it anywhere.
+    pub async fn capture_shared(store: &RwLock<TicketStore>) -> Self {
+        Self::capture(&*store.read().await)
+    }
+
+    /// Builds a store holding exactly the snapshot's tickets, links, comments,
+    /// attachments, status changes and history. Links that are no longer
+    /// valid, e.g. to a ticket missing from the snapshot, are dropped, and so
+    /// are status changes of missing tickets.
+    pub fn restore(self) -> TicketStore {
+        let mut store = TicketStore::new();
+        for project in self.projects {
+            store.restore_project(project);
+        }
+        for ticket in self.tickets {
+            store.restore(ticket);
+        }
+        // Deleted tickets are live until their links and attachments are back.
+        for trashed in &self.trash {
+            store.restore(trashed.ticket.clone());
+        }
+        for link in self.links {
+            let _ = store.link(link);
+        }
+        for comment in self.comments {
+            store.comments_mut().restore(comment);
+        }
+        for attachment in self.attachments {
+            store.attach(attachment);
+        }
+        store.comments_mut().reserve_ids(self.next_comment_id);
+        for trashed in self.trash {
+            store.restore_trashed(trashed);
+        }
+        for (id, changes) in self.status_changes {
+            if store.get(id).is_some() || store.trashed(id).is_some() {
+                store.restore_status_changes(id, changes);
+            }
+        }
+        for (user, history) in self.history {
+            store.restore_history(user, history);
+        }
+        store
+    }
+
+    pub fn encode(&self, mut writer: impl Write) -> Result<(), SnapshotError> {
+        let payload = PayloadV8 {
+            projects: self.projects.iter().map(ProjectRecord::from).collect(),
+            tickets: self.tickets.iter().map(RecordV2::from).collect(),
+            trash: self.trash.iter().map(TrashRecord::from).collect(),
+            links: self.links.iter().map(LinkRecord::from).collect(),
+            next_comment_id: self.next_comment_id.0,
+            comments: self.comments.iter().map(CommentRecord::from).collect(),
+            attachments: self
+                .attachments
+                .iter()
+                .map(AttachmentRecord::from)
+                .collect(),
+            status_changes: self
+                .status_changes
+                .iter()
+                .map(|(id, changes)| {
+                    let changes = changes.iter().map(StatusChangeRecord::from).collect();
+                    (id_record(*id), changes)
+                })
+                .collect(),
+            history: self
+                .history
+                .iter()
+                .map(|(user, history)| HistoryRecord {
+                    user: user.clone(),
+                    undo: history.undo.iter().map(OperationRecord::from).collect(),
+                    redo: history.redo.iter().map(OperationRecord::from).collect(),
+                })
+                .collect(),
+        };
+        let payload =
+            bincode::serialize(&payload).map_err(|e| SnapshotError::Corrupt(e.to_string()))?;
+
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&crc32fast::hash(&payload).to_le_bytes())?;
+        writer.write_all(&(payload.len() as u64).to_le_bytes())?;
+        writer.write_all(&payload)?;
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        self.encode(&mut bytes)
+            .expect("Encoding into memory doesn't fail");
+        bytes
+    }
+
+    /// Reads a snapshot in the current or any older format version.
+    pub fn decode(bytes: &[u8]) -> Result<Self, SnapshotError> {
+        if bytes.len() < MAGIC.len() || bytes[..MAGIC.len()] != MAGIC {
+            return Err(SnapshotError::NotASnapshot);
+        }
+        if bytes.len() < HEADER_LEN {
+            return Err(SnapshotError::Truncated {
+                expected: HEADER_LEN as u64,
+                found: bytes.len() as u64,
+            });
+        }
+        let (header, payload) = bytes.split_at(HEADER_LEN);
+        let version = u16::from_le_bytes(header[4..6].try_into().unwrap());
+        let checksum = u32::from_le_bytes(header[6..10].try_into().unwrap());
+        let len = u64::from_le_bytes(header[10..18].try_into().unwrap());
+
+        if version == 0 || version > VERSION {
+            return Err(SnapshotError::UnsupportedVersion { found: version });
+        }
+        if (payload.len() as u64) < len {
+            return Err(SnapshotError::Truncated {
+                expected: HEADER_LEN as u64 + len,
+                found: bytes.len() as u64,
+            });
+        }
+        if (payload.len() as u64) > len {
+            return Err(SnapshotError::Corrupt(String::from(
+                "Unexpected bytes after the payload",
+            )));
+        }
+        let found = crc32fast::hash(payload);
+        if found != checksum {
+            return Err(SnapshotError::ChecksumMismatch {
+                expected: checksum,
+                found,
+            });
+        }
+
+        // Each older version is migrated one step at a time.
+        let payload: PayloadV8 = match version {
+            1 => PayloadV5::from(deserialize::<PayloadV1>(payload)?).into(),
+            2 => PayloadV5::from(deserialize::<PayloadV2>(payload)?).into(),
+            3 => PayloadV5::from(deserialize::<PayloadV3>(payload)?).into(),
+            4 => PayloadV5::from(deserialize::<PayloadV4>(payload)?).into(),
+            5 => deserialize::<PayloadV5>(payload)?.into(),
+            6 => PayloadV7::from(deserialize::<PayloadV6>(payload)?).into(),
+            7 => deserialize::<PayloadV7>(payload)?.into(),
+            _ => deserialize::<PayloadV8>(payload)?,
+        };
+        payload.try_into()
+    }
+
+    /// Writes the snapshot to `path`, replacing the file in one go.
+    pub fn save(&self, path: &Path) -> Result<(), anyhow::Error> {
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let mut temp = tempfile::NamedTempFile::new_in(dir)?;
+        self.encode(&mut temp)?;
+        temp.persist(path)?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, SnapshotError> {
+        Self::decode(&fs::read(path)?)
+    }
+}
+
+fn deserialize<'a, T: Deserialize<'a>>(payload: &'a [u8]) -> Result<T, SnapshotError> {
+    bincode::deserialize(payload).map_err(|e| SnapshotError::Corrupt(e.to_string()))
+}
+
+// The payload of each format version. Fields are plain types, so changes to
+// `Ticket` or its serde attributes can't silently change the format.
+// Up to version 4, ticket ids are bare numbers; records are generic over the
+// id so later versions can reuse them.
+
+/// A ticket id in versions 5 and 6: the project key and the number.
+type IdV5 = (String, u64);
+
+/// A ticket id from version 7 on: the project key and the serial.
+type IdV7 = (String, SerialRecord);
+
+#[derive(Serialize, Deserialize)]
+enum SerialRecord {
+    Number(u64),
+    Ulid(u128),
+    Uuid(u128),
+}
+
+/// The id a ticket from before version 5 gets.
+fn legacy_id(number: u64) -> IdV5 {
+    (ProjectKey::DEFAULT.to_string(), number)
+}
+
+#[derive(Deserialize)]
+struct PayloadV1 {
+    next_id: u64,
+    tickets: Vec<RecordV1>,
+}
+
+#[derive(Deserialize)]
+struct RecordV1 {
+    id: u64,
+    title: String,
+    description: String,
+    status: u8,
+}
+
+#[derive(Deserialize)]
+struct PayloadV2 {
+    next_id: u64,
+    tickets: Vec<RecordV2>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RecordV2<Id = u64> {
+    id: Id,
+    title: String,
+    description: String,
+    status: u8,
+    reporter: Option<String>,
+    last_editor: Option<String>,
+}
+
+impl<Id> RecordV2<Id> {
+    fn map_id<T>(self, f: impl Fn(Id) -> T) -> RecordV2<T> {
+        RecordV2 {
+            id: f(self.id),
+            title: self.title,
+            description: self.description,
+            status: self.status,
+            reporter: self.reporter,
+            last_editor: self.last_editor,
+        }
+    }
+}
+
+impl From<PayloadV1> for PayloadV2 {
+    fn from(v1: PayloadV1) -> Self {
+        Self {
+            next_id: v1.next_id,
+            tickets: v1
+                .tickets
+                .into_iter()
+                .map(|record| RecordV2 {
+                    id: record.id,
+                    title: record.title,
+                    description: record.description,
+                    status: record.status,
+                    reporter: None,
+                    last_editor: None,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct PayloadV3 {
+    next_id: u64,
+    tickets: Vec<RecordV2>,
+    links: Vec<LinkRecord>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct LinkRecord<Id = u64> {
+    from: Id,
+    kind: u8,
+    to: Id,
+}
+
+impl<Id> LinkRecord<Id> {
+    fn map_id<T>(self, f: impl Fn(Id) -> T) -> LinkRecord<T> {
+        LinkRecord {
+            from: f(self.from),
+            kind: self.kind,
+            to: f(self.to),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct PayloadV4 {
+    next_id: u64,
+    tickets: Vec<RecordV2>,
+    links: Vec<LinkRecord>,
+    next_comment_id: u64,
+    comments: Vec<CommentRecord>,
+    attachments: Vec<AttachmentRecord>,
+}
+
+/// Times are seconds since the Unix epoch and nanoseconds within the second,
+/// so they round-trip exactly.
+#[derive(Serialize, Deserialize)]
+struct CommentRecord<Id = u64> {
+    id: u64,
+    ticket: Id,
+    author: String,
+    body: String,
+    created_at: (i64, u32),
+    edited_at: Option<(i64, u32)>,
+}
+
+impl<Id> CommentRecord<Id> {
+    fn map_id<T>(self, f: impl Fn(Id) -> T) -> CommentRecord<T> {
+        CommentRecord {
+            id: self.id,
+            ticket: f(self.ticket),
+            author: self.author,
+            body: self.body,
+            created_at: self.created_at,
+            edited_at: self.edited_at,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct AttachmentRecord<Id = u64> {
+    ticket: Id,
+    digest: String,
+    name: String,
+    mime: String,
+    size: u64,
+    uploaded_by: String,
+    uploaded_at: (i64, u32),
+}
+
+impl<Id> AttachmentRecord<Id> {
+    fn map_id<T>(self, f: impl Fn(Id) -> T) -> AttachmentRecord<T> {
+        AttachmentRecord {
+            ticket: f(self.ticket),
+            digest: self.digest,
+            name: self.name,
+            mime: self.mime,
+            size: self.size,
+            uploaded_by: self.uploaded_by,
+            uploaded_at: self.uploaded_at,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct PayloadV5 {
+    projects: Vec<ProjectRecord>,
+    tickets: Vec<RecordV2<IdV5>>,
+    links: Vec<LinkRecord<IdV5>>,
+    next_comment_id: u64,
+    comments: Vec<CommentRecord<IdV5>>,
+    attachments: Vec<AttachmentRecord<IdV5>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ProjectRecord {
+    key: String,
+    name: String,
+    description: String,
+    archived: bool,
+    next_number: u64,
+}
+
+impl From<PayloadV1> for PayloadV5 {
+    fn from(v1: PayloadV1) -> Self {
+        PayloadV2::from(v1).into()
+    }
+}
+
+impl From<PayloadV2> for PayloadV5 {
+    fn from(v2: PayloadV2) -> Self {
+        PayloadV3 {
+            next_id: v2.next_id,
+            tickets: v2.tickets,
+            links: vec![],
+        }
+        .into()
+    }
+}
+
+impl From<PayloadV3> for PayloadV5 {
+    fn from(v3: PayloadV3) -> Self {
+        PayloadV4 {
+            next_id: v3.next_id,
+            tickets: v3.tickets,
+            links: v3.links,
+            next_comment_id: 0,
+            comments: vec![],
+            attachments: vec![],
+        }
+        .into()
+    }
+}
+
+impl From<PayloadV4> for PayloadV5 {
+    fn from(v4: PayloadV4) -> Self {
+        let default = Project::default_project();
+        Self {
+            projects: vec![ProjectRecord {
+                next_number: v4.next_id,
+                ..ProjectRecord::from(&default)
+            }],
+            tickets: v4
+                .tickets
+                .into_iter()
+                .map(|record| record.map_id(legacy_id))
+                .collect(),
+            links: v4
+                .links
+                .into_iter()
+                .map(|record| record.map_id(legacy_id))
+                .collect(),
+            next_comment_id: v4.next_comment_id,
+            comments: v4
+                .comments
+                .into_iter()
+                .map(|record| record.map_id(legacy_id))
+                .collect(),
+            attachments: v4
+                .attachments
+                .into_iter()
+                .map(|record| record.map_id(legacy_id))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct PayloadV6 {
+    projects: Vec<ProjectRecord>,
+    tickets: Vec<RecordV2<IdV5>>,
+    trash: Vec<TrashRecord>,
+    links: Vec<LinkRecord<IdV5>>,
+    next_comment_id: u64,
+    comments: Vec<CommentRecord<IdV5>>,
+    attachments: Vec<AttachmentRecord<IdV5>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TrashRecord<Id = IdV5> {
+    ticket: RecordV2<Id>,
+    deleted_by: String,
+    deleted_at: (i64, u32),
+}
+
+impl<Id> TrashRecord<Id> {
+    fn map_id<T>(self, f: impl Fn(Id) -> T) -> TrashRecord<T> {
+        TrashRecord {
+            ticket: self.ticket.map_id(f),
+            deleted_by: self.deleted_by,
+            deleted_at: self.deleted_at,
+        }
+    }
+}
+
+impl From<PayloadV5> for PayloadV6 {
+    fn from(v5: PayloadV5) -> Self {
+        Self {
+            projects: v5.projects,
+            tickets: v5.tickets,
+            trash: vec![],
+            links: v5.links,
+            next_comment_id: v5.next_comment_id,
+            comments: v5.comments,
+            attachments: v5.attachments,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct PayloadV7 {
+    projects: Vec<ProjectRecord>,
+    tickets: Vec<RecordV2<IdV7>>,
+    trash: Vec<TrashRecord<IdV7>>,
+    links: Vec<LinkRecord<IdV7>>,
+    next_comment_id: u64,
+    comments: Vec<CommentRecord<IdV7>>,
+    attachments: Vec<AttachmentRecord<IdV7>>,
+}
+
+impl From<PayloadV5> for PayloadV7 {
+    fn from(v5: PayloadV5) -> Self {
+        PayloadV6::from(v5).into()
+    }
+}
+
+impl From<PayloadV6> for PayloadV7 {
+    fn from(v6: PayloadV6) -> Self {
+        let numbered = |(key, number): IdV5| (key, SerialRecord::Number(number));
+        Self {
+            projects: v6.projects,
+            tickets: v6
+                .tickets
+                .into_iter()
+                .map(|record| record.map_id(numbered))
+                .collect(),
+            trash: v6
+                .trash
+                .into_iter()
+                .map(|record| record.map_id(numbered))
+                .collect(),
+            links: v6
+                .links
+                .into_iter()
+                .map(|record| record.map_id(numbered))
+                .collect(),
+            next_comment_id: v6.next_comment_id,
+            comments: v6
+                .comments
+                .into_iter()
+                .map(|record| record.map_id(numbered))
+                .collect(),
+            attachments: v6
+                .attachments
+                .into_iter()
+                .map(|record| record.map_id(numbered))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct PayloadV8 {
+    projects: Vec<ProjectRecord>,
+    tickets: Vec<RecordV2<IdV7>>,
+    trash: Vec<TrashRecord<IdV7>>,
+    links: Vec<LinkRecord<IdV7>>,
+    next_comment_id: u64,
+    comments: Vec<CommentRecord<IdV7>>,
+    attachments: Vec<AttachmentRecord<IdV7>>,
+    status_changes: Vec<(IdV7, Vec<StatusChangeRecord>)>,
+    history: Vec<HistoryRecord>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StatusChangeRecord {
+    status: u8,
+    at: (i64, u32),
+}
+
+#[derive(Serialize, Deserialize)]
+struct HistoryRecord {
+    user: String,
+    undo: Vec<OperationRecord>,
+    redo: Vec<OperationRecord>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct OperationRecord {
+    id: IdV7,
+    before: Option<RecordV2<IdV7>>,
+    after: Option<RecordV2<IdV7>>,
+}
+
+impl From<PayloadV5> for PayloadV8 {
+    fn from(v5: PayloadV5) -> Self {
+        PayloadV7::from(v5).into()
+    }
+}
+
+impl From<PayloadV7> for PayloadV8 {
+    fn from(v7: PayloadV7) -> Self {
+        Self {
+            projects: v7.projects,
+            tickets: v7.tickets,
+            trash: v7.trash,
+            links: v7.links,
+            next_comment_id: v7.next_comment_id,
+            comments: v7.comments,
+            attachments: v7.attachments,
+            status_changes: vec![],
+            history: vec![],
+        }
+    }
+}
+
+fn id_record(id: TicketId) -> IdV7 {
+    let serial = match id.serial {
+        Serial::Number(number) => SerialRecord::Number(number),
+        Serial::Ulid(ulid) => SerialRecord::Ulid(ulid.0),
+        Serial::Uuid(uuid) => SerialRecord::Uuid(uuid.as_u128()),
+    };
+    (id.project.to_string(), serial)
+}
+
+fn ticket_id((key, serial): IdV7) -> Result<TicketId, SnapshotError> {
+    let serial = match serial {
+        SerialRecord::Number(number) => Serial::Number(number),
+        SerialRecord::Ulid(ulid) => Serial::Ulid(Ulid(ulid)),
+        SerialRecord::Uuid(uuid) => Serial::Uuid(Uuid::from_u128(uuid)),
+    };
+    let project = key
+        .parse()
+        .map_err(|e| SnapshotError::Corrupt(format!("ticket {}-{}: {}", key, serial, e)))?;
+    Ok(TicketId::new(project, serial))
+}
+
+impl From<&Project> for ProjectRecord {
+    fn from(project: &Project) -> Self {
+        Self {
+            key: project.key.to_string(),
+            name: project.settings.name.clone(),
+            description: project.settings.description.clone(),
+            archived: project.settings.archived,
+            next_number: project.next_number,
+        }
+    }
+}
+
+impl From<&Comment> for CommentRecord<IdV7> {
+    fn from(comment: &Comment) -> Self {
+        Self {
+            id: comment.id.0,
+            ticket: id_record(comment.ticket),
+            author: comment.author.clone(),
+            body: comment.body.to_string(),
+            created_at: timestamp(comment.created_at),
+            edited_at: comment.edited_at.map(timestamp),
+        }
+    }
+}
+
+impl From<&Attachment> for AttachmentRecord<IdV7> {
+    fn from(attachment: &Attachment) -> Self {
+        Self {
+            ticket: id_record(attachment.ticket),
+            digest: attachment.digest.clone(),
+            name: attachment.name.clone(),
+            mime: attachment.mime.clone(),
+            size: attachment.size,
+            uploaded_by: attachment.uploaded_by.clone(),
+            uploaded_at: timestamp(attachment.uploaded_at),
+        }
+    }
+}
+
+fn timestamp(time: DateTime<Utc>) -> (i64, u32) {
+    (time.timestamp(), time.timestamp_subsec_nanos())
+}
+
+fn time((seconds, nanos): (i64, u32)) -> Result<DateTime<Utc>, SnapshotError> {
+    DateTime::from_timestamp(seconds, nanos)
+        .ok_or_else(|| SnapshotError::Corrupt(format!("invalid time {}.{:09}", seconds, nanos)))
+}
+
+impl From<&Link> for LinkRecord<IdV7> {
+    fn from(link: &Link) -> Self {
+        Self {
+            from: id_record(link.from),
+            kind: match link.kind {
+                LinkKind::ChildOf => 0,
+                LinkKind::Blocks => 1,
+                LinkKind::DuplicateOf => 2,
+            },
+            to: id_record(link.to),
+        }
+    }
+}
+
+impl From<&Ticket> for RecordV2<IdV7> {
+    fn from(ticket: &Ticket) -> Self {
+        Self {
+            id: id_record(ticket.id),
+            title: ticket.title.0.clone(),
+            description: ticket.description.to_string(),
+            status: status_record(ticket.status),
+            reporter: ticket.reporter.clone(),
+            last_editor: ticket.last_editor.clone(),
+        }
+    }
+}
+
+fn status_record(status: Status) -> u8 {
+    match status {
+        Status::ToDo => 0,
+        Status::InProgress => 1,
+        Status::Done => 2,
+    }
+}
+
+fn status(record: u8) -> Option<Status> {
+    match record {
+        0 => Some(Status::ToDo),
+        1 => Some(Status::InProgress),
+        2 => Some(Status::Done),
+        _ => None,
+    }
+}
+
+impl From<&StatusChange> for StatusChangeRecord {
+    fn from(change: &StatusChange) -> Self {
+        Self {
+            status: status_record(change.status),
+            at: timestamp(change.at),
+        }
+    }
+}
+
+impl From<&Operation> for OperationRecord {
+    fn from(operation: &Operation) -> Self {
+        Self {
+            id: id_record(operation.id),
+            before: operation.before.as_ref().map(RecordV2::from),
+            after: operation.after.as_ref().map(RecordV2::from),
+        }
+    }
+}
+
+impl TryFrom<OperationRecord> for Operation {
+    type Error = SnapshotError;
+
+    fn try_from(record: OperationRecord) -> Result<Self, Self::Error> {
+        Ok(Operation {
+            id: ticket_id(record.id)?,
+            before: record.before.map(Ticket::try_from).transpose()?,
+            after: record.after.map(Ticket::try_from).transpose()?,
+        })
+    }
+}
+
+impl From<&Trashed> for TrashRecord<IdV7> {
+    fn from(trashed: &Trashed) -> Self {
+        Self {
+            ticket: RecordV2::from(&trashed.ticket),
+            deleted_by: trashed.deleted_by.clone(),
+            deleted_at: timestamp(trashed.deleted_at),
+        }
+    }
+}
+
+impl TryFrom<RecordV2<IdV7>> for Ticket {
+    type Error = SnapshotError;
+
+    fn try_from(record: RecordV2<IdV7>) -> Result<Self, Self::Error> {
+        let id = ticket_id(record.id)?;
+        let corrupt =
+            |e: &dyn std::fmt::Display| SnapshotError::Corrupt(format!("ticket {}: {}", id, e));
+        let status = status(record.status)
+            .ok_or_else(|| corrupt(&format!("unknown status {}", record.status)))?;
+        Ok(Ticket {
+            id,
+            title: TicketTitle::try_from(record.title.as_str()).map_err(|e| corrupt(&e))?,
+            description: MarkdownDescription::try_from(record.description.as_str())
+                .map_err(|e| corrupt(&e))?,
+            status,
+            reporter: record.reporter,
+            last_editor: record.last_editor,
+        })
+    }
+}
+
+impl TryFrom<PayloadV8> for Snapshot {
+    type Error = SnapshotError;
+
+    fn try_from(payload: PayloadV8) -> Result<Self, Self::Error> {
+        let mut projects = Vec::with_capacity(payload.projects.len());
+        for record in payload.projects {
+            let key = record
+                .key
+                .parse()
+                .map_err(|e| SnapshotError::Corrupt(format!("project {}: {}", record.key, e)))?;
+            projects.push(Project {
+                key,
+                settings: ProjectSettings {
+                    name: record.name,
+                    description: record.description,
+                    archived: record.archived,
+                },
+                next_number: record.next_number,
+            });
+        }
+        let tickets = payload
+            .tickets
+            .into_iter()
+            .map(Ticket::try_from)
+            .collect::<Result<_, _>>()?;
+        let mut trash = Vec::with_capacity(payload.trash.len());
+        for record in payload.trash {
+            trash.push(Trashed {
+                ticket: record.ticket.try_into()?,
+                deleted_by: record.deleted_by,
+                deleted_at: time(record.deleted_at)?,
+            });
+        }
+        let mut links = Vec::with_capacity(payload.links.len());
+        for record in payload.links {
+            let (from, to) = (ticket_id(record.from)?, ticket_id(record.to)?);
+            let kind = match record.kind {
+                0 => LinkKind::ChildOf,
+                1 => LinkKind::Blocks,
+                2 => LinkKind::DuplicateOf,
+                kind => {
+                    let message = format!("link {} -> {}: unknown kind {}", from, to, kind);
+                    return Err(SnapshotError::Corrupt(message));
+                }
+            };
+            links.push(Link { from, kind, to });
+        }
+        let mut comments = Vec::with_capacity(payload.comments.len());
+        for record in payload.comments {
+            let body = MarkdownDescription::try_from(record.body.as_str())
+                .map_err(|e| SnapshotError::Corrupt(format!("comment {}: {}", record.id, e)))?;
+            comments.push(Comment {
+                id: CommentId(record.id),
+                ticket: ticket_id(record.ticket)?,
+                author: record.author,
+                body,
+                created_at: time(record.created_at)?,
+                edited_at: record.edited_at.map(time).transpose()?,
+            });
+        }
+        let mut attachments = Vec::with_capacity(payload.attachments.len());
+        for record in payload.attachments {
+            attachments.push(Attachment {
+                ticket: ticket_id(record.ticket)?,
+                digest: record.digest,
+                name: record.name,
+                mime: record.mime,
+                size: record.size,
+                uploaded_by: record.uploaded_by,
+                uploaded_at: time(record.uploaded_at)?,
+            });
+        }
+        let mut status_changes = BTreeMap::new();
+        for (id, records) in payload.status_changes {
+            let id = ticket_id(id)?;
+            let mut changes = Vec::with_capacity(records.len());
+            for record in records {
+                let status = status(record.status).ok_or_else(|| {
+                    let message = format!("ticket {}: unknown status {}", id, record.status);
+                    SnapshotError::Corrupt(message)
+                })?;
+                changes.push(StatusChange {
+                    status,
+                    at: time(record.at)?,
+                });
+            }
+            status_changes.insert(id, changes);
+        }
+        let mut history = BTreeMap::new();
+        for record in payload.history {
+            let operations = |records: Vec<OperationRecord>| {
+                records
+                    .into_iter()
+                    .map(Operation::try_from)
+                    .collect::<Result<_, _>>()
+            };
+            let user_history = History {
+                undo: operations(record.undo)?,
+                redo: operations(record.redo)?,
+            };
+            history.insert(record.user, user_history);
+        }
+        Ok(Self {
+            projects,
+            tickets,
+            trash,
+            links,
+            next_comment_id: CommentId(payload.next_comment_id),
+            comments,
+            attachments,
+            status_changes,
+            history,
+        })
+    }
+}
@@ -0,0 +1,688 @@
+//! Tickets and projects kept in an SQLite database file instead of in memory.
+//!
+//! SQLite calls block, so every call runs on tokio's blocking thread pool
+//! with `spawn_blocking`, one at a time on a single connection. The schema is
+//! created and upgraded by `MIGRATIONS` when the database is opened.
+//!
+//! Deleted tickets stay in the `tickets` table, with the time they were
+//! deleted, until they're purged. The rest of a server's store, e.g. links
+//! and comments, is kept in the `sections` table as one JSON document per
+//! `store::Section`; the command-line tools don't use it yet.
+//!
+//! The `number` column holds every kind of serial: numbers as integers, and
+//! ULIDs and UUIDs as text, which SQLite sorts after them.
+
+use anyhow::{anyhow, Context};
+use chrono::{DateTime, Duration, Utc};
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use rusqlite::{params, Connection, OptionalExtension, Row, Transaction};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use ticket_fields::{MarkdownDescription, TicketTitle};
+
+use crate::auth::{Action, User};
+use crate::data::{Status, Ticket, TicketDraft, TicketPatch};
+use crate::handlers::PatchError;
+use crate::ids::{IdGenerator, IdStrategy, Serial};
+use crate::links::{Link, Links};
+use crate::projects::{
+    Project, ProjectDraft, ProjectError, ProjectKey, ProjectPatch, ProjectSettings,
+};
+use crate::store::{Change, Section, TicketId, TicketStore};
+use crate::trash::Trashed;
+
+/// The schema, one step per release that changed it. Each step runs once, in
+/// a transaction, and `PRAGMA user_version` records how many have run.
+/// Steps that have been released must never change: add a new one instead.
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE projects (
+        key TEXT PRIMARY KEY,
+        name TEXT NOT NULL,
+        description TEXT NOT NULL DEFAULT '',
+        archived INTEGER NOT NULL DEFAULT 0,
+        next_number INTEGER NOT NULL DEFAULT 0
+    );
+    CREATE TABLE tickets (
+        project TEXT NOT NULL REFERENCES projects (key),
+        number INTEGER NOT NULL,
+        title TEXT NOT NULL,
+        description TEXT NOT NULL,
+        status TEXT NOT NULL CHECK (status IN ('ToDo', 'InProgress', 'Done')),
+        reporter TEXT,
+        last_editor TEXT,
+        PRIMARY KEY (project, number)
+    );",
+    // For listing the tickets with a given status, e.g. for each board column.
+    "CREATE INDEX tickets_by_status ON tickets (status, project, number);",
+    // Written as a whole whenever they change, see `TicketStore::section`.
+    "CREATE TABLE sections (
+        name TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );",
+    // Deleted tickets stay in the table until they're purged, see `trash`.
+    "ALTER TABLE tickets ADD COLUMN deleted_at TEXT;
+    ALTER TABLE tickets ADD COLUMN deleted_by TEXT;",
+];
+
+/// The schema version of databases written by this release.
+pub const SCHEMA_VERSION: usize = MIGRATIONS.len();
+
+/// A database of tickets and projects. Clones share the same connection.
+#[derive(Clone)]
+pub struct SqliteStore {
+    connection: Arc<Mutex<Connection>>,
+    ids: Arc<Mutex<IdGenerator>>,
+}
+
+impl SqliteStore {
+    /// Opens the database at `path`, creating it if it doesn't exist, and
+    /// brings its schema up to date. This blocks, like loading a store file:
+    /// call it at startup, or from `spawn_blocking`.
+    pub fn open(path: &Path) -> Result<Self, anyhow::Error> {
+        let mut connection = Connection::open(path)
+            .with_context(|| format!("Failed to open the database at {}", path.display()))?;
+        connection.pragma_update(None, "foreign_keys", true)?;
+        // Readers don't wait for the writer, and the other way around.
+        connection.pragma_update(None, "journal_mode", "WAL")?;
+        migrate(&mut connection)
+            .with_context(|| format!("Failed to migrate the database at {}", path.display()))?;
+
+        let default = Project::default_project();
+        connection.execute(
+            "INSERT OR IGNORE INTO projects (key, name) VALUES (?1, ?2)",
+            params![default.key, default.settings.name],
+        )?;
+
+        // New ULIDs and UUIDs come after the ones already there, even if the
+        // clock went back. Both sort like their text.
+        let mut ids = IdGenerator::default();
+        for len in [ulid::ULID_LEN, 36] {
+            let last: Option<Serial> = connection.query_row(
+                "SELECT max(number) FROM tickets
+                 WHERE typeof(number) = 'text' AND length(number) = ?1",
+                [len],
+                |row| row.get(0),
+            )?;
+            if let Some(serial) = last {
+                ids.observe(serial);
+            }
+        }
+        Ok(Self {
+            connection: Arc::new(Mutex::new(connection)),
+            ids: Arc::new(Mutex::new(ids)),
+        })
+    }
+
+    /// How tickets created from now on get their ids. Clones share it.
+    pub fn set_id_strategy(&self, strategy: IdStrategy) {
+        self.ids.lock().unwrap().set_strategy(strategy);
+    }
+
+    /// Runs `f` on the connection, on the blocking thread pool.
+    async fn call<T, F>(&self, f: F) -> Result<T, anyhow::Error>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> Result<T, anyhow::Error> + Send + 'static,
+    {
+        let connection = Arc::clone(&self.connection);
+        tokio::task::spawn_blocking(move || f(&mut connection.lock().unwrap())).await?
+    }
+
+    /// Adds a ticket by `reporter` to `project`, which has to exist and not be archived.
+    pub async fn create(
+        &self,
+        project: ProjectKey,
+        draft: TicketDraft,
+        reporter: &User,
+    ) -> Result<TicketId, anyhow::Error> {
+        let reporter = reporter.name.clone();
+        let ids = Arc::clone(&self.ids);
+        self.call(move |connection| {
+            let tx = connection.transaction()?;
+            let mut project =
+                select_project(&tx, project)?.ok_or(ProjectError::NotFound(project))?;
+            if project.settings.archived {
+                return Err(ProjectError::Archived(project.key).into());
+            }
+            let serial = ids.lock().unwrap().generate(&mut project);
+            let ticket = Ticket {
+                id: TicketId::new(project.key, serial),
+                title: draft.title,
+                description: draft.description,
+                status: Status::ToDo,
+                reporter: Some(reporter),
+                last_editor: None,
+            };
+            upsert_project(&tx, &project)?;
+            upsert_ticket(&tx, &ticket, None)?;
+            tx.commit()?;
+            Ok(ticket.id)
+        })
+        .await
+    }
+
+    pub async fn get(&self, id: TicketId) -> Result<Option<Ticket>, anyhow::Error> {
+        self.call(move |connection| {
+            let ticket = connection
+                .query_row(
+                    "SELECT * FROM tickets
+                     WHERE project = ?1 AND number = ?2 AND deleted_at IS NULL",
+                    params![id.project, id.serial],
+                    ticket_from_row,
+                )
+                .optional()?;
+            Ok(ticket)
+        })
+        .await
+    }
+
+    /// Every ticket, or only those with `status`, ordered by id.
+    pub async fn list(&self, status: Option<Status>) -> Result<Vec<Ticket>, anyhow::Error> {
+        self.call(move |connection| {
+            let mut statement = connection.prepare(
+                "SELECT * FROM tickets
+                 WHERE (?1 IS NULL OR status = ?1) AND deleted_at IS NULL
+                 ORDER BY project, number",
+            )?;
+            let tickets = statement
+                .query_map([status], ticket_from_row)?
+                .collect::<Result<_, _>>()?;
+            Ok(tickets)
+        })
+        .await
+    }
+
+    /// The tickets of `project`, ordered by id.
+    pub async fn list_in(&self, project: ProjectKey) -> Result<Vec<Ticket>, anyhow::Error> {
+        self.call(move |connection| {
+            select_project(connection, project)?.ok_or(ProjectError::NotFound(project))?;
+            let mut statement = connection.prepare(
+                "SELECT * FROM tickets
+                 WHERE project = ?1 AND deleted_at IS NULL
+                 ORDER BY number",
+            )?;
+            let tickets = statement
+                .query_map([project], ticket_from_row)?
+                .collect::<Result<_, _>>()?;
+            Ok(tickets)
+        })
+        .await
+    }
+
+    /// Applies `patch` as `user`, checked like `handlers::update_ticket_endpoint`.
+    /// The ticket is read and written in one transaction, so concurrent
+    /// patches can't undo each other. Fails with a `PatchError` if the
+    /// patch isn't allowed.
+    pub async fn patch(&self, patch: TicketPatch, user: &User) -> Result<(), anyhow::Error> {
+        let user = user.clone();
+        self.call(move |connection| {
+            // Taking the write lock up front, as the ticket is read to be written.
+            let tx =
+                connection.transaction_with_behavior(rusqlite::TransactionBehavior::Immediate)?;
+            let mut ticket = tx
+                .query_row(
+                    "SELECT * FROM tickets
+                     WHERE project = ?1 AND number = ?2 AND deleted_at IS NULL",
+                    params![patch.id.project, patch.id.serial],
+                    ticket_from_row,
+                )
+                .optional()?
+                .ok_or(PatchError::NotFound)?;
+
+            let edits_content = patch.title.is_some() || patch.description.is_some();
+            if edits_content && !user.can(Action::Edit(&ticket)) {
+                return Err(PatchError::Forbidden.into());
+            }
+            if patch.status.is_some() && !user.can(Action::ChangeStatus) {
+                return Err(PatchError::Forbidden.into());
+            }
+            if let Some(status) = patch.status {
+                check_status(&tx, patch.id, status)?;
+            }
+
+            if let Some(title) = patch.title {
+                ticket.title = title;
+            }
+            if let Some(description) = patch.description {
+                ticket.description = description;
+            }
+            if let Some(status) = patch.status {
+                ticket.status = status;
+            }
+            ticket.last_editor = Some(user.name);
+            upsert_ticket(&tx, &ticket, None)?;
+            tx.commit()?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Moves a ticket to the trash, like `TicketStore::delete`.
+    /// Returns whether there was such a ticket.
+    pub async fn delete(&self, id: TicketId, by: &User) -> Result<bool, anyhow::Error> {
+        let by = by.name.clone();
+        self.call(move |connection| {
+            let deleted = connection.execute(
+                "UPDATE tickets SET deleted_at = ?3, deleted_by = ?4
+                 WHERE project = ?1 AND number = ?2 AND deleted_at IS NULL",
+                params![id.project, id.serial, Utc::now(), by],
+            )?;
+            Ok(deleted > 0)
+        })
+        .await
+    }
+
+    /// The deleted tickets, ordered by id.
+    pub async fn trash(&self) -> Result<Vec<Trashed>, anyhow::Error> {
+        self.call(|connection| {
+            let mut statement = connection.prepare(
+                "SELECT * FROM tickets WHERE deleted_at IS NOT NULL ORDER BY project, number",
+            )?;
+            let trash = statement
+                .query_map([], trashed_from_row)?
+                .collect::<Result<_, _>>()?;
+            Ok(trash)
+        })
+        .await
+    }
+
+    /// Moves a deleted ticket back out of the trash.
+    /// Returns whether there was such a ticket in the trash.
+    pub async fn undelete(&self, id: TicketId) -> Result<bool, anyhow::Error> {
+        self.call(move |connection| {
+            let restored = connection.execute(
+                "UPDATE tickets SET deleted_at = NULL, deleted_by = NULL
+                 WHERE project = ?1 AND number = ?2 AND deleted_at IS NOT NULL",
+                params![id.project, id.serial],
+            )?;
+            Ok(restored > 0)
+        })
+        .await
+    }
+
+    /// Removes a deleted ticket for good. Its number isn't given to another
+    /// ticket, as the project's counter doesn't go back.
+    /// Returns whether there was such a ticket in the trash.
+    pub async fn purge(&self, id: TicketId) -> Result<bool, anyhow::Error> {
+        self.call(move |connection| {
+            let purged = connection.execute(
+                "DELETE FROM tickets
+                 WHERE project = ?1 AND number = ?2 AND deleted_at IS NOT NULL",
+                params![id.project, id.serial],
+            )?;
+            Ok(purged > 0)
+        })
+        .await
+    }
+
+    /// Purges every ticket that has been in the trash for `retention` or
+    /// longer, in one transaction. Returns their ids.
+    pub async fn purge_expired(
+        &self,
+        retention: Duration,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<TicketId>, anyhow::Error> {
+        self.call(move |connection| {
+            let tx = connection.transaction()?;
+            let trash: Vec<Trashed> = tx
+                .prepare("SELECT * FROM tickets WHERE deleted_at IS NOT NULL")?
+                .query_map([], trashed_from_row)?
+                .collect::<Result<_, _>>()?;
+            let mut purged = vec![];
+            for trashed in trash {
+                if trashed.expired(retention, now) {
+                    let id = trashed.ticket.id;
+                    tx.execute(
+                        "DELETE FROM tickets WHERE project = ?1 AND number = ?2",
+                        params![id.project, id.serial],
+                    )?;
+                    purged.push(id);
+                }
+            }
+            tx.commit()?;
+            Ok(purged)
+        })
+        .await
+    }
+
+    /// Every project, ordered by key.
+    pub async fn projects(&self) -> Result<Vec<Project>, anyhow::Error> {
+        self.call(|connection| {
+            let mut statement = connection.prepare("SELECT * FROM projects ORDER BY key")?;
+            let projects = statement
+                .query_map([], project_from_row)?
+                .collect::<Result<_, _>>()?;
+            Ok(projects)
+        })
+        .await
+    }
+
+    pub async fn create_project(&self, draft: ProjectDraft) -> Result<Project, anyhow::Error> {
+        self.call(move |connection| {
+            let tx = connection.transaction()?;
+            if select_project(&tx, draft.key)?.is_some() {
+                return Err(ProjectError::Exists(draft.key).into());
+            }
+            let project = Project::new(draft.key, draft.settings)?;
+            upsert_project(&tx, &project)?;
+            tx.commit()?;
+            Ok(project)
+        })
+        .await
+    }
+
+    pub async fn update_project(
+        &self,
+        key: ProjectKey,
+        patch: ProjectPatch,
+    ) -> Result<Project, anyhow::Error> {
+        self.call(move |connection| {
+            let tx = connection.transaction()?;
+            let mut project = select_project(&tx, key)?.ok_or(ProjectError::NotFound(key))?;
+            project.update(patch)?;
+            upsert_project(&tx, &project)?;
+            tx.commit()?;
+            Ok(project)
+        })
+        .await
+    }
+
+    /// An in-memory store with everything in the database, e.g. for a
+    /// server to work on. See `write_changes` to keep the database up to
+    /// date with it.
+    pub async fn load(&self) -> Result<TicketStore, anyhow::Error> {
+        let projects = self.projects().await?;
+        let tickets = self.list(None).await?;
+        let trash = self.trash().await?;
+        let sections: Vec<(String, String)> = self
+            .call(|connection| {
+                let mut statement = connection.prepare("SELECT name, value FROM sections")?;
+                let sections = statement
+                    .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                    .collect::<Result<_, _>>()?;
+                Ok(sections)
+            })
+            .await?;
+        let mut store = TicketStore::new();
+        for project in projects {
+            store.restore_project(project);
+        }
+        for ticket in tickets {
+            store.restore(ticket);
+        }
+        for trashed in trash {
+            store.restore_trashed(trashed);
+        }
+        for (name, value) in sections {
+            let section = Section::ALL
+                .into_iter()
+                .find(|section| section.as_str() == name)
+                .ok_or_else(|| anyhow!("Unknown section `{}` in the database", name))?;
+            store
+                .restore_section(section, &value)
+                .with_context(|| format!("Invalid `{}` section in the database", name))?;
+        }
+        Ok(store)
+    }
+
+    /// Writes `changes`, journaled by `store` (see `TicketStore::journal_to`),
+    /// in one transaction: all of them or none. Sections are written as they
+    /// are in `store` now.
+    pub async fn write_changes(
+        &self,
+        store: &TicketStore,
+        changes: Vec<Change>,
+    ) -> Result<(), anyhow::Error> {
+        let sections: BTreeSet<Section> = changes
+            .iter()
+            .filter_map(|change| match change {
+                Change::Section(section) => Some(*section),
+                _ => None,
+            })
+            .collect();
+        let sections = sections
+            .into_iter()
+            .map(|section| Ok((section, store.section(section)?)))
+            .collect::<Result<Vec<_>, serde_json::Error>>()?;
+        self.call(move |connection| {
+            let tx = connection.transaction()?;
+            for change in &changes {
+                match change {
+                    Change::Ticket(ticket) => upsert_ticket(&tx, ticket, None)?,
+                    Change::TicketTrashed(trashed) => upsert_ticket(
+                        &tx,
+                        &trashed.ticket,
+                        Some((&trashed.deleted_at, &trashed.deleted_by)),
+                    )?,
+                    Change::TicketRemoved(id) => {
+                        tx.execute(
+                            "DELETE FROM tickets WHERE project = ?1 AND number = ?2",
+                            params![id.project, id.serial],
+                        )?;
+                    }
+                    Change::Project(project) => upsert_project(&tx, project)?,
+                    Change::Section(_) => {}
+                }
+            }
+            for (section, value) in &sections {
+                tx.execute(
+                    "INSERT INTO sections (name, value) VALUES (?1, ?2)
+                     ON CONFLICT (name) DO UPDATE SET value = excluded.value",
+                    params![section.as_str(), value],
+                )?;
+            }
+            tx.commit()?;
+            Ok(())
+        })
+        .await
+    }
+}
+
+/// Checks that ticket `id` can be moved to `status`, like `TicketStore::check_status`,
+/// with the links a server wrote. Fails with a `PatchError` if it can't.
+fn check_status(tx: &Transaction, id: TicketId, status: Status) -> Result<(), anyhow::Error> {
+    let links: Option<String> = tx
+        .query_row(
+            "SELECT value FROM sections WHERE name = ?1",
+            [Section::Links.as_str()],
+            |row| row.get(0),
+        )
+        .optional()?;
+    let links: Links = match links {
+        Some(links) => serde_json::from_str::<Vec<Link>>(&links)?
+            .into_iter()
+            .collect(),
+        None => return Ok(()),
+    };
+    let mut statuses = BTreeMap::new();
+    for blocker in links.blockers(id) {
+        let status: Option<Status> = tx
+            .query_row(
+                "SELECT status FROM tickets
+                 WHERE project = ?1 AND number = ?2 AND deleted_at IS NULL",
+                params![blocker.project, blocker.serial],
+                |row| row.get(0),
+            )
+            .optional()?;
+        statuses.extend(status.map(|status| (blocker, status)));
+    }
+    links
+        .check_status(id, status, |blocker| statuses.get(&blocker).copied())
+        .map_err(PatchError::from)?;
+    Ok(())
+}
+
+/// Brings the schema of `connection` up to `SCHEMA_VERSION`.
+fn migrate(connection: &mut Connection) -> Result<(), anyhow::Error> {
+    let version: usize = connection.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    if version > SCHEMA_VERSION {
+        return Err(anyhow!(
+            "The database has schema version {}, but this release only knows up to {}. \
+             It was written by a newer release.",
+            version,
+            SCHEMA_VERSION
+        ));
+    }
+    for (done, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        let tx = connection.transaction()?;
+        tx.execute_batch(migration)?;
+        tx.pragma_update(None, "user_version", done + 1)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+fn select_project(connection: &Connection, key: ProjectKey) -> rusqlite::Result<Option<Project>> {
+    connection
+        .query_row(
+            "SELECT * FROM projects WHERE key = ?1",
+            [key],
+            project_from_row,
+        )
+        .optional()
+}
+
+fn upsert_project(tx: &Transaction, project: &Project) -> rusqlite::Result<()> {
+    tx.execute(
+        "INSERT INTO projects (key, name, description, archived, next_number)
+         VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT (key) DO UPDATE SET
+             name = excluded.name,
+             description = excluded.description,
+             archived = excluded.archived,
+             next_number = excluded.next_number",
+        params![
+            project.key,
+            project.settings.name,
+            project.settings.description,
+            project.settings.archived,
+            project.next_number,
+        ],
+    )?;
+    Ok(())
+}
+
+/// `deleted` is when and by whom the ticket was moved to the trash, if it was.
+fn upsert_ticket(
+    tx: &Transaction,
+    ticket: &Ticket,
+    deleted: Option<(&DateTime<Utc>, &String)>,
+) -> rusqlite::Result<()> {
+    let (deleted_at, deleted_by) = deleted.unzip();
+    tx.execute(
+        "INSERT INTO tickets (project, number, title, description, status, reporter,
+                              last_editor, deleted_at, deleted_by)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+         ON CONFLICT (project, number) DO UPDATE SET
+             title = excluded.title,
+             description = excluded.description,
+             status = excluded.status,
+             reporter = excluded.reporter,
+             last_editor = excluded.last_editor,
+             deleted_at = excluded.deleted_at,
+             deleted_by = excluded.deleted_by",
+        params![
+            ticket.id.project,
+            ticket.id.serial,
+            ticket.title.0,
+            ticket.description.as_str(),
+            ticket.status,
+            ticket.reporter,
+            ticket.last_editor,
+            deleted_at,
+            deleted_by,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Fields are validated again, as anything can write to the file.
+fn ticket_from_row(row: &Row) -> rusqlite::Result<Ticket> {
+    let invalid = |column: &str, e: Box<dyn std::error::Error + Send + Sync>| {
+        let index = row.as_ref().column_index(column).unwrap_or_default();
+        rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, e)
+    };
+    let title: String = row.get("title")?;
+    let description: String = row.get("description")?;
+    Ok(Ticket {
+        id: TicketId::new(row.get("project")?, row.get::<_, Serial>("number")?),
+        title: TicketTitle::try_from(title.as_str()).map_err(|e| invalid("title", e.into()))?,
+        description: MarkdownDescription::try_from(description.as_str())
+            .map_err(|e| invalid("description", e.into()))?,
+        status: row.get("status")?,
+        reporter: row.get("reporter")?,
+        last_editor: row.get("last_editor")?,
+    })
+}
+
+fn trashed_from_row(row: &Row) -> rusqlite::Result<Trashed> {
+    Ok(Trashed {
+        ticket: ticket_from_row(row)?,
+        deleted_by: row.get("deleted_by")?,
+        deleted_at: row.get("deleted_at")?,
+    })
+}
+
+fn project_from_row(row: &Row) -> rusqlite::Result<Project> {
+    Ok(Project {
+        key: row.get("key")?,
+        settings: ProjectSettings {
+            name: row.get("name")?,
+            description: row.get("description")?,
+            archived: row.get("archived")?,
+        },
+        next_number: row.get("next_number")?,
+    })
+}
+
+impl ToSql for Status {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.to_string()))
+    }
+}
+
+impl FromSql for Status {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value
+            .as_str()?
+            .parse()
+            .map_err(|e: anyhow::Error| FromSqlError::Other(e.into()))
+    }
+}
+
+impl ToSql for Serial {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        match self {
+            Serial::Number(number) => number.to_sql(),
+            serial => Ok(ToSqlOutput::from(serial.to_string())),
+        }
+    }
+}
+
+impl FromSql for Serial {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        match value {
+            ValueRef::Integer(_) => u64::column_result(value).map(Serial::Number),
+            value => value
+                .as_str()?
+                .parse()
+                .map_err(|e: anyhow::Error| FromSqlError::Other(e.into())),
+        }
+    }
+}
+
+impl ToSql for ProjectKey {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.as_str()))
+    }
+}
+
+impl FromSql for ProjectKey {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value
+            .as_str()?
+            .parse()
+            .map_err(|e: anyhow::Error| FromSqlError::Other(e.into()))
+    }
+}
@@ -1,61 +1,1137 @@
 use std::collections::BTreeMap;
 use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
 use std::str::FromStr;
-use std::sync::{Arc, RwLock};
-use anyhow::anyhow;
-use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex, RwLock};
+use anyhow::{anyhow, Context};
+use chrono::{DateTime, Duration, Utc};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
+use crate::attachments::Attachment;
+use crate::auth::{Action, User};
+use crate::comments::{Comment, CommentId, Comments};
 use crate::data::{Status, Ticket, TicketDraft};
+use crate::history::{Conflict, History, Operation, Report};
+use crate::ids::{IdGenerator, IdStrategy, Serial};
+use crate::links::{Blocked, Link, LinkError, Links};
+use crate::projects::{
+    Project, ProjectDraft, ProjectError, ProjectKey, ProjectPatch, ProjectSettings,
+};
+use crate::query::{Query, SavedQuery, SavedQueryError};
+use crate::reports::{self, Period, StatusChange, Summary};
+use crate::templates::{Template, TemplateError};
+use crate::trash::Trashed;
+use ticket_fields::{MarkdownDescription, Policy};
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
-pub struct TicketId(pub u64);
+/// A ticket's project and its serial, written `API-42` for the ticket
+/// numbered 42 in project `API`. See `ids` for the other kinds of serials.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TicketId {
+    pub project: ProjectKey,
+    pub serial: Serial,
+}
+
+impl TicketId {
+    pub fn new(project: ProjectKey, serial: impl Into<Serial>) -> Self {
+        Self {
+            project,
+            serial: serial.into(),
+        }
+    }
+}
+
+/// A bare number is a ticket of the default project,
+/// which is how tickets were numbered before projects existed.
+impl From<u64> for TicketId {
+    fn from(number: u64) -> Self {
+        TicketId::new(ProjectKey::DEFAULT, number)
+    }
+}
 
 impl fmt::Display for TicketId {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.0)
+        write!(f, "{}-{}", self.project, self.serial)
     }
 }
 
 impl FromStr for TicketId {
     type Err = anyhow::Error;
 
+    /// Parses `API-42`, or a bare serial for the default project.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let id = s.parse::<u64>().map_err(|_| anyhow!("Failed parsing"))?;
+        // A bare UUID has hyphens too, so it has to be tried first.
+        if let Ok(serial) = s.parse::<Serial>() {
+            return Ok(TicketId::new(ProjectKey::DEFAULT, serial));
+        }
+        let invalid = || anyhow!("`{}` is not a ticket id, expected e.g. API-42", s);
+        // Keys have no hyphens, but UUIDs do.
+        let (project, serial) = s.split_once('-').ok_or_else(invalid)?;
+        let project = project.parse().map_err(|_| invalid())?;
+        let serial: Serial = serial.parse().map_err(|_| invalid())?;
+        Ok(TicketId::new(project, serial))
+    }
+}
+
+impl Serialize for TicketId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Also accepts the bare numbers of stores written before projects existed.
+impl<'de> Deserialize<'de> for TicketId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl de::Visitor<'_> for Visitor {
+            type Value = TicketId;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a ticket id like API-42")
+            }
+
+            fn visit_u64<E: de::Error>(self, number: u64) -> Result<TicketId, E> {
+                Ok(TicketId::from(number))
+            }
+
+            fn visit_str<E: de::Error>(self, id: &str) -> Result<TicketId, E> {
+                id.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+/// A change to a store, recorded in its journal.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Change {
+    /// The ticket was added, changed or restored from the trash.
+    Ticket(Ticket),
+    /// The ticket was moved to the trash.
+    TicketTrashed(Trashed),
+    /// The ticket was removed for good.
+    TicketRemoved(TicketId),
+    /// The project was added, or its settings or counter changed.
+    Project(Project),
+    /// Something in the section changed. Sections are small enough to be
+    /// written as a whole, see `TicketStore::section`.
+    Section(Section),
+}
+
+/// The parts of a store besides its tickets and projects.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Section {
+    Links,
+    Comments,
+    Attachments,
+    History,
+    Templates,
+    Queries,
+    StatusChanges,
+}
+
+impl Section {
+    pub const ALL: [Section; 7] = [
+        Section::Links,
+        Section::Comments,
+        Section::Attachments,
+        Section::History,
+        Section::Templates,
+        Section::Queries,
+        Section::StatusChanges,
+    ];
 
-        Ok(TicketId(id))
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Section::Links => "links",
+            Section::Comments => "comments",
+            Section::Attachments => "attachments",
+            Section::History => "history",
+            Section::Templates => "templates",
+            Section::Queries => "queries",
+            Section::StatusChanges => "status_changes",
+        }
+    }
+}
+
+/// The changes a store made since they were last taken, see `TicketStore::journal_to`.
+/// Clones share the same changes.
+#[derive(Clone, Debug, Default)]
+pub struct Journal(Arc<Mutex<Vec<Change>>>);
+
+impl Journal {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Every change since the last call, oldest first.
+    pub fn take(&self) -> Vec<Change> {
+        std::mem::take(&mut self.0.lock().unwrap())
     }
 
+    fn push(&self, change: Change) {
+        self.0.lock().unwrap().push(change);
+    }
 }
 
 #[derive(Clone)]
 pub struct TicketStore {
     pub tickets: BTreeMap<TicketId, Arc<RwLock<Ticket>>>,
-    counter: u64,
+    /// Deleted tickets, which aren't in `tickets`.
+    trash: BTreeMap<TicketId, Trashed>,
+    /// Always holds the default project.
+    projects: BTreeMap<ProjectKey, Project>,
+    links: Links,
+    comments: Comments,
+    /// Keyed by ticket and digest.
+    attachments: BTreeMap<(TicketId, String), Attachment>,
+    ids: IdGenerator,
+    /// Keyed by user name.
+    history: BTreeMap<String, History>,
+    /// Keyed by name.
+    templates: BTreeMap<String, Template>,
+    /// Keyed by name.
+    queries: BTreeMap<String, SavedQuery>,
+    /// Oldest first. Deleted tickets keep theirs until they're purged.
+    status_changes: BTreeMap<TicketId, Vec<StatusChange>>,
+    journal: Option<Journal>,
+}
+
+impl Default for TicketStore {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl TicketStore {
+    /// An empty store with only the default project.
     pub fn new() -> Self {
+        let default = Project::default_project();
         Self {
             tickets: BTreeMap::new(),
-            counter: 0,
+            trash: BTreeMap::new(),
+            projects: BTreeMap::from([(default.key, default)]),
+            links: Links::new(),
+            comments: Comments::new(),
+            attachments: BTreeMap::new(),
+            ids: IdGenerator::default(),
+            history: BTreeMap::new(),
+            templates: BTreeMap::new(),
+            queries: BTreeMap::new(),
+            status_changes: BTreeMap::new(),
+            journal: None,
+        }
+    }
+
+    /// How tickets added from now on get their ids. Existing tickets keep theirs.
+    pub fn set_id_strategy(&mut self, strategy: IdStrategy) {
+        self.ids.set_strategy(strategy);
+    }
+
+    pub fn id_generator(&self) -> &IdGenerator {
+        &self.ids
+    }
+
+    /// Records every change from now on in `journal`, e.g. to keep a copy
+    /// of the store in a database.
+    pub fn journal_to(&mut self, journal: Journal) {
+        self.journal = Some(journal);
+    }
+
+    fn record(&self, change: Change) {
+        if let Some(journal) = &self.journal {
+            journal.push(change);
+        }
+    }
+
+    fn changed(&self, section: Section) {
+        self.record(Change::Section(section));
+    }
+
+    /// `section` as JSON, in the same form as in the files written by `save`.
+    pub fn section(&self, section: Section) -> Result<String, serde_json::Error> {
+        match section {
+            Section::Links => serde_json::to_string(&self.links.iter().collect::<Vec<_>>()),
+            Section::Comments => serde_json::to_string(&CommentsSection {
+                next_comment_id: self.comments.next_id().0,
+                comments: self.comments.iter().cloned().collect(),
+            }),
+            Section::Attachments => {
+                serde_json::to_string(&self.attachments.values().collect::<Vec<_>>())
+            }
+            Section::History => serde_json::to_string(&self.history),
+            Section::Templates => {
+                serde_json::to_string(&self.templates.values().collect::<Vec<_>>())
+            }
+            Section::Queries => serde_json::to_string(&self.queries.values().collect::<Vec<_>>()),
+            Section::StatusChanges => serde_json::to_string(&self.status_changes),
+        }
+    }
+
+    /// Replaces `section` with `json`, as written by `section`. Call it once
+    /// every ticket is restored, live or deleted: whatever is about tickets
+    /// that aren't in the store is left out.
+    pub fn restore_section(&mut self, section: Section, json: &str) -> Result<(), anyhow::Error> {
+        let known = |id: &TicketId| self.tickets.contains_key(id) || self.trash.contains_key(id);
+        match section {
+            Section::Links => {
+                let links: Vec<Link> = serde_json::from_str(json)?;
+                self.links = links
+                    .into_iter()
+                    .filter(|link| known(&link.from) && known(&link.to))
+                    .collect();
+            }
+            Section::Comments => {
+                let section: CommentsSection = serde_json::from_str(json)?;
+                let mut comments = Comments::new();
+                for comment in section.comments {
+                    if known(&comment.ticket) {
+                        comments.restore(comment);
+                    }
+                }
+                comments.reserve_ids(CommentId(section.next_comment_id));
+                self.comments = comments;
+            }
+            Section::Attachments => {
+                let attachments: Vec<Attachment> = serde_json::from_str(json)?;
+                self.attachments = attachments
+                    .into_iter()
+                    .filter(|attachment| known(&attachment.ticket))
+                    .map(|attachment| ((attachment.ticket, attachment.digest.clone()), attachment))
+                    .collect();
+            }
+            Section::History => self.history = serde_json::from_str(json)?,
+            Section::Templates => {
+                let templates: Vec<Template> = serde_json::from_str(json)?;
+                self.templates = templates
+                    .into_iter()
+                    .map(|template| (template.name.clone(), template))
+                    .collect();
+            }
+            Section::Queries => {
+                let queries: Vec<SavedQuery> = serde_json::from_str(json)?;
+                self.queries = queries
+                    .into_iter()
+                    .map(|query| (query.name.clone(), query))
+                    .collect();
+            }
+            Section::StatusChanges => {
+                let mut status_changes: BTreeMap<TicketId, Vec<StatusChange>> =
+                    serde_json::from_str(json)?;
+                status_changes.retain(|id, _| known(id));
+                self.status_changes = status_changes;
+            }
         }
+        Ok(())
     }
 
+    /// Journals ticket `id` after it was changed through its lock.
+    /// Call it once the lock is released.
+    pub fn touch(&self, id: TicketId) {
+        if let Some(ticket) = self.tickets.get(&id) {
+            let ticket = ticket.read().unwrap().clone();
+            self.record(Change::Ticket(ticket));
+        }
+    }
+
+    /// Like `touch`, and records the change from `before` in the history of
+    /// `by`, so they can undo it.
+    pub fn edited(&mut self, before: Ticket, by: &User) {
+        let id = before.id;
+        self.touch(id);
+        if let Some(ticket) = self.tickets.get(&id) {
+            let after = ticket.read().unwrap().clone();
+            if after.status != before.status {
+                self.status_changed(id, after.status);
+            }
+            self.remember(
+                by,
+                Operation {
+                    id,
+                    before: Some(before),
+                    after: Some(after),
+                },
+            );
+        }
+    }
+
+    /// Adds a ticket to the default project.
     pub fn add_ticket(&mut self, ticket: TicketDraft) -> TicketId {
-        let id = TicketId(self.counter);
-        self.counter += 1;
+        self.insert(ProjectKey::DEFAULT, ticket, None)
+            .expect("The default project always exists and can't be archived")
+    }
+
+    /// Like `add_ticket`, but records `reporter` as the ticket's author.
+    pub fn add_ticket_by(&mut self, ticket: TicketDraft, reporter: &User) -> TicketId {
+        self.insert(ProjectKey::DEFAULT, ticket, Some(reporter))
+            .expect("The default project always exists and can't be archived")
+    }
+
+    /// Adds a ticket by `reporter` to `project`, which has to exist and not be archived.
+    pub fn add_ticket_to(
+        &mut self,
+        project: ProjectKey,
+        ticket: TicketDraft,
+        reporter: &User,
+    ) -> Result<TicketId, ProjectError> {
+        self.insert(project, ticket, Some(reporter))
+    }
+
+    fn insert(
+        &mut self,
+        project: ProjectKey,
+        ticket: TicketDraft,
+        reporter: Option<&User>,
+    ) -> Result<TicketId, ProjectError> {
+        let project = self
+            .projects
+            .get_mut(&project)
+            .ok_or(ProjectError::NotFound(project))?;
+        if project.settings.archived {
+            return Err(ProjectError::Archived(project.key));
+        }
+        let id = TicketId::new(project.key, self.ids.generate(project));
+        let project = project.clone();
         let ticket = Ticket {
             id,
             title: ticket.title,
             description: ticket.description,
             status: Status::ToDo,
+            reporter: reporter.map(|reporter| reporter.name.clone()),
+            last_editor: None,
         };
+        self.record(Change::Project(project));
+        self.record(Change::Ticket(ticket.clone()));
+        if let Some(reporter) = reporter {
+            let operation = Operation {
+                id,
+                before: None,
+                after: Some(ticket.clone()),
+            };
+            self.remember(reporter, operation);
+        }
         let ticket = Arc::new(RwLock::new(ticket));
         self.tickets.insert(id, ticket);
-        id
+        self.status_changed(id, Status::ToDo);
+        Ok(id)
+    }
+
+    fn status_changed(&mut self, id: TicketId, status: Status) {
+        let change = StatusChange {
+            status,
+            at: Utc::now(),
+        };
+        self.status_changes.entry(id).or_default().push(change);
+        self.changed(Section::StatusChanges);
+    }
+
+    /// When ticket `id` was created and changed status, oldest first. Empty
+    /// for tickets that were restored rather than created, see `reports`.
+    pub fn status_changes(&self, id: TicketId) -> &[StatusChange] {
+        self.status_changes.get(&id).map_or(&[], Vec::as_slice)
+    }
+
+    /// The status changes of every ticket that has some, ordered by id.
+    pub fn all_status_changes(&self) -> impl Iterator<Item = (TicketId, &[StatusChange])> {
+        self.status_changes
+            .iter()
+            .map(|(id, changes)| (*id, changes.as_slice()))
+    }
+
+    /// Replaces the status changes of ticket `id`, e.g. with a snapshot's.
+    pub fn restore_status_changes(&mut self, id: TicketId, changes: Vec<StatusChange>) {
+        self.status_changes.insert(id, changes);
+        self.changed(Section::StatusChanges);
+    }
+
+    /// Summarizes the tickets that aren't deleted, see `reports::summarize`.
+    pub fn summary(&self, period: Period, now: DateTime<Utc>) -> Summary {
+        let tickets: Vec<Ticket> = self
+            .tickets
+            .values()
+            .map(|ticket| ticket.read().unwrap().clone())
+            .collect();
+        let tickets = tickets
+            .iter()
+            .map(|ticket| (ticket, self.status_changes(ticket.id)));
+        reports::summarize(tickets, period, now)
+    }
+
+    /// The id the next ticket added to `project` gets with sequential ids.
+    pub fn next_id(&self, project: ProjectKey) -> Option<TicketId> {
+        let project = self.projects.get(&project)?;
+        Some(TicketId::new(project.key, project.next_number))
+    }
+
+    /// Inserts a ticket with its id as is, e.g. from a backup, replacing any
+    /// ticket with the same id. Later tickets of its project get higher ids.
+    /// Its project is created if it doesn't exist yet, named after its key.
+    pub fn restore(&mut self, ticket: Ticket) {
+        self.reserve(ticket.id);
+        self.record(Change::Ticket(ticket.clone()));
+        self.tickets.insert(ticket.id, Arc::new(RwLock::new(ticket)));
+    }
+
+    /// Makes sure tickets added to `id.project` from now on get higher ids
+    /// than `id`, creating the project like `restore` if needed.
+    pub fn reserve(&mut self, id: TicketId) {
+        match id.serial {
+            Serial::Number(number) => self.reserve_ids(id.project, number + 1),
+            serial => {
+                self.reserve_ids(id.project, 0);
+                self.ids.observe(serial);
+            }
+        }
+    }
+
+    /// Makes sure tickets added to `project` from now on get `next_number` or
+    /// a higher number, creating the project like `restore` if needed.
+    pub fn reserve_ids(&mut self, project: ProjectKey, next_number: u64) {
+        let created = !self.projects.contains_key(&project);
+        let project = self.projects.entry(project).or_insert_with(|| Project {
+            key: project,
+            settings: ProjectSettings::new(project.as_str()),
+            next_number: 0,
+        });
+        if created || project.next_number < next_number {
+            project.next_number = project.next_number.max(next_number);
+            let project = project.clone();
+            self.record(Change::Project(project));
+        }
+    }
+
+    /// Every project, ordered by key.
+    pub fn projects(&self) -> impl Iterator<Item = &Project> {
+        self.projects.values()
+    }
+
+    pub fn project(&self, key: ProjectKey) -> Option<&Project> {
+        self.projects.get(&key)
+    }
+
+    pub fn create_project(&mut self, draft: ProjectDraft) -> Result<&Project, ProjectError> {
+        if self.projects.contains_key(&draft.key) {
+            return Err(ProjectError::Exists(draft.key));
+        }
+        let project = Project::new(draft.key, draft.settings)?;
+        self.record(Change::Project(project.clone()));
+        Ok(self.projects.entry(draft.key).or_insert(project))
+    }
+
+    pub fn update_project(
+        &mut self,
+        key: ProjectKey,
+        patch: ProjectPatch,
+    ) -> Result<&Project, ProjectError> {
+        let project = self
+            .projects
+            .get_mut(&key)
+            .ok_or(ProjectError::NotFound(key))?;
+        project.update(patch)?;
+        let project = project.clone();
+        self.record(Change::Project(project));
+        Ok(&self.projects[&key])
+    }
+
+    /// Inserts a project as is, e.g. from a backup, replacing any project
+    /// with the same key but keeping the higher of their counters.
+    pub fn restore_project(&mut self, project: Project) {
+        let next_number = match self.projects.get(&project.key) {
+            Some(existing) => existing.next_number.max(project.next_number),
+            None => project.next_number,
+        };
+        let project = Project {
+            next_number,
+            ..project
+        };
+        self.record(Change::Project(project.clone()));
+        self.projects.insert(project.key, project);
+    }
+
+    /// The tickets of `project`, ordered by id.
+    pub fn tickets_in(&self, project: ProjectKey) -> Vec<Arc<RwLock<Ticket>>> {
+        // Number 0 is the lowest serial.
+        self.tickets
+            .range(TicketId::new(project, 0)..)
+            .take_while(|(id, _)| id.project == project)
+            .map(|(_, ticket)| ticket.clone())
+            .collect()
+    }
+
+    /// The tickets matching `query`, ordered by id.
+    pub fn matching(&self, query: &Query) -> Vec<Arc<RwLock<Ticket>>> {
+        self.tickets
+            .values()
+            .filter(|ticket| query.matches(&ticket.read().unwrap()))
+            .cloned()
+            .collect()
+    }
+
+    /// Ordered by name.
+    pub fn templates(&self) -> impl Iterator<Item = &Template> {
+        self.templates.values()
+    }
+
+    pub fn template(&self, name: &str) -> Option<&Template> {
+        self.templates.get(name)
+    }
+
+    pub fn add_template(&mut self, template: Template) -> Result<&Template, TemplateError> {
+        template.check()?;
+        if self.templates.contains_key(&template.name) {
+            return Err(TemplateError::Exists(template.name));
+        }
+        self.changed(Section::Templates);
+        Ok(self
+            .templates
+            .entry(template.name.clone())
+            .or_insert(template))
+    }
+
+    pub fn remove_template(&mut self, name: &str) -> Option<Template> {
+        let template = self.templates.remove(name)?;
+        self.changed(Section::Templates);
+        Some(template)
+    }
+
+    /// Ordered by name.
+    pub fn saved_queries(&self) -> impl Iterator<Item = &SavedQuery> {
+        self.queries.values()
+    }
+
+    pub fn saved_query(&self, name: &str) -> Option<&SavedQuery> {
+        self.queries.get(name)
+    }
+
+    pub fn save_query(&mut self, query: SavedQuery) -> Result<&SavedQuery, SavedQueryError> {
+        query.parse()?;
+        if self.queries.contains_key(&query.name) {
+            return Err(SavedQueryError::Exists(query.name));
+        }
+        self.changed(Section::Queries);
+        Ok(self.queries.entry(query.name.clone()).or_insert(query))
+    }
+
+    pub fn remove_query(&mut self, name: &str) -> Option<SavedQuery> {
+        let query = self.queries.remove(name)?;
+        self.changed(Section::Queries);
+        Some(query)
+    }
+
+    /// Runs the saved query `name`, like `matching`.
+    pub fn run_query(&self, name: &str) -> Result<Vec<Arc<RwLock<Ticket>>>, SavedQueryError> {
+        let query = self
+            .queries
+            .get(name)
+            .ok_or_else(|| SavedQueryError::NotFound(name.to_string()))?
+            .parse()?;
+        Ok(self.matching(&query))
+    }
+
+    /// Creates a ticket by `reporter` in `project` for every ticket of template
+    /// `name`, with its placeholders replaced by `values`. If any of them is
+    /// invalid, under the built-in rules or `policy`, none are created.
+    /// Returns their ids, in the template's order.
+    pub fn create_from_template(
+        &mut self,
+        name: &str,
+        project: ProjectKey,
+        values: &BTreeMap<String, String>,
+        reporter: &User,
+        policy: &Policy,
+    ) -> Result<Vec<TicketId>, TemplateError> {
+        let template = self
+            .templates
+            .get(name)
+            .ok_or_else(|| TemplateError::NotFound(name.to_string()))?;
+        let drafts = template.instantiate(values, policy)?;
+        let project = self
+            .projects
+            .get(&project)
+            .ok_or(ProjectError::NotFound(project))?;
+        if project.settings.archived {
+            return Err(ProjectError::Archived(project.key).into());
+        }
+        let project = project.key;
+        let ids = drafts
+            .into_iter()
+            .map(|draft| {
+                self.insert(project, draft, Some(reporter))
+                    .expect("Checked that the project exists and isn't archived")
+            })
+            .collect();
+        Ok(ids)
     }
 
     pub fn get(&self, id: TicketId) -> Option<Arc<RwLock<Ticket>>> {
         self.tickets.get(&id).cloned()
     }
+
+    /// Moves a ticket to the trash, keeping its links, comments and
+    /// attachments for when it's restored. See `trash`.
+    pub fn delete(&mut self, id: TicketId, by: &User) -> Option<&Trashed> {
+        let before = self.tickets.get(&id)?.read().unwrap().clone();
+        let operation = Operation {
+            id,
+            before: Some(before),
+            after: None,
+        };
+        self.remember(by, operation);
+        self.move_to_trash(id, by)
+    }
+
+    fn move_to_trash(&mut self, id: TicketId, by: &User) -> Option<&Trashed> {
+        let ticket = self.tickets.remove(&id)?;
+        let trashed = Trashed {
+            ticket: ticket.read().unwrap().clone(),
+            deleted_by: by.name.clone(),
+            deleted_at: Utc::now(),
+        };
+        self.record(Change::TicketTrashed(trashed.clone()));
+        Some(self.trash.entry(id).or_insert(trashed))
+    }
+
+    /// The deleted tickets, ordered by id.
+    pub fn trash(&self) -> impl Iterator<Item = &Trashed> {
+        self.trash.values()
+    }
+
+    pub fn trashed(&self, id: TicketId) -> Option<&Trashed> {
+        self.trash.get(&id)
+    }
+
+    /// Moves a deleted ticket back out of the trash, as it was when it was deleted.
+    pub fn undelete(&mut self, id: TicketId) -> Option<Arc<RwLock<Ticket>>> {
+        let trashed = self.trash.remove(&id)?;
+        self.record(Change::Ticket(trashed.ticket.clone()));
+        let ticket = Arc::new(RwLock::new(trashed.ticket));
+        self.tickets.insert(id, ticket.clone());
+        Some(ticket)
+    }
+
+    /// Inserts a deleted ticket as is, e.g. from a backup, like `restore`.
+    /// A live ticket with the same id is moved to the trash in its place.
+    pub fn restore_trashed(&mut self, trashed: Trashed) {
+        let id = trashed.ticket.id;
+        self.reserve(id);
+        self.tickets.remove(&id);
+        self.record(Change::TicketTrashed(trashed.clone()));
+        self.trash.insert(id, trashed);
+    }
+
+    /// Removes a deleted ticket for good, see `remove`.
+    pub fn purge(&mut self, id: TicketId) -> Option<Trashed> {
+        let trashed = self.trash.remove(&id)?;
+        self.forget(id);
+        Some(trashed)
+    }
+
+    /// Purges every ticket that has been in the trash for `retention` or
+    /// longer. Returns their ids.
+    pub fn purge_expired(&mut self, retention: Duration, now: DateTime<Utc>) -> Vec<TicketId> {
+        let expired: Vec<TicketId> = self
+            .trash
+            .values()
+            .filter(|trashed| trashed.expired(retention, now))
+            .map(|trashed| trashed.ticket.id)
+            .collect();
+        for id in &expired {
+            self.purge(*id);
+        }
+        expired
+    }
+
+    fn remember(&mut self, user: &User, operation: Operation) {
+        self.history
+            .entry(user.name.clone())
+            .or_default()
+            .push(operation);
+        self.changed(Section::History);
+    }
+
+    /// The operations `user` can undo and redo.
+    pub fn history(&self, user: &User) -> Option<&History> {
+        self.history.get(&user.name)
+    }
+
+    /// The history of every user who has one, keyed by user name.
+    pub fn histories(&self) -> impl Iterator<Item = (&str, &History)> {
+        self.history
+            .iter()
+            .filter(|(_, history)| !history.is_empty())
+            .map(|(user, history)| (user.as_str(), history))
+    }
+
+    /// Replaces the history of the user called `user`, e.g. with a snapshot's.
+    pub fn restore_history(&mut self, user: String, history: History) {
+        self.history.insert(user, history);
+        self.changed(Section::History);
+    }
+
+    /// Reverts the last `count` operations of `user` that haven't been undone
+    /// yet, most recent first, stopping at the first conflict.
+    pub fn undo(&mut self, user: &User, count: usize) -> Report {
+        self.replay(user, count, true)
+    }
+
+    /// Applies again the last `count` operations `user` undid, stopping at
+    /// the first conflict.
+    pub fn redo(&mut self, user: &User, count: usize) -> Report {
+        self.replay(user, count, false)
+    }
+
+    fn replay(&mut self, user: &User, count: usize, undo: bool) -> Report {
+        self.changed(Section::History);
+        let mut report = Report::default();
+        for _ in 0..count {
+            let history = self.history.entry(user.name.clone()).or_default();
+            let stack = if undo {
+                &mut history.undo
+            } else {
+                &mut history.redo
+            };
+            let Some(operation) = stack.pop() else {
+                break;
+            };
+            let id = operation.id;
+            let (from, to) = if undo {
+                (operation.after.clone(), operation.before.clone())
+            } else {
+                (operation.before.clone(), operation.after.clone())
+            };
+            if let Err(conflict) = self.revert(id, from, to, user) {
+                report.conflict = Some(conflict.to_string());
+                if conflict.keeps_operation() {
+                    let history = self.history.entry(user.name.clone()).or_default();
+                    if undo {
+                        history.undo.push(operation);
+                    } else {
+                        history.redo.push(operation);
+                    }
+                }
+                break;
+            }
+            let history = self.history.entry(user.name.clone()).or_default();
+            if undo {
+                history.redo.push(operation);
+            } else {
+                history.undo.push(operation);
+            }
+            report.tickets.push(id);
+        }
+        report
+    }
+
+    /// Changes ticket `id` from `from` to `to`, if it's still `from` and
+    /// `user` is allowed to. `user` is who moves it to the trash, if `to` is `None`.
+    fn revert(
+        &mut self,
+        id: TicketId,
+        from: Option<Ticket>,
+        to: Option<Ticket>,
+        user: &User,
+    ) -> Result<(), Conflict> {
+        let current = self
+            .tickets
+            .get(&id)
+            .map(|ticket| ticket.read().unwrap().clone());
+        match (&from, current) {
+            (Some(from), Some(current)) if *from == current => {}
+            (None, None) if self.trash.contains_key(&id) => {}
+            (_, Some(_)) => return Err(Conflict::Changed(id)),
+            (_, None) if self.trash.contains_key(&id) => return Err(Conflict::Changed(id)),
+            (_, None) => return Err(Conflict::Purged(id)),
+        }
+        // Checked like the endpoints making the same changes.
+        let allowed = match (&from, &to) {
+            (Some(from), Some(to)) => {
+                let edits_content =
+                    from.title != to.title || from.description != to.description;
+                (!edits_content || user.can(Action::Edit(from)))
+                    && (from.status == to.status || user.can(Action::ChangeStatus))
+            }
+            // Deleting or restoring it.
+            _ => user.can(Action::Delete),
+        };
+        if !allowed {
+            return Err(Conflict::Forbidden(id));
+        }
+        if let (Some(from), Some(to)) = (&from, &to) {
+            if from.status != to.status {
+                self.check_status(id, to.status)
+                    .map_err(|Blocked(blockers)| Conflict::Blocked { id, blockers })?;
+            }
+        }
+
+        let Some(to) = to else {
+            self.move_to_trash(id, user);
+            return Ok(());
+        };
+        let ticket = match self.tickets.get(&id) {
+            Some(ticket) => ticket.clone(),
+            None => self.undelete(id).expect("Checked that it's in the trash"),
+        };
+        let mut guard = ticket.write().unwrap();
+        let changed = *guard != to;
+        let status = (guard.status != to.status).then_some(to.status);
+        *guard = to;
+        drop(guard);
+        if changed {
+            self.touch(id);
+        }
+        if let Some(status) = status {
+            self.status_changed(id, status);
+        }
+        Ok(())
+    }
+
+    /// Removes a ticket for good, without going through the trash, along with
+    /// every link from or to it (see `Links::remove_ticket`), its comments
+    /// and its attachments' metadata. Attachment contents stay in their
+    /// directory, as other tickets may have the same file attached.
+    /// Its id isn't given to another ticket.
+    pub fn remove(&mut self, id: TicketId) -> Option<Arc<RwLock<Ticket>>> {
+        let ticket = self.tickets.remove(&id)?;
+        self.forget(id);
+        Some(ticket)
+    }
+
+    fn forget(&mut self, id: TicketId) {
+        self.record(Change::TicketRemoved(id));
+        self.links.remove_ticket(id);
+        self.comments.remove_ticket(id);
+        self.attachments.retain(|(ticket, _), _| *ticket != id);
+        self.status_changes.remove(&id);
+        for section in [
+            Section::Links,
+            Section::Comments,
+            Section::Attachments,
+            Section::StatusChanges,
+        ] {
+            self.changed(section);
+        }
+    }
+
+    pub fn comments(&self) -> &Comments {
+        &self.comments
+    }
+
+    /// For editing and removing comments. Use `add_comment` to add them.
+    pub fn comments_mut(&mut self) -> &mut Comments {
+        self.changed(Section::Comments);
+        &mut self.comments
+    }
+
+    /// Adds a comment by `author` to `ticket`'s thread,
+    /// or returns `None` if there is no such ticket.
+    pub fn add_comment(
+        &mut self,
+        ticket: TicketId,
+        author: &User,
+        body: MarkdownDescription,
+    ) -> Option<CommentId> {
+        self.tickets.get(&ticket)?;
+        self.changed(Section::Comments);
+        Some(self.comments.add(ticket, &author.name, body))
+    }
+
+    /// The files attached to `ticket`, ordered by digest.
+    pub fn attachments(&self, ticket: TicketId) -> Vec<Attachment> {
+        self.attachments
+            .range((ticket, String::new())..)
+            .take_while(|((id, _), _)| *id == ticket)
+            .map(|(_, attachment)| attachment.clone())
+            .collect()
+    }
+
+    /// Attachments of deleted tickets are hidden until the ticket is restored.
+    pub fn attachment(&self, ticket: TicketId, digest: &str) -> Option<&Attachment> {
+        self.tickets.get(&ticket)?;
+        self.attachments.get(&(ticket, digest.to_string()))
+    }
+
+    /// Records an attachment, replacing the one with the same contents on the
+    /// same ticket, if any. Returns `false` if there is no such ticket.
+    pub fn attach(&mut self, attachment: Attachment) -> bool {
+        if !self.tickets.contains_key(&attachment.ticket) {
+            return false;
+        }
+        let key = (attachment.ticket, attachment.digest.clone());
+        self.attachments.insert(key, attachment);
+        self.changed(Section::Attachments);
+        true
+    }
+
+    /// Every attachment of every ticket.
+    pub fn all_attachments(&self) -> impl Iterator<Item = &Attachment> {
+        self.attachments.values()
+    }
+
+    pub fn links(&self) -> &Links {
+        &self.links
+    }
+
+    /// Adds a link between two tickets of the store.
+    /// Returns `false` if the link was already there.
+    pub fn link(&mut self, link: Link) -> Result<bool, LinkError> {
+        for id in [link.from, link.to] {
+            if !self.tickets.contains_key(&id) {
+                return Err(LinkError::NotFound(id));
+            }
+        }
+        let added = self.links.add(link)?;
+        self.changed(Section::Links);
+        Ok(added)
+    }
+
+    /// Returns whether the link was there.
+    pub fn unlink(&mut self, link: &Link) -> bool {
+        self.changed(Section::Links);
+        self.links.remove(link)
+    }
+
+    /// The tickets directly blocking `id` that aren't done yet.
+    /// A ticket can't be moved to `Done` while there are any.
+    pub fn open_blockers(&self, id: TicketId) -> Vec<TicketId> {
+        match self.check_status(id, Status::Done) {
+            Ok(()) => vec![],
+            Err(Blocked(blockers)) => blockers,
+        }
+    }
+
+    /// Checks that ticket `id` can be moved to `status`, see `Links::check_status`.
+    /// Every change of status has to go through it, e.g. patches, undo and imports.
+    /// Call it before locking the ticket, as it reads the blockers' statuses.
+    pub fn check_status(&self, id: TicketId, status: Status) -> Result<(), Blocked> {
+        self.links.check_status(id, status, |blocker| {
+            Some(self.tickets.get(&blocker)?.read().unwrap().status)
+        })
+    }
+
+    /// Reads a store written by `save`.
+    pub fn load(path: &Path) -> Result<Self, anyhow::Error> {
+        let contents = fs::read(path)
+            .with_context(|| format!("Failed to read the store at {}", path.display()))?;
+        let file: StoreFile = serde_json::from_slice(&contents)
+            .with_context(|| format!("{} is not a valid store", path.display()))?;
+
+        let mut store = Self::new();
+        // Stores written before projects existed only count the default project's tickets.
+        store.reserve_ids(ProjectKey::DEFAULT, file.next_id);
+        for project in file.projects {
+            store.restore_project(project);
+        }
+        for ticket in file.tickets {
+            store.restore(ticket);
+        }
+        // Deleted tickets are live until their attachments are back.
+        for trashed in &file.trash {
+            store.restore(trashed.ticket.clone());
+        }
+        store.links = file.links.into_iter().collect();
+        for comment in file.comments {
+            store.comments.restore(comment);
+        }
+        store.comments.reserve_ids(CommentId(file.next_comment_id));
+        for attachment in file.attachments {
+            store.attach(attachment);
+        }
+        for trashed in file.trash {
+            store.restore_trashed(trashed);
+        }
+        store.history = file.history;
+        for template in file.templates {
+            store.templates.insert(template.name.clone(), template);
+        }
+        for query in file.queries {
+            store.queries.insert(query.name.clone(), query);
+        }
+        store.status_changes = file.status_changes;
+        Ok(store)
+    }
+
+    /// Writes every ticket to `path` as JSON. The file is replaced in one go,
+    /// so readers never see a half-written store.
+    pub fn save(&self, path: &Path) -> Result<(), anyhow::Error> {
+        let file = StoreFile {
+            next_id: 0,
+            projects: self.projects.values().cloned().collect(),
+            tickets: self
+                .tickets
+                .values()
+                .map(|ticket| ticket.read().unwrap().clone())
+                .collect(),
+            trash: self.trash.values().cloned().collect(),
+            links: self.links.iter().copied().collect(),
+            next_comment_id: self.comments.next_id().0,
+            comments: self.comments.iter().cloned().collect(),
+            attachments: self.attachments.values().cloned().collect(),
+            history: self
+                .history
+                .iter()
+                .filter(|(_, history)| !history.is_empty())
+                .map(|(user, history)| (user.clone(), history.clone()))
+                .collect(),
+            templates: self.templates.values().cloned().collect(),
+            queries: self.queries.values().cloned().collect(),
+            status_changes: self.status_changes.clone(),
+        };
+
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let mut temp = tempfile::NamedTempFile::new_in(dir)?;
+        serde_json::to_writer_pretty(&mut temp, &file)?;
+        temp.write_all(b"\n")?;
+        temp.persist(path)
+            .with_context(|| format!("Failed to write the store to {}", path.display()))?;
+        Ok(())
+    }
+}
+
+/// The JSON form of `Section::Comments`.
+#[derive(Serialize, Deserialize)]
+struct CommentsSection {
+    next_comment_id: u64,
+    comments: Vec<Comment>,
+}
+
+/// The on-disk form of a `TicketStore`.
+#[derive(Serialize, Deserialize)]
+struct StoreFile {
+    /// Only in stores written before projects existed.
+    #[serde(default, skip_serializing)]
+    next_id: u64,
+    #[serde(default)]
+    projects: Vec<Project>,
+    tickets: Vec<Ticket>,
+    /// Missing from stores written before tickets could be deleted.
+    #[serde(default)]
+    trash: Vec<Trashed>,
+    /// Missing from stores written before tickets could be linked.
+    #[serde(default)]
+    links: Vec<Link>,
+    // These are missing from stores written before tickets had comments and attachments.
+    #[serde(default)]
+    next_comment_id: u64,
+    #[serde(default)]
+    comments: Vec<Comment>,
+    #[serde(default)]
+    attachments: Vec<Attachment>,
+    /// Keyed by user name, and only written if someone has a history.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    history: BTreeMap<String, History>,
+    /// Missing from stores written before templates existed.
+    #[serde(default)]
+    templates: Vec<Template>,
+    /// Missing from stores written before queries could be saved.
+    #[serde(default)]
+    queries: Vec<SavedQuery>,
+    /// Keyed by ticket, and missing from stores written before they were recorded.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    status_changes: BTreeMap<TicketId, Vec<StatusChange>>,
 }
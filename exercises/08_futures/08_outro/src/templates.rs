@@ -0,0 +1,215 @@
+//! Templates for sets of tickets created together, e.g. the checklist of
+//! every release.
+//!
+//! Titles and descriptions can hold placeholders like `{{version}}`, which
+//! are replaced with values given when the template is used. Tickets are only
+//! validated after that, since a title can fit within the limits with one
+//! value and not with another.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use ticket_fields::{
+    MarkdownDescription, Policy, TicketDescriptionError, TicketTitle, TicketTitleError, Violations,
+};
+
+use crate::data::TicketDraft;
+use crate::projects::{ProjectError, ProjectKey};
+
+/// One ticket of a template, before placeholders are replaced.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TicketTemplate {
+    pub title: String,
+    pub description: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Template {
+    /// 1 to 50 letters, digits, `-` and `_`, e.g. `release`.
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Created in this order.
+    pub tickets: Vec<TicketTemplate>,
+}
+
+impl Template {
+    pub const MAX_NAME_LEN: usize = 50;
+    pub const MAX_TICKETS: usize = 100;
+
+    /// Checks the name, the number of tickets and that every placeholder is well-formed.
+    pub fn check(&self) -> Result<(), TemplateError> {
+        let valid_name = (1..=Template::MAX_NAME_LEN).contains(&self.name.len())
+            && self
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid_name {
+            return Err(TemplateError::InvalidName);
+        }
+        if self.tickets.is_empty() || self.tickets.len() > Template::MAX_TICKETS {
+            return Err(TemplateError::TicketCount);
+        }
+        for (index, ticket) in self.tickets.iter().enumerate() {
+            for text in [&ticket.title, &ticket.description] {
+                parse(text).map_err(|problem| problem.at(index))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The names of the placeholders, each once.
+    pub fn placeholders(&self) -> BTreeSet<&str> {
+        self.tickets
+            .iter()
+            .flat_map(|ticket| [&ticket.title, &ticket.description])
+            .filter_map(|text| parse(text).ok())
+            .flatten()
+            .filter_map(|part| match part {
+                Part::Placeholder(name) => Some(name),
+                Part::Text(_) => None,
+            })
+            .collect()
+    }
+
+    /// Replaces every placeholder with its value and validates the tickets,
+    /// `policy` included. Every placeholder needs a value, and every value a
+    /// placeholder.
+    pub fn instantiate(
+        &self,
+        values: &BTreeMap<String, String>,
+        policy: &Policy,
+    ) -> Result<Vec<TicketDraft>, TemplateError> {
+        self.check()?;
+        let placeholders = self.placeholders();
+        if let Some(name) = placeholders
+            .iter()
+            .find(|name| !values.contains_key(**name))
+        {
+            return Err(TemplateError::MissingValue(name.to_string()));
+        }
+        if let Some(name) = values
+            .keys()
+            .find(|name| !placeholders.contains(name.as_str()))
+        {
+            return Err(TemplateError::UnknownValue(name.clone()));
+        }
+
+        let mut drafts = Vec::with_capacity(self.tickets.len());
+        for (index, ticket) in self.tickets.iter().enumerate() {
+            let title = substitute(&ticket.title, values);
+            let title = TicketTitle::validate_with(&title, policy)
+                .map_err(Violations::first)
+                .map_err(|error| TemplateError::InvalidTitle { index, error })?;
+            let description = substitute(&ticket.description, values);
+            let description = MarkdownDescription::validate_with(&description, policy)
+                .map_err(Violations::first)
+                .map_err(|error| TemplateError::InvalidDescription { index, error })?;
+            drafts.push(TicketDraft { title, description });
+        }
+        Ok(drafts)
+    }
+}
+
+/// The body of `POST /templates/{name}/tickets`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TemplateRequest {
+    /// The project to create the tickets in, the default one if missing.
+    #[serde(default)]
+    pub project: Option<ProjectKey>,
+    /// The value of every placeholder, by name.
+    #[serde(default)]
+    pub values: BTreeMap<String, String>,
+}
+
+/// Why a template couldn't be added or used.
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum TemplateError {
+    #[error("No template named {0}")]
+    NotFound(String),
+    #[error("There is already a template named {0}")]
+    Exists(String),
+    #[error(
+        "Template names must be 1 to {} letters, digits, `-` and `_`",
+        Template::MAX_NAME_LEN
+    )]
+    InvalidName,
+    #[error("Templates must have 1 to {} tickets", Template::MAX_TICKETS)]
+    TicketCount,
+    #[error("Ticket {index} of the template has a `{{{{` without a matching `}}}}`")]
+    Unclosed { index: usize },
+    #[error("Ticket {index} of the template has a placeholder with an invalid name, `{name}`")]
+    InvalidPlaceholder { index: usize, name: String },
+    #[error("No value for placeholder `{0}`")]
+    MissingValue(String),
+    #[error("The template has no placeholder `{0}`")]
+    UnknownValue(String),
+    #[error("Ticket {index} of the template: {error}")]
+    InvalidTitle {
+        index: usize,
+        error: TicketTitleError,
+    },
+    #[error("Ticket {index} of the template: {error}")]
+    InvalidDescription {
+        index: usize,
+        error: TicketDescriptionError,
+    },
+    #[error(transparent)]
+    Project(#[from] ProjectError),
+}
+
+enum Part<'a> {
+    Text(&'a str),
+    Placeholder(&'a str),
+}
+
+/// What's wrong with a placeholder, before knowing which ticket it's in.
+enum Problem {
+    Unclosed,
+    InvalidName(String),
+}
+
+impl Problem {
+    fn at(self, index: usize) -> TemplateError {
+        match self {
+            Problem::Unclosed => TemplateError::Unclosed { index },
+            Problem::InvalidName(name) => TemplateError::InvalidPlaceholder { index, name },
+        }
+    }
+}
+
+/// Splits `text` into text and placeholders. Names are trimmed, so `{{ version }}`
+/// is the same placeholder as `{{version}}`.
+fn parse(mut text: &str) -> Result<Vec<Part<'_>>, Problem> {
+    let mut parts = vec![];
+    while let Some(start) = text.find("{{") {
+        let end = text[start..].find("}}").ok_or(Problem::Unclosed)? + start;
+        let name = text[start + 2..end].trim();
+        let valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.');
+        if !valid {
+            return Err(Problem::InvalidName(name.to_string()));
+        }
+        parts.push(Part::Text(&text[..start]));
+        parts.push(Part::Placeholder(name));
+        text = &text[end + 2..];
+    }
+    parts.push(Part::Text(text));
+    Ok(parts)
+}
+
+/// `text` with its placeholders replaced. Values are inserted as they are,
+/// even if they look like placeholders themselves.
+fn substitute(text: &str, values: &BTreeMap<String, String>) -> String {
+    let Ok(parts) = parse(text) else {
+        return text.to_string();
+    };
+    parts
+        .into_iter()
+        .map(|part| match part {
+            Part::Text(text) => text,
+            Part::Placeholder(name) => values.get(name).map_or("", String::as_str),
+        })
+        .collect()
+}
@@ -0,0 +1,605 @@
+use anyhow::{anyhow, bail};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::io::{BufRead, Write};
+use std::path::Path;
+use std::str::FromStr;
+use ticket_fields::{MarkdownDescription, Policy, TicketTitle};
+
+use crate::data::{Status, Ticket};
+use crate::projects::{Project, ProjectKey, ProjectSettings};
+use crate::store::{TicketId, TicketStore};
+
+/// The formats tickets can be imported from and exported to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// One JSON ticket per line.
+    Jsonl,
+    /// A header row, then one ticket per row. See `CsvMapping`.
+    Csv,
+    /// A checklist with one section per status:
+    ///
+    /// ```text
+    /// ## ToDo
+    ///
+    /// - [ ] #TKT-3 The title
+    ///   The description, indented,
+    ///   over as many lines as needed.
+    ///   _Reported by alice_
+    /// ```
+    Markdown,
+}
+
+impl Format {
+    /// Guesses the format from a file extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "jsonl" | "ndjson" => Some(Format::Jsonl),
+            "csv" => Some(Format::Csv),
+            "md" | "markdown" => Some(Format::Markdown),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "jsonl" | "ndjson" => Ok(Format::Jsonl),
+            "csv" => Ok(Format::Csv),
+            "md" | "markdown" => Ok(Format::Markdown),
+            _ => Err(anyhow!("Unknown format `{}`", s)),
+        }
+    }
+}
+
+/// Which CSV column holds each ticket field. Only the title and description
+/// columns are required on import. Column names are matched ignoring case.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CsvMapping {
+    pub id: String,
+    pub title: String,
+    pub description: String,
+    pub status: String,
+    pub reporter: String,
+}
+
+impl Default for CsvMapping {
+    fn default() -> Self {
+        Self {
+            id: String::from("id"),
+            title: String::from("title"),
+            description: String::from("description"),
+            status: String::from("status"),
+            reporter: String::from("reporter"),
+        }
+    }
+}
+
+impl CsvMapping {
+    /// Applies an override like `title=Summary`: the title is in the `Summary` column.
+    pub fn set(&mut self, mapping: &str) -> Result<(), anyhow::Error> {
+        let (field, column) = mapping
+            .split_once('=')
+            .ok_or_else(|| anyhow!("Expected `field=column`, got `{}`", mapping))?;
+        let slot = match field.trim() {
+            "id" => &mut self.id,
+            "title" => &mut self.title,
+            "description" => &mut self.description,
+            "status" => &mut self.status,
+            "reporter" => &mut self.reporter,
+            field => bail!("Unknown ticket field `{}`", field),
+        };
+        *slot = column.trim().to_string();
+        Ok(())
+    }
+
+    fn header(&self) -> [&str; 5] {
+        [
+            &self.id,
+            &self.title,
+            &self.description,
+            &self.status,
+            &self.reporter,
+        ]
+    }
+}
+
+/// A problem with one row of an import.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RowError {
+    /// 1-based line in the input where the row starts.
+    pub line: usize,
+    /// The ticket field at fault, or `None` if the row couldn't be read at all.
+    pub field: Option<&'static str>,
+    pub message: String,
+}
+
+impl fmt::Display for RowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.field {
+            Some(field) => write!(f, "line {}, {}: {}", self.line, field, self.message),
+            None => write!(f, "line {}: {}", self.line, self.message),
+        }
+    }
+}
+
+/// A row that passed validation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Row {
+    pub line: usize,
+    /// The id in the input, if it had one.
+    pub id: Option<TicketId>,
+    pub title: TicketTitle,
+    pub description: MarkdownDescription,
+    pub status: Status,
+    pub reporter: Option<String>,
+}
+
+/// Every row of an input, validated.
+#[derive(Clone, Debug, Default)]
+pub struct Parsed {
+    pub rows: Vec<Row>,
+    pub errors: Vec<RowError>,
+}
+
+/// Fields as read, before validation.
+#[derive(Default)]
+struct RawRow {
+    id: Option<String>,
+    title: Option<String>,
+    description: Option<String>,
+    status: Option<String>,
+    reporter: Option<String>,
+}
+
+impl Parsed {
+    fn push(&mut self, line: usize, raw: RawRow) {
+        let mut error = |field: &'static str, message: String| {
+            self.errors.push(RowError {
+                line,
+                field: Some(field),
+                message,
+            })
+        };
+
+        let id = match non_empty(raw.id) {
+            Some(id) => match id.parse::<TicketId>() {
+                Ok(id) => Some(id),
+                Err(_) => {
+                    error("id", format!("`{}` is not a ticket id", id));
+                    None
+                }
+            },
+            None => None,
+        };
+        let title = match raw.title {
+            Some(title) => TicketTitle::try_from(title).map_err(|e| e.to_string()),
+            None => Err(String::from("Missing")),
+        }
+        .map_err(|message| error("title", message))
+        .ok();
+        let description = match raw.description {
+            Some(description) => {
+                MarkdownDescription::try_from(description).map_err(|e| e.to_string())
+            }
+            None => Err(String::from("Missing")),
+        }
+        .map_err(|message| error("description", message))
+        .ok();
+        let status = match non_empty(raw.status) {
+            Some(status) => status
+                .parse::<Status>()
+                .map_err(|e| error("status", e.to_string()))
+                .ok(),
+            None => Some(Status::ToDo),
+        };
+
+        if let (Some(title), Some(description), Some(status)) = (title, description, status) {
+            self.rows.push(Row {
+                line,
+                id,
+                title,
+                description,
+                status,
+                reporter: non_empty(raw.reporter),
+            });
+        }
+    }
+
+    fn unreadable(&mut self, line: usize, message: impl fmt::Display) {
+        self.errors.push(RowError {
+            line,
+            field: None,
+            message: message.to_string(),
+        });
+    }
+}
+
+fn non_empty(value: Option<String>) -> Option<String> {
+    value
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+/// Reads and validates every row of `reader`, one at a time.
+/// Only problems that make the whole input unusable, like I/O errors or a
+/// CSV header without the mapped columns, are returned as errors.
+pub fn parse(
+    reader: impl BufRead,
+    format: Format,
+    mapping: &CsvMapping,
+) -> Result<Parsed, anyhow::Error> {
+    match format {
+        Format::Jsonl => parse_jsonl(reader),
+        Format::Csv => parse_csv(reader, mapping),
+        Format::Markdown => parse_markdown(reader),
+    }
+}
+
+fn parse_jsonl(reader: impl BufRead) -> Result<Parsed, anyhow::Error> {
+    let mut parsed = Parsed::default();
+    for (index, line) in reader.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let mut object: Map<String, Value> = match serde_json::from_str(&line) {
+            Ok(object) => object,
+            Err(e) => {
+                parsed.unreadable(line_number, e);
+                continue;
+            }
+        };
+        let mut field = |name: &str| match object.remove(name) {
+            Some(Value::String(value)) => Some(value),
+            Some(Value::Number(value)) => Some(value.to_string()),
+            _ => None,
+        };
+        let raw = RawRow {
+            id: field("id"),
+            title: field("title"),
+            description: field("description"),
+            status: field("status"),
+            reporter: field("reporter"),
+        };
+        parsed.push(line_number, raw);
+    }
+    Ok(parsed)
+}
+
+fn parse_csv(reader: impl BufRead, mapping: &CsvMapping) -> Result<Parsed, anyhow::Error> {
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(reader);
+    let headers = reader.headers()?.clone();
+    let column = |name: &str| {
+        headers
+            .iter()
+            .position(|header| header.trim().eq_ignore_ascii_case(name))
+    };
+    let required =
+        |name: &str| column(name).ok_or_else(|| anyhow!("The CSV header has no `{}` column", name));
+    let (title, description) = (required(&mapping.title)?, required(&mapping.description)?);
+    let (id, status, reporter) = (
+        column(&mapping.id),
+        column(&mapping.status),
+        column(&mapping.reporter),
+    );
+
+    let mut parsed = Parsed::default();
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                let line = e.position().map_or(0, |position| position.line() as usize);
+                parsed.unreadable(line, e);
+                continue;
+            }
+        };
+        let line = record
+            .position()
+            .map_or(0, |position| position.line() as usize);
+        let field = |index: Option<usize>| Some(record.get(index?)?.to_string());
+        let raw = RawRow {
+            id: field(id),
+            title: field(Some(title)),
+            description: field(Some(description)),
+            status: field(status),
+            reporter: field(reporter),
+        };
+        parsed.push(line, raw);
+    }
+    Ok(parsed)
+}
+
+fn parse_markdown(reader: impl BufRead) -> Result<Parsed, anyhow::Error> {
+    let mut parsed = Parsed::default();
+    let mut section: Option<Status> = None;
+    // The item being read, and the line it started on.
+    let mut item: Option<(usize, RawRow, Vec<String>)> = None;
+
+    let finish = |parsed: &mut Parsed, item: Option<(usize, RawRow, Vec<String>)>| {
+        if let Some((line, mut raw, mut description)) = item {
+            while description.last().is_some_and(|line| line.is_empty()) {
+                description.pop();
+            }
+            if !description.is_empty() {
+                raw.description = Some(description.join("\n"));
+            }
+            parsed.push(line, raw);
+        }
+    };
+
+    for (index, line) in reader.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line?;
+
+        if let Some(heading) = line.strip_prefix("## ") {
+            finish(&mut parsed, item.take());
+            section = heading.parse().ok();
+        } else if let Some((done, rest)) = checklist_item(&line) {
+            finish(&mut parsed, item.take());
+            let (id, title) = match rest.strip_prefix('#').and_then(|rest| rest.split_once(' ')) {
+                Some((id, title)) if id.parse::<TicketId>().is_ok() => {
+                    (Some(id.to_string()), title)
+                }
+                _ => (None, rest),
+            };
+            let status = if done { Some(Status::Done) } else { section };
+            let raw = RawRow {
+                id,
+                title: Some(title.trim().to_string()),
+                status: status.map(|status| status.to_string()),
+                ..RawRow::default()
+            };
+            item = Some((line_number, raw, vec![]));
+        } else if let Some((_, raw, description)) = &mut item {
+            if line.trim().is_empty() {
+                description.push(String::new());
+            } else if let Some(text) = line.strip_prefix("  ").or_else(|| line.strip_prefix('\t')) {
+                let text = text.trim_end();
+                match text
+                    .strip_prefix("_Reported by ")
+                    .and_then(|rest| rest.strip_suffix('_'))
+                {
+                    Some(reporter) => raw.reporter = Some(reporter.to_string()),
+                    None => description.push(text.to_string()),
+                }
+            } else {
+                finish(&mut parsed, item.take());
+            }
+        }
+    }
+    finish(&mut parsed, item.take());
+    Ok(parsed)
+}
+
+/// Splits `- [ ] rest` or `- [x] rest` into whether it's checked and the rest.
+fn checklist_item(line: &str) -> Option<(bool, &str)> {
+    let rest = line
+        .strip_prefix("- [")
+        .or_else(|| line.strip_prefix("* ["))?;
+    let (mark, rest) = rest.split_at_checked(1)?;
+    let rest = rest.strip_prefix("] ")?;
+    match mark {
+        " " => Some((false, rest)),
+        "x" | "X" => Some((true, rest)),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Ids {
+    /// Keep the ids from the input. Every row needs one, and none may be taken.
+    Preserve,
+    /// Give every ticket a new id, like newly created ones.
+    Remap,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ImportOptions {
+    pub ids: Ids,
+    /// Validate and report, but don't change the store.
+    pub dry_run: bool,
+}
+
+/// Where an imported row ended up.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Imported {
+    pub line: usize,
+    /// The id in the input, if it had one.
+    pub source: Option<TicketId>,
+    pub id: TicketId,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ImportReport {
+    /// Empty if there were any errors: imports are all or nothing.
+    pub imported: Vec<Imported>,
+    pub errors: Vec<RowError>,
+    pub dry_run: bool,
+}
+
+impl ImportReport {
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+/// Adds every row of `parsed` to `store`, or none of them if any row is
+/// invalid, `policy` included.
+pub fn import(
+    store: &mut TicketStore,
+    parsed: Parsed,
+    options: ImportOptions,
+    policy: &Policy,
+) -> ImportReport {
+    let Parsed { rows, mut errors } = parsed;
+
+    if options.ids == Ids::Preserve {
+        let mut seen = BTreeSet::new();
+        for row in &rows {
+            let message = match row.id {
+                None => "Missing, but needed to preserve ids",
+                Some(id) if store.get(id).is_some() => "Already taken in the store",
+                Some(id) if !seen.insert(id) => "Used by an earlier row",
+                Some(_) => continue,
+            };
+            errors.push(RowError {
+                line: row.line,
+                field: Some("id"),
+                message: message.to_string(),
+            });
+        }
+        errors.sort_by_key(|error| error.line);
+    }
+
+    let mut report = ImportReport {
+        imported: vec![],
+        errors,
+        dry_run: options.dry_run,
+    };
+    if !report.is_ok() {
+        return report;
+    }
+
+    // New ids stay in the project of the row's id, or the default project.
+    // They're generated on copies, so a dry run doesn't use any up.
+    let mut ids = store.id_generator().clone();
+    let mut projects = BTreeMap::new();
+    let mut tickets = vec![];
+    for row in rows {
+        let id = match (options.ids, row.id) {
+            (Ids::Preserve, Some(id)) => id,
+            _ => {
+                let key = row.id.map_or(ProjectKey::DEFAULT, |id| id.project);
+                let project = projects.entry(key).or_insert_with(|| {
+                    store.project(key).cloned().unwrap_or_else(|| Project {
+                        key,
+                        settings: ProjectSettings::new(key.as_str()),
+                        next_number: 0,
+                    })
+                });
+                TicketId::new(key, ids.generate(project))
+            }
+        };
+        let mut error = |field: &'static str, message: String| {
+            report.errors.push(RowError {
+                line: row.line,
+                field: Some(field),
+                message,
+            })
+        };
+        if let Err(violations) = TicketTitle::validate_with(&row.title.0, policy) {
+            error("title", violations.to_string());
+        }
+        if let Err(violations) =
+            MarkdownDescription::validate_with(row.description.as_str(), policy)
+        {
+            error("description", violations.to_string());
+        }
+        // Preserved ids may have links, e.g. if the ticket is in the trash.
+        if let Err(blocked) = store.check_status(id, row.status) {
+            error("status", blocked.to_string());
+        }
+        report.imported.push(Imported {
+            line: row.line,
+            source: row.id,
+            id,
+        });
+        tickets.push(Ticket {
+            id,
+            title: row.title,
+            description: row.description,
+            status: row.status,
+            reporter: row.reporter,
+            last_editor: None,
+        });
+    }
+    if !report.is_ok() {
+        report.imported.clear();
+        return report;
+    }
+    if !options.dry_run {
+        for ticket in tickets {
+            store.restore(ticket);
+        }
+    }
+    report
+}
+
+/// Writes `tickets` to `writer` as they come, except for Markdown, where
+/// they are grouped by status first.
+pub fn export(
+    tickets: impl IntoIterator<Item = Ticket>,
+    format: Format,
+    mapping: &CsvMapping,
+    mut writer: impl Write,
+) -> Result<(), anyhow::Error> {
+    match format {
+        Format::Jsonl => {
+            for ticket in tickets {
+                serde_json::to_writer(&mut writer, &ticket)?;
+                writeln!(writer)?;
+            }
+        }
+        Format::Csv => {
+            let mut writer = csv::Writer::from_writer(writer);
+            writer.write_record(mapping.header())?;
+            for ticket in tickets {
+                writer.write_record([
+                    ticket.id.to_string().as_str(),
+                    &ticket.title.0,
+                    ticket.description.as_str(),
+                    &ticket.status.to_string(),
+                    ticket.reporter.as_deref().unwrap_or_default(),
+                ])?;
+            }
+            writer.flush()?;
+        }
+        Format::Markdown => {
+            let mut sections: [Vec<Ticket>; 3] = Default::default();
+            for ticket in tickets {
+                let section = match ticket.status {
+                    Status::ToDo => 0,
+                    Status::InProgress => 1,
+                    Status::Done => 2,
+                };
+                sections[section].push(ticket);
+            }
+
+            writeln!(writer, "# Tickets")?;
+            for tickets in sections.iter().filter(|tickets| !tickets.is_empty()) {
+                writeln!(writer, "\n## {}\n", tickets[0].status)?;
+                for ticket in tickets {
+                    write_markdown_item(&mut writer, ticket)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_markdown_item(writer: &mut impl Write, ticket: &Ticket) -> std::io::Result<()> {
+    let mark = if ticket.status == Status::Done {
+        'x'
+    } else {
+        ' '
+    };
+    writeln!(writer, "- [{}] #{} {}", mark, ticket.id, ticket.title.0)?;
+    for line in ticket.description.as_str().lines() {
+        match line.trim_end() {
+            "" => writeln!(writer)?,
+            line => writeln!(writer, "  {}", line)?,
+        }
+    }
+    if let Some(reporter) = &ticket.reporter {
+        writeln!(writer, "  _Reported by {}_", reporter)?;
+    }
+    Ok(())
+}
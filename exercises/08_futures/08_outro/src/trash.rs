@@ -0,0 +1,26 @@
+//! Deleted tickets.
+//!
+//! Deleting a ticket moves it to the trash: it's hidden from everything but
+//! the trash listing, and can be restored with its links, comments and
+//! attachments until it's purged. Purging is permanent. Either way, its id is
+//! never given to another ticket.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::data::Ticket;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Trashed {
+    pub ticket: Ticket,
+    /// Name of the user who deleted the ticket.
+    pub deleted_by: String,
+    pub deleted_at: DateTime<Utc>,
+}
+
+impl Trashed {
+    /// Whether the ticket has been in the trash for `retention` or longer.
+    pub fn expired(&self, retention: Duration, now: DateTime<Utc>) -> bool {
+        self.deleted_at + retention <= now
+    }
+}
@@ -0,0 +1,252 @@
+#[cfg(test)]
+mod tests {
+    use outro_08::attachments::{sniff, AttachmentDir, AttachmentError};
+    use outro_08::auth::{Role, User};
+    use outro_08::client::{Client, ClientError};
+    use outro_08::data::TicketDraft;
+    use outro_08::limits::RateLimitConfig;
+    use outro_08::server::{self, Config};
+    use outro_08::snapshot::Snapshot;
+    use outro_08::store::TicketId;
+    use std::net::SocketAddr;
+    use tempfile::TempDir;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+
+    /// The directory holds attachments and has to outlive the server.
+    async fn start(max_attachment_bytes: u64) -> (SocketAddr, TempDir) {
+        let dir = TempDir::new().unwrap();
+        let config = Config {
+            addr: String::from("127.0.0.1:0"),
+            attachments_dir: dir.path().join("attachments"),
+            max_attachment_bytes,
+            // Uploads share the `POST /tickets` quota, which these tests would exceed.
+            rate_limits: RateLimitConfig {
+                routes: vec![],
+                ..RateLimitConfig::default()
+            },
+            ..Config::default()
+        };
+        let (listeners, state) = server::init(&config).await.unwrap();
+        let mut tokens = state.tokens.write().await;
+        tokens.insert("admin".into(), User::new("root", Role::Admin));
+        tokens.insert("viewer".into(), User::new("vic", Role::Viewer));
+        drop(tokens);
+        let addr = listeners.http_addr().unwrap();
+        tokio::spawn(server::serve(listeners, state));
+        (addr, dir)
+    }
+
+    /// Sends a raw request and returns the whole response.
+    async fn raw(addr: SocketAddr, request: &[u8]) -> String {
+        let mut socket = TcpStream::connect(addr).await.unwrap();
+        socket.write_all(request).await.unwrap();
+        let mut response = vec![];
+        socket.read_to_end(&mut response).await.unwrap();
+        String::from_utf8_lossy(&response).into_owned()
+    }
+
+    #[test]
+    fn test_sniff() {
+        assert_eq!(sniff(PNG), "image/png");
+        assert_eq!(sniff(b"\xff\xd8\xff\xe0"), "image/jpeg");
+        assert_eq!(sniff(b"GIF89a"), "image/gif");
+        assert_eq!(sniff(b"%PDF-1.7"), "application/pdf");
+        assert_eq!(sniff(b"RIFF\0\0\0\0WEBPVP8 "), "image/webp");
+        assert_eq!(sniff(b"Hello,\n\tworld"), "text/plain; charset=utf-8");
+        assert_eq!(sniff(b""), "text/plain; charset=utf-8");
+        // A character cut in half at the end is still text.
+        assert_eq!(sniff(&"é".as_bytes()[..1]), "text/plain; charset=utf-8");
+        assert_eq!(sniff(b"\0\x01\x02"), "application/octet-stream");
+        assert_eq!(sniff(b"\xc3\x28"), "application/octet-stream");
+    }
+
+    #[tokio::test]
+    async fn test_content_addressed_storage() {
+        let dir = TempDir::new().unwrap();
+        let attachments = AttachmentDir::new(dir.path(), 16);
+
+        let stored = attachments.write(&b"hello"[..], 5).await.unwrap();
+        assert_eq!(
+            stored.digest,
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+        assert_eq!(stored.size, 5);
+        assert_eq!(stored.mime, "text/plain; charset=utf-8");
+        let again = attachments.write(&b"hello"[..], 5).await.unwrap();
+        assert_eq!(again, stored);
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        let mut contents = vec![];
+        let mut file = attachments.open(&stored.digest).await.unwrap();
+        file.read_to_end(&mut contents).await.unwrap();
+        assert_eq!(contents, b"hello");
+
+        assert!(matches!(
+            attachments.write(&[0; 17][..], 17).await,
+            Err(AttachmentError::TooLarge { max: 16, size: 17 })
+        ));
+        assert!(matches!(
+            attachments.write(&b"short"[..], 10).await,
+            Err(AttachmentError::Truncated {
+                expected: 10,
+                found: 5
+            })
+        ));
+        // Neither left anything behind.
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+        assert!(attachments.open("../tickets.json").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_upload_and_download() {
+        let (addr, _dir) = start(AttachmentDir::DEFAULT_MAX_BYTES).await;
+        let admin = Client::new(addr.to_string(), "admin");
+        let viewer = Client::new(addr.to_string(), "viewer");
+        let id = admin
+            .create(&TicketDraft::new("A".into(), None))
+            .await
+            .unwrap();
+
+        // The name doesn't decide the type, the contents do.
+        let attachment = admin.upload(id, "screen shot.txt", PNG).await.unwrap();
+        assert_eq!(attachment.name, "screen shot.txt");
+        assert_eq!(attachment.mime, "image/png");
+        assert_eq!(attachment.size, PNG.len() as u64);
+        assert_eq!(attachment.uploaded_by, "root");
+        // The server closes the connection after an upload, so the client opens another.
+        assert_eq!(
+            viewer.attachments(id).await.unwrap(),
+            std::slice::from_ref(&attachment)
+        );
+        assert_eq!(
+            admin.attachments(id).await.unwrap(),
+            std::slice::from_ref(&attachment)
+        );
+
+        let contents = viewer.download(id, &attachment.digest).await.unwrap();
+        assert_eq!(contents, PNG);
+        let request = format!(
+            "GET /tickets/{}/attachments/{} HTTP/1.1\r\n\
+             Authorization: Bearer viewer\r\nConnection: close\r\n\r\n",
+            id, attachment.digest
+        );
+        let response = raw(addr, request.as_bytes()).await;
+        assert!(
+            response.contains("Content-Type: image/png\r\n"),
+            "{}",
+            response
+        );
+        assert!(response.contains("filename=\"screen shot.txt\""));
+        assert!(response.contains("X-Content-Type-Options: nosniff\r\n"));
+
+        assert!(matches!(
+            viewer.upload(id, "a.png", PNG).await,
+            Err(ClientError::Forbidden)
+        ));
+        assert!(matches!(
+            admin.upload(TicketId::from(9), "a.png", PNG).await,
+            Err(ClientError::NotFound)
+        ));
+        assert!(matches!(
+            viewer.download(id, &"0".repeat(64)).await,
+            Err(ClientError::NotFound)
+        ));
+        for name in ["", "../secrets", "a/b", ".."] {
+            assert!(matches!(
+                admin.upload(id, name, PNG).await,
+                Err(ClientError::BadRequest(_))
+            ));
+        }
+
+        // The same file on another ticket is stored once, but listed on both.
+        let other = admin
+            .create(&TicketDraft::new("B".into(), None))
+            .await
+            .unwrap();
+        let copy = admin.upload(other, "copy.png", PNG).await.unwrap();
+        assert_eq!(copy.digest, attachment.digest);
+        assert_eq!(admin.download(other, &copy.digest).await.unwrap(), PNG);
+
+        admin.delete(id).await.unwrap();
+        assert!(matches!(
+            viewer.download(id, &attachment.digest).await,
+            Err(ClientError::NotFound)
+        ));
+        assert_eq!(admin.download(other, &copy.digest).await.unwrap(), PNG);
+    }
+
+    #[tokio::test]
+    async fn test_size_limit() {
+        let (addr, _dir) = start(8).await;
+        let admin = Client::new(addr.to_string(), "admin");
+        let id = admin
+            .create(&TicketDraft::new("A".into(), None))
+            .await
+            .unwrap();
+
+        admin.upload(id, "small.txt", b"12345678").await.unwrap();
+        assert!(matches!(
+            admin.upload(id, "big.txt", b"123456789").await,
+            Err(ClientError::TooLarge(_))
+        ));
+
+        // Refused before the body is sent, as the server answers right away.
+        let request = format!(
+            "POST /tickets/{}/attachments?name=big.bin HTTP/1.1\r\n\
+             Authorization: Bearer admin\r\nContent-Length: 1000000\r\n\r\n",
+            id
+        );
+        let response = raw(addr, request.as_bytes()).await;
+        assert!(response.starts_with("HTTP/1.1 413"), "{}", response);
+        assert!(response.contains("Connection: close\r\n"));
+    }
+
+    #[tokio::test]
+    async fn test_truncated_upload() {
+        let (addr, _dir) = start(AttachmentDir::DEFAULT_MAX_BYTES).await;
+        let admin = Client::new(addr.to_string(), "admin");
+        let id = admin
+            .create(&TicketDraft::new("A".into(), None))
+            .await
+            .unwrap();
+
+        let mut socket = TcpStream::connect(addr).await.unwrap();
+        let request = format!(
+            "POST /tickets/{}/attachments?name=a.txt HTTP/1.1\r\n\
+             Authorization: Bearer admin\r\nContent-Length: 10\r\n\r\nshort",
+            id
+        );
+        socket.write_all(request.as_bytes()).await.unwrap();
+        socket.shutdown().await.unwrap();
+        let mut response = String::new();
+        socket.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 400"), "{}", response);
+        assert!(admin.attachments(id).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_attachments_survive_snapshots() {
+        let (addr, dir) = start(AttachmentDir::DEFAULT_MAX_BYTES).await;
+        let admin = Client::new(addr.to_string(), "admin");
+        let id = admin
+            .create(&TicketDraft::new("A".into(), None))
+            .await
+            .unwrap();
+        let attachment = admin.upload(id, "a.png", PNG).await.unwrap();
+
+        let mut store = outro_08::store::TicketStore::new();
+        store.add_ticket(TicketDraft::new("A".into(), None));
+        assert!(store.attach(attachment.clone()));
+        let snapshot = Snapshot::capture(&store);
+        let restored = Snapshot::decode(&snapshot.to_bytes()).unwrap().restore();
+        assert_eq!(restored.attachments(id), std::slice::from_ref(&attachment));
+
+        let path = dir.path().join("tickets.json");
+        restored.save(&path).unwrap();
+        let loaded = outro_08::store::TicketStore::load(&path).unwrap();
+        assert_eq!(loaded.attachment(id, &attachment.digest), Some(&attachment));
+    }
+}
@@ -0,0 +1,179 @@
+#[cfg(test)]
+mod tests {
+    use outro_08::auth::{IssuedToken, Role, TokenStore, User};
+    use outro_08::data::{Status, Ticket};
+    use outro_08::limits::{Quota, RateLimitConfig};
+    use outro_08::server::{self, Config};
+    use std::io::Write;
+    use std::net::SocketAddr;
+    use tempfile::TempPath;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    const TOKENS: &str = "\
+# token        name    role
+admin-token    root    admin
+maint-token    mia     maintainer
+alice-token    alice   reporter
+bob-token      bob     reporter
+viewer-token   vic     viewer
+";
+
+    /// The token file is deleted when the returned path is dropped.
+    async fn start() -> (SocketAddr, TempPath) {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(TOKENS.as_bytes()).unwrap();
+        let tokens_file = file.into_temp_path();
+
+        let config = Config {
+            addr: String::from("127.0.0.1:0"),
+            rate_limits: RateLimitConfig {
+                default: Quota::new(1000, 1000.0),
+                routes: vec![],
+                ..RateLimitConfig::default()
+            },
+            tokens_file: Some(tokens_file.to_path_buf()),
+            ..Config::default()
+        };
+        let (listeners, state) = server::init(&config).await.unwrap();
+        let addr = listeners.http_addr().unwrap();
+        tokio::spawn(server::serve(listeners, state));
+        (addr, tokens_file)
+    }
+
+    async fn send(
+        addr: SocketAddr,
+        method: &str,
+        path: &str,
+        token: Option<&str>,
+        body: &str,
+    ) -> String {
+        let auth = token
+            .map(|token| format!("Authorization: Bearer {}\r\n", token))
+            .unwrap_or_default();
+        let request = format!(
+            "{} {} HTTP/1.1\r\nConnection: close\r\n{}Content-Length: {}\r\n\r\n{}",
+            method,
+            path,
+            auth,
+            body.len(),
+            body
+        );
+
+        let mut socket = TcpStream::connect(addr).await.unwrap();
+        socket.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        socket.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    fn body(response: &str) -> &str {
+        response.split_once("\r\n\r\n").unwrap().1
+    }
+
+    const DRAFT: &str = r#"{"title":"A title","description":"A description"}"#;
+
+    #[test]
+    fn test_parse_token_file() {
+        let tokens = TokenStore::parse(TOKENS).unwrap();
+        assert_eq!(
+            tokens.authenticate("alice-token"),
+            Some(User::new("alice", Role::Reporter))
+        );
+        assert_eq!(tokens.authenticate("nope"), None);
+        assert_eq!(
+            tokens.authenticate_header(b"Bearer admin-token"),
+            Some(User::new("root", Role::Admin))
+        );
+        assert_eq!(tokens.authenticate_header(b"Basic admin-token"), None);
+
+        assert!(TokenStore::parse("token-without-user").is_err());
+        assert!(TokenStore::parse("token user superuser").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_unauthenticated_requests_are_rejected() {
+        let (addr, _tokens_file) = start().await;
+
+        let response = send(addr, "POST", "/tickets", None, DRAFT).await;
+        assert!(response.starts_with("HTTP/1.1 401 Unauthorized"));
+        assert!(response.contains("WWW-Authenticate: Bearer"));
+
+        let response = send(addr, "GET", "/tickets/0", Some("made-up"), "").await;
+        assert!(response.starts_with("HTTP/1.1 401 Unauthorized"));
+    }
+
+    #[tokio::test]
+    async fn test_reporter_and_last_editor_are_recorded() {
+        let (addr, _tokens_file) = start().await;
+
+        let response = send(addr, "POST", "/tickets", Some("alice-token"), DRAFT).await;
+        assert!(response.starts_with("HTTP/1.1 201 Created"));
+        let id = body(&response);
+
+        let patch = format!(r#"{{"id":{},"title":"A new title"}}"#, id);
+        let response = send(addr, "PATCH", "/tickets/0", Some("maint-token"), &patch).await;
+        assert!(response.starts_with("HTTP/1.1 204 No Content"));
+
+        let response = send(addr, "GET", "/tickets/0", Some("viewer-token"), "").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        let ticket: Ticket = serde_json::from_str(body(&response)).unwrap();
+        assert_eq!(ticket.reporter.as_deref(), Some("alice"));
+        assert_eq!(ticket.last_editor.as_deref(), Some("mia"));
+        assert_eq!(ticket.title.0, "A new title");
+    }
+
+    #[tokio::test]
+    async fn test_role_rules() {
+        let (addr, _tokens_file) = start().await;
+
+        // Viewers can't create tickets.
+        let response = send(addr, "POST", "/tickets", Some("viewer-token"), DRAFT).await;
+        assert!(response.starts_with("HTTP/1.1 403 Forbidden"));
+
+        send(addr, "POST", "/tickets", Some("alice-token"), DRAFT).await;
+
+        // Reporters can only edit their own tickets...
+        let patch = r#"{"id":0,"title":"Edited"}"#;
+        let response = send(addr, "PATCH", "/tickets/0", Some("bob-token"), patch).await;
+        assert!(response.starts_with("HTTP/1.1 403 Forbidden"));
+        let response = send(addr, "PATCH", "/tickets/0", Some("alice-token"), patch).await;
+        assert!(response.starts_with("HTTP/1.1 204 No Content"));
+
+        // ...and can't change their status.
+        let patch = r#"{"id":0,"status":"Done"}"#;
+        let response = send(addr, "PATCH", "/tickets/0", Some("alice-token"), patch).await;
+        assert!(response.starts_with("HTTP/1.1 403 Forbidden"));
+        let response = send(addr, "PATCH", "/tickets/0", Some("maint-token"), patch).await;
+        assert!(response.starts_with("HTTP/1.1 204 No Content"));
+
+        let response = send(addr, "GET", "/tickets/0", Some("viewer-token"), "").await;
+        let ticket: Ticket = serde_json::from_str(body(&response)).unwrap();
+        assert_eq!(ticket.status, Status::Done);
+
+        // Only admins can delete.
+        let response = send(addr, "DELETE", "/tickets/0", Some("maint-token"), "").await;
+        assert!(response.starts_with("HTTP/1.1 403 Forbidden"));
+        let response = send(addr, "DELETE", "/tickets/0", Some("admin-token"), "").await;
+        assert!(response.starts_with("HTTP/1.1 204 No Content"));
+        let response = send(addr, "GET", "/tickets/0", Some("viewer-token"), "").await;
+        assert!(response.starts_with("HTTP/1.1 404 Not Found"));
+    }
+
+    #[tokio::test]
+    async fn test_admin_issues_tokens() {
+        let (addr, _tokens_file) = start().await;
+
+        let request = r#"{"name":"carol","role":"Reporter"}"#;
+        let response = send(addr, "POST", "/admin/tokens", Some("maint-token"), request).await;
+        assert!(response.starts_with("HTTP/1.1 403 Forbidden"));
+
+        let response = send(addr, "POST", "/admin/tokens", Some("admin-token"), request).await;
+        assert!(response.starts_with("HTTP/1.1 201 Created"));
+        let issued: IssuedToken = serde_json::from_str(body(&response)).unwrap();
+        assert_eq!(issued.role, Role::Reporter);
+
+        let response = send(addr, "POST", "/tickets", Some(&issued.token), DRAFT).await;
+        assert!(response.starts_with("HTTP/1.1 201 Created"));
+    }
+}
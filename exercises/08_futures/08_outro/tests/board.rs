@@ -0,0 +1,197 @@
+#[cfg(test)]
+mod tests {
+    use outro_08::auth::{Role, User};
+    use outro_08::backend::Backend;
+    use outro_08::board::{Board, Field, Message};
+    use outro_08::client::Client;
+    use outro_08::data::{Status, TicketDraft};
+    use outro_08::server::{self, Config};
+    use outro_08::store::{TicketId, TicketStore};
+    use ratatui::backend::TestBackend;
+    use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use ratatui::Terminal;
+    use std::sync::Arc;
+    use tokio::sync::RwLock;
+
+    fn store(titles: &[&str]) -> Arc<RwLock<TicketStore>> {
+        let mut store = TicketStore::new();
+        for title in titles {
+            store.add_ticket(TicketDraft::new(title.to_string(), None));
+        }
+        Arc::new(RwLock::new(store))
+    }
+
+    async fn board(store: &Arc<RwLock<TicketStore>>, role: Role) -> Board {
+        let backend = Backend::shared(Arc::clone(store), User::new("mia", role));
+        Board::new(backend).await.unwrap()
+    }
+
+    async fn press(board: &mut Board, keys: &str) {
+        for c in keys.chars() {
+            board.handle_key(key(KeyCode::Char(c))).await;
+        }
+    }
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    async fn status(store: &Arc<RwLock<TicketStore>>, id: u64) -> Status {
+        let store = store.read().await;
+        let ticket = store.get(TicketId::from(id)).unwrap();
+        let status = ticket.read().unwrap().status;
+        status
+    }
+
+    fn render(board: &Board) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(90, 20)).unwrap();
+        terminal.draw(|frame| board.draw(frame)).unwrap();
+        let buffer = terminal.backend().buffer();
+        buffer
+            .content()
+            .chunks(buffer.area.width as usize)
+            .map(|row| row.iter().map(|cell| cell.symbol()).collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[tokio::test]
+    async fn test_navigate_and_move() {
+        let store = store(&["First", "Second"]);
+        let mut board = board(&store, Role::Admin).await;
+        assert_eq!(board.column(0).len(), 2);
+
+        press(&mut board, "j>").await;
+        assert_eq!(status(&store, 1).await, Status::InProgress);
+        // The selection follows the ticket.
+        assert_eq!(board.focused_column(), 1);
+        assert_eq!(board.selected().unwrap().id, TicketId::from(1));
+
+        press(&mut board, ">>").await;
+        assert_eq!(status(&store, 1).await, Status::Done);
+        assert_eq!(board.focused_column(), 2);
+
+        press(&mut board, "hh<").await;
+        assert_eq!(status(&store, 0).await, Status::ToDo);
+        assert_eq!(board.selected().unwrap().id, TicketId::from(0));
+    }
+
+    #[tokio::test]
+    async fn test_edit_with_validation() {
+        let store = store(&["Typo"]);
+        let mut board = board(&store, Role::Admin).await;
+
+        press(&mut board, "e").await;
+        for _ in 0.."Typo".len() {
+            board.handle_key(key(KeyCode::Backspace)).await;
+        }
+        let editor = board.editor().unwrap();
+        assert_eq!(editor.title().unwrap_err(), "The title cannot be empty");
+        assert!(render(&board).contains("The title cannot be empty"));
+
+        // Saving is refused until the title is valid.
+        board.handle_key(key(KeyCode::Enter)).await;
+        assert!(board.editor().is_some());
+        assert!(matches!(board.message(), Some(Message::Error(_))));
+
+        press(&mut board, "Fixed").await;
+        board.handle_key(key(KeyCode::Tab)).await;
+        assert_eq!(board.editor().unwrap().field, Field::Description);
+        press(&mut board, "!").await;
+        board.handle_key(key(KeyCode::Enter)).await;
+        assert!(board.editor().is_none());
+
+        let ticket = store.read().await.get(TicketId::from(0)).unwrap();
+        let ticket = ticket.read().unwrap().clone();
+        assert_eq!(ticket.title.0, "Fixed");
+        assert_eq!(ticket.description.as_str(), "Default description!");
+        assert_eq!(ticket.last_editor.as_deref(), Some("mia"));
+    }
+
+    #[tokio::test]
+    async fn test_filter() {
+        let store = store(&["Crash on start", "Add dark mode", "Crash on exit"]);
+        let mut board = board(&store, Role::Admin).await;
+
+        press(&mut board, "/CRASH").await;
+        board.handle_key(key(KeyCode::Enter)).await;
+        assert_eq!(board.filter(), "CRASH");
+        let titles: Vec<&str> = board
+            .column(0)
+            .iter()
+            .map(|ticket| ticket.title.0.as_str())
+            .collect();
+        assert_eq!(titles, ["Crash on start", "Crash on exit"]);
+
+        // Esc clears the filter first, then quits.
+        board.handle_key(key(KeyCode::Esc)).await;
+        assert_eq!(board.column(0).len(), 3);
+        assert!(!board.should_quit());
+        board.handle_key(key(KeyCode::Esc)).await;
+        assert!(board.should_quit());
+    }
+
+    #[tokio::test]
+    async fn test_render_columns() {
+        let store = store(&["First", "Second"]);
+        let mut board = board(&store, Role::Admin).await;
+        press(&mut board, ">").await;
+
+        let screen = render(&board);
+        assert!(screen.contains("ToDo (1)"));
+        assert!(screen.contains("InProgress (1)"));
+        assert!(screen.contains("Done (0)"));
+        assert!(screen.contains("TKT-0 First"));
+        assert!(screen.contains("TKT-1 Second"));
+    }
+
+    #[tokio::test]
+    async fn test_permission_errors_are_shown() {
+        let store = store(&["First"]);
+        let mut board = board(&store, Role::Reporter).await;
+
+        press(&mut board, ">").await;
+        assert!(matches!(board.message(), Some(Message::Error(e)) if e == "Not allowed"));
+        assert_eq!(status(&store, 0).await, Status::ToDo);
+        assert!(render(&board).contains("Not allowed"));
+    }
+
+    #[tokio::test]
+    async fn test_remote_board() {
+        let config = Config {
+            addr: String::from("127.0.0.1:0"),
+            ..Config::default()
+        };
+        let (listeners, state) = server::init(&config).await.unwrap();
+        state
+            .tokens
+            .write()
+            .await
+            .insert("maint".into(), User::new("mia", Role::Maintainer));
+        state
+            .tokens
+            .write()
+            .await
+            .insert("viewer".into(), User::new("vic", Role::Viewer));
+        state
+            .store
+            .write()
+            .await
+            .add_ticket(TicketDraft::new("Remote".into(), None));
+        let store = Arc::clone(&state.store);
+        let addr = listeners.http_addr().unwrap();
+        tokio::spawn(server::serve(listeners, state));
+
+        let client = Client::new(addr.to_string(), "maint");
+        let mut board = Board::new(Backend::remote(client)).await.unwrap();
+        press(&mut board, ">").await;
+        assert_eq!(status(&store, 0).await, Status::InProgress);
+
+        let client = Client::new(addr.to_string(), "viewer");
+        let mut board = Board::new(Backend::remote(client)).await.unwrap();
+        press(&mut board, "lex").await;
+        board.handle_key(key(KeyCode::Enter)).await;
+        assert!(matches!(board.message(), Some(Message::Error(e)) if e == "Not allowed"));
+        assert!(board.editor().is_some());
+    }
+}
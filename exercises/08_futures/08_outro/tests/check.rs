@@ -57,7 +57,7 @@ mod tests {
             let counter_clone = Arc::clone(&counter);
 
             let handle = tokio::spawn(async move {
-                let ticket_id = TicketId(0);
+                let ticket_id = TicketId::from(0);
 
                 {
                     let store_guard = store_clone.read().await;
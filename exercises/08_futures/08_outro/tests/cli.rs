@@ -0,0 +1,473 @@
+#[cfg(test)]
+mod tests {
+    use outro_08::auth::{Role, User};
+    use outro_08::data::{Status, Ticket};
+    use outro_08::reports::{Period, Summary};
+    use outro_08::server::{self, Config};
+    use std::net::SocketAddr;
+    use std::path::Path;
+    use std::process::Output;
+    use tempfile::TempDir;
+    use tokio::process::Command;
+
+    async fn run(store: &Path, args: &[&str]) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_tickets"))
+            .env_remove("TICKETS_SERVER")
+            .env_remove("TICKETS_TOKEN")
+            .arg("--store")
+            .arg(store)
+            .args(args)
+            .output()
+            .await
+            .unwrap()
+    }
+
+    /// Runs the command, checks it succeeded and returns its stdout.
+    async fn ok(store: &Path, args: &[&str]) -> String {
+        let output = run(store, args).await;
+        assert!(
+            output.status.success(),
+            "{:?} failed: {}",
+            args,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    async fn list(store: &Path, args: &[&str]) -> Vec<Ticket> {
+        let args = [&["list", "--format", "json"], args].concat();
+        serde_json::from_str(&ok(store, &args).await).unwrap()
+    }
+
+    async fn start() -> SocketAddr {
+        let config = Config {
+            addr: String::from("127.0.0.1:0"),
+            ..Config::default()
+        };
+        let (listeners, state) = server::init(&config).await.unwrap();
+        state
+            .tokens
+            .write()
+            .await
+            .insert("admin".into(), User::new("root", Role::Admin));
+        let addr = listeners.http_addr().unwrap();
+        tokio::spawn(server::serve(listeners, state));
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_local_store() {
+        let dir = TempDir::new().unwrap();
+        let store = dir.path().join("tickets.json");
+
+        let id = ok(
+            &store,
+            &["create", "--title", "First", "--description", "One"],
+        )
+        .await;
+        assert_eq!(id.trim(), "TKT-0");
+        ok(
+            &store,
+            &["create", "--title", "Second", "--description", "Two"],
+        )
+        .await;
+        assert!(store.exists());
+
+        ok(&store, &["move", "0", "in-progress"]).await;
+        ok(&store, &["edit", "1", "--title", "Renamed"]).await;
+
+        let tickets = list(&store, &[]).await;
+        assert_eq!(tickets.len(), 2);
+        assert_eq!(tickets[0].status, Status::InProgress);
+        assert_eq!(tickets[1].title.0, "Renamed");
+        assert_eq!(tickets[1].description.as_str(), "Two");
+
+        let in_progress = list(&store, &["--status", "InProgress"]).await;
+        assert_eq!(in_progress.len(), 1);
+
+        let table = ok(&store, &["list"]).await;
+        assert!(table.trim_start().starts_with("ID  STATUS"));
+        assert!(table.contains("Renamed"));
+
+        ok(&store, &["delete", "0"]).await;
+        let output = run(&store, &["show", "0"]).await;
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("No ticket with id TKT-0"));
+
+        // New ids keep counting up after a delete.
+        let id = ok(
+            &store,
+            &["create", "--title", "Third", "--description", "Three"],
+        )
+        .await;
+        assert_eq!(id.trim(), "TKT-2");
+    }
+
+    #[tokio::test]
+    async fn test_invalid_fields_are_rejected_before_connecting() {
+        let dir = TempDir::new().unwrap();
+        let store = dir.path().join("tickets.json");
+        let long_title = "a".repeat(51);
+
+        // Nothing listens on port 1, so reaching the network would fail differently.
+        let args = [
+            "--server",
+            "127.0.0.1:1",
+            "--token",
+            "admin",
+            "create",
+            "--title",
+            &long_title,
+            "--description",
+            "B",
+        ];
+        let output = run(&store, &args).await;
+        assert_eq!(output.status.code(), Some(2));
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("The title cannot be longer than 50 characters"));
+
+        let output = run(&store, &["move", "0", "someday"]).await;
+        assert_eq!(output.status.code(), Some(2));
+        assert!(!store.exists());
+    }
+
+    #[tokio::test]
+    async fn test_remote_server() {
+        let addr = start().await.to_string();
+        let dir = TempDir::new().unwrap();
+        let unused = dir.path().join("unused.json");
+        let remote = ["--server", &addr, "--token", "admin"];
+
+        let create = [
+            &remote[..],
+            &["create", "--title", "A", "--description", "B"],
+        ]
+        .concat();
+        assert_eq!(ok(&unused, &create).await.trim(), "TKT-0");
+        let move_ = [&remote[..], &["move", "0", "done"]].concat();
+        ok(&unused, &move_).await;
+
+        let tickets = list(&unused, &remote).await;
+        assert_eq!(tickets[0].status, Status::Done);
+        assert_eq!(tickets[0].reporter.as_deref(), Some("root"));
+        assert!(!unused.exists());
+
+        let wrong_token = ["--server", &addr, "--token", "nope", "list"];
+        let output = run(&unused, &wrong_token).await;
+        assert!(String::from_utf8_lossy(&output.stderr).contains("Missing or invalid bearer token"));
+    }
+
+    #[tokio::test]
+    async fn test_export_then_import() {
+        let dir = TempDir::new().unwrap();
+        let source = dir.path().join("source.json");
+        ok(
+            &source,
+            &[
+                "create",
+                "--title",
+                "A, with comma",
+                "--description",
+                "Quoted \"B\"",
+            ],
+        )
+        .await;
+        ok(&source, &["create", "--title", "C", "--description", "D"]).await;
+        ok(&source, &["move", "1", "done"]).await;
+
+        let formats = [
+            ("json", "export.json"),
+            ("jsonl", "export.jsonl"),
+            ("csv", "export.csv"),
+            ("markdown", "export.md"),
+        ];
+        for (format, file) in formats {
+            let export = dir.path().join(file);
+            let export = export.to_str().unwrap();
+            ok(&source, &["export", "--format", format, "--output", export]).await;
+
+            let target = dir.path().join(format!("target-{}.json", format));
+            let ids = ok(&target, &["import", "--dry-run", export]).await;
+            assert_eq!(ids, "TKT-0\nTKT-1\n");
+            assert!(!target.exists());
+            let ids = ok(&target, &["import", export]).await;
+            assert_eq!(ids, "TKT-0\nTKT-1\n");
+
+            let imported = list(&target, &[]).await;
+            let original = list(&source, &[]).await;
+            assert_eq!(imported.len(), original.len());
+            for (imported, original) in imported.iter().zip(&original) {
+                assert_eq!(imported.title, original.title);
+                assert_eq!(imported.description, original.description);
+                assert_eq!(imported.status, original.status);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_and_restore() {
+        let dir = TempDir::new().unwrap();
+        let store = dir.path().join("tickets.json");
+        let snapshot = dir.path().join("tickets.snapshot");
+        let snapshot = snapshot.to_str().unwrap();
+        ok(&store, &["create", "--title", "Kept", "--description", "A"]).await;
+        ok(&store, &["snapshot", snapshot]).await;
+
+        ok(&store, &["delete", "0"]).await;
+        ok(&store, &["create", "--title", "Lost", "--description", "B"]).await;
+        ok(&store, &["restore", snapshot]).await;
+
+        let tickets = list(&store, &[]).await;
+        assert_eq!(tickets.len(), 1);
+        assert_eq!(tickets[0].title.0, "Kept");
+
+        std::fs::write(dir.path().join("garbage"), "garbage").unwrap();
+        let garbage = dir.path().join("garbage");
+        let output = run(&store, &["restore", garbage.to_str().unwrap()]).await;
+        assert!(String::from_utf8_lossy(&output.stderr).contains("Not a ticket store snapshot"));
+    }
+
+    #[tokio::test]
+    async fn test_links() {
+        let dir = TempDir::new().unwrap();
+        let store = dir.path().join("tickets.json");
+        for title in ["Epic", "Task", "Blocker"] {
+            ok(&store, &["create", "--title", title, "--description", "A"]).await;
+        }
+
+        ok(&store, &["link", "1", "child-of", "0"]).await;
+        ok(&store, &["link", "2", "blocks", "1"]).await;
+        let output = run(&store, &["link", "1", "blocks", "2"]).await;
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("cycle"));
+        let output = run(&store, &["move", "1", "done"]).await;
+        assert!(!output.status.success());
+
+        let links = ok(&store, &["links", "1"]).await;
+        assert_eq!(links, "parent:       TKT-0\nblocked by:   TKT-2\nall blockers: TKT-2\n");
+
+        ok(&store, &["delete", "2"]).await;
+        ok(&store, &["move", "1", "done"]).await;
+    }
+
+    #[tokio::test]
+    async fn test_id_strategies() {
+        let dir = TempDir::new().unwrap();
+        let store = dir.path().join("tickets.json");
+        let create = ["create", "--title", "A", "--description", "B"];
+
+        let ulid = ok(&store, &[&["--ids", "ulid"], &create[..]].concat()).await;
+        let ulid = ulid.trim();
+        assert_eq!(ulid.len(), "TKT-".len() + 26, "{}", ulid);
+        let uuid = ok(&store, &[&["--ids", "uuid7"], &create[..]].concat()).await;
+        let uuid = uuid.trim();
+        assert_eq!(uuid.len(), "TKT-".len() + 36, "{}", uuid);
+        assert_eq!(ok(&store, &create).await, "TKT-0\n");
+
+        // Any kind of id works, with or without the project key.
+        ok(&store, &["move", ulid, "done"]).await;
+        ok(&store, &["move", &uuid["TKT-".len()..], "done"]).await;
+        let tickets = list(&store, &["--status", "done"]).await;
+        let ids: Vec<_> = tickets.iter().map(|ticket| ticket.id.to_string()).collect();
+        assert_eq!(ids, [ulid, uuid]);
+
+        let output = run(&store, &["--ids", "uuid4", "list"]).await;
+        assert!(!output.status.success());
+    }
+
+    #[tokio::test]
+    async fn test_trash() {
+        let dir = TempDir::new().unwrap();
+        let store = dir.path().join("tickets.json");
+        for title in ["Kept", "Deleted"] {
+            ok(&store, &["create", "--title", title, "--description", "A"]).await;
+        }
+
+        ok(&store, &["delete", "1"]).await;
+        assert_eq!(list(&store, &[]).await.len(), 1);
+        let table = ok(&store, &["trash"]).await;
+        assert!(table.starts_with("ID     DELETED"));
+        assert!(table.contains("TKT-1") && table.contains("Deleted"));
+
+        ok(&store, &["undelete", "1"]).await;
+        assert_eq!(list(&store, &[]).await.len(), 2);
+        let output = run(&store, &["undelete", "1"]).await;
+        assert!(String::from_utf8_lossy(&output.stderr).contains("isn't in the trash"));
+
+        ok(&store, &["delete", "1"]).await;
+        assert_eq!(ok(&store, &["purge", "--older-than-days", "1"]).await, "");
+        assert!(!run(&store, &["purge"]).await.status.success());
+        ok(&store, &["purge", "1"]).await;
+        assert_eq!(ok(&store, &["trash"]).await.lines().count(), 1);
+
+        // Purged ids aren't reused either.
+        let id = ok(&store, &["create", "--title", "New", "--description", "A"]).await;
+        assert_eq!(id.trim(), "TKT-2");
+    }
+
+    #[tokio::test]
+    async fn test_undo_and_redo() {
+        let dir = TempDir::new().unwrap();
+        let store = dir.path().join("tickets.json");
+        ok(&store, &["create", "--title", "A", "--description", "B"]).await;
+        ok(&store, &["edit", "0", "--title", "Renamed"]).await;
+        ok(&store, &["move", "0", "done"]).await;
+
+        let undone = ok(&store, &["undo", "--count", "2"]).await;
+        assert_eq!(undone, "TKT-0\nTKT-0\n");
+        let tickets = list(&store, &[]).await;
+        assert_eq!(tickets[0].title.0, "A");
+        assert_eq!(tickets[0].status, Status::ToDo);
+
+        assert_eq!(ok(&store, &["redo"]).await, "TKT-0\n");
+        assert_eq!(list(&store, &[]).await[0].title.0, "Renamed");
+        let undone = ok(&store, &["undo", "--count", "5"]).await;
+        assert_eq!(undone, "TKT-0\nTKT-0\n");
+        assert!(list(&store, &[]).await.is_empty());
+        assert_eq!(ok(&store, &["undo"]).await, "");
+    }
+
+    #[tokio::test]
+    async fn test_templates() {
+        let dir = TempDir::new().unwrap();
+        let store = dir.path().join("tickets.json");
+        let file = dir.path().join("release.json");
+        let template = r#"{
+            "name": "release",
+            "tickets": [
+                { "title": "Release {{version}}", "description": "Tag it" },
+                { "title": "Announce {{version}}", "description": "On the {{channel}}" }
+            ]
+        }"#;
+        std::fs::write(&file, template).unwrap();
+        let file = file.to_str().unwrap();
+
+        assert_eq!(ok(&store, &["add-template", file]).await, "release\n");
+        let table = ok(&store, &["templates"]).await;
+        assert_eq!(
+            table,
+            "NAME     TICKETS  PLACEHOLDERS\nrelease        2  channel, version\n"
+        );
+
+        let args = ["from-template", "release", "--set", "version=1.2.0"];
+        let output = run(&store, &args).await;
+        assert!(String::from_utf8_lossy(&output.stderr).contains("`channel`"));
+        assert!(!store.exists() || list(&store, &[]).await.is_empty());
+
+        let args = [&args[..], &["--set", "channel=blog"]].concat();
+        assert_eq!(ok(&store, &args).await, "TKT-0\nTKT-1\n");
+        let tickets = list(&store, &[]).await;
+        assert_eq!(tickets[1].title.0, "Announce 1.2.0");
+        assert_eq!(tickets[1].description.as_str(), "On the blog");
+
+        ok(&store, &["remove-template", "release"]).await;
+        assert!(!run(&store, &args).await.status.success());
+    }
+
+    #[tokio::test]
+    async fn test_queries() {
+        let dir = TempDir::new().unwrap();
+        let store = dir.path().join("tickets.json");
+        ok(
+            &store,
+            &["create", "--title", "Login page", "--description", "B"],
+        )
+        .await;
+        ok(
+            &store,
+            &["create", "--title", "Logout", "--description", "B"],
+        )
+        .await;
+        ok(&store, &["move", "1", "in-progress"]).await;
+
+        let tickets = list(&store, &["--query", "title:~LOG AND NOT status:ToDo"]).await;
+        let ids: Vec<String> = tickets.iter().map(|ticket| ticket.id.to_string()).collect();
+        assert_eq!(ids, ["TKT-1"]);
+        let output = run(&store, &["list", "--query", "reportr:bob"]).await;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.contains("at character 1: Unknown field `reportr`"),
+            "{}",
+            stderr
+        );
+
+        let query = r#"title:"login page" OR status:InProgress"#;
+        ok(&store, &["save-query", "mine", query]).await;
+        assert_eq!(
+            ok(&store, &["queries"]).await,
+            format!("NAME  QUERY\nmine  {}\n", query)
+        );
+        assert_eq!(list(&store, &["--saved", "mine"]).await.len(), 2);
+        assert_eq!(
+            list(&store, &["--saved", "mine", "--status", "to-do"])
+                .await
+                .len(),
+            1
+        );
+        assert!(!run(&store, &["save-query", "bad", "title:"])
+            .await
+            .status
+            .success());
+
+        ok(&store, &["remove-query", "mine"]).await;
+        assert!(!run(&store, &["list", "--saved", "mine"])
+            .await
+            .status
+            .success());
+    }
+
+    #[tokio::test]
+    async fn test_report() {
+        let dir = TempDir::new().unwrap();
+        let store = dir.path().join("tickets.json");
+        ok(&store, &["create", "--title", "A", "--description", "B"]).await;
+        ok(&store, &["move", "0", "done"]).await;
+
+        let table = ok(&store, &["report", "--windows", "2"]).await;
+        assert!(
+            table.starts_with("STATUS      TICKETS\nToDo              0\n"),
+            "{}",
+            table
+        );
+        assert!(table.contains("\nto done           1 "), "{}", table);
+        let json = ok(&store, &["report", "--format", "json"]).await;
+        let summary: Summary = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            summary.throughput.len(),
+            Period::default().windows() as usize
+        );
+        assert_eq!(summary.throughput[3].done, 1);
+
+        let output = run(&store, &["report", "--days", "0"]).await;
+        assert!(String::from_utf8_lossy(&output.stderr).contains("`days` must be 1 to 365"));
+    }
+
+    #[tokio::test]
+    async fn test_comments() {
+        let dir = TempDir::new().unwrap();
+        let store = dir.path().join("tickets.json");
+        ok(&store, &["create", "--title", "A", "--description", "B"]).await;
+
+        assert_eq!(ok(&store, &["comment", "0", "First"]).await, "0\n");
+        assert_eq!(ok(&store, &["comment", "0", "Second"]).await, "1\n");
+        let output = run(&store, &["comment", "3", "Nowhere"]).await;
+        assert!(!output.status.success());
+
+        let table = ok(&store, &["comments", "0"]).await;
+        let bodies: Vec<&str> = table
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .collect();
+        assert_eq!(bodies, ["First", "", "Second"]);
+        assert!(table.starts_with("#0 "));
+    }
+
+    #[tokio::test]
+    async fn test_completions() {
+        let dir = TempDir::new().unwrap();
+        let store = dir.path().join("tickets.json");
+        let script = ok(&store, &["completions", "bash"]).await;
+        assert!(script.contains("tickets"));
+        assert!(!store.exists());
+    }
+}
@@ -0,0 +1,325 @@
+#[cfg(test)]
+mod tests {
+    use outro_08::auth::{Role, User};
+    use outro_08::client::{Client, ClientConfig, ClientError};
+    use outro_08::data::{DescriptionFormat, Status, TicketDraft, TicketPatch};
+    use outro_08::limits::{Quota, RateLimitConfig};
+    use outro_08::links::{Link, LinkKind};
+    use outro_08::server::{self, Config};
+    use outro_08::store::TicketId;
+    use std::net::SocketAddr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use ticket_fields::test_helpers::{ticket_description, ticket_title};
+    use ticket_fields::MarkdownDescription;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    async fn start(config: Config) -> SocketAddr {
+        let config = Config {
+            addr: String::from("127.0.0.1:0"),
+            ..config
+        };
+        let (listeners, state) = server::init(&config).await.unwrap();
+        let mut tokens = state.tokens.write().await;
+        tokens.insert("admin".into(), User::new("root", Role::Admin));
+        tokens.insert("viewer".into(), User::new("vic", Role::Viewer));
+        drop(tokens);
+        let addr = listeners.http_addr().unwrap();
+        tokio::spawn(server::serve(listeners, state));
+        addr
+    }
+
+    fn draft() -> TicketDraft {
+        TicketDraft {
+            title: ticket_title(),
+            description: ticket_description().into(),
+        }
+    }
+
+    fn quick(retries: u32) -> ClientConfig {
+        ClientConfig {
+            timeout: Duration::from_millis(200),
+            retries,
+            backoff: Duration::from_millis(10),
+            ..ClientConfig::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rendered_descriptions() {
+        let addr = start(Config::default()).await;
+        let client = Client::new(addr.to_string(), "admin");
+        let draft = TicketDraft {
+            title: ticket_title(),
+            description: MarkdownDescription::try_from("Blocked by **#3**<script>").unwrap(),
+        };
+        let id = client.create(&draft).await.unwrap();
+
+        let ticket = client.get(id).await.unwrap();
+        assert_eq!(ticket.description.references(), [3]);
+        let markdown = client.description(id, DescriptionFormat::Markdown).await;
+        assert_eq!(markdown.unwrap(), "Blocked by **#3**<script>");
+        let html = client.description(id, DescriptionFormat::Html).await;
+        assert_eq!(
+            html.unwrap(),
+            "<p>Blocked by <strong>#3</strong>&lt;script&gt;</p>\n"
+        );
+        let text = client.description(id, DescriptionFormat::Text).await;
+        assert_eq!(text.unwrap(), "Blocked by #3<script>");
+
+        let mut socket = TcpStream::connect(addr).await.unwrap();
+        let request = "GET /tickets?description=pdf HTTP/1.1\r\n\
+                       Authorization: Bearer admin\r\nConnection: close\r\n\r\n";
+        socket.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        socket.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 400"), "{}", response);
+        assert!(response.contains("Unknown description format `pdf`"));
+    }
+
+    #[tokio::test]
+    async fn test_malformed_query_params() {
+        let addr = start(Config::default()).await;
+        let client = Client::new(addr.to_string(), "admin");
+        let id = client.create(&draft()).await.unwrap();
+        client.delete(id).await.unwrap();
+
+        for target in [
+            "DELETE /trash?older_than_days=%zz",
+            "GET /tickets?q=%zz",
+            "POST /undo?count=%",
+            "GET /tickets?description=%ff",
+        ] {
+            let mut socket = TcpStream::connect(addr).await.unwrap();
+            let request = format!(
+                "{} HTTP/1.1\r\nAuthorization: Bearer admin\r\nConnection: close\r\n\r\n",
+                target
+            );
+            socket.write_all(request.as_bytes()).await.unwrap();
+            let mut response = String::new();
+            socket.read_to_string(&mut response).await.unwrap();
+            assert!(response.starts_with("HTTP/1.1 400"), "{}", response);
+            assert!(
+                response.contains("query parameter is malformed"),
+                "{}",
+                response
+            );
+        }
+        // Nothing was purged or undone.
+        let trash = client.trash().await.unwrap();
+        assert_eq!(trash.len(), 1);
+        assert_eq!(trash[0].ticket.id, id);
+    }
+
+    #[tokio::test]
+    async fn test_crud_round_trip() {
+        let addr = start(Config::default()).await;
+        let client = Client::new(addr.to_string(), "admin");
+
+        let first = client.create(&draft()).await.unwrap();
+        let second = client.create(&draft()).await.unwrap();
+
+        let ticket = client.get(first).await.unwrap();
+        assert_eq!(ticket.id, first);
+        assert_eq!(ticket.title, ticket_title());
+        assert_eq!(ticket.status, Status::ToDo);
+        assert_eq!(ticket.reporter.as_deref(), Some("root"));
+
+        client
+            .patch(&TicketPatch {
+                id: second,
+                title: None,
+                description: None,
+                status: Some(Status::Done),
+            })
+            .await
+            .unwrap();
+        let tickets = client.list().await.unwrap();
+        let ids: Vec<TicketId> = tickets.iter().map(|ticket| ticket.id).collect();
+        assert_eq!(ids, vec![first, second]);
+        assert_eq!(tickets[1].status, Status::Done);
+
+        client.delete(first).await.unwrap();
+        assert!(matches!(
+            client.get(first).await,
+            Err(ClientError::NotFound)
+        ));
+        assert_eq!(client.list().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_links() {
+        let addr = start(Config::default()).await;
+        let client = Client::new(addr.to_string(), "admin");
+        let blocker = client.create(&draft()).await.unwrap();
+        let blocked = client.create(&draft()).await.unwrap();
+        let blocks = Link {
+            from: blocker,
+            kind: LinkKind::Blocks,
+            to: blocked,
+        };
+        client.link(blocks).await.unwrap();
+
+        let links = client.links(blocked).await.unwrap();
+        assert_eq!(links.blocked_by, vec![blocker]);
+        let backwards = Link {
+            from: blocked,
+            kind: LinkKind::Blocks,
+            to: blocker,
+        };
+        assert!(matches!(
+            client.link(backwards).await,
+            Err(ClientError::Conflict(message)) if message.contains("cycle")
+        ));
+
+        let done = TicketPatch {
+            id: blocked,
+            title: None,
+            description: None,
+            status: Some(Status::Done),
+        };
+        assert!(matches!(
+            client.patch(&done).await,
+            Err(ClientError::Conflict(message)) if message.contains(&blocker.to_string())
+        ));
+
+        client.unlink(blocks).await.unwrap();
+        assert!(matches!(
+            client.unlink(blocks).await,
+            Err(ClientError::NotFound)
+        ));
+        client.patch(&done).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_error_responses_are_typed() {
+        let addr = start(Config::default()).await;
+
+        let viewer = Client::new(addr.to_string(), "viewer");
+        assert!(matches!(
+            viewer.create(&draft()).await,
+            Err(ClientError::Forbidden)
+        ));
+
+        let stranger = Client::new(addr.to_string(), "nope");
+        assert!(matches!(
+            stranger.list().await,
+            Err(ClientError::Unauthorized)
+        ));
+
+        let admin = Client::new(addr.to_string(), "admin");
+        assert!(matches!(
+            admin.delete(TicketId::from(42)).await,
+            Err(ClientError::NotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_connections_are_reused() {
+        // With a single connection slot, a second connection would wait
+        // in the backlog until the first one is closed, and time out.
+        let addr = start(Config {
+            max_connections: 1,
+            keep_alive: Duration::from_secs(60),
+            ..Config::default()
+        })
+        .await;
+        let client = Client::with_config(addr.to_string(), "admin", quick(0));
+
+        let id = client.create(&draft()).await.unwrap();
+        for _ in 0..5 {
+            client.get(id).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_connections_closed_by_the_server_are_replaced() {
+        let addr = start(Config {
+            keep_alive: Duration::from_millis(50),
+            ..Config::default()
+        })
+        .await;
+        let client = Client::with_config(addr.to_string(), "admin", quick(0));
+
+        client.create(&draft()).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        // Not idempotent, so this only works without counting as a retry.
+        client.create(&draft()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_requests_are_retried() {
+        let addr = start(Config {
+            rate_limits: RateLimitConfig {
+                default: Quota::new(1, 20.0),
+                routes: vec![],
+                ..RateLimitConfig::default()
+            },
+            ..Config::default()
+        })
+        .await;
+
+        let client = Client::with_config(addr.to_string(), "admin", quick(0));
+        client.list().await.unwrap();
+        assert!(matches!(
+            client.list().await,
+            Err(ClientError::RateLimited(retry_after)) if retry_after > Duration::ZERO
+        ));
+
+        let client = Client::with_config(addr.to_string(), "admin", quick(1));
+        client.list().await.unwrap();
+    }
+
+    /// A server that accepts connections and never answers.
+    /// Returns how many connections it has accepted so far.
+    async fn black_hole() -> (SocketAddr, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let accepted = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&accepted);
+        tokio::spawn(async move {
+            let mut sockets = vec![];
+            while let Ok((socket, _)) = listener.accept().await {
+                counter.fetch_add(1, Ordering::SeqCst);
+                sockets.push(socket);
+            }
+        });
+        (addr, accepted)
+    }
+
+    #[tokio::test]
+    async fn test_only_idempotent_requests_are_retried_after_timeouts() {
+        let (addr, accepted) = black_hole().await;
+        let client = Client::with_config(addr.to_string(), "admin", quick(2));
+
+        assert!(matches!(
+            client.get(TicketId::from(0)).await,
+            Err(ClientError::Timeout(_))
+        ));
+        assert_eq!(accepted.load(Ordering::SeqCst), 3);
+
+        assert!(matches!(
+            client.create(&draft()).await,
+            Err(ClientError::Timeout(_))
+        ));
+        assert_eq!(accepted.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn test_server_answers_every_request_on_a_connection() {
+        let addr = start(Config::default()).await;
+        let request = "GET /tickets HTTP/1.1\r\nAuthorization: Bearer admin\r\n\r\n";
+        let last =
+            "GET /tickets HTTP/1.1\r\nAuthorization: Bearer admin\r\nConnection: close\r\n\r\n";
+
+        // Pipelined: all three are sent before reading any response.
+        let mut socket = TcpStream::connect(addr).await.unwrap();
+        let requests = format!("{}{}{}", request, request, last);
+        socket.write_all(requests.as_bytes()).await.unwrap();
+        let mut responses = String::new();
+        socket.read_to_string(&mut responses).await.unwrap();
+        assert_eq!(responses.matches("HTTP/1.1 200 OK").count(), 3);
+    }
+}
@@ -0,0 +1,172 @@
+#[cfg(test)]
+mod tests {
+    use outro_08::auth::{Role, User};
+    use outro_08::client::{Client, ClientError};
+    use outro_08::comments::{CommentDraft, CommentId, Comments};
+    use outro_08::data::TicketDraft;
+    use outro_08::server::{self, Config};
+    use outro_08::snapshot::Snapshot;
+    use outro_08::store::{TicketId, TicketStore};
+    use std::net::SocketAddr;
+    use tempfile::TempDir;
+    use ticket_fields::MarkdownDescription;
+
+    fn body(text: &str) -> MarkdownDescription {
+        MarkdownDescription::try_from(text).unwrap()
+    }
+
+    fn draft(text: &str) -> CommentDraft {
+        CommentDraft { body: body(text) }
+    }
+
+    async fn start() -> SocketAddr {
+        let config = Config {
+            addr: String::from("127.0.0.1:0"),
+            ..Config::default()
+        };
+        let (listeners, state) = server::init(&config).await.unwrap();
+        let mut tokens = state.tokens.write().await;
+        tokens.insert("admin".into(), User::new("root", Role::Admin));
+        tokens.insert("maintainer".into(), User::new("mia", Role::Maintainer));
+        tokens.insert("alice".into(), User::new("alice", Role::Reporter));
+        tokens.insert("bob".into(), User::new("bob", Role::Reporter));
+        tokens.insert("viewer".into(), User::new("vic", Role::Viewer));
+        drop(tokens);
+        let addr = listeners.http_addr().unwrap();
+        tokio::spawn(server::serve(listeners, state));
+        addr
+    }
+
+    #[test]
+    fn test_threads() {
+        let mut comments = Comments::new();
+        let first = comments.add(TicketId::from(1), "alice", body("First"));
+        comments.add(TicketId::from(2), "bob", body("Elsewhere"));
+        let third = comments.add(TicketId::from(1), "bob", body("Third"));
+
+        let thread = comments.thread(TicketId::from(1));
+        let ids: Vec<CommentId> = thread.iter().map(|comment| comment.id).collect();
+        assert_eq!(ids, [first, third]);
+        assert_eq!(thread[0].author, "alice");
+        assert_eq!(thread[0].edited_at, None);
+
+        assert!(comments.edit(first, body("Edited")));
+        let edited = comments.get(first).unwrap();
+        assert_eq!(edited.body, body("Edited"));
+        assert!(edited.edited_at.unwrap() >= edited.created_at);
+        assert!(!comments.edit(CommentId(9), body("Nope")));
+
+        // Ids aren't reused once a comment is gone.
+        comments.remove(third);
+        assert_eq!(comments.next_id(), CommentId(3));
+        comments.remove_ticket(TicketId::from(1));
+        assert!(comments.thread(TicketId::from(1)).is_empty());
+        assert_eq!(comments.thread(TicketId::from(2)).len(), 1);
+    }
+
+    #[test]
+    fn test_store_comments() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("tickets.json");
+        let alice = User::new("alice", Role::Reporter);
+        let mut store = TicketStore::new();
+        let id = store.add_ticket(TicketDraft::new("A".into(), None));
+        assert_eq!(store.add_comment(TicketId::from(7), &alice, body("Hi")), None);
+        let comment = store.add_comment(id, &alice, body("Hi")).unwrap();
+
+        store.save(&path).unwrap();
+        let loaded = TicketStore::load(&path).unwrap();
+        assert_eq!(loaded.comments(), store.comments());
+
+        let snapshot = Snapshot::capture(&store);
+        let restored = Snapshot::decode(&snapshot.to_bytes()).unwrap().restore();
+        assert_eq!(restored.comments(), store.comments());
+
+        store.remove(id);
+        assert_eq!(store.comments().get(comment), None);
+    }
+
+    #[tokio::test]
+    async fn test_comment_permissions() {
+        let addr = start().await;
+        let admin = Client::new(addr.to_string(), "admin");
+        let alice = Client::new(addr.to_string(), "alice");
+        let bob = Client::new(addr.to_string(), "bob");
+        let maintainer = Client::new(addr.to_string(), "maintainer");
+        let viewer = Client::new(addr.to_string(), "viewer");
+        let id = admin
+            .create(&TicketDraft::new("A".into(), None))
+            .await
+            .unwrap();
+
+        let comment = alice.comment(id, &draft("Looks **bad**")).await.unwrap();
+        assert_eq!(comment.author, "alice");
+        assert_eq!(comment.ticket, id);
+        assert!(matches!(
+            viewer.comment(id, &draft("Me too")).await,
+            Err(ClientError::Forbidden)
+        ));
+        assert!(matches!(
+            alice.comment(TicketId::from(9), &draft("Hello?")).await,
+            Err(ClientError::NotFound)
+        ));
+        assert_eq!(
+            viewer.comments(id).await.unwrap(),
+            std::slice::from_ref(&comment)
+        );
+
+        // Reporters can only change their own comments.
+        assert!(matches!(
+            bob.edit_comment(id, comment.id, &draft("Hacked")).await,
+            Err(ClientError::Forbidden)
+        ));
+        alice
+            .edit_comment(id, comment.id, &draft("Looks fine"))
+            .await
+            .unwrap();
+        maintainer
+            .edit_comment(id, comment.id, &draft("Looks great"))
+            .await
+            .unwrap();
+        let thread = viewer.comments(id).await.unwrap();
+        assert_eq!(thread[0].body, body("Looks great"));
+        assert!(thread[0].edited_at.is_some());
+
+        // Comments are only found under their own ticket.
+        let other = admin
+            .create(&TicketDraft::new("B".into(), None))
+            .await
+            .unwrap();
+        assert!(matches!(
+            alice.delete_comment(other, comment.id).await,
+            Err(ClientError::NotFound)
+        ));
+        assert!(matches!(
+            bob.delete_comment(id, comment.id).await,
+            Err(ClientError::Forbidden)
+        ));
+        alice.delete_comment(id, comment.id).await.unwrap();
+        assert!(viewer.comments(id).await.unwrap().is_empty());
+        assert!(matches!(
+            alice.delete_comment(id, comment.id).await,
+            Err(ClientError::NotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_deleting_a_ticket_deletes_its_comments() {
+        let addr = start().await;
+        let admin = Client::new(addr.to_string(), "admin");
+        let id = admin
+            .create(&TicketDraft::new("A".into(), None))
+            .await
+            .unwrap();
+        admin.comment(id, &draft("Bye")).await.unwrap();
+
+        admin.delete(id).await.unwrap();
+        assert!(matches!(
+            admin.comments(id).await,
+            Err(ClientError::NotFound)
+        ));
+    }
+}
@@ -0,0 +1,297 @@
+#[cfg(test)]
+mod tests {
+    use outro_08::auth::{Role, User};
+    use outro_08::client::{Client, ClientError};
+    use outro_08::data::{Status, TicketDraft, TicketPatch};
+    use outro_08::history::{History, Report};
+    use outro_08::links::{Link, LinkKind};
+    use outro_08::server::{self, Config};
+    use outro_08::store::{TicketId, TicketStore};
+    use std::net::SocketAddr;
+    use tempfile::TempDir;
+    use ticket_fields::TicketTitle;
+
+    // Undoing takes the same permissions as the change, e.g. deleting the
+    // ticket to undo creating it, so most tests undo as admins.
+    fn alice() -> User {
+        User::new("alice", Role::Admin)
+    }
+
+    fn bob() -> User {
+        User::new("bob", Role::Admin)
+    }
+
+    fn carol() -> User {
+        User::new("carol", Role::Reporter)
+    }
+
+    fn draft(title: &str) -> TicketDraft {
+        TicketDraft::new(title.into(), None)
+    }
+
+    /// Renames ticket `id` as `by`, the way `PATCH /tickets/{id}` does.
+    fn rename(store: &mut TicketStore, id: TicketId, title: &str, by: &User) {
+        let ticket = store.get(id).unwrap();
+        let before = ticket.read().unwrap().clone();
+        ticket.write().unwrap().title = TicketTitle::try_from(title).unwrap();
+        store.edited(before, by);
+    }
+
+    fn title(store: &TicketStore, id: TicketId) -> Option<String> {
+        let ticket = store.get(id)?;
+        let title = ticket.read().unwrap().title.0.clone();
+        Some(title)
+    }
+
+    fn undone(tickets: &[TicketId]) -> Report {
+        Report {
+            tickets: tickets.to_vec(),
+            conflict: None,
+        }
+    }
+
+    async fn start() -> SocketAddr {
+        let config = Config {
+            addr: String::from("127.0.0.1:0"),
+            ..Config::default()
+        };
+        let (listeners, state) = server::init(&config).await.unwrap();
+        let mut tokens = state.tokens.write().await;
+        tokens.insert("alice".into(), User::new("alice", Role::Admin));
+        tokens.insert("bob".into(), User::new("bob", Role::Maintainer));
+        tokens.insert("carol".into(), User::new("carol", Role::Reporter));
+        drop(tokens);
+        let addr = listeners.http_addr().unwrap();
+        tokio::spawn(server::serve(listeners, state));
+        addr
+    }
+
+    #[test]
+    fn test_undo_and_redo() {
+        let mut store = TicketStore::new();
+        let id = store.add_ticket_by(draft("First"), &alice());
+        rename(&mut store, id, "Second", &alice());
+        store.delete(id, &alice());
+
+        assert_eq!(store.undo(&alice(), 1), undone(&[id]));
+        assert_eq!(title(&store, id).as_deref(), Some("Second"));
+        assert_eq!(store.undo(&alice(), 1), undone(&[id]));
+        let ticket = store.get(id).unwrap().read().unwrap().clone();
+        assert_eq!(ticket.title.0, "First");
+        assert_eq!(store.undo(&alice(), 1), undone(&[id]));
+        assert!(store.get(id).is_none());
+        assert!(store.trashed(id).is_some());
+        // Nothing left to undo.
+        assert_eq!(store.undo(&alice(), 1), undone(&[]));
+
+        assert_eq!(store.redo(&alice(), 2), undone(&[id, id]));
+        assert_eq!(title(&store, id).as_deref(), Some("Second"));
+        assert_eq!(store.redo(&alice(), 5), undone(&[id]));
+        assert!(store.get(id).is_none());
+        let history = store.history(&alice()).unwrap();
+        assert_eq!((history.undo.len(), history.redo.len()), (3, 0));
+    }
+
+    #[test]
+    fn test_undo_skips_other_users() {
+        let mut store = TicketStore::new();
+        let a = store.add_ticket_by(draft("A"), &alice());
+        let b = store.add_ticket_by(draft("B"), &bob());
+        rename(&mut store, a, "A2", &alice());
+        rename(&mut store, b, "B2", &bob());
+        rename(&mut store, a, "A3", &alice());
+
+        assert_eq!(store.undo(&alice(), 2), undone(&[a, a]));
+        assert_eq!(title(&store, a).as_deref(), Some("A"));
+        // Bob's changes in between are left alone.
+        assert_eq!(title(&store, b).as_deref(), Some("B2"));
+        assert!(store.history(&bob()).unwrap().redo.is_empty());
+    }
+
+    #[test]
+    fn test_conflicts_drop_the_operation() {
+        let mut store = TicketStore::new();
+        let a = store.add_ticket_by(draft("A"), &alice());
+        let b = store.add_ticket_by(draft("B"), &alice());
+        rename(&mut store, a, "Alice's", &alice());
+        rename(&mut store, b, "Alice's", &alice());
+        rename(&mut store, a, "Bob's", &bob());
+
+        // Stops at the first conflict, after undoing what came before it.
+        let report = store.undo(&alice(), 3);
+        assert_eq!(report.tickets, [b]);
+        let conflict = report.conflict.unwrap();
+        assert!(conflict.contains("TKT-0 changed since"), "{}", conflict);
+        assert_eq!(title(&store, a).as_deref(), Some("Bob's"));
+        assert_eq!(title(&store, b).as_deref(), Some("B"));
+
+        // The conflicting operation is gone, the ones before it are still there.
+        let history = store.history(&alice()).unwrap();
+        assert_eq!((history.undo.len(), history.redo.len()), (2, 1));
+        assert_eq!(store.undo(&alice(), 1), undone(&[b]));
+
+        let id = store.add_ticket_by(draft("C"), &alice());
+        store.delete(id, &bob());
+        store.purge(id);
+        let report = store.undo(&alice(), 1);
+        assert!(report.tickets.is_empty());
+        assert!(report.conflict.unwrap().contains("purged"));
+    }
+
+    #[test]
+    fn test_blocked_operations_are_kept() {
+        let mut store = TicketStore::new();
+        let blocker = store.add_ticket(draft("Blocker"));
+        let id = store.add_ticket_by(draft("A"), &alice());
+        let ticket = store.get(id).unwrap();
+        let before = ticket.read().unwrap().clone();
+        ticket.write().unwrap().status = Status::Done;
+        store.edited(before, &alice());
+        store.undo(&alice(), 1);
+        let link = Link {
+            from: blocker,
+            kind: LinkKind::Blocks,
+            to: id,
+        };
+        store.link(link).unwrap();
+
+        let report = store.redo(&alice(), 1);
+        assert!(report.tickets.is_empty());
+        assert_eq!(
+            report.conflict.unwrap(),
+            "Ticket TKT-1 can't be done while it's blocked by TKT-0, so the operation was kept"
+        );
+        assert_eq!(ticket.read().unwrap().status, Status::ToDo);
+        assert_eq!(store.history(&alice()).unwrap().redo.len(), 1);
+
+        store.get(blocker).unwrap().write().unwrap().status = Status::Done;
+        assert_eq!(store.redo(&alice(), 1), undone(&[id]));
+        assert_eq!(ticket.read().unwrap().status, Status::Done);
+    }
+
+    #[test]
+    fn test_operations_take_the_same_permissions() {
+        let mut store = TicketStore::new();
+        let id = store.add_ticket_by(draft("A"), &carol());
+        rename(&mut store, id, "B", &carol());
+
+        // Reporters can edit the tickets they reported, but not delete them.
+        let report = store.undo(&carol(), 2);
+        assert_eq!(report.tickets, [id]);
+        assert_eq!(
+            report.conflict.unwrap(),
+            "Not allowed to change ticket TKT-0 that way, so the operation was dropped"
+        );
+        assert_eq!(title(&store, id).as_deref(), Some("A"));
+        let history = store.history(&carol()).unwrap();
+        assert_eq!((history.undo.len(), history.redo.len()), (0, 1));
+
+        // Nor change statuses.
+        let ticket = store.get(id).unwrap();
+        let before = ticket.read().unwrap().clone();
+        ticket.write().unwrap().status = Status::InProgress;
+        store.edited(before, &carol());
+        let report = store.undo(&carol(), 1);
+        assert!(report.tickets.is_empty());
+        assert!(report.conflict.unwrap().starts_with("Not allowed"));
+        assert_eq!(ticket.read().unwrap().status, Status::InProgress);
+
+        // Nor edit anyone else's.
+        let other = store.add_ticket_by(draft("C"), &alice());
+        rename(&mut store, other, "D", &carol());
+        let report = store.undo(&carol(), 1);
+        assert!(report.tickets.is_empty());
+        assert!(report.conflict.unwrap().starts_with("Not allowed"));
+        assert_eq!(title(&store, other).as_deref(), Some("D"));
+    }
+
+    #[test]
+    fn test_new_operations_clear_redo() {
+        let mut store = TicketStore::new();
+        let id = store.add_ticket_by(draft("A"), &alice());
+        rename(&mut store, id, "B", &alice());
+        store.undo(&alice(), 1);
+        assert_eq!(store.history(&alice()).unwrap().redo.len(), 1);
+
+        rename(&mut store, id, "C", &alice());
+        assert_eq!(store.redo(&alice(), 1), undone(&[]));
+        assert_eq!(title(&store, id).as_deref(), Some("C"));
+    }
+
+    #[test]
+    fn test_history_is_bounded() {
+        let mut store = TicketStore::new();
+        let id = store.add_ticket_by(draft("0"), &alice());
+        for i in 1..=History::MAX_OPERATIONS + 10 {
+            rename(&mut store, id, &i.to_string(), &alice());
+        }
+        let history = store.history(&alice()).unwrap();
+        assert_eq!(history.undo.len(), History::MAX_OPERATIONS);
+
+        let report = store.undo(&alice(), History::MAX_OPERATIONS + 10);
+        assert_eq!(report.tickets.len(), History::MAX_OPERATIONS);
+        assert_eq!(title(&store, id).as_deref(), Some("10"));
+    }
+
+    #[test]
+    fn test_history_is_saved() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("tickets.json");
+        let mut store = TicketStore::new();
+        let id = store.add_ticket_by(draft("A"), &alice());
+        rename(&mut store, id, "B", &alice());
+        store.save(&path).unwrap();
+
+        let mut loaded = TicketStore::load(&path).unwrap();
+        assert_eq!(loaded.history(&alice()), store.history(&alice()));
+        assert_eq!(loaded.undo(&alice(), 1), undone(&[id]));
+        assert_eq!(title(&loaded, id).as_deref(), Some("A"));
+
+        // Stores without a history still load.
+        let store = TicketStore::new();
+        store.save(&path).unwrap();
+        let json = std::fs::read_to_string(&path).unwrap();
+        assert!(!json.contains("history"));
+        let loaded = TicketStore::load(&path).unwrap();
+        assert!(loaded.history(&alice()).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_undo_endpoint() {
+        let addr = start().await;
+        let alice = Client::new(addr.to_string(), "alice");
+        let bob = Client::new(addr.to_string(), "bob");
+
+        let id = alice.create(&draft("A")).await.unwrap();
+        let patch = TicketPatch {
+            id,
+            title: None,
+            description: None,
+            status: Some(Status::Done),
+        };
+        alice.patch(&patch).await.unwrap();
+        let other = bob.create(&draft("B")).await.unwrap();
+
+        assert_eq!(alice.undo(1).await.unwrap(), undone(&[id]));
+        assert_eq!(alice.get(id).await.unwrap().status, Status::ToDo);
+        assert_eq!(alice.redo(1).await.unwrap(), undone(&[id]));
+        assert_eq!(alice.get(id).await.unwrap().status, Status::Done);
+
+        assert_eq!(alice.undo(2).await.unwrap(), undone(&[id, id]));
+        assert!(matches!(alice.get(id).await, Err(ClientError::NotFound)));
+        assert!(alice.get(other).await.is_ok());
+
+        assert!(matches!(
+            alice.undo(0).await,
+            Err(ClientError::BadRequest(_))
+        ));
+
+        // Reporters can only undo what they could do by hand.
+        let carol = Client::new(addr.to_string(), "carol");
+        let id = carol.create(&draft("C")).await.unwrap();
+        let report = carol.undo(1).await.unwrap();
+        assert!(report.tickets.is_empty());
+        assert!(report.conflict.unwrap().contains("Not allowed"));
+        assert!(carol.get(id).await.is_ok());
+    }
+}
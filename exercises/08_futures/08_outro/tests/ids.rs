@@ -0,0 +1,240 @@
+#[cfg(test)]
+mod tests {
+    use outro_08::auth::{Role, User};
+    use outro_08::data::TicketDraft;
+    use outro_08::ids::{IdGenerator, IdStrategy, Serial};
+    use outro_08::projects::{Project, ProjectKey};
+    use outro_08::snapshot::Snapshot;
+    use outro_08::sqlite::SqliteStore;
+    use outro_08::store::{TicketId, TicketStore};
+    use std::sync::Arc;
+    use tempfile::TempDir;
+    use tokio::sync::RwLock;
+
+    const ULID: &str = "01J9ZB3X4N5Q8W7RYV2T6KDMCE";
+    const UUID: &str = "01927c3e-5a61-7d3f-9e24-6b8f0c1a2d3e";
+
+    fn draft(title: &str) -> TicketDraft {
+        TicketDraft::new(title.into(), None)
+    }
+
+    fn key(key: &str) -> ProjectKey {
+        key.parse().unwrap()
+    }
+
+    fn generate(strategy: IdStrategy, n: usize) -> Vec<Serial> {
+        let mut ids = IdGenerator::new(strategy);
+        let mut project = Project::default_project();
+        (0..n).map(|_| ids.generate(&mut project)).collect()
+    }
+
+    #[test]
+    fn test_forms() {
+        for serial in ["42", ULID, UUID] {
+            let id: TicketId = format!("API-{}", serial).parse().unwrap();
+            assert_eq!(id.project, key("API"));
+            assert_eq!(id.serial.to_string(), serial);
+            assert_eq!(id.to_string(), format!("API-{}", serial));
+
+            // Bare serials are tickets of the default project.
+            let bare: TicketId = serial.parse().unwrap();
+            assert_eq!(bare, TicketId::new(ProjectKey::DEFAULT, id.serial));
+
+            let json = serde_json::to_string(&id).unwrap();
+            assert_eq!(json, format!("\"API-{}\"", serial));
+            assert_eq!(serde_json::from_str::<TicketId>(&json).unwrap(), id);
+        }
+        assert!(matches!(ULID.parse(), Ok(Serial::Ulid(_))));
+        assert!(matches!(UUID.parse(), Ok(Serial::Uuid(_))));
+
+        // Parsing isn't case-sensitive, but ids are always written the same way.
+        let id: TicketId = format!("api-{}", ULID.to_lowercase()).parse().unwrap();
+        assert_eq!(id.to_string(), format!("API-{}", ULID));
+        let id: TicketId = format!("API-{}", UUID.to_uppercase()).parse().unwrap();
+        assert_eq!(id.to_string(), format!("API-{}", UUID));
+
+        for id in [
+            "API-01J9ZB3X4N5Q8W7RYV2T6KDMC",
+            "API-01J9ZB3X4N5Q8W7RYV2T6KDMCU",
+            "API-01927c3e5a617d3f9e246b8f0c1a2d3e",
+            "API-{01927c3e-5a61-7d3f-9e24-6b8f0c1a2d3e}",
+            "API-01927c3e-5a61-7d3f-9e24-6b8f0c1a2d3g",
+            "API-+42",
+        ] {
+            assert!(id.parse::<TicketId>().is_err(), "{}", id);
+        }
+    }
+
+    #[test]
+    fn test_strategies() {
+        for strategy in IdStrategy::ALL {
+            assert_eq!(
+                strategy.to_string().parse::<IdStrategy>().unwrap(),
+                strategy
+            );
+        }
+        assert_eq!("ULID".parse::<IdStrategy>().unwrap(), IdStrategy::Ulid);
+        assert!("uuid4".parse::<IdStrategy>().is_err());
+
+        let mut store = TicketStore::new();
+        assert_eq!(store.add_ticket(draft("A")), TicketId::from(0));
+        store.set_id_strategy(IdStrategy::Ulid);
+        let ulid = store.add_ticket(draft("B"));
+        assert!(matches!(ulid.serial, Serial::Ulid(_)));
+        store.set_id_strategy(IdStrategy::Uuid7);
+        let uuid = store.add_ticket(draft("C"));
+        let Serial::Uuid(value) = uuid.serial else {
+            panic!("{} isn't a UUID", uuid);
+        };
+        assert_eq!(value.get_version_num(), 7);
+        assert_eq!(value.get_variant(), uuid::Variant::RFC4122);
+
+        // Switching back picks up the count where it left off.
+        store.set_id_strategy(IdStrategy::Sequential);
+        assert_eq!(store.add_ticket(draft("D")), TicketId::from(1));
+        let ids: Vec<_> = store.tickets.keys().copied().collect();
+        assert_eq!(ids, [TicketId::from(0), TicketId::from(1), ulid, uuid]);
+    }
+
+    #[test]
+    fn test_time_based_ids_are_monotonic() {
+        // Far more than fit in a millisecond each.
+        for strategy in [IdStrategy::Ulid, IdStrategy::Uuid7] {
+            let serials = generate(strategy, 10_000);
+            assert!(
+                serials.windows(2).all(|pair| pair[0] < pair[1]),
+                "{}",
+                strategy
+            );
+            // The text forms sort the same way, e.g. in SQLite.
+            let text: Vec<_> = serials.iter().map(Serial::to_string).collect();
+            assert!(
+                text.windows(2).all(|pair| pair[0] < pair[1]),
+                "{}",
+                strategy
+            );
+        }
+    }
+
+    #[test]
+    fn test_new_ids_come_after_restored_ones() {
+        let mut store = TicketStore::new();
+        store.set_id_strategy(IdStrategy::Ulid);
+        let id = store.add_ticket(draft("Now"));
+
+        // A ticket from a store whose clock is a year ahead.
+        let future = match generate(IdStrategy::Ulid, 1)[0] {
+            Serial::Ulid(ulid) => {
+                let year = 365 * 24 * 60 * 60 * 1000;
+                ulid::Ulid::from_parts(ulid.timestamp_ms() + year, ulid.random())
+            }
+            serial => panic!("{} isn't a ULID", serial),
+        };
+        let mut ticket = store.get(id).unwrap().read().unwrap().clone();
+        ticket.id = TicketId::new(key("API"), future);
+        store.restore(ticket.clone());
+
+        let later =
+            store.add_ticket_to(key("API"), draft("Later"), &User::new("root", Role::Admin));
+        assert!(later.unwrap() > ticket.id);
+        // Numbers are still counted per project.
+        assert_eq!(
+            store.next_id(key("API")),
+            Some(TicketId::new(key("API"), 0))
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_inserts() {
+        for strategy in IdStrategy::ALL {
+            let mut store = TicketStore::new();
+            store.set_id_strategy(strategy);
+            let store = Arc::new(RwLock::new(store));
+            // Ids in the order the tickets were inserted in.
+            let inserted = Arc::new(std::sync::Mutex::new(vec![]));
+
+            let mut tasks = vec![];
+            for task in 0..8 {
+                let store = Arc::clone(&store);
+                let inserted = Arc::clone(&inserted);
+                tasks.push(tokio::spawn(async move {
+                    for i in 0..200 {
+                        let mut store = store.write().await;
+                        let id = store.add_ticket(draft(&format!("{} {}", task, i)));
+                        inserted.lock().unwrap().push(id);
+                        drop(store);
+                        tokio::task::yield_now().await;
+                    }
+                }));
+            }
+            for task in tasks {
+                task.await.unwrap();
+            }
+
+            let inserted = inserted.lock().unwrap().clone();
+            assert_eq!(inserted.len(), 8 * 200);
+            assert!(
+                inserted.windows(2).all(|pair| pair[0] < pair[1]),
+                "{}",
+                strategy
+            );
+            let stored: Vec<_> = store.read().await.tickets.keys().copied().collect();
+            assert_eq!(stored, inserted);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ids_are_kept_in_snapshots_and_databases() {
+        let dir = TempDir::new().unwrap();
+        let root = User::new("root", Role::Admin);
+        let mut store = TicketStore::new();
+        let mut ids = vec![];
+        for strategy in IdStrategy::ALL {
+            store.set_id_strategy(strategy);
+            ids.push(store.add_ticket_by(draft(&strategy.to_string()), &root));
+        }
+
+        let restored = Snapshot::decode(&Snapshot::capture(&store).to_bytes())
+            .unwrap()
+            .restore();
+        let restored_ids: Vec<_> = restored.tickets.keys().copied().collect();
+        assert_eq!(restored_ids, ids);
+
+        let path = dir.path().join("tickets.db");
+        let db = SqliteStore::open(&path).unwrap();
+        let mut created = vec![];
+        for strategy in IdStrategy::ALL {
+            db.set_id_strategy(strategy);
+            created.push(
+                db.create(ProjectKey::DEFAULT, draft("A"), &root)
+                    .await
+                    .unwrap(),
+            );
+        }
+        for id in &created {
+            assert_eq!(db.get(*id).await.unwrap().unwrap().id, *id);
+        }
+        let mut listed: Vec<_> = db
+            .list(None)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|ticket| ticket.id)
+            .collect();
+        // SQLite only sorts serials of the same kind the same way.
+        listed.sort();
+        assert_eq!(listed, created);
+        drop(db);
+
+        // A reopened database keeps generating later ids.
+        let db = SqliteStore::open(&path).unwrap();
+        db.set_id_strategy(IdStrategy::Ulid);
+        let later = db
+            .create(ProjectKey::DEFAULT, draft("B"), &root)
+            .await
+            .unwrap();
+        assert!(later > created[1]);
+        let loaded = db.load().await.unwrap();
+        assert!(loaded.get(created[2]).is_some());
+    }
+}
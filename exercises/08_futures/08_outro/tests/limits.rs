@@ -0,0 +1,152 @@
+#[cfg(test)]
+mod tests {
+    use outro_08::auth::{Role, User};
+    use outro_08::limits::{Quota, RateLimitConfig, RateLimiter, RouteQuota};
+    use outro_08::server::{self, Config};
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::time::{Duration, Instant};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    const CLIENT: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+    const OTHER_CLIENT: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+
+    fn config() -> RateLimitConfig {
+        RateLimitConfig {
+            default: Quota::new(3, 1.0),
+            routes: vec![RouteQuota::new("POST", "/tickets", Quota::new(1, 0.5))],
+            idle_timeout: Duration::from_secs(30),
+        }
+    }
+
+    #[test]
+    fn test_burst_then_limited() {
+        let limiter = RateLimiter::new(config());
+        let now = Instant::now();
+
+        for _ in 0..3 {
+            assert!(limiter.check_at(CLIENT, "GET", "/tickets/0", now).is_ok());
+        }
+        let retry_after = limiter
+            .check_at(CLIENT, "GET", "/tickets/0", now)
+            .unwrap_err();
+        assert_eq!(retry_after, Duration::from_secs(1));
+
+        // Other clients have their own bucket.
+        assert!(limiter
+            .check_at(OTHER_CLIENT, "GET", "/tickets/0", now)
+            .is_ok());
+
+        // Tokens come back over time.
+        let later = now + Duration::from_secs(1);
+        assert!(limiter.check_at(CLIENT, "GET", "/tickets/0", later).is_ok());
+    }
+
+    #[test]
+    fn test_route_quota_is_separate_and_stricter() {
+        let limiter = RateLimiter::new(config());
+        let now = Instant::now();
+
+        assert!(limiter.check_at(CLIENT, "POST", "/tickets", now).is_ok());
+        let retry_after = limiter
+            .check_at(CLIENT, "POST", "/tickets", now)
+            .unwrap_err();
+        assert_eq!(retry_after, Duration::from_secs(2));
+
+        // Reads are still allowed.
+        assert!(limiter.check_at(CLIENT, "GET", "/tickets/0", now).is_ok());
+    }
+
+    #[test]
+    fn test_route_quota_matches_whole_segments() {
+        let limiter = RateLimiter::new(config());
+        let now = Instant::now();
+
+        limiter.check_at(CLIENT, "POST", "/tickets", now).unwrap();
+        // Neither a longer name nor a nested path is `/tickets`, so they
+        // fall back to the default quota.
+        for path in [
+            "/ticketsX",
+            "/tickets/TKT-1/comments",
+            "/tickets/TKT-1/attachments",
+        ] {
+            assert!(limiter.check_at(CLIENT, "POST", path, now).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_route_quota_wildcards() {
+        let quota = RouteQuota::new("POST", "/projects/*/tickets", Quota::new(1, 0.5));
+        let limiter = RateLimiter::new(RateLimitConfig {
+            routes: vec![quota],
+            ..config()
+        });
+        let now = Instant::now();
+
+        limiter
+            .check_at(CLIENT, "POST", "/projects/API/tickets", now)
+            .unwrap();
+        assert!(limiter
+            .check_at(CLIENT, "POST", "/projects/WEB/tickets", now)
+            .is_err());
+        assert!(limiter.check_at(CLIENT, "POST", "/projects", now).is_ok());
+        assert!(limiter
+            .check_at(CLIENT, "POST", "/projects/API/tickets/more", now)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_idle_clients_are_evicted() {
+        let limiter = RateLimiter::new(config());
+        let now = Instant::now();
+
+        limiter.check_at(CLIENT, "GET", "/tickets/0", now).unwrap();
+        limiter
+            .check_at(OTHER_CLIENT, "POST", "/tickets", now)
+            .unwrap();
+        assert_eq!(limiter.tracked_clients(), 2);
+
+        let later = now + Duration::from_secs(31);
+        limiter
+            .check_at(CLIENT, "GET", "/tickets/0", later)
+            .unwrap();
+        assert_eq!(limiter.tracked_clients(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_server_responds_with_429() {
+        let config = Config {
+            addr: String::from("127.0.0.1:0"),
+            rate_limits: config(),
+            ..Config::default()
+        };
+        let (listeners, state) = server::init(&config).await.unwrap();
+        state
+            .tokens
+            .write()
+            .await
+            .insert("secret".into(), User::new("alice", Role::Reporter));
+        let addr = listeners.http_addr().unwrap();
+        tokio::spawn(server::serve(listeners, state));
+
+        let body = r#"{"title":"A title","description":"A description"}"#;
+        let request = format!(
+            "POST /tickets HTTP/1.1\r\nConnection: close\r\nAuthorization: Bearer secret\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        );
+
+        let mut responses = vec![];
+        for _ in 0..2 {
+            let mut socket = TcpStream::connect(addr).await.unwrap();
+            socket.write_all(request.as_bytes()).await.unwrap();
+            let mut response = String::new();
+            socket.read_to_string(&mut response).await.unwrap();
+            responses.push(response);
+        }
+
+        assert!(responses[0].starts_with("HTTP/1.1 201 Created"));
+        assert!(responses[1].starts_with("HTTP/1.1 429 Too Many Requests"));
+        assert!(responses[1].contains("Retry-After: 2\r\n"));
+    }
+}
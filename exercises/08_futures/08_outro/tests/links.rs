@@ -0,0 +1,171 @@
+#[cfg(test)]
+mod tests {
+    use outro_08::auth::{Role, User};
+    use outro_08::data::{Status, TicketDraft, TicketPatch};
+    use outro_08::handlers::{self, PatchError};
+    use outro_08::links::{Link, LinkError, LinkKind, Links, TicketLinks};
+    use outro_08::store::{TicketId, TicketStore};
+    use std::sync::Arc;
+    use tempfile::TempDir;
+    use tokio::sync::RwLock;
+
+    fn link(from: u64, kind: LinkKind, to: u64) -> Link {
+        Link {
+            from: TicketId::from(from),
+            kind,
+            to: TicketId::from(to),
+        }
+    }
+
+    fn ids(ids: &[u64]) -> Vec<TicketId> {
+        ids.iter().copied().map(TicketId::from).collect()
+    }
+
+    fn store(tickets: usize) -> TicketStore {
+        let mut store = TicketStore::new();
+        for i in 0..tickets {
+            store.add_ticket(TicketDraft::new(format!("Ticket {}", i), None));
+        }
+        store
+    }
+
+    #[test]
+    fn test_blocking_cycles_are_rejected() {
+        let mut links = Links::new();
+        links.add(link(1, LinkKind::Blocks, 2)).unwrap();
+        links.add(link(2, LinkKind::Blocks, 3)).unwrap();
+        assert_eq!(links.add(link(1, LinkKind::Blocks, 2)), Ok(false));
+
+        let error = links.add(link(3, LinkKind::Blocks, 1)).unwrap_err();
+        assert_eq!(
+            error,
+            LinkError::Cycle {
+                kind: LinkKind::Blocks,
+                path: ids(&[1, 2, 3]),
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "Linking would create a cycle: TKT-1 -> TKT-2 -> TKT-3"
+        );
+        assert_eq!(
+            links.add(link(4, LinkKind::Blocks, 4)),
+            Err(LinkError::SelfLink)
+        );
+        // Cycles only count links of the same kind.
+        assert_eq!(links.add(link(3, LinkKind::DuplicateOf, 1)), Ok(true));
+    }
+
+    #[test]
+    fn test_single_parent_and_original() {
+        let mut links = Links::new();
+        links.add(link(2, LinkKind::ChildOf, 1)).unwrap();
+        assert_eq!(
+            links.add(link(2, LinkKind::ChildOf, 3)),
+            Err(LinkError::HasParent {
+                child: TicketId::from(2),
+                parent: TicketId::from(1)
+            })
+        );
+        assert!(matches!(
+            links.add(link(1, LinkKind::ChildOf, 2)),
+            Err(LinkError::Cycle { .. })
+        ));
+
+        links.add(link(5, LinkKind::DuplicateOf, 4)).unwrap();
+        assert_eq!(
+            links.add(link(5, LinkKind::DuplicateOf, 6)),
+            Err(LinkError::AlreadyDuplicate {
+                duplicate: TicketId::from(5),
+                original: TicketId::from(4)
+            })
+        );
+    }
+
+    #[test]
+    fn test_transitive_queries() {
+        let mut links = Links::new();
+        // 1 and 2 block 3, which blocks 4. 5 and 6 are subtasks of 4, 7 of 6.
+        for new in [
+            link(1, LinkKind::Blocks, 3),
+            link(2, LinkKind::Blocks, 3),
+            link(3, LinkKind::Blocks, 4),
+            link(5, LinkKind::ChildOf, 4),
+            link(6, LinkKind::ChildOf, 4),
+            link(7, LinkKind::ChildOf, 6),
+        ] {
+            links.add(new).unwrap();
+        }
+
+        assert_eq!(links.blockers(TicketId::from(4)), ids(&[3]));
+        assert_eq!(links.all_blockers(TicketId::from(4)), ids(&[3, 1, 2]));
+        assert_eq!(links.blocking(TicketId::from(1)), ids(&[3]));
+        assert_eq!(links.descendants(TicketId::from(4)), ids(&[5, 6, 7]));
+
+        let summary = TicketLinks::of(&links, TicketId::from(6));
+        assert_eq!(summary.parent, Some(TicketId::from(4)));
+        assert_eq!(summary.children, ids(&[7]));
+        assert!(summary.all_blockers.is_empty());
+    }
+
+    #[test]
+    fn test_deleting_a_ticket_removes_its_links() {
+        let mut store = store(4);
+        store.link(link(1, LinkKind::ChildOf, 0)).unwrap();
+        store.link(link(0, LinkKind::Blocks, 2)).unwrap();
+        store.link(link(3, LinkKind::Blocks, 2)).unwrap();
+        assert_eq!(
+            store.link(link(0, LinkKind::Blocks, 9)),
+            Err(LinkError::NotFound(TicketId::from(9)))
+        );
+
+        store.remove(TicketId::from(0));
+        // The subtask is kept, without a parent.
+        assert!(store.get(TicketId::from(1)).is_some());
+        assert_eq!(store.links().parent(TicketId::from(1)), None);
+        assert_eq!(store.links().blockers(TicketId::from(2)), ids(&[3]));
+    }
+
+    #[test]
+    fn test_links_are_saved() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("tickets.json");
+        let mut store = store(2);
+        store.link(link(0, LinkKind::Blocks, 1)).unwrap();
+        store.save(&path).unwrap();
+
+        let loaded = TicketStore::load(&path).unwrap();
+        assert_eq!(loaded.links(), store.links());
+    }
+
+    #[tokio::test]
+    async fn test_blocked_tickets_cant_be_done() {
+        let mut store = store(3);
+        store.link(link(0, LinkKind::Blocks, 2)).unwrap();
+        store.link(link(1, LinkKind::Blocks, 2)).unwrap();
+        let store = Arc::new(RwLock::new(store));
+        let user = User::new("alice", Role::Maintainer);
+        let done = |id: u64| TicketPatch {
+            id: TicketId::from(id),
+            title: None,
+            description: None,
+            status: Some(Status::Done),
+        };
+
+        let result = handlers::update_ticket_endpoint(done(2), Arc::clone(&store), &user).await;
+        assert_eq!(result, Err(PatchError::Blocked(ids(&[0, 1]))));
+
+        handlers::update_ticket_endpoint(done(0), Arc::clone(&store), &user)
+            .await
+            .unwrap();
+        let result = handlers::update_ticket_endpoint(done(2), Arc::clone(&store), &user).await;
+        assert_eq!(result, Err(PatchError::Blocked(ids(&[1]))));
+
+        handlers::update_ticket_endpoint(done(1), Arc::clone(&store), &user)
+            .await
+            .unwrap();
+        handlers::update_ticket_endpoint(done(2), Arc::clone(&store), &user)
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,107 @@
+#[cfg(test)]
+mod tests {
+    use outro_08::auth::{Role, User};
+    use outro_08::limits::RateLimitConfig;
+    use outro_08::loadgen::{self, Latency, LoadConfig, Mix, Operation};
+    use outro_08::server::{self, Config};
+    use std::net::SocketAddr;
+    use std::time::Duration;
+
+    async fn start(rate_limits: RateLimitConfig) -> SocketAddr {
+        let config = Config {
+            addr: String::from("127.0.0.1:0"),
+            rate_limits,
+            ..Config::default()
+        };
+        let (listeners, state) = server::init(&config).await.unwrap();
+        let mut tokens = state.tokens.write().await;
+        tokens.insert("alice".into(), User::new("alice", Role::Maintainer));
+        drop(tokens);
+        let addr = listeners.http_addr().unwrap();
+        tokio::spawn(server::serve(listeners, state));
+        addr
+    }
+
+    fn config(addr: SocketAddr) -> LoadConfig {
+        LoadConfig {
+            addr: addr.to_string(),
+            token: String::from("alice"),
+            concurrency: 4,
+            duration: Duration::from_millis(300),
+            ..LoadConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_mix() {
+        let mix: Mix = "get=3, patch=1".parse().unwrap();
+        assert_eq!(
+            mix,
+            Mix {
+                create: 0,
+                get: 3,
+                patch: 1
+            }
+        );
+        assert_eq!(
+            "create=1,get=8,patch=1".parse::<Mix>().unwrap(),
+            Mix::default()
+        );
+        assert!("get=0".parse::<Mix>().is_err());
+        assert!("delete=1".parse::<Mix>().is_err());
+        assert!("get".parse::<Mix>().is_err());
+    }
+
+    #[test]
+    fn test_latency_percentiles() {
+        let mut samples: Vec<Duration> = (1..=200).rev().map(Duration::from_millis).collect();
+        let latency = Latency::new(&mut samples);
+        assert_eq!(latency.p50, Duration::from_millis(100));
+        assert_eq!(latency.p90, Duration::from_millis(180));
+        assert_eq!(latency.p99, Duration::from_millis(198));
+        assert_eq!(latency.max, Duration::from_millis(200));
+        assert_eq!(Latency::new(&mut []), Latency::default());
+        let mut one = [Duration::from_millis(7)];
+        assert_eq!(Latency::new(&mut one).p50, Duration::from_millis(7));
+    }
+
+    #[tokio::test]
+    async fn test_run() {
+        let addr = start(RateLimitConfig::unlimited()).await;
+        for keep_alive in [true, false] {
+            let report = loadgen::run(LoadConfig {
+                keep_alive,
+                ..config(addr)
+            })
+            .await
+            .unwrap();
+            assert!(report.errors.is_empty(), "{:?}", report.errors);
+            let operations: Vec<Operation> = report
+                .operations
+                .iter()
+                .map(|report| report.operation)
+                .collect();
+            assert_eq!(operations, Operation::ALL);
+            let get = &report.operations[1];
+            assert!(get.requests > 0);
+            assert!(get.latency.p50 <= get.latency.p99);
+            assert!(report.elapsed >= Duration::from_millis(300));
+            assert!(report.to_string().starts_with("OPERATION"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reports_rate_limiting() {
+        let addr = start(RateLimitConfig::default()).await;
+        let report = loadgen::run(LoadConfig {
+            mix: "get=1".parse().unwrap(),
+            ..config(addr)
+        })
+        .await
+        .unwrap();
+        assert!(report.errors["429"] > 0);
+        assert_eq!(report.operations.len(), 1);
+        assert_eq!(report.operations[0].errors, report.errors["429"]);
+        assert!(report.to_string().contains("\nERROR"));
+    }
+}
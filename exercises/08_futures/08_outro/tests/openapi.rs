@@ -0,0 +1,431 @@
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use outro_08::attachments::Attachment;
+    use outro_08::auth::{IssuedToken, Role, TokenRequest, User};
+    use outro_08::comments::{Comment, CommentDraft, CommentId};
+    use outro_08::data::{ErrorBody, Status, Ticket, TicketDraft, TicketPatch};
+    use outro_08::history::Report;
+    use outro_08::limits::{Quota, RateLimitConfig};
+    use outro_08::links::{LinkKind, LinkRequest, TicketLinks};
+    use outro_08::openapi::{self, ApiSchema};
+    use outro_08::projects::{Project, ProjectDraft, ProjectPatch, ProjectSettings};
+    use outro_08::query::SavedQuery;
+    use outro_08::reports::{self, Durations, Period, Summary};
+    use outro_08::routes::Route;
+    use outro_08::server::{self, Config};
+    use outro_08::store::TicketId;
+    use outro_08::trash::Trashed;
+    use serde::Serialize;
+    use serde_json::Value;
+    use std::collections::BTreeSet;
+    use std::net::SocketAddr;
+    use tempfile::TempDir;
+    use ticket_fields::test_helpers::{ticket_description, ticket_title};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    /// The directory holds attachments and has to outlive the server.
+    async fn start() -> (SocketAddr, TempDir) {
+        let dir = TempDir::new().unwrap();
+        // Walking every operation takes more requests than the default quotas allow.
+        let config = Config {
+            addr: String::from("127.0.0.1:0"),
+            attachments_dir: dir.path().to_path_buf(),
+            rate_limits: RateLimitConfig {
+                default: Quota::new(1000, 1000.0),
+                routes: vec![],
+                ..RateLimitConfig::default()
+            },
+            ..Config::default()
+        };
+        let (listeners, state) = server::init(&config).await.unwrap();
+        state
+            .tokens
+            .write()
+            .await
+            .insert("admin".into(), User::new("root", Role::Admin));
+        let addr = listeners.http_addr().unwrap();
+        tokio::spawn(server::serve(listeners, state));
+        (addr, dir)
+    }
+
+    /// Returns the status code and the body.
+    async fn send(
+        addr: SocketAddr,
+        method: &str,
+        path: &str,
+        token: Option<&str>,
+        body: &str,
+    ) -> (u16, String) {
+        let auth = token
+            .map(|token| format!("Authorization: Bearer {}\r\n", token))
+            .unwrap_or_default();
+        let request = format!(
+            "{} {} HTTP/1.1\r\nConnection: close\r\n{}Content-Length: {}\r\n\r\n{}",
+            method,
+            path,
+            auth,
+            body.len(),
+            body
+        );
+
+        let mut socket = TcpStream::connect(addr).await.unwrap();
+        socket.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        socket.read_to_string(&mut response).await.unwrap();
+
+        let status = response[9..12].parse().unwrap();
+        let body = response
+            .split_once("\r\n\r\n")
+            .map(|(_, body)| body.to_string())
+            .unwrap_or_default();
+        (status, body)
+    }
+
+    fn operations(spec: &Value) -> Vec<(String, String, Value)> {
+        let mut operations = vec![];
+        for (path, item) in spec["paths"].as_object().unwrap() {
+            for (method, operation) in item.as_object().unwrap() {
+                operations.push((method.to_uppercase(), path.clone(), operation.clone()));
+            }
+        }
+        operations
+    }
+
+    fn documented(operation: &Value, status: u16) -> bool {
+        operation["responses"].get(status.to_string()).is_some()
+    }
+
+    #[test]
+    fn test_every_route_is_documented() {
+        let spec = openapi::spec();
+        let documented: BTreeSet<(String, String)> = operations(&spec)
+            .into_iter()
+            .map(|(method, path, _)| (method, path))
+            .collect();
+        let handled: BTreeSet<(String, String)> = Route::ALL
+            .into_iter()
+            .map(|route| (route.method().to_string(), route.path().to_string()))
+            .collect();
+        assert_eq!(documented, handled);
+    }
+
+    #[test]
+    fn test_references_resolve() {
+        fn walk(value: &Value, spec: &Value) {
+            match value {
+                Value::Object(map) => {
+                    if let Some(Value::String(reference)) = map.get("$ref") {
+                        let pointer = reference.strip_prefix('#').unwrap();
+                        assert!(spec.pointer(pointer).is_some(), "dangling {}", reference);
+                    }
+                    map.values().for_each(|value| walk(value, spec));
+                }
+                Value::Array(values) => values.iter().for_each(|value| walk(value, spec)),
+                _ => {}
+            }
+        }
+
+        let spec = openapi::spec();
+        walk(&spec, &spec);
+    }
+
+    /// The documented properties must be exactly the fields serde produces,
+    /// and every required property must always be present.
+    fn check_fields<T: ApiSchema + Serialize>(sample: &T) {
+        let schema = T::schema();
+        let properties: BTreeSet<&String> =
+            schema["properties"].as_object().unwrap().keys().collect();
+        let serialized = serde_json::to_value(sample).unwrap();
+        let fields: BTreeSet<&String> = serialized.as_object().unwrap().keys().collect();
+        assert_eq!(properties, fields, "{} drifted", T::NAME);
+
+        // OpenAPI 3.0 doesn't allow empty `required` lists, so they're left out.
+        for required in schema["required"].as_array().into_iter().flatten() {
+            let required = required.as_str().unwrap();
+            assert!(!serialized[required].is_null(), "{}.{}", T::NAME, required);
+        }
+    }
+
+    fn check_enum<T: ApiSchema + Serialize>(variants: &[T]) {
+        let documented: BTreeSet<String> = T::schema()["enum"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|variant| variant.to_string())
+            .collect();
+        let serialized: BTreeSet<String> = variants
+            .iter()
+            .map(|variant| serde_json::to_string(variant).unwrap())
+            .collect();
+        assert_eq!(documented, serialized, "{} drifted", T::NAME);
+    }
+
+    #[test]
+    fn test_schemas_match_types() {
+        let ticket = Ticket {
+            id: TicketId::from(0),
+            title: ticket_title(),
+            description: ticket_description().into(),
+            status: Status::ToDo,
+            reporter: Some("alice".into()),
+            last_editor: Some("bob".into()),
+        };
+        check_fields(&ticket);
+        check_fields(&TicketDraft {
+            title: ticket_title(),
+            description: ticket_description().into(),
+        });
+        check_fields(&TicketPatch {
+            id: TicketId::from(0),
+            title: Some(ticket_title()),
+            description: Some(ticket_description().into()),
+            status: Some(Status::Done),
+        });
+        check_fields(&TokenRequest {
+            name: "carol".into(),
+            role: Role::Viewer,
+        });
+        check_fields(&IssuedToken {
+            token: "token".into(),
+            name: "carol".into(),
+            role: Role::Viewer,
+        });
+        check_fields(&ErrorBody {
+            error: "Oops".into(),
+        });
+        check_fields(&LinkRequest {
+            kind: LinkKind::Blocks,
+            to: TicketId::from(1),
+        });
+        check_fields(&TicketLinks {
+            parent: Some(TicketId::from(1)),
+            duplicate_of: Some(TicketId::from(2)),
+            ..TicketLinks::default()
+        });
+
+        check_fields(&Comment {
+            id: CommentId(0),
+            ticket: TicketId::from(0),
+            author: "alice".into(),
+            body: ticket_description().into(),
+            created_at: Utc::now(),
+            edited_at: Some(Utc::now()),
+        });
+        check_fields(&CommentDraft {
+            body: ticket_description().into(),
+        });
+        check_fields(&Attachment {
+            ticket: TicketId::from(0),
+            digest: "0".repeat(64),
+            name: "notes.txt".into(),
+            mime: "text/plain; charset=utf-8".into(),
+            size: 5,
+            uploaded_by: "alice".into(),
+            uploaded_at: Utc::now(),
+        });
+
+        check_fields(&Project {
+            key: "API".parse().unwrap(),
+            settings: ProjectSettings::new("Public API"),
+            next_number: 42,
+        });
+        check_fields(&ProjectDraft {
+            key: "API".parse().unwrap(),
+            settings: ProjectSettings::new("Public API"),
+        });
+        check_fields(&ProjectPatch {
+            name: Some("API".into()),
+            description: Some("Everything behind /v1".into()),
+            archived: Some(true),
+        });
+
+        check_fields(&Trashed {
+            ticket: ticket.clone(),
+            deleted_by: "root".into(),
+            deleted_at: Utc::now(),
+        });
+
+        check_fields(&Report {
+            tickets: vec![TicketId::from(0)],
+            conflict: Some("Ticket TKT-0 changed since, so the operation was dropped".into()),
+        });
+
+        check_fields(&SavedQuery {
+            name: "in-progress".into(),
+            query: "status:InProgress".into(),
+        });
+
+        let durations = Durations {
+            tickets: 1,
+            average_hours: Some(2.5),
+            max_hours: Some(2.5),
+            longest: Some(TicketId::from(0)),
+        };
+        check_fields(&durations);
+        let summary = reports::summarize([(&ticket, &[][..])], Period::default(), Utc::now());
+        check_fields(&summary.counts[0]);
+        check_fields(&summary.throughput[0]);
+        check_fields(&summary.age_buckets[0]);
+        check_fields(&Summary {
+            cycle_time: durations,
+            open_age: durations,
+            ..summary
+        });
+
+        check_enum(&[Status::ToDo, Status::InProgress, Status::Done]);
+        check_enum(&[Role::Viewer, Role::Reporter, Role::Maintainer, Role::Admin]);
+        check_enum(&LinkKind::ALL);
+    }
+
+    #[tokio::test]
+    async fn test_handlers_only_return_documented_responses() {
+        let (addr, _dir) = start().await;
+        let spec = openapi::spec();
+
+        // Deleting goes last so the other `/tickets/{id}` operations find the ticket,
+        // and the link examples need a second ticket to link to.
+        // Comment and attachment operations work on the ones posted below,
+        // and `/trash/{id}` operations on a third ticket, deleted before each.
+        // `/templates/{name}` and `/queries/{name}` operations use the template and
+        // query posted to `/templates` and `/queries`, which sort before them.
+        let mut operations = operations(&spec);
+        operations.sort_by_key(|(method, path, _)| (method == "DELETE", path == "/tickets/{id}"));
+
+        for _ in 0..3 {
+            send(
+                addr,
+                "POST",
+                "/tickets",
+                Some("admin"),
+                r#"{"title":"A","description":"B"}"#,
+            )
+            .await;
+        }
+        send(
+            addr,
+            "POST",
+            "/tickets/0/comments",
+            Some("admin"),
+            r#"{"body":"First!"}"#,
+        )
+        .await;
+        let (_, attachment) = send(
+            addr,
+            "POST",
+            "/tickets/0/attachments?name=a.txt",
+            Some("admin"),
+            "hello",
+        )
+        .await;
+        let attachment: Attachment = serde_json::from_str(&attachment).unwrap();
+
+        for (method, path, operation) in operations {
+            let id = if path.starts_with("/trash/") {
+                // A 404 means it's still in the trash from the last operation.
+                send(addr, "DELETE", "/tickets/2", Some("admin"), "").await;
+                "2"
+            } else {
+                "0"
+            };
+            let name = if path.starts_with("/queries/") {
+                "in-progress"
+            } else {
+                "release"
+            };
+            let mut path = path
+                .replace("{id}", id)
+                .replace("{key}", "TKT")
+                .replace("{comment}", "0")
+                .replace("{name}", name)
+                .replace("{attachment}", &attachment.digest);
+            let required: Vec<String> = operation["parameters"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter(|parameter| parameter["in"] == "query" && parameter["required"] == true)
+                .map(|parameter| {
+                    let example = parameter["example"].as_str().unwrap();
+                    format!("{}={}", parameter["name"].as_str().unwrap(), example)
+                })
+                .collect();
+            if !required.is_empty() {
+                path = format!("{}?{}", path, required.join("&"));
+            }
+            let example = match operation.pointer("/requestBody/content") {
+                Some(content) if content.get("application/octet-stream").is_some() => {
+                    String::from("file contents")
+                }
+                Some(content) => content["application/json"]["example"].to_string(),
+                None => String::new(),
+            };
+            let json = operation
+                .pointer("/requestBody/content/application~1json")
+                .is_some();
+
+            let (status, _) = send(addr, &method, &path, Some("admin"), &example).await;
+            assert!(status < 300, "{} {} returned {}", method, path, status);
+            assert!(
+                documented(&operation, status),
+                "{} {} -> {}",
+                method,
+                path,
+                status
+            );
+
+            if operation.get("security") != Some(&Value::Array(vec![])) {
+                let (status, _) = send(addr, &method, &path, None, &example).await;
+                assert_eq!(status, 401);
+                assert!(documented(&operation, status));
+            }
+
+            if json {
+                let (status, body) = send(addr, &method, &path, Some("admin"), "{").await;
+                assert_eq!(status, 400);
+                assert!(documented(&operation, status));
+                serde_json::from_str::<ErrorBody>(&body).unwrap();
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_field_constraints_are_enforced() {
+        let (addr, _dir) = start().await;
+        let spec = openapi::spec();
+        let max = spec["components"]["schemas"]["TicketTitle"]["x-maxGraphemes"]
+            .as_u64()
+            .unwrap() as usize;
+
+        // Limits count user-perceived characters, not bytes.
+        let title = "é".repeat(max);
+        let body = format!(r#"{{"title":"{}","description":"B"}}"#, title);
+        let (status, _) = send(addr, "POST", "/tickets", Some("admin"), &body).await;
+        assert_eq!(status, 201);
+
+        let title = "a".repeat(max + 1);
+        let body = format!(r#"{{"title":"{}","description":"B"}}"#, title);
+        let (status, _) = send(addr, "POST", "/tickets", Some("admin"), &body).await;
+        assert_eq!(status, 400);
+    }
+
+    #[tokio::test]
+    async fn test_spec_is_served_without_auth() {
+        let (addr, _dir) = start().await;
+        let (status, body) = send(addr, "GET", "/openapi.json", None, "").await;
+        assert_eq!(status, 200);
+        assert_eq!(
+            serde_json::from_str::<Value>(&body).unwrap(),
+            openapi::spec()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unknown_routes_are_not_found() {
+        let (addr, _dir) = start().await;
+        let (status, _) = send(addr, "PUT", "/tickets/0", Some("admin"), "").await;
+        assert_eq!(status, 404);
+        let (status, _) = send(addr, "GET", "/nowhere", Some("admin"), "").await;
+        assert_eq!(status, 404);
+    }
+}
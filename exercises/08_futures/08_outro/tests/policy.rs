@@ -0,0 +1,122 @@
+#[cfg(test)]
+mod tests {
+    use outro_08::auth::{Role, User};
+    use outro_08::client::{Client, ClientError};
+    use outro_08::data::{Status, TicketDraft, TicketPatch};
+    use outro_08::server::{self, Config};
+    use outro_08::templates::{Template, TemplateRequest, TicketTemplate};
+    use std::collections::BTreeMap;
+    use std::path::Path;
+    use tempfile::TempDir;
+    use ticket_fields::{MarkdownDescription, Policy, TicketTitle};
+
+    fn strict() -> Policy {
+        let policy = r#"{"title": {"max_graphemes": 10, "banned_words": ["urgent"]}}"#;
+        serde_json::from_str(policy).unwrap()
+    }
+
+    async fn start(database: &Path, policy: Option<Policy>) -> Client {
+        let config = Config {
+            addr: String::from("127.0.0.1:0"),
+            database: Some(database.to_path_buf()),
+            policy,
+            ..Config::default()
+        };
+        let (listeners, state) = server::init(&config).await.unwrap();
+        state
+            .tokens
+            .write()
+            .await
+            .insert("admin".into(), User::new("root", Role::Admin));
+        let addr = listeners.http_addr().unwrap();
+        tokio::spawn(server::serve(listeners, state));
+        Client::new(addr.to_string(), "admin")
+    }
+
+    fn assert_rejected<T: std::fmt::Debug>(result: Result<T, ClientError>) {
+        match result {
+            Err(ClientError::BadRequest(message)) => assert!(
+                message.contains("The title cannot contain \"Urgent\""),
+                "{}",
+                message
+            ),
+            other => panic!("Expected a bad request, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_server_enforces_its_policy() {
+        let dir = TempDir::new().unwrap();
+        let client = start(&dir.path().join("tickets.db"), Some(strict())).await;
+
+        // Only the built-in rules apply on the client side.
+        let draft = TicketDraft {
+            title: TicketTitle::try_from("Urgent fix").unwrap(),
+            description: MarkdownDescription::try_from("A").unwrap(),
+        };
+        assert_rejected(client.create(&draft).await);
+        assert_rejected(client.create_in("TKT".parse().unwrap(), &draft).await);
+
+        let draft = TicketDraft {
+            title: TicketTitle::try_from("Small fix").unwrap(),
+            ..draft
+        };
+        let id = client.create(&draft).await.unwrap();
+        let patch = TicketPatch {
+            id,
+            title: Some(TicketTitle::try_from("Urgent fix").unwrap()),
+            description: None,
+            status: None,
+        };
+        assert_rejected(client.patch(&patch).await);
+
+        let template = Template {
+            name: "hotfix".into(),
+            description: "Fix it now".into(),
+            tickets: vec![TicketTemplate {
+                title: "{{word}} fix".into(),
+                description: "Fix it".into(),
+            }],
+        };
+        client.create_template(&template).await.unwrap();
+        let request = TemplateRequest {
+            project: None,
+            values: BTreeMap::from([(String::from("word"), String::from("Urgent"))]),
+        };
+        assert_rejected(client.create_from_template("hotfix", &request).await);
+        assert_eq!(client.list().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_policies_only_apply_to_their_server() {
+        let dir = TempDir::new().unwrap();
+        let client = start(&dir.path().join("tickets.db"), None).await;
+        let draft = TicketDraft::new(String::from("Urgent fix"), None);
+        assert!(client.create(&draft).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_stricter_policies_keep_existing_tickets() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("tickets.db");
+        let client = start(&path, None).await;
+        let draft = TicketDraft::new(String::from("Urgent fix"), None);
+        let id = client.create(&draft).await.unwrap();
+
+        let client = start(&path, Some(strict())).await;
+        assert_eq!(client.get(id).await.unwrap().title.0, "Urgent fix");
+        // Only new titles and descriptions are checked.
+        let patch = TicketPatch {
+            id,
+            title: None,
+            description: None,
+            status: Some(Status::InProgress),
+        };
+        client.patch(&patch).await.unwrap();
+        let patch = TicketPatch {
+            title: Some(TicketTitle::try_from("Urgent fix!").unwrap()),
+            ..patch
+        };
+        assert_rejected(client.patch(&patch).await);
+    }
+}
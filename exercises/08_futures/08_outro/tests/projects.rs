@@ -0,0 +1,245 @@
+#[cfg(test)]
+mod tests {
+    use outro_08::auth::{Role, User};
+    use outro_08::client::{Client, ClientError};
+    use outro_08::data::TicketDraft;
+    use outro_08::projects::{
+        ProjectDraft, ProjectError, ProjectKey, ProjectPatch, ProjectSettings,
+    };
+    use outro_08::server::{self, Config};
+    use outro_08::snapshot::Snapshot;
+    use outro_08::store::{TicketId, TicketStore};
+    use std::net::SocketAddr;
+    use tempfile::TempDir;
+
+    fn key(key: &str) -> ProjectKey {
+        key.parse().unwrap()
+    }
+
+    fn project(key_: &str, name: &str) -> ProjectDraft {
+        ProjectDraft {
+            key: key(key_),
+            settings: ProjectSettings::new(name),
+        }
+    }
+
+    fn archive() -> ProjectPatch {
+        ProjectPatch {
+            archived: Some(true),
+            ..ProjectPatch::default()
+        }
+    }
+
+    async fn start() -> SocketAddr {
+        let config = Config {
+            addr: String::from("127.0.0.1:0"),
+            ..Config::default()
+        };
+        let (listeners, state) = server::init(&config).await.unwrap();
+        let mut tokens = state.tokens.write().await;
+        tokens.insert("admin".into(), User::new("root", Role::Admin));
+        tokens.insert("alice".into(), User::new("alice", Role::Reporter));
+        drop(tokens);
+        let addr = listeners.http_addr().unwrap();
+        tokio::spawn(server::serve(listeners, state));
+        addr
+    }
+
+    #[test]
+    fn test_ids() {
+        let id: TicketId = "api-42".parse().unwrap();
+        assert_eq!(id, TicketId::new(key("API"), 42));
+        assert_eq!(id.to_string(), "API-42");
+        // Bare numbers are tickets of the default project, as before projects.
+        assert_eq!("7".parse::<TicketId>().unwrap(), TicketId::from(7));
+        assert_eq!(TicketId::from(7).to_string(), "TKT-7");
+
+        for id in [
+            "", "API-", "-4", "A-4", "API-x", "1API-4", "API-4-2", "ÄPI-4",
+        ] {
+            assert!(id.parse::<TicketId>().is_err(), "{}", id);
+        }
+        assert!("ABCDEFGHIJK".parse::<ProjectKey>().is_err());
+        assert_eq!(key("abcdefghij").as_str(), "ABCDEFGHIJ");
+
+        // Ids are strings in JSON, but numbers from before projects are still read.
+        assert_eq!(serde_json::to_string(&id).unwrap(), r#""API-42""#);
+        assert_eq!(
+            serde_json::from_str::<TicketId>("3").unwrap(),
+            TicketId::from(3)
+        );
+        assert_eq!(serde_json::from_str::<TicketId>(r#""API-42""#).unwrap(), id);
+    }
+
+    #[test]
+    fn test_numbers_are_per_project() {
+        let alice = User::new("alice", Role::Reporter);
+        let mut store = TicketStore::new();
+        store.create_project(project("API", "Public API")).unwrap();
+
+        let draft = || TicketDraft::new("A".into(), None);
+        assert_eq!(store.add_ticket(draft()), TicketId::from(0));
+        let api = store.add_ticket_to(key("API"), draft(), &alice).unwrap();
+        assert_eq!(api, TicketId::new(key("API"), 0));
+        assert_eq!(store.add_ticket(draft()), TicketId::from(1));
+        let next = store.add_ticket_to(key("API"), draft(), &alice).unwrap();
+        assert_eq!(next.to_string(), "API-1");
+        assert_eq!(store.tickets_in(key("API")).len(), 2);
+
+        assert_eq!(
+            store.add_ticket_to(key("WEB"), draft(), &alice),
+            Err(ProjectError::NotFound(key("WEB")))
+        );
+        assert_eq!(
+            store.create_project(project("api", "Again")).unwrap_err(),
+            ProjectError::Exists(key("API"))
+        );
+        assert_eq!(
+            store.create_project(project("WEB", " ")).unwrap_err(),
+            ProjectError::InvalidName
+        );
+    }
+
+    #[test]
+    fn test_archiving() {
+        let alice = User::new("alice", Role::Reporter);
+        let mut store = TicketStore::new();
+        store.create_project(project("OLD", "Legacy")).unwrap();
+        let id = store
+            .add_ticket_to(key("OLD"), TicketDraft::new("A".into(), None), &alice)
+            .unwrap();
+
+        store.update_project(key("OLD"), archive()).unwrap();
+        assert_eq!(
+            store.add_ticket_to(key("OLD"), TicketDraft::new("B".into(), None), &alice),
+            Err(ProjectError::Archived(key("OLD")))
+        );
+        // Its tickets are kept.
+        assert!(store.get(id).is_some());
+
+        assert_eq!(
+            store
+                .update_project(ProjectKey::DEFAULT, archive())
+                .unwrap_err(),
+            ProjectError::ArchiveDefault
+        );
+        assert!(
+            !store
+                .project(ProjectKey::DEFAULT)
+                .unwrap()
+                .settings
+                .archived
+        );
+    }
+
+    #[test]
+    fn test_projects_are_saved() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("tickets.json");
+        let alice = User::new("alice", Role::Reporter);
+        let mut store = TicketStore::new();
+        store.create_project(project("API", "Public API")).unwrap();
+        let id = store
+            .add_ticket_to(key("API"), TicketDraft::new("A".into(), None), &alice)
+            .unwrap();
+        store.remove(id);
+
+        store.save(&path).unwrap();
+        let loaded = TicketStore::load(&path).unwrap();
+        assert!(loaded.projects().eq(store.projects()));
+        // Numbers aren't reused after a delete.
+        assert_eq!(
+            loaded.next_id(key("API")),
+            Some(TicketId::new(key("API"), 1))
+        );
+
+        let snapshot = Snapshot::capture(&store);
+        let restored = Snapshot::decode(&snapshot.to_bytes()).unwrap().restore();
+        assert!(restored.projects().eq(store.projects()));
+    }
+
+    #[test]
+    fn test_loads_stores_from_before_projects() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("tickets.json");
+        let file = r#"{
+            "next_id": 3,
+            "tickets": [
+                {"id": 0, "title": "A", "description": "B", "status": "ToDo"},
+                {"id": 2, "title": "C", "description": "D", "status": "ToDo"}
+            ],
+            "links": [{"from": 2, "kind": "Blocks", "to": 0}]
+        }"#;
+        std::fs::write(&path, file).unwrap();
+
+        let mut store = TicketStore::load(&path).unwrap();
+        assert_eq!(store.projects().count(), 1);
+        assert!(store.get(TicketId::from(2)).is_some());
+        assert_eq!(store.open_blockers(TicketId::from(0)), [TicketId::from(2)]);
+        assert_eq!(
+            store.add_ticket(TicketDraft::new("E".into(), None)),
+            TicketId::from(3)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_project_routes() {
+        let addr = start().await;
+        let admin = Client::new(addr.to_string(), "admin");
+        let alice = Client::new(addr.to_string(), "alice");
+        let draft = TicketDraft::new("A".into(), None);
+
+        assert!(matches!(
+            alice.create_project(&project("API", "Public API")).await,
+            Err(ClientError::Forbidden)
+        ));
+        let api = admin
+            .create_project(&project("api", "Public API"))
+            .await
+            .unwrap();
+        assert_eq!(api.key, key("API"));
+        assert!(matches!(
+            admin.create_project(&project("API", "Again")).await,
+            Err(ClientError::Conflict(_))
+        ));
+        let keys: Vec<ProjectKey> = alice
+            .projects()
+            .await
+            .unwrap()
+            .iter()
+            .map(|project| project.key)
+            .collect();
+        assert_eq!(keys, [key("API"), ProjectKey::DEFAULT]);
+
+        let id = alice.create_in(key("API"), &draft).await.unwrap();
+        assert_eq!(id.to_string(), "API-0");
+        assert_eq!(alice.get(id).await.unwrap().id, id);
+        assert_eq!(alice.create(&draft).await.unwrap().to_string(), "TKT-0");
+        let tickets = alice.project_tickets(key("API")).await.unwrap();
+        assert_eq!(tickets.len(), 1);
+        assert_eq!(alice.project(key("API")).await.unwrap().next_number, 1);
+        assert!(matches!(
+            alice.create_in(key("WEB"), &draft).await,
+            Err(ClientError::NotFound)
+        ));
+        assert!(matches!(
+            alice.project_tickets(key("WEB")).await,
+            Err(ClientError::NotFound)
+        ));
+
+        assert!(matches!(
+            alice.update_project(key("API"), &archive()).await,
+            Err(ClientError::Forbidden)
+        ));
+        let archived = admin.update_project(key("API"), &archive()).await.unwrap();
+        assert!(archived.settings.archived);
+        assert!(matches!(
+            alice.create_in(key("API"), &draft).await,
+            Err(ClientError::Conflict(_))
+        ));
+        assert!(matches!(
+            admin.update_project(ProjectKey::DEFAULT, &archive()).await,
+            Err(ClientError::BadRequest(_))
+        ));
+    }
+}
@@ -0,0 +1,139 @@
+// Arbitrary input through the hand-rolled parts of request handling: the
+// connection loop, path routing and query strings. The fuzz targets in
+// `fuzz/` push the same pipeline much further; these run with every `cargo test`.
+#[cfg(test)]
+mod tests {
+    use outro_08::auth::{Role, TokenStore, User};
+    use outro_08::helpers;
+    use outro_08::routes::{self, Route};
+    use outro_08::server::{Config, State};
+    use outro_08::store::{TicketId, TicketStore};
+    use proptest::prelude::*;
+    use tempfile::TempDir;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Sends `input` to a fresh server over one in-memory connection, closes
+    /// it, and returns everything the server wrote back. Panics if the
+    /// server did.
+    fn serve(input: &[u8]) -> Vec<u8> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .start_paused(true)
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let dir = TempDir::new().unwrap();
+            let config = Config {
+                attachments_dir: dir.path().to_path_buf(),
+                ..Config::default()
+            };
+            let mut tokens = TokenStore::new();
+            tokens.insert("alice".into(), User::new("alice", Role::Maintainer));
+            let state = State::new(&config, TicketStore::new(), tokens);
+
+            let (client, server) = tokio::io::duplex(64 * 1024);
+            let peer = "127.0.0.1:4000".parse().unwrap();
+            let server = tokio::spawn(outro_08::handle_connection(server, peer, state));
+            let (mut reader, mut writer) = tokio::io::split(client);
+            let write = async {
+                // The server may hang up before reading everything.
+                let _ = writer.write_all(input).await;
+                let _ = writer.shutdown().await;
+            };
+            let mut output = vec![];
+            let read = reader.read_to_end(&mut output);
+            let _ = tokio::join!(write, read);
+            // Errors only mean the connection was dropped; panics are bugs.
+            let _ = server.await.unwrap();
+            output
+        })
+    }
+
+    /// Requests that get past parsing, so the handlers see weird input too.
+    fn request() -> impl Strategy<Value = Vec<u8>> {
+        let method = prop::sample::select(vec!["GET", "POST", "PATCH", "DELETE", "PUT"]);
+        let path = prop_oneof![
+            prop::sample::select(Route::ALL.map(Route::path).to_vec()).prop_map(String::from),
+            "/(tickets|projects|trash|templates|queries)/[A-Za-z0-9%-]{0,12}(/[a-z]{0,12})?",
+            "/tickets\\?(q|status|project|format)=[ -~&&[^ #]]{0,20}",
+        ];
+        let token = prop::sample::select(vec!["", "Authorization: Bearer alice\r\n"]);
+        let body = prop_oneof![
+            Just(String::new()),
+            concat!(
+                "\\{\"(title|description|status|id|name|query|to|kind)\": ?",
+                "(\"[ -~&&[^\"\\\\]]{0,20}\"|[0-9]{1,3}|null)\\}"
+            ),
+            "[ -~]{0,40}",
+        ];
+        let length = prop_oneof![Just(None), (0..100usize).prop_map(Some)];
+        (method, path, token, body, length).prop_map(|(method, path, token, body, length)| {
+            let length = length.unwrap_or(body.len());
+            format!(
+                "{} {} HTTP/1.1\r\n{}Content-Length: {}\r\n\r\n{}",
+                method, path, token, length, body
+            )
+            .into_bytes()
+        })
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn test_arbitrary_bytes_get_http_responses(
+            input in prop::collection::vec(any::<u8>(), 0..512),
+        ) {
+            let output = serve(&input);
+            prop_assert!(output.is_empty() || output.starts_with(b"HTTP/1.1 "));
+        }
+
+        #[test]
+        fn test_pipelined_requests_get_http_responses(
+            requests in prop::collection::vec(request(), 1..4),
+        ) {
+            let output = serve(&requests.concat());
+            prop_assert!(output.starts_with(b"HTTP/1.1 "));
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_ticket_ids_route(
+            number in any::<u64>(),
+            route in prop::sample::select(vec![Route::GetTicket, Route::GetLinks, Route::ListComments]),
+        ) {
+            let id = TicketId::from(number);
+            prop_assert_eq!(id.to_string().parse::<TicketId>().unwrap(), id);
+            let path = route.path().replace("{id}", &id.to_string());
+            let (found, params) = routes::route("GET", &path).unwrap();
+            prop_assert_eq!(found, route);
+            prop_assert_eq!(params.id, Some(id));
+        }
+
+        #[test]
+        fn test_route_never_panics(method in "[A-Z]{0,7}", path in "(/[ -~&&[^/]]{0,10}){0,4}") {
+            if let Some((route, _)) = routes::route(&method, &path) {
+                prop_assert_eq!(route.method(), method);
+            }
+        }
+
+        #[test]
+        fn test_query_params_decode_what_was_encoded(name in "[a-z]{1,8}", value in any::<String>()) {
+            let encoded: String = value.bytes().map(|byte| format!("%{:02X}", byte)).collect();
+            let query = format!("_=1&{}={}&{}=second", name, encoded, name);
+            prop_assert_eq!(helpers::query_param(&query, &name).unwrap(), Some(value.clone()));
+            // Only what has to be escaped, with spaces as `+`.
+            let encoded: String = value
+                .chars()
+                .map(|c| match c {
+                    ' ' => String::from("+"),
+                    '%' | '+' | '&' | '=' => format!("%{:02X}", c as u8),
+                    c => c.to_string(),
+                })
+                .collect();
+            let query = format!("{}={}", name, encoded);
+            prop_assert_eq!(helpers::query_param(&query, &name).unwrap(), Some(value));
+        }
+    }
+}
@@ -0,0 +1,276 @@
+#[cfg(test)]
+mod tests {
+    use outro_08::auth::{Role, User};
+    use outro_08::client::{Client, ClientError};
+    use outro_08::data::{Status, Ticket, TicketDraft};
+    use outro_08::query::{Condition, Field, Query, QueryError, SavedQuery, SavedQueryError};
+    use outro_08::server::{self, Config};
+    use outro_08::store::{TicketId, TicketStore};
+    use std::net::SocketAddr;
+    use tempfile::TempDir;
+    use ticket_fields::{MarkdownDescription, TicketTitle};
+
+    fn ticket(id: u64, title: &str, status: Status, reporter: Option<&str>) -> Ticket {
+        Ticket {
+            id: TicketId::from(id),
+            title: TicketTitle::try_from(title).unwrap(),
+            description: MarkdownDescription::try_from("Steps to reproduce").unwrap(),
+            status,
+            reporter: reporter.map(String::from),
+            last_editor: None,
+        }
+    }
+
+    fn tickets() -> Vec<Ticket> {
+        vec![
+            ticket(0, "Login fails", Status::InProgress, Some("alice")),
+            ticket(1, "Login is slow", Status::InProgress, Some("bob")),
+            ticket(2, "Logout button", Status::ToDo, Some("alice")),
+            ticket(3, "Fix the login form", Status::Done, None),
+        ]
+    }
+
+    /// The ids of the tickets from `tickets()` that match `query`.
+    fn matching(query: &str) -> Vec<u64> {
+        let query: Query = query.parse().unwrap();
+        (0..)
+            .zip(tickets())
+            .filter(|(_, ticket)| query.matches(ticket))
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    fn error(query: &str) -> QueryError {
+        query.parse::<Query>().unwrap_err()
+    }
+
+    async fn start() -> SocketAddr {
+        let config = Config {
+            addr: String::from("127.0.0.1:0"),
+            ..Config::default()
+        };
+        let (listeners, state) = server::init(&config).await.unwrap();
+        let mut tokens = state.tokens.write().await;
+        tokens.insert("alice".into(), User::new("alice", Role::Reporter));
+        tokens.insert("viewer".into(), User::new("vic", Role::Viewer));
+        drop(tokens);
+        let addr = listeners.http_addr().unwrap();
+        tokio::spawn(server::serve(listeners, state));
+        addr
+    }
+
+    #[test]
+    fn test_conditions() {
+        assert_eq!(matching("status:InProgress"), [0, 1]);
+        assert_eq!(matching("status:done"), [3]);
+        assert_eq!(matching("title:~LOGIN"), [0, 1, 3]);
+        assert_eq!(matching(r#"title:"login fails""#), [0]);
+        assert_eq!(matching("title:login"), Vec::<u64>::new());
+        assert_eq!(matching("reporter:ALICE"), [0, 2]);
+        // Tickets without a reporter don't match, unless negated.
+        assert_eq!(matching("NOT reporter:~a"), [1, 3]);
+        assert_eq!(matching("id:TKT-2 OR id:3"), [2, 3]);
+        assert_eq!(matching("project:tkt"), [0, 1, 2, 3]);
+        assert_eq!(matching("description:~reproduce"), [0, 1, 2, 3]);
+        assert_eq!(matching("editor:alice"), Vec::<u64>::new());
+    }
+
+    #[test]
+    fn test_operators() {
+        let query = r#"status:InProgress AND title:~"login" AND NOT reporter:bob"#;
+        assert_eq!(matching(query), [0]);
+        // AND binds tighter than OR, and NOT tighter than both.
+        assert_eq!(
+            matching("status:Done OR title:~login AND reporter:bob"),
+            [1, 3]
+        );
+        assert_eq!(
+            matching("(status:Done OR title:~login) AND reporter:bob"),
+            [1]
+        );
+        assert_eq!(matching("NOT status:ToDo AND NOT status:Done"), [0, 1]);
+        assert_eq!(matching("NOT (status:ToDo OR status:Done)"), [0, 1]);
+        // Next to each other means AND, and keywords ignore case.
+        assert_eq!(matching("title:~log reporter:alice not status:todo"), [0]);
+        // Keywords after an operator are values.
+        assert_eq!(
+            matching("title:~\"and\" OR reporter:and"),
+            Vec::<u64>::new()
+        );
+        let query: Query = r#"title:"say \"hi\" \\o/""#.parse().unwrap();
+        let expected = Condition::Text {
+            field: Field::Title,
+            value: r#"say "hi" \o/"#.into(),
+            contains: false,
+        };
+        assert_eq!(query, Query::Condition(expected));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            error("status:InProgress AND NOT reportr:bob").to_string(),
+            "Invalid query at character 27: Unknown field `reportr`, expected one of \
+             id, project, status, title, description, reporter, editor"
+        );
+        assert_eq!(
+            error("status:Open").to_string(),
+            format!(
+                "Invalid query at character 8: {}",
+                "Open".parse::<Status>().unwrap_err()
+            )
+        );
+        assert_eq!(
+            error("title:").message,
+            "Expected a value, found the end of the query"
+        );
+        assert_eq!(error("title:").position, 7);
+        assert_eq!(
+            error("(status:Done").message,
+            "Expected `AND`, `OR` or `)`, found the end of the query"
+        );
+        assert_eq!(
+            error("status:Done)").message,
+            "Expected `AND`, `OR` or the end of the query, found `)`"
+        );
+        assert_eq!(
+            error("status:Done AND").message,
+            "Expected a field, `NOT` or `(`, found the end of the query"
+        );
+        assert_eq!(
+            error("").message,
+            "Expected a field, `NOT` or `(`, found the end of the query"
+        );
+        assert_eq!(
+            error("title login").message,
+            "Expected `:` or `:~` after `title`, found `login`"
+        );
+        let error = error("status:~Do");
+        assert_eq!(error.position, 7);
+        assert_eq!(
+            error.message,
+            "`status` can only be matched exactly, with `:`"
+        );
+        // Positions count characters, not bytes.
+        let error = self::error("title:\"é\" AND \"x");
+        assert_eq!(error.position, 15);
+        assert_eq!(error.message, "This quote is never closed");
+    }
+
+    #[test]
+    fn test_nesting_depth_is_limited() {
+        let nested = |open: &str, close: &str, depth: usize| {
+            format!("{}status:Done{}", open.repeat(depth), close.repeat(depth))
+        };
+        assert_eq!(matching(&nested("(", ")", 64)), vec![3]);
+        assert_eq!(matching(&nested("NOT ", "", 63)), vec![0, 1, 2]);
+        assert_eq!(matching(&nested("NOT (", ")", 32)), vec![3]);
+
+        let error = self::error(&nested("(", ")", 65));
+        assert_eq!(error.position, 65);
+        assert_eq!(error.message, "Queries can't nest more than 64 levels deep");
+        let error = self::error(&nested("NOT ", "", 65));
+        assert_eq!(error.position, 257);
+        // Used to overflow the stack.
+        for open in ["(", "NOT ", "NOT ("] {
+            assert!(self::error(&open.repeat(100_000))
+                .message
+                .contains("64 levels"));
+        }
+    }
+
+    #[test]
+    fn test_saved_queries() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("tickets.json");
+        let mut store = TicketStore::new();
+        for ticket in tickets() {
+            store.restore(ticket);
+        }
+        let query = SavedQuery {
+            name: "in-progress".into(),
+            query: "status:InProgress".into(),
+        };
+        store.save_query(query.clone()).unwrap();
+        assert_eq!(
+            store.save_query(query.clone()),
+            Err(SavedQueryError::Exists("in-progress".into()))
+        );
+        let invalid = SavedQuery {
+            name: "two words".into(),
+            ..query.clone()
+        };
+        assert_eq!(store.save_query(invalid), Err(SavedQueryError::InvalidName));
+        let invalid = SavedQuery {
+            name: "bad".into(),
+            query: "status:".into(),
+        };
+        assert!(matches!(
+            store.save_query(invalid),
+            Err(SavedQueryError::Invalid(_))
+        ));
+        assert_eq!(store.run_query("in-progress").unwrap().len(), 2);
+        assert_eq!(
+            store.run_query("nope").unwrap_err(),
+            SavedQueryError::NotFound("nope".into())
+        );
+
+        // Queries run against the tickets as they are now.
+        store.restore(ticket(4, "New", Status::InProgress, None));
+        assert_eq!(store.run_query("in-progress").unwrap().len(), 3);
+
+        store.save(&path).unwrap();
+        let mut loaded = TicketStore::load(&path).unwrap();
+        assert_eq!(loaded.saved_query("in-progress"), Some(&query));
+        assert_eq!(loaded.remove_query("in-progress"), Some(query));
+        assert_eq!(loaded.saved_queries().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_query_endpoints() {
+        let addr = start().await;
+        let alice = Client::new(addr.to_string(), "alice");
+        let viewer = Client::new(addr.to_string(), "viewer");
+        for title in ["Login fails", "Logout button"] {
+            alice
+                .create(&TicketDraft::new(title.into(), None))
+                .await
+                .unwrap();
+        }
+
+        let found = viewer
+            .search("title:~\"login\" AND reporter:alice")
+            .await
+            .unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].title.0, "Login fails");
+        let error = viewer.search("title:~login AND (").await;
+        assert!(
+            matches!(&error, Err(ClientError::BadRequest(message)) if message.contains("character 19")),
+            "{:?}",
+            error
+        );
+
+        let query = SavedQuery {
+            name: "logins".into(),
+            query: "title:~log".into(),
+        };
+        assert!(matches!(
+            viewer.save_query(&query).await,
+            Err(ClientError::Forbidden)
+        ));
+        assert_eq!(alice.save_query(&query).await.unwrap(), query);
+        assert!(matches!(
+            alice.save_query(&query).await,
+            Err(ClientError::Conflict(_))
+        ));
+        assert_eq!(viewer.saved_queries().await.unwrap(), [query]);
+        assert_eq!(viewer.run_query("logins").await.unwrap().len(), 2);
+
+        alice.delete_query("logins").await.unwrap();
+        assert!(matches!(
+            viewer.run_query("logins").await,
+            Err(ClientError::NotFound)
+        ));
+    }
+}
@@ -0,0 +1,285 @@
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Duration, TimeZone, Utc};
+    use outro_08::auth::{Role, User};
+    use outro_08::client::Client;
+    use outro_08::data::{Status, Ticket, TicketDraft, TicketPatch};
+    use outro_08::reports::{self, InvalidPeriod, Period, StatusChange, Summary};
+    use outro_08::server::{self, Config};
+    use outro_08::store::{TicketId, TicketStore};
+    use std::net::SocketAddr;
+    use tempfile::TempDir;
+    use ticket_fields::{MarkdownDescription, TicketTitle};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, 29, 12, 0, 0).unwrap()
+    }
+
+    fn days_ago(days: i64) -> DateTime<Utc> {
+        now() - Duration::days(days)
+    }
+
+    fn ticket(id: u64, status: Status) -> Ticket {
+        Ticket {
+            id: TicketId::from(id),
+            title: TicketTitle::try_from("A title").unwrap(),
+            description: MarkdownDescription::try_from("A description").unwrap(),
+            status,
+            reporter: None,
+            last_editor: None,
+        }
+    }
+
+    fn change(status: Status, at: DateTime<Utc>) -> StatusChange {
+        StatusChange { status, at }
+    }
+
+    /// Four tickets over the last five weeks, and one without a timeline.
+    fn summary() -> Summary {
+        let tickets = [
+            ticket(0, Status::Done),
+            ticket(1, Status::Done),
+            ticket(2, Status::InProgress),
+            ticket(3, Status::ToDo),
+            ticket(4, Status::ToDo),
+        ];
+        let changes = [
+            vec![
+                change(Status::ToDo, days_ago(34)),
+                change(Status::Done, days_ago(30)),
+            ],
+            // Done, reopened and done again: the cycle ends at the last move.
+            vec![
+                change(Status::ToDo, days_ago(10)),
+                change(Status::Done, days_ago(9)),
+                change(Status::InProgress, days_ago(8)),
+                change(Status::Done, days_ago(4)),
+            ],
+            vec![
+                change(Status::ToDo, days_ago(3)),
+                change(Status::InProgress, days_ago(2)),
+            ],
+            vec![change(Status::ToDo, now() - Duration::hours(6))],
+            vec![],
+        ];
+        let tickets = tickets
+            .iter()
+            .zip(&changes)
+            .map(|(ticket, changes)| (ticket, changes.as_slice()));
+        reports::summarize(tickets, Period::default(), now())
+    }
+
+    /// Moves ticket `id` to `status` as `by`, the way `PATCH /tickets/{id}` does.
+    fn move_to(store: &mut TicketStore, id: TicketId, status: Status, by: &User) {
+        let ticket = store.get(id).unwrap();
+        let before = ticket.read().unwrap().clone();
+        ticket.write().unwrap().status = status;
+        store.edited(before, by);
+    }
+
+    fn statuses(store: &TicketStore, id: TicketId) -> Vec<Status> {
+        store
+            .status_changes(id)
+            .iter()
+            .map(|change| change.status)
+            .collect()
+    }
+
+    async fn start() -> SocketAddr {
+        let config = Config {
+            addr: String::from("127.0.0.1:0"),
+            ..Config::default()
+        };
+        let (listeners, state) = server::init(&config).await.unwrap();
+        let mut tokens = state.tokens.write().await;
+        tokens.insert("alice".into(), User::new("alice", Role::Maintainer));
+        tokens.insert("viewer".into(), User::new("vic", Role::Viewer));
+        drop(tokens);
+        let addr = listeners.http_addr().unwrap();
+        tokio::spawn(server::serve(listeners, state));
+        addr
+    }
+
+    /// The status code of `GET path` as the viewer.
+    async fn status(addr: SocketAddr, path: &str) -> u16 {
+        let request = format!(
+            "GET {} HTTP/1.1\r\nConnection: close\r\nAuthorization: Bearer viewer\r\n\r\n",
+            path
+        );
+        let mut socket = TcpStream::connect(addr).await.unwrap();
+        socket.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        socket.read_to_string(&mut response).await.unwrap();
+        response[9..12].parse().unwrap()
+    }
+
+    #[test]
+    fn test_counts_and_throughput() {
+        let summary = summary();
+        let counts: Vec<(Status, usize)> = summary
+            .counts
+            .iter()
+            .map(|count| (count.status, count.tickets))
+            .collect();
+        assert_eq!(
+            counts,
+            [
+                (Status::ToDo, 2),
+                (Status::InProgress, 1),
+                (Status::Done, 2)
+            ]
+        );
+
+        let windows: Vec<(DateTime<Utc>, usize, usize)> = summary
+            .throughput
+            .iter()
+            .map(|window| (window.start, window.created, window.done))
+            .collect();
+        assert_eq!(
+            windows,
+            [
+                (days_ago(28), 0, 0),
+                (days_ago(21), 0, 0),
+                (days_ago(14), 1, 1),
+                (days_ago(7), 2, 1),
+            ]
+        );
+        assert_eq!(summary.throughput[3].end, now());
+        assert_eq!(Period::new(0, 4), Err(InvalidPeriod));
+        assert_eq!(Period::new(7, Period::MAX_WINDOWS + 1), Err(InvalidPeriod));
+    }
+
+    #[test]
+    fn test_cycle_time_and_ages() {
+        let summary = summary();
+        assert_eq!(summary.cycle_time.tickets, 2);
+        // 4 and 6 days.
+        assert_eq!(summary.cycle_time.average_hours, Some(120.0));
+        assert_eq!(summary.cycle_time.max_hours, Some(144.0));
+        assert_eq!(summary.cycle_time.longest, Some(TicketId::from(1)));
+
+        // Ticket 4 has no timeline, so it isn't counted.
+        assert_eq!(summary.open_age.tickets, 2);
+        assert_eq!(summary.open_age.average_hours, Some(39.0));
+        assert_eq!(summary.open_age.longest, Some(TicketId::from(2)));
+        let buckets: Vec<usize> = summary
+            .age_buckets
+            .iter()
+            .map(|bucket| bucket.tickets)
+            .collect();
+        assert_eq!(buckets, [1, 1, 0, 0]);
+
+        let empty = reports::summarize([], Period::default(), now());
+        assert_eq!(empty.cycle_time.tickets, 0);
+        assert_eq!(empty.cycle_time.average_hours, None);
+    }
+
+    #[test]
+    fn test_tables() {
+        let table = summary().to_string();
+        let expected = "\
+STATUS      TICKETS
+ToDo              2
+InProgress        1
+Done              2
+
+WINDOW                   CREATED     DONE
+2024-03-01 - 2024-03-08        0        0
+2024-03-08 - 2024-03-15        0        0
+2024-03-15 - 2024-03-22        1        1
+2024-03-22 - 2024-03-29        2        1
+
+TIME        TICKETS  AVERAGE      MAX  LONGEST
+to done           2     5.0d     6.0d  TKT-1
+open              2    39.0h     3.0d  TKT-2
+
+OPEN FOR    TICKETS
+0-1d              1
+1-7d              1
+7-30d             0
+30d+              0
+";
+        assert_eq!(table, expected);
+    }
+
+    #[test]
+    fn test_store_records_status_changes() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("tickets.json");
+        let alice = User::new("alice", Role::Maintainer);
+        let mut store = TicketStore::new();
+        let id = store.add_ticket_by(TicketDraft::new("A".into(), None), &alice);
+        move_to(&mut store, id, Status::InProgress, &alice);
+        // Edits that keep the status aren't status changes.
+        move_to(&mut store, id, Status::InProgress, &alice);
+        move_to(&mut store, id, Status::Done, &alice);
+        assert_eq!(
+            statuses(&store, id),
+            [Status::ToDo, Status::InProgress, Status::Done]
+        );
+        // Undoing moves it back, which is a status change too.
+        store.undo(&alice, 1);
+        assert_eq!(statuses(&store, id).last(), Some(&Status::InProgress));
+        let summary = store.summary(Period::default(), Utc::now());
+        assert_eq!(summary.throughput[3].done, 1);
+
+        store.save(&path).unwrap();
+        let loaded = TicketStore::load(&path).unwrap();
+        assert_eq!(loaded.status_changes(id), store.status_changes(id));
+
+        // Restored tickets have no timeline, and purged ones lose theirs.
+        let mut store = TicketStore::new();
+        store.restore(loaded.get(id).unwrap().read().unwrap().clone());
+        assert!(store.status_changes(id).is_empty());
+        let mut store = loaded;
+        store.delete(id, &alice);
+        assert_eq!(
+            store
+                .summary(Period::default(), Utc::now())
+                .open_age
+                .tickets,
+            0
+        );
+        assert!(!store.status_changes(id).is_empty());
+        store.purge(id);
+        assert!(store.status_changes(id).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_summary_endpoint() {
+        let addr = start().await;
+        let alice = Client::new(addr.to_string(), "alice");
+        let viewer = Client::new(addr.to_string(), "viewer");
+        let id = alice
+            .create(&TicketDraft::new("A".into(), None))
+            .await
+            .unwrap();
+        alice
+            .create(&TicketDraft::new("B".into(), None))
+            .await
+            .unwrap();
+        let patch = TicketPatch {
+            id,
+            title: None,
+            description: None,
+            status: Some(Status::Done),
+        };
+        alice.patch(&patch).await.unwrap();
+
+        let summary = viewer.summary(Period::new(1, 2).unwrap()).await.unwrap();
+        assert_eq!(summary.counts[2].tickets, 1);
+        assert_eq!(summary.throughput.len(), 2);
+        assert_eq!(
+            (summary.throughput[1].created, summary.throughput[1].done),
+            (2, 1)
+        );
+        assert_eq!(summary.cycle_time.longest, Some(id));
+        assert_eq!(summary.age_buckets[0].tickets, 1);
+
+        assert_eq!(status(addr, "/reports/summary").await, 200);
+        assert_eq!(status(addr, "/reports/summary?days=0").await, 400);
+        assert_eq!(status(addr, "/reports/summary?windows=many").await, 400);
+    }
+}
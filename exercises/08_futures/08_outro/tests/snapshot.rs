@@ -0,0 +1,201 @@
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use outro_08::auth::{Role, User};
+    use outro_08::data::{Status, TicketDraft};
+    use outro_08::links::{Link, LinkKind};
+    use outro_08::projects::ProjectKey;
+    use outro_08::reports::Period;
+    use outro_08::snapshot::{Snapshot, SnapshotError, MAGIC, VERSION};
+    use outro_08::store::{TicketId, TicketStore};
+    use serde::Serialize;
+    use std::sync::Arc;
+    use tempfile::TempDir;
+    use tokio::sync::RwLock;
+
+    fn store() -> TicketStore {
+        let mut store = TicketStore::new();
+        store.add_ticket(TicketDraft::new("First".into(), None));
+        let id = store.add_ticket(TicketDraft::new("Second".into(), Some("Two".into())));
+        store.add_ticket(TicketDraft::new("Deleted".into(), None));
+        let blocks = Link {
+            from: TicketId::from(0),
+            kind: LinkKind::Blocks,
+            to: id,
+        };
+        store.link(blocks).unwrap();
+        store
+            .link(Link {
+                from: TicketId::from(2),
+                kind: LinkKind::ChildOf,
+                to: TicketId::from(0),
+            })
+            .unwrap();
+        store.remove(TicketId::from(2));
+        let trashed = store.add_ticket(TicketDraft::new("Trashed".into(), None));
+        store
+            .link(Link {
+                from: trashed,
+                kind: LinkKind::Blocks,
+                to: TicketId::from(0),
+            })
+            .unwrap();
+        store.delete(trashed, &User::new("root", Role::Admin));
+        {
+            let ticket = store.get(id).unwrap();
+            let mut ticket = ticket.write().unwrap();
+            ticket.status = Status::InProgress;
+            ticket.reporter = Some(String::from("alice"));
+            ticket.last_editor = Some(String::from("bob"));
+        }
+        store
+    }
+
+    fn with_header(version: u16, payload: &[u8]) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(version.to_le_bytes());
+        bytes.extend(crc32fast::hash(payload).to_le_bytes());
+        bytes.extend((payload.len() as u64).to_le_bytes());
+        bytes.extend(payload);
+        bytes
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let original = store();
+        let snapshot = Snapshot::capture(&original);
+        let decoded = Snapshot::decode(&snapshot.to_bytes()).unwrap();
+        assert_eq!(decoded, snapshot);
+        assert_eq!(decoded.links.len(), 2);
+        assert_eq!(decoded.trash.len(), 1);
+
+        let mut restored = decoded.restore();
+        assert_eq!(Snapshot::capture(&restored), snapshot);
+        // Neither the removed ticket's id nor the deleted one's are reused.
+        assert_eq!(
+            restored.add_ticket(TicketDraft::new("Next".into(), None)),
+            TicketId::from(4)
+        );
+        // Links of deleted tickets come back with them.
+        restored.undelete(TicketId::from(3)).unwrap();
+        assert_eq!(
+            restored.open_blockers(TicketId::from(0)),
+            [TicketId::from(3)]
+        );
+    }
+
+    #[test]
+    fn test_report_data_survives() {
+        let alice = User::new("alice", Role::Admin);
+        let mut store = TicketStore::new();
+        let id = store.add_ticket_by(TicketDraft::new("Reported".into(), None), &alice);
+        let ticket = store.get(id).unwrap();
+        let before = ticket.read().unwrap().clone();
+        ticket.write().unwrap().status = Status::Done;
+        store.edited(before, &alice);
+        store.undo(&alice, 1);
+
+        let decoded = Snapshot::decode(&Snapshot::capture(&store).to_bytes()).unwrap();
+        let mut restored = decoded.restore();
+        assert_eq!(restored.status_changes(id).len(), 3);
+        assert_eq!(restored.status_changes(id), store.status_changes(id));
+        let now = Utc::now();
+        assert_eq!(
+            restored.summary(Period::default(), now),
+            store.summary(Period::default(), now)
+        );
+        assert_eq!(restored.history(&alice), store.history(&alice));
+        // What was undone before the snapshot can be redone after it.
+        assert_eq!(restored.redo(&alice, 1).tickets, [id]);
+        let status = restored.get(id).unwrap().read().unwrap().status;
+        assert_eq!(status, Status::Done);
+    }
+
+    #[tokio::test]
+    async fn test_capture_shared_and_files() {
+        let store = Arc::new(RwLock::new(store()));
+        let snapshot = Snapshot::capture_shared(&store).await;
+        // Later changes don't affect the snapshot.
+        store
+            .write()
+            .await
+            .add_ticket(TicketDraft::new("Later".into(), None));
+        assert_eq!(snapshot.tickets.len(), 2);
+
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("tickets.snapshot");
+        snapshot.save(&path).unwrap();
+        assert_eq!(Snapshot::load(&path).unwrap(), snapshot);
+    }
+
+    #[test]
+    fn test_rejects_corrupt_files() {
+        let bytes = Snapshot::capture(&store()).to_bytes();
+
+        let error = Snapshot::decode(b"{\"next_id\": 0}").unwrap_err();
+        assert!(matches!(error, SnapshotError::NotASnapshot));
+
+        let error = Snapshot::decode(&bytes[..bytes.len() - 1]).unwrap_err();
+        assert!(matches!(error, SnapshotError::Truncated { .. }));
+        let error = Snapshot::decode(&bytes[..8]).unwrap_err();
+        assert!(matches!(error, SnapshotError::Truncated { .. }));
+
+        let mut flipped = bytes.clone();
+        *flipped.last_mut().unwrap() ^= 1;
+        let error = Snapshot::decode(&flipped).unwrap_err();
+        assert!(matches!(error, SnapshotError::ChecksumMismatch { .. }));
+        assert!(error.to_string().starts_with("The snapshot is corrupt"));
+
+        // A valid checksum doesn't make invalid tickets acceptable.
+        let mut payload = bytes[18..].to_vec();
+        let title = payload.windows(5).position(|w| w == b"First").unwrap();
+        payload[title - 8..title].copy_from_slice(&0u64.to_le_bytes());
+        let error = Snapshot::decode(&with_header(VERSION, &payload)).unwrap_err();
+        assert!(matches!(error, SnapshotError::Corrupt(_)));
+    }
+
+    #[test]
+    fn test_rejects_future_versions() {
+        let bytes = Snapshot::capture(&store()).to_bytes();
+        let future = with_header(VERSION + 1, &bytes[18..]);
+        let error = Snapshot::decode(&future).unwrap_err();
+        assert!(
+            matches!(error, SnapshotError::UnsupportedVersion { found } if found == VERSION + 1)
+        );
+        assert!(error.to_string().contains("newer release"));
+    }
+
+    #[test]
+    fn test_migrates_version_1() {
+        // Version 1 had no reporters or editors.
+        #[derive(Serialize)]
+        struct PayloadV1 {
+            next_id: u64,
+            tickets: Vec<(u64, String, String, u8)>,
+        }
+        let payload = PayloadV1 {
+            next_id: 5,
+            tickets: vec![
+                (1, "Old".into(), "Ticket".into(), 2),
+                (4, "Older".into(), "Ticket".into(), 0),
+            ],
+        };
+        let bytes = with_header(1, &bincode::serialize(&payload).unwrap());
+
+        let snapshot = Snapshot::decode(&bytes).unwrap();
+        // Tickets from before projects go to the default one, with its counter.
+        assert_eq!(snapshot.projects.len(), 1);
+        assert_eq!(snapshot.projects[0].key, ProjectKey::DEFAULT);
+        assert_eq!(snapshot.projects[0].next_number, 5);
+        assert_eq!(snapshot.tickets[0].id, TicketId::from(1));
+        assert_eq!(snapshot.tickets[0].title.0, "Old");
+        assert_eq!(snapshot.tickets[0].status, Status::Done);
+        assert_eq!(snapshot.tickets[1].reporter, None);
+        assert!(snapshot.links.is_empty());
+        assert!(snapshot.status_changes.is_empty());
+
+        // Writing it back uses the current version.
+        let bytes = snapshot.to_bytes();
+        assert_eq!(u16::from_le_bytes([bytes[4], bytes[5]]), VERSION);
+    }
+}
@@ -0,0 +1,528 @@
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use outro_08::attachments::Attachment;
+    use outro_08::auth::{Role, User};
+    use outro_08::backend::Backend;
+    use outro_08::client::{Client, ClientError};
+    use outro_08::comments::CommentDraft;
+    use outro_08::data::{Status, TicketDraft, TicketPatch};
+    use outro_08::handlers::PatchError;
+    use outro_08::links::{Link, LinkKind};
+    use outro_08::projects::{ProjectDraft, ProjectKey, ProjectPatch, ProjectSettings};
+    use outro_08::query::SavedQuery;
+    use outro_08::server::{self, Config};
+    use outro_08::sqlite::{SqliteStore, SCHEMA_VERSION};
+    use outro_08::store::{Journal, Section, TicketId, TicketStore};
+    use outro_08::templates::{Template, TicketTemplate};
+    use std::net::SocketAddr;
+    use std::path::{Path, PathBuf};
+    use std::time::Duration;
+    use tempfile::TempDir;
+    use ticket_fields::test_helpers::ticket_title;
+    use ticket_fields::MarkdownDescription;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+    use tokio::time::timeout;
+
+    fn key(key: &str) -> ProjectKey {
+        key.parse().unwrap()
+    }
+
+    fn draft(title: &str) -> TicketDraft {
+        TicketDraft::new(title.into(), Some(String::from("A description")))
+    }
+
+    fn patch(id: TicketId) -> TicketPatch {
+        TicketPatch {
+            id,
+            title: None,
+            description: None,
+            status: None,
+        }
+    }
+
+    fn connection(path: &Path) -> rusqlite::Connection {
+        rusqlite::Connection::open(path).unwrap()
+    }
+
+    /// The same scenario for the JSON store file and for the database,
+    /// through `Backend` so the permission checks are exercised too.
+    /// `reopen` gives a fresh backend on the same file, as `user`.
+    async fn check_behavior(reopen: impl Fn(User) -> Backend) {
+        let root = User::new("root", Role::Admin);
+        let alice = User::new("alice", Role::Reporter);
+
+        let backend = reopen(root.clone());
+        let first = backend.create(draft("First")).await.unwrap();
+        let second = backend.create(draft("Second")).await.unwrap();
+        assert_eq!(first, TicketId::from(0));
+        assert_eq!(second, TicketId::from(1));
+
+        // Every project counts on its own.
+        backend
+            .create_project(ProjectDraft {
+                key: key("API"),
+                settings: ProjectSettings::new("Public API"),
+            })
+            .await
+            .unwrap();
+        assert!(backend
+            .create_project(ProjectDraft {
+                key: key("API"),
+                settings: ProjectSettings::new("Again"),
+            })
+            .await
+            .is_err());
+        let api = backend.create_in(key("API"), draft("Api")).await.unwrap();
+        assert_eq!(api, TicketId::new(key("API"), 0));
+        assert!(backend.create_in(key("NOPE"), draft("No")).await.is_err());
+        assert_eq!(backend.list_in(key("API")).await.unwrap().len(), 1);
+        let keys: Vec<_> = backend
+            .projects()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|project| project.key)
+            .collect();
+        assert_eq!(keys, vec![key("API"), ProjectKey::DEFAULT]);
+
+        // Reporters edit their own tickets, and nobody else's, but can't move them.
+        let as_alice = reopen(alice.clone());
+        let hers = as_alice.create(draft("Hers")).await.unwrap();
+        let mut retitle = patch(hers);
+        retitle.title = Some(ticket_title());
+        as_alice.patch(retitle).await.unwrap();
+        let mut retitle = patch(first);
+        retitle.title = Some(ticket_title());
+        let error = as_alice.patch(retitle).await.unwrap_err();
+        assert_eq!(error.to_string(), "Not allowed");
+        let mut done = patch(hers);
+        done.status = Some(Status::Done);
+        assert!(as_alice.patch(done.clone()).await.is_err());
+        assert!(as_alice.delete(hers).await.is_err());
+        let error = as_alice.patch(patch(TicketId::from(99))).await.unwrap_err();
+        assert_eq!(error.to_string(), "No ticket with id TKT-99");
+
+        let backend = reopen(root.clone());
+        backend.patch(done).await.unwrap();
+        let ticket = backend.get(hers).await.unwrap();
+        assert_eq!(ticket.status, Status::Done);
+        assert_eq!(ticket.title, ticket_title());
+        assert_eq!(ticket.reporter.as_deref(), Some("alice"));
+        assert_eq!(ticket.last_editor.as_deref(), Some("root"));
+
+        // Deleted tickets go to the trash, and can come back.
+        backend.delete(second).await.unwrap();
+        assert!(backend.get(second).await.is_err());
+        assert!(backend.delete(second).await.is_err());
+        let trash = backend.trash().await.unwrap();
+        assert_eq!(trash.len(), 1);
+        assert_eq!(trash[0].ticket.id, second);
+        assert_eq!(trash[0].deleted_by, "root");
+        backend.undelete(second).await.unwrap();
+        assert!(backend.undelete(second).await.is_err());
+        assert_eq!(backend.get(second).await.unwrap().title.0, "Second");
+
+        // Purged ids aren't handed out again.
+        backend.delete(second).await.unwrap();
+        assert!(backend.purge_expired(1).await.unwrap().is_empty());
+        assert_eq!(backend.purge_expired(0).await.unwrap(), vec![second]);
+        assert!(backend.trash().await.unwrap().is_empty());
+        assert!(backend.purge(second).await.is_err());
+        let next = backend.create(draft("Next")).await.unwrap();
+        assert_eq!(next, TicketId::from(3));
+        let ids: Vec<_> = backend
+            .list()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|ticket| ticket.id)
+            .collect();
+        assert_eq!(ids, vec![api, first, hers, next]);
+    }
+
+    #[tokio::test]
+    async fn test_store_file_and_database_behave_the_same() {
+        let dir = TempDir::new().unwrap();
+        let store = dir.path().join("tickets.json");
+        check_behavior(|user| match Backend::local(&store).unwrap() {
+            Backend::Local { path, store, .. } => Backend::Local { path, store, user },
+            _ => unreachable!(),
+        })
+        .await;
+
+        let db = SqliteStore::open(&dir.path().join("tickets.db")).unwrap();
+        check_behavior(|user| Backend::database(db.clone(), user)).await;
+    }
+
+    #[tokio::test]
+    async fn test_database_outlives_its_connection() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("tickets.db");
+        let root = User::new("root", Role::Admin);
+
+        let db = SqliteStore::open(&path).unwrap();
+        let id = db
+            .create(ProjectKey::DEFAULT, draft("Kept"), &root)
+            .await
+            .unwrap();
+        db.create_project(ProjectDraft {
+            key: key("OLD"),
+            settings: ProjectSettings::new("Old"),
+        })
+        .await
+        .unwrap();
+        let archive = ProjectPatch {
+            archived: Some(true),
+            ..ProjectPatch::default()
+        };
+        db.update_project(key("OLD"), archive).await.unwrap();
+        drop(db);
+
+        let db = SqliteStore::open(&path).unwrap();
+        assert_eq!(db.get(id).await.unwrap().unwrap().title.0, "Kept");
+        assert!(db.create(key("OLD"), draft("No"), &root).await.is_err());
+        let next = db.create(ProjectKey::DEFAULT, draft("Next"), &root).await;
+        assert_eq!(next.unwrap(), TicketId::from(1));
+        assert_eq!(db.list(Some(Status::ToDo)).await.unwrap().len(), 2);
+        assert!(db.list(Some(Status::Done)).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_migrations() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("tickets.db");
+        SqliteStore::open(&path).unwrap();
+        // Opening an up-to-date database again doesn't run anything twice.
+        SqliteStore::open(&path).unwrap();
+
+        let connection = connection(&path);
+        let version: usize = connection
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, SCHEMA_VERSION);
+
+        // Listing by status doesn't scan the whole table.
+        let plan: String = connection
+            .query_row(
+                "EXPLAIN QUERY PLAN SELECT * FROM tickets WHERE status = 'Done'",
+                [],
+                |row| row.get(3),
+            )
+            .unwrap();
+        assert!(plan.contains("tickets_by_status"), "{}", plan);
+
+        connection
+            .pragma_update(None, "user_version", SCHEMA_VERSION + 1)
+            .unwrap();
+        drop(connection);
+        let error = SqliteStore::open(&path).err().unwrap();
+        assert!(format!("{:#}", error).contains("newer release"));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_rows_are_rejected() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("tickets.db");
+        let db = SqliteStore::open(&path).unwrap();
+        connection(&path)
+            .execute(
+                "INSERT INTO tickets (project, number, title, description, status)
+                 VALUES ('TKT', 0, '', 'A description', 'ToDo')",
+                [],
+            )
+            .unwrap();
+        assert!(db.get(TicketId::from(0)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_journal_keeps_the_database_in_sync() {
+        let dir = TempDir::new().unwrap();
+        let db = SqliteStore::open(&dir.path().join("tickets.db")).unwrap();
+        let root = User::new("root", Role::Admin);
+
+        let journal = Journal::new();
+        let mut store = TicketStore::new();
+        store.journal_to(journal.clone());
+        let first = store.add_ticket_by(draft("First"), &root);
+        let second = store.add_ticket_by(draft("Second"), &root);
+        store
+            .create_project(ProjectDraft {
+                key: key("API"),
+                settings: ProjectSettings::new("Public API"),
+            })
+            .unwrap();
+        let api = store
+            .add_ticket_to(key("API"), draft("Api"), &root)
+            .unwrap();
+        store.get(first).unwrap().write().unwrap().status = Status::Done;
+        store.touch(first);
+        store.remove(second);
+        store.delete(api, &root);
+        db.write_changes(&store, journal.take()).await.unwrap();
+        assert!(journal.take().is_empty());
+
+        let loaded = db.load().await.unwrap();
+        let tickets: Vec<_> = loaded
+            .tickets
+            .values()
+            .map(|ticket| ticket.read().unwrap().clone())
+            .collect();
+        assert_eq!(tickets.len(), 1);
+        assert_eq!(
+            loaded.get(first).unwrap().read().unwrap().status,
+            Status::Done
+        );
+        assert!(loaded.get(second).is_none());
+        assert!(loaded.get(api).is_none());
+        assert_eq!(loaded.trashed(api).unwrap().deleted_by, "root");
+        assert_eq!(loaded.next_id(ProjectKey::DEFAULT), Some(TicketId::from(2)));
+        assert_eq!(loaded.project(key("API")).unwrap().next_number, 1);
+    }
+
+    #[tokio::test]
+    async fn test_database_keeps_every_section() {
+        let dir = TempDir::new().unwrap();
+        let db = SqliteStore::open(&dir.path().join("tickets.db")).unwrap();
+        let root = User::new("root", Role::Admin);
+
+        let journal = Journal::new();
+        let mut store = TicketStore::new();
+        store.journal_to(journal.clone());
+        let a = store.add_ticket_by(draft("A"), &root);
+        let b = store.add_ticket_by(draft("B"), &root);
+        let purged = store.add_ticket_by(draft("Purged"), &root);
+        for to in [b, purged] {
+            let link = Link {
+                from: a,
+                kind: LinkKind::Blocks,
+                to,
+            };
+            store.link(link).unwrap();
+        }
+        for ticket in [a, purged] {
+            let body = MarkdownDescription::try_from("A comment").unwrap();
+            store.add_comment(ticket, &root, body).unwrap();
+            store.attach(Attachment {
+                ticket,
+                digest: "ab".repeat(32),
+                name: String::from("notes.txt"),
+                mime: String::from("text/plain"),
+                size: 5,
+                uploaded_by: String::from("root"),
+                uploaded_at: Utc::now(),
+            });
+        }
+        let template = Template {
+            name: String::from("release"),
+            description: String::new(),
+            tickets: vec![TicketTemplate {
+                title: String::from("Release {version}"),
+                description: String::from("Ship it"),
+            }],
+        };
+        store.add_template(template).unwrap();
+        let query = SavedQuery {
+            name: String::from("done"),
+            query: String::from("status:Done"),
+        };
+        store.save_query(query).unwrap();
+        let before = store.get(a).unwrap().read().unwrap().clone();
+        store.get(a).unwrap().write().unwrap().status = Status::Done;
+        store.edited(before, &root);
+        store.delete(purged, &root);
+        store.purge(purged);
+        db.write_changes(&store, journal.take()).await.unwrap();
+
+        let loaded = db.load().await.unwrap();
+        for section in Section::ALL {
+            assert_eq!(
+                loaded.section(section).unwrap(),
+                store.section(section).unwrap(),
+                "{:?}",
+                section
+            );
+        }
+        assert_eq!(loaded.links().blockers(b), [a]);
+        assert_eq!(loaded.comments().iter().count(), 1);
+        assert_eq!(loaded.attachments(a).len(), 1);
+        assert_eq!(loaded.status_changes(a).len(), 2);
+        assert!(loaded.template("release").is_some());
+        assert!(loaded.saved_query("done").is_some());
+        assert_eq!(loaded.history(&root).unwrap().undo.len(), 5);
+
+        // Only sections that changed are written again.
+        let mut loaded = loaded;
+        loaded.journal_to(journal.clone());
+        loaded.remove_query("done");
+        let changes = journal.take();
+        db.write_changes(&loaded, changes).await.unwrap();
+        let reloaded = db.load().await.unwrap();
+        assert!(reloaded.saved_query("done").is_none());
+        assert!(reloaded.template("release").is_some());
+    }
+
+    #[tokio::test]
+    async fn test_database_patches_check_blockers() {
+        let dir = TempDir::new().unwrap();
+        let db = SqliteStore::open(&database(&dir)).unwrap();
+        let root = User::new("root", Role::Admin);
+        let journal = Journal::new();
+        let mut store = TicketStore::new();
+        store.journal_to(journal.clone());
+        let blocker = store.add_ticket_by(draft("Blocker"), &root);
+        let blocked = store.add_ticket_by(draft("Blocked"), &root);
+        let link = Link {
+            from: blocker,
+            kind: LinkKind::Blocks,
+            to: blocked,
+        };
+        store.link(link).unwrap();
+        db.write_changes(&store, journal.take()).await.unwrap();
+
+        let mut done = patch(blocked);
+        done.status = Some(Status::Done);
+        let error = db.patch(done.clone(), &root).await.unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<PatchError>(),
+            Some(&PatchError::Blocked(vec![blocker]))
+        );
+        assert_eq!(db.get(blocked).await.unwrap().unwrap().status, Status::ToDo);
+
+        let mut blocker_done = patch(blocker);
+        blocker_done.status = Some(Status::Done);
+        db.patch(blocker_done, &root).await.unwrap();
+        db.patch(done, &root).await.unwrap();
+    }
+
+    /// A server on the database at `path`, with an admin token `admin`.
+    async fn start(path: &Path) -> (SocketAddr, server::State) {
+        let config = Config {
+            addr: String::from("127.0.0.1:0"),
+            database: Some(path.to_path_buf()),
+            attachments_dir: path.with_file_name("attachments"),
+            ..Config::default()
+        };
+        let (listeners, state) = server::init(&config).await.unwrap();
+        state
+            .tokens
+            .write()
+            .await
+            .insert("admin".into(), User::new("root", Role::Admin));
+        let addr = listeners.http_addr().unwrap();
+        tokio::spawn(server::serve(listeners, state.clone()));
+        (addr, state)
+    }
+
+    fn database(dir: &TempDir) -> PathBuf {
+        dir.path().join("tickets.db")
+    }
+
+    #[tokio::test]
+    async fn test_server_keeps_tickets_in_the_database() {
+        let dir = TempDir::new().unwrap();
+        let path = database(&dir);
+        let (addr, _) = start(&path).await;
+
+        let client = Client::new(addr.to_string(), String::from("admin"));
+        let id = client.create(&draft("Served")).await.unwrap();
+        let blocker = client.create(&draft("Blocker")).await.unwrap();
+        let mut done = patch(id);
+        done.status = Some(Status::Done);
+        client.patch(&done).await.unwrap();
+        let link = Link {
+            from: blocker,
+            kind: LinkKind::Blocks,
+            to: id,
+        };
+        client.link(link).await.unwrap();
+        let comment = CommentDraft {
+            body: MarkdownDescription::try_from("Written before the response").unwrap(),
+        };
+        client.comment(id, &comment).await.unwrap();
+
+        // Changes are written before the server responds.
+        let db = SqliteStore::open(&path).unwrap();
+        let ticket = db.get(id).await.unwrap().unwrap();
+        assert_eq!(ticket.status, Status::Done);
+
+        // A server started on the same database picks up where the last one stopped.
+        let (_, state) = start(&path).await;
+        let store = state.store.read().await;
+        assert_eq!(store.get(id).unwrap().read().unwrap().title.0, "Served");
+        assert_eq!(store.next_id(ProjectKey::DEFAULT), Some(TicketId::from(2)));
+        assert_eq!(store.links().blockers(id), [blocker]);
+        let comments: Vec<_> = store.comments().iter().collect();
+        assert_eq!(comments[0].body.as_str(), "Written before the response");
+    }
+
+    #[tokio::test]
+    async fn test_stalled_uploads_dont_hold_up_changes() {
+        let dir = TempDir::new().unwrap();
+        let (addr, state) = start(&database(&dir)).await;
+        let client = Client::new(addr.to_string(), String::from("admin"));
+        let id = client.create(&draft("Attached")).await.unwrap();
+
+        // Sends less of the body than it announced, then nothing.
+        let mut upload = TcpStream::connect(addr).await.unwrap();
+        let request = format!(
+            "POST /tickets/{}/attachments?name=a.txt HTTP/1.1\r\n\
+             Authorization: Bearer admin\r\nContent-Length: 100\r\n\r\nThe start",
+            id
+        );
+        upload.write_all(request.as_bytes()).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let created = timeout(Duration::from_secs(2), client.create(&draft("Meanwhile"))).await;
+        assert!(created.expect("held up by the upload").is_ok());
+
+        // It's dropped once the body stops arriving for `keep_alive`.
+        let mut response = vec![];
+        let closed = timeout(state.keep_alive * 2, upload.read_to_end(&mut response)).await;
+        assert!(closed.is_ok(), "the upload is still open");
+        assert!(client.attachments(id).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_failed_writes_fail_the_request() {
+        let dir = TempDir::new().unwrap();
+        let path = database(&dir);
+        let (addr, state) = start(&path).await;
+        let client = Client::new(addr.to_string(), String::from("admin"));
+        let kept = client.create(&draft("Kept")).await.unwrap();
+
+        connection(&path)
+            .execute_batch(
+                "CREATE TRIGGER full BEFORE INSERT ON tickets
+                 BEGIN SELECT RAISE(ABORT, 'disk full'); END;",
+            )
+            .unwrap();
+        let result = client.create(&draft("Lost")).await;
+        assert!(
+            matches!(result, Err(ClientError::Unexpected { status: 500, .. })),
+            "{:?}",
+            result
+        );
+        // The server is back to what's in the database.
+        let titles: Vec<_> = client
+            .list()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|ticket| ticket.title.0)
+            .collect();
+        assert_eq!(titles, ["Kept"]);
+        let store = state.store.read().await;
+        let history = store.history(&User::new("root", Role::Admin)).unwrap();
+        assert_eq!(history.undo.len(), 1);
+        drop(store);
+
+        connection(&path)
+            .execute_batch("DROP TRIGGER full")
+            .unwrap();
+        let id = client.create(&draft("Written")).await.unwrap();
+        assert_eq!(id, TicketId::from(1));
+        assert!(client.get(kept).await.is_ok());
+    }
+}
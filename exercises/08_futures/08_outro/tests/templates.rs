@@ -0,0 +1,324 @@
+#[cfg(test)]
+mod tests {
+    use outro_08::auth::{Role, User};
+    use outro_08::client::{Client, ClientError};
+    use outro_08::projects::{ProjectDraft, ProjectError, ProjectPatch, ProjectSettings};
+    use outro_08::server::{self, Config};
+    use outro_08::store::{TicketId, TicketStore};
+    use outro_08::templates::{Template, TemplateError, TemplateRequest, TicketTemplate};
+    use std::collections::BTreeMap;
+    use std::net::SocketAddr;
+    use tempfile::TempDir;
+    use ticket_fields::Policy;
+
+    fn release() -> Template {
+        Template {
+            name: "release".into(),
+            description: "Every release".into(),
+            tickets: vec![
+                TicketTemplate {
+                    title: "Release {{version}}".into(),
+                    description: "Tag {{ version }} and publish it".into(),
+                },
+                TicketTemplate {
+                    title: "Announce {{version}}".into(),
+                    description: "Write the post for {{channel}}".into(),
+                },
+            ],
+        }
+    }
+
+    fn values(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    async fn start() -> SocketAddr {
+        let config = Config {
+            addr: String::from("127.0.0.1:0"),
+            ..Config::default()
+        };
+        let (listeners, state) = server::init(&config).await.unwrap();
+        let mut tokens = state.tokens.write().await;
+        tokens.insert("maintainer".into(), User::new("mia", Role::Maintainer));
+        tokens.insert("alice".into(), User::new("alice", Role::Reporter));
+        tokens.insert("viewer".into(), User::new("vic", Role::Viewer));
+        drop(tokens);
+        let addr = listeners.http_addr().unwrap();
+        tokio::spawn(server::serve(listeners, state));
+        addr
+    }
+
+    #[test]
+    fn test_placeholders() {
+        let template = release();
+        template.check().unwrap();
+        let placeholders: Vec<&str> = template.placeholders().into_iter().collect();
+        assert_eq!(placeholders, ["channel", "version"]);
+
+        let drafts = template
+            .instantiate(
+                &values(&[("version", "1.2.0"), ("channel", "{{version}}")]),
+                &Policy::default(),
+            )
+            .unwrap();
+        assert_eq!(drafts.len(), 2);
+        assert_eq!(drafts[0].title.0, "Release 1.2.0");
+        assert_eq!(drafts[0].description.as_str(), "Tag 1.2.0 and publish it");
+        // Values aren't substituted again.
+        assert_eq!(
+            drafts[1].description.as_str(),
+            "Write the post for {{version}}"
+        );
+
+        assert_eq!(
+            template.instantiate(&values(&[("version", "1.2.0")]), &Policy::default()),
+            Err(TemplateError::MissingValue("channel".into()))
+        );
+        let typo = values(&[("version", "1"), ("channel", "blog"), ("verison", "1")]);
+        assert_eq!(
+            template.instantiate(&typo, &Policy::default()),
+            Err(TemplateError::UnknownValue("verison".into()))
+        );
+    }
+
+    #[test]
+    fn test_invalid_templates() {
+        let mut template = release();
+        template.tickets[1].title = "Announce {{version".into();
+        assert_eq!(template.check(), Err(TemplateError::Unclosed { index: 1 }));
+        template.tickets[1].title = "Announce {{ two words }}".into();
+        assert_eq!(
+            template.check(),
+            Err(TemplateError::InvalidPlaceholder {
+                index: 1,
+                name: "two words".into()
+            })
+        );
+
+        let mut template = release();
+        template.name = "release/1".into();
+        assert_eq!(template.check(), Err(TemplateError::InvalidName));
+        template.name = "r".repeat(Template::MAX_NAME_LEN + 1);
+        assert_eq!(template.check(), Err(TemplateError::InvalidName));
+
+        let mut template = release();
+        template.tickets.clear();
+        assert_eq!(template.check(), Err(TemplateError::TicketCount));
+    }
+
+    #[test]
+    fn test_tickets_are_validated_after_substitution() {
+        let template = release();
+        // Fits in a title on its own, but not after `Announce `.
+        let version = "1".repeat(45);
+        let error = template
+            .instantiate(
+                &values(&[("version", &version), ("channel", "blog")]),
+                &Policy::default(),
+            )
+            .unwrap_err();
+        assert!(
+            matches!(error, TemplateError::InvalidTitle { index: 0, .. }),
+            "{}",
+            error
+        );
+        assert!(error
+            .to_string()
+            .starts_with("Ticket 0 of the template: The title"));
+
+        // Like any other ticket, they're trimmed.
+        let drafts = template
+            .instantiate(
+                &values(&[("version", "1"), ("channel", " ")]),
+                &Policy::default(),
+            )
+            .unwrap();
+        assert_eq!(drafts[1].description.as_str(), "Write the post for");
+        let error = template
+            .instantiate(
+                &values(&[("version", "\u{7}"), ("channel", "blog")]),
+                &Policy::default(),
+            )
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            TemplateError::InvalidTitle { index: 0, .. }
+        ));
+    }
+
+    #[test]
+    fn test_store_creates_all_or_nothing() {
+        // An admin, to undo the creates at the end.
+        let alice = User::new("alice", Role::Admin);
+        let mut store = TicketStore::new();
+        store.add_template(release()).unwrap();
+        assert_eq!(
+            store.add_template(release()).unwrap_err(),
+            TemplateError::Exists("release".into())
+        );
+
+        let long = "a".repeat(60);
+        let ids = store.create_from_template(
+            "release",
+            "TKT".parse().unwrap(),
+            &values(&[("version", "1"), ("channel", &long)]),
+            &alice,
+            &Policy::default(),
+        );
+        assert_eq!(ids, Ok(vec![TicketId::from(0), TicketId::from(1)]));
+
+        // The second ticket is invalid, so the first isn't created either.
+        let mut template = release();
+        template.name = "strict".into();
+        template.tickets[1].title = "Announce {{channel}}".into();
+        store.add_template(template).unwrap();
+        let result = store.create_from_template(
+            "strict",
+            "TKT".parse().unwrap(),
+            &values(&[("version", "2"), ("channel", &long)]),
+            &alice,
+            &Policy::default(),
+        );
+        assert!(matches!(
+            result,
+            Err(TemplateError::InvalidTitle { index: 1, .. })
+        ));
+        assert_eq!(store.tickets.len(), 2);
+        assert_eq!(
+            store.next_id("TKT".parse().unwrap()),
+            Some(TicketId::from(2))
+        );
+
+        let draft = ProjectDraft {
+            key: "OLD".parse().unwrap(),
+            settings: ProjectSettings::new("Old"),
+        };
+        store.create_project(draft).unwrap();
+        let patch = ProjectPatch {
+            archived: Some(true),
+            ..ProjectPatch::default()
+        };
+        store.update_project("OLD".parse().unwrap(), patch).unwrap();
+        let result = store.create_from_template(
+            "release",
+            "OLD".parse().unwrap(),
+            &values(&[("version", "1"), ("channel", "blog")]),
+            &alice,
+            &Policy::default(),
+        );
+        assert_eq!(
+            result,
+            Err(TemplateError::Project(ProjectError::Archived(
+                "OLD".parse().unwrap()
+            )))
+        );
+        assert_eq!(
+            store.create_from_template(
+                "nope",
+                "TKT".parse().unwrap(),
+                &values(&[]),
+                &alice,
+                &Policy::default()
+            ),
+            Err(TemplateError::NotFound("nope".into()))
+        );
+
+        // Every ticket can be undone on its own.
+        assert_eq!(store.undo(&alice, 2).tickets.len(), 2);
+        assert!(store.tickets.is_empty());
+    }
+
+    #[test]
+    fn test_templates_are_saved() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("tickets.json");
+        let mut store = TicketStore::new();
+        store.add_template(release()).unwrap();
+        store.save(&path).unwrap();
+
+        let mut loaded = TicketStore::load(&path).unwrap();
+        assert_eq!(loaded.template("release"), Some(&release()));
+        assert_eq!(loaded.remove_template("release"), Some(release()));
+        assert_eq!(loaded.templates().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_template_endpoints() {
+        let addr = start().await;
+        let maintainer = Client::new(addr.to_string(), "maintainer");
+        let alice = Client::new(addr.to_string(), "alice");
+        let viewer = Client::new(addr.to_string(), "viewer");
+
+        assert!(matches!(
+            alice.create_template(&release()).await,
+            Err(ClientError::Forbidden)
+        ));
+        assert_eq!(
+            maintainer.create_template(&release()).await.unwrap(),
+            release()
+        );
+        assert!(matches!(
+            maintainer.create_template(&release()).await,
+            Err(ClientError::Conflict(_))
+        ));
+        let mut invalid = release();
+        invalid.name = "two words".into();
+        assert!(matches!(
+            maintainer.create_template(&invalid).await,
+            Err(ClientError::BadRequest(_))
+        ));
+        assert_eq!(viewer.templates().await.unwrap(), [release()]);
+        assert_eq!(viewer.template("release").await.unwrap(), release());
+
+        let request = TemplateRequest {
+            project: None,
+            values: values(&[("version", "1.2.0"), ("channel", "blog")]),
+        };
+        assert!(matches!(
+            viewer.create_from_template("release", &request).await,
+            Err(ClientError::Forbidden)
+        ));
+        let ids = alice
+            .create_from_template("release", &request)
+            .await
+            .unwrap();
+        assert_eq!(ids, [TicketId::from(0), TicketId::from(1)]);
+        let ticket = alice.get(ids[1]).await.unwrap();
+        assert_eq!(ticket.title.0, "Announce 1.2.0");
+        assert_eq!(ticket.reporter.as_deref(), Some("alice"));
+
+        let missing = TemplateRequest::default();
+        let error = alice.create_from_template("release", &missing).await;
+        assert!(
+            matches!(&error, Err(ClientError::BadRequest(message)) if message.contains("channel")),
+            "{:?}",
+            error
+        );
+        let unknown = TemplateRequest {
+            project: Some("NOPE".parse().unwrap()),
+            ..request.clone()
+        };
+        assert!(matches!(
+            alice.create_from_template("release", &unknown).await,
+            Err(ClientError::NotFound)
+        ));
+        assert_eq!(alice.list().await.unwrap().len(), 2);
+
+        assert!(matches!(
+            alice.delete_template("release").await,
+            Err(ClientError::Forbidden)
+        ));
+        maintainer.delete_template("release").await.unwrap();
+        assert!(matches!(
+            alice.create_from_template("release", &request).await,
+            Err(ClientError::NotFound)
+        ));
+        assert!(matches!(
+            viewer.template("release").await,
+            Err(ClientError::NotFound)
+        ));
+    }
+}
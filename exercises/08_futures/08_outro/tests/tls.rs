@@ -0,0 +1,186 @@
+#[cfg(test)]
+mod tests {
+    use outro_08::auth::{Role, User};
+    use outro_08::server::{self, Config};
+    use outro_08::tls::TlsConfig;
+    use std::io::Write;
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tempfile::NamedTempFile;
+    use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+    use tokio::net::TcpStream;
+    use tokio::time::timeout;
+    use tokio_rustls::rustls::pki_types::{CertificateDer, ServerName};
+    use tokio_rustls::rustls::{self, ClientConfig, RootCertStore};
+    use tokio_rustls::TlsConnector;
+
+    struct SelfSigned {
+        cert: CertificateDer<'static>,
+        cert_file: NamedTempFile,
+        key_file: NamedTempFile,
+    }
+
+    fn self_signed() -> SelfSigned {
+        let certified = rcgen::generate_simple_self_signed(vec!["localhost".into()]).unwrap();
+        let mut cert_file = NamedTempFile::new().unwrap();
+        cert_file
+            .write_all(certified.cert.pem().as_bytes())
+            .unwrap();
+        let mut key_file = NamedTempFile::new().unwrap();
+        key_file
+            .write_all(certified.key_pair.serialize_pem().as_bytes())
+            .unwrap();
+
+        SelfSigned {
+            cert: certified.cert.der().clone(),
+            cert_file,
+            key_file,
+        }
+    }
+
+    async fn start(certs: &SelfSigned, tls_only: bool) -> (Option<SocketAddr>, Option<SocketAddr>) {
+        start_with(certs, tls_only, Config::default()).await
+    }
+
+    /// Like `start`, with the rest of the settings from `config`.
+    async fn start_with(
+        certs: &SelfSigned,
+        tls_only: bool,
+        config: Config,
+    ) -> (Option<SocketAddr>, Option<SocketAddr>) {
+        let config = Config {
+            addr: String::from("127.0.0.1:0"),
+            tls_only,
+            tls: Some(TlsConfig {
+                addr: String::from("127.0.0.1:0"),
+                cert_path: certs.cert_file.path().to_path_buf(),
+                key_path: certs.key_file.path().to_path_buf(),
+            }),
+            ..config
+        };
+        let (listeners, state) = server::init(&config).await.unwrap();
+        state
+            .tokens
+            .write()
+            .await
+            .insert("secret".into(), User::new("alice", Role::Reporter));
+        let addrs = (listeners.http_addr(), listeners.https_addr());
+        tokio::spawn(server::serve(listeners, state));
+        addrs
+    }
+
+    async fn send(mut socket: impl AsyncRead + AsyncWrite + Unpin, request: &str) -> String {
+        socket.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        socket.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    /// A connector that only trusts `cert`.
+    fn connector(cert: &CertificateDer<'static>) -> TlsConnector {
+        let mut roots = RootCertStore::empty();
+        roots.add(cert.clone()).unwrap();
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let config = ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        TlsConnector::from(Arc::new(config))
+    }
+
+    async fn connect_tls(
+        addr: SocketAddr,
+        cert: &CertificateDer<'static>,
+    ) -> impl AsyncRead + AsyncWrite + Unpin {
+        let socket = TcpStream::connect(addr).await.unwrap();
+        connector(cert)
+            .connect(ServerName::try_from("localhost").unwrap(), socket)
+            .await
+            .unwrap()
+    }
+
+    const CREATE: &str = "POST /tickets HTTP/1.1\r\nConnection: close\r\nAuthorization: Bearer secret\r\nContent-Length: 49\r\n\r\n{\"title\":\"A title\",\"description\":\"A description\"}";
+    const GET: &str = "GET /tickets/0 HTTP/1.1\r\nConnection: close\r\nAuthorization: Bearer secret\r\n\r\n";
+
+    #[tokio::test]
+    async fn test_https_alongside_http() {
+        let certs = self_signed();
+        let (http, https) = start(&certs, false).await;
+
+        let socket = connect_tls(https.unwrap(), &certs.cert).await;
+        let response = send(socket, CREATE).await;
+        assert!(response.starts_with("HTTP/1.1 201 Created"));
+
+        // Both listeners serve the same store.
+        let socket = TcpStream::connect(http.unwrap()).await.unwrap();
+        let response = send(socket, GET).await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains(r#""reporter":"alice""#));
+    }
+
+    #[tokio::test]
+    async fn test_tls_only() {
+        let certs = self_signed();
+        let (http, https) = start(&certs, true).await;
+        assert!(http.is_none());
+
+        let socket = connect_tls(https.unwrap(), &certs.cert).await;
+        let response = send(socket, GET).await;
+        assert!(response.starts_with("HTTP/1.1 404 Not Found"));
+    }
+
+    #[tokio::test]
+    async fn test_untrusted_certificate_is_rejected() {
+        let certs = self_signed();
+        let (_, https) = start(&certs, true).await;
+
+        let other = self_signed();
+        let socket = TcpStream::connect(https.unwrap()).await.unwrap();
+        let result = connector(&other.cert)
+            .connect(ServerName::try_from("localhost").unwrap(), socket)
+            .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_stalled_handshake_is_dropped() {
+        let certs = self_signed();
+        let config = Config {
+            max_connections: 1,
+            handshake_timeout: Duration::from_millis(200),
+            ..Config::default()
+        };
+        let (_, https) = start_with(&certs, true, config).await;
+
+        // Takes the only connection slot and never starts the handshake.
+        let mut stalled = TcpStream::connect(https.unwrap()).await.unwrap();
+        let mut buffer = [0; 16];
+        let read = timeout(Duration::from_secs(5), stalled.read(&mut buffer)).await;
+        assert!(matches!(read, Ok(Ok(0) | Err(_))), "not closed: {:?}", read);
+
+        // The slot is free again.
+        let connect = timeout(
+            Duration::from_secs(5),
+            connect_tls(https.unwrap(), &certs.cert),
+        );
+        let response = send(connect.await.unwrap(), GET).await;
+        assert!(response.starts_with("HTTP/1.1 404 Not Found"));
+    }
+
+    #[tokio::test]
+    async fn test_missing_key_file() {
+        let certs = self_signed();
+        let config = Config {
+            addr: String::from("127.0.0.1:0"),
+            tls: Some(TlsConfig {
+                addr: String::from("127.0.0.1:0"),
+                cert_path: certs.cert_file.path().to_path_buf(),
+                key_path: "does/not/exist.pem".into(),
+            }),
+            ..Config::default()
+        };
+        assert!(server::init(&config).await.is_err());
+    }
+}
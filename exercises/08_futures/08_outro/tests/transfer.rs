@@ -0,0 +1,295 @@
+#[cfg(test)]
+mod tests {
+    use outro_08::auth::{Role, User};
+    use outro_08::data::{Status, Ticket, TicketDraft};
+    use outro_08::links::{Link, LinkKind};
+    use outro_08::projects::ProjectKey;
+    use outro_08::store::{TicketId, TicketStore};
+    use outro_08::transfer::{
+        self, CsvMapping, Format, Ids, ImportOptions, ImportReport, Parsed, RowError,
+    };
+    use ticket_fields::Policy;
+
+    /// Imports under the built-in rules only.
+    fn import(store: &mut TicketStore, parsed: Parsed, options: ImportOptions) -> ImportReport {
+        transfer::import(store, parsed, options, &Policy::default())
+    }
+
+    fn store() -> TicketStore {
+        let mut store = TicketStore::new();
+        store.add_ticket(TicketDraft::new(
+            "Crash, on start".into(),
+            Some("It \"crashes\".\n\nEvery time.".into()),
+        ));
+        let id = store.add_ticket(TicketDraft::new("Dark mode".into(), None));
+        {
+            let ticket = store.get(id).unwrap();
+            let mut ticket = ticket.write().unwrap();
+            ticket.status = Status::Done;
+            ticket.reporter = Some(String::from("alice"));
+        }
+        store
+    }
+
+    fn tickets(store: &TicketStore) -> Vec<Ticket> {
+        store
+            .tickets
+            .values()
+            .map(|ticket| ticket.read().unwrap().clone())
+            .collect()
+    }
+
+    fn export(store: &TicketStore, format: Format) -> String {
+        let mut out = vec![];
+        transfer::export(tickets(store), format, &CsvMapping::default(), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn parse(input: &str, format: Format) -> Parsed {
+        transfer::parse(input.as_bytes(), format, &CsvMapping::default()).unwrap()
+    }
+
+    const PRESERVE: ImportOptions = ImportOptions {
+        ids: Ids::Preserve,
+        dry_run: false,
+    };
+    const REMAP: ImportOptions = ImportOptions {
+        ids: Ids::Remap,
+        dry_run: false,
+    };
+
+    #[test]
+    fn test_round_trips() {
+        let original = store();
+        for format in [Format::Jsonl, Format::Csv, Format::Markdown] {
+            let parsed = parse(&export(&original, format), format);
+            assert_eq!(parsed.errors, [], "{:?}", format);
+
+            let mut copy = TicketStore::new();
+            let report = import(&mut copy, parsed, PRESERVE);
+            assert!(report.is_ok());
+            assert_eq!(tickets(&copy), tickets(&original), "{:?}", format);
+            let default = ProjectKey::DEFAULT;
+            assert_eq!(copy.next_id(default), original.next_id(default));
+        }
+    }
+
+    #[test]
+    fn test_markdown() {
+        let markdown = export(&store(), Format::Markdown);
+        assert_eq!(
+            markdown,
+            "# Tickets\n\
+             \n\
+             ## ToDo\n\
+             \n\
+             - [ ] #TKT-0 Crash, on start\n  \
+               It \"crashes\".\n\
+             \n  \
+               Every time.\n\
+             \n\
+             ## Done\n\
+             \n\
+             - [x] #TKT-1 Dark mode\n  \
+               Default description\n  \
+               _Reported by alice_\n"
+        );
+
+        // Checked items are done wherever they are; ids are optional.
+        let parsed = parse(
+            "## InProgress\n- [ ] Started\n  Details\n- [x] Finished\n  More",
+            Format::Markdown,
+        );
+        assert_eq!(parsed.errors, []);
+        assert_eq!(parsed.rows[0].status, Status::InProgress);
+        assert_eq!(parsed.rows[0].id, None);
+        assert_eq!(parsed.rows[0].description.as_str(), "Details");
+        assert_eq!(parsed.rows[1].status, Status::Done);
+    }
+
+    #[test]
+    fn test_errors_per_row_and_field() {
+        let long_title = "a".repeat(51);
+        let input = format!(
+            "{{\"title\": \"Fine\", \"description\": \"Fine\"}}\n\
+             \n\
+             {{\"title\": \"{}\", \"description\": \"\", \"status\": \"someday\"}}\n\
+             not json\n\
+             {{\"id\": \"x\", \"description\": \"Fine\"}}\n",
+            long_title
+        );
+        let parsed = parse(&input, Format::Jsonl);
+        assert_eq!(parsed.rows.len(), 1);
+        let errors: Vec<String> = parsed.errors.iter().map(RowError::to_string).collect();
+        assert_eq!(errors.len(), 6);
+        assert_eq!(
+            errors[0],
+            "line 3, title: The title cannot be longer than 50 characters, but it has 51"
+        );
+        assert_eq!(
+            errors[1],
+            "line 3, description: The description cannot be empty"
+        );
+        assert!(errors[2].starts_with("line 3, status: "));
+        assert!(errors[3].starts_with("line 4: "));
+        assert_eq!(errors[4], "line 5, id: `x` is not a ticket id");
+        assert_eq!(errors[5], "line 5, title: Missing");
+
+        // Nothing is imported if any row is invalid.
+        let mut store = TicketStore::new();
+        let report = import(&mut store, parsed, REMAP);
+        assert_eq!(report.errors.len(), 6);
+        assert!(report.imported.is_empty());
+        assert!(store.tickets.is_empty());
+    }
+
+    #[test]
+    fn test_dry_run() {
+        let mut store = store();
+        let parsed = parse("- [ ] New\n  Ticket", Format::Markdown);
+        let options = ImportOptions {
+            ids: Ids::Remap,
+            dry_run: true,
+        };
+        let report = import(&mut store, parsed, options);
+        assert!(report.dry_run);
+        assert_eq!(report.imported[0].id, TicketId::from(2));
+        assert_eq!(store.tickets.len(), 2);
+        assert_eq!(store.next_id(ProjectKey::DEFAULT), Some(TicketId::from(2)));
+    }
+
+    #[test]
+    fn test_preserve_or_remap_ids() {
+        let input = "{\"id\": 7, \"title\": \"A\", \"description\": \"B\"}\n\
+                     {\"id\": 1, \"title\": \"C\", \"description\": \"D\"}\n";
+
+        let mut store = store();
+        let report = import(&mut store, parse(input, Format::Jsonl), REMAP);
+        let ids: Vec<_> = report.imported.iter().map(|i| (i.source, i.id)).collect();
+        assert_eq!(
+            ids,
+            [
+                (Some(TicketId::from(7)), TicketId::from(2)),
+                (Some(TicketId::from(1)), TicketId::from(3))
+            ]
+        );
+
+        // Id 1 is taken.
+        let mut store = self::store();
+        let report = import(&mut store, parse(input, Format::Jsonl), PRESERVE);
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(
+            report.errors[0].to_string(),
+            "line 2, id: Already taken in the store"
+        );
+        assert_eq!(store.tickets.len(), 2);
+
+        let mut store = TicketStore::new();
+        let report = import(&mut store, parse(input, Format::Jsonl), PRESERVE);
+        assert!(report.is_ok());
+        assert_eq!(store.get(TicketId::from(7)).unwrap().read().unwrap().title.0, "A");
+        // New tickets don't collide with preserved ids.
+        assert_eq!(
+            store.add_ticket(TicketDraft::new("E".into(), None)),
+            TicketId::from(8)
+        );
+
+        let duplicate = "{\"id\": 1, \"title\": \"A\", \"description\": \"B\"}\n\
+                         {\"title\": \"C\", \"description\": \"D\"}\n\
+                         {\"id\": 1, \"title\": \"C\", \"description\": \"D\"}\n";
+        let report = import(
+            &mut TicketStore::new(),
+            parse(duplicate, Format::Jsonl),
+            PRESERVE,
+        );
+        let errors: Vec<String> = report.errors.iter().map(RowError::to_string).collect();
+        assert_eq!(
+            errors,
+            [
+                "line 2, id: Missing, but needed to preserve ids",
+                "line 3, id: Used by an earlier row",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_imports_follow_the_policy() {
+        let policy: Policy =
+            serde_json::from_str(r#"{"description": {"banned_words": ["asap"]}}"#).unwrap();
+        let input = "{\"title\": \"A\", \"description\": \"B\"}\n\
+                     {\"title\": \"C\", \"description\": \"Fix it ASAP\"}\n";
+
+        let mut store = TicketStore::new();
+        let parsed = parse(input, Format::Jsonl);
+        let report = transfer::import(&mut store, parsed, REMAP, &policy);
+        let errors: Vec<String> = report.errors.iter().map(RowError::to_string).collect();
+        assert_eq!(
+            errors,
+            ["line 2, description: The description cannot contain \"ASAP\", but has it at character 8"]
+        );
+        assert!(report.imported.is_empty());
+        assert!(store.tickets.is_empty());
+
+        assert!(import(&mut store, parse(input, Format::Jsonl), REMAP).is_ok());
+    }
+
+    #[test]
+    fn test_blocked_tickets_cant_be_imported_as_done() {
+        let mut store = TicketStore::new();
+        let blocker = store.add_ticket(TicketDraft::new("Blocker".into(), None));
+        let blocked = store.add_ticket(TicketDraft::new("Blocked".into(), None));
+        let link = Link {
+            from: blocker,
+            kind: LinkKind::Blocks,
+            to: blocked,
+        };
+        store.link(link).unwrap();
+        // Deleted tickets keep their links, and their ids can be imported again.
+        store.delete(blocked, &User::new("alice", Role::Admin));
+
+        let input = "{\"id\": 1, \"title\": \"A\", \"description\": \"B\", \"status\": \"Done\"}\n";
+        let report = import(&mut store, parse(input, Format::Jsonl), PRESERVE);
+        assert!(report.imported.is_empty());
+        let errors: Vec<String> = report.errors.iter().map(RowError::to_string).collect();
+        assert_eq!(
+            errors,
+            ["line 1, status: The ticket can't be done while it's blocked by TKT-0"]
+        );
+        assert!(store.get(blocked).is_none());
+
+        let input = input.replace("Done", "InProgress");
+        let report = import(&mut store, parse(&input, Format::Jsonl), PRESERVE);
+        assert!(report.is_ok());
+    }
+
+    #[test]
+    fn test_csv_mapping() {
+        let mut mapping = CsvMapping::default();
+        mapping.set("title=Summary").unwrap();
+        mapping.set("description = Details").unwrap();
+        mapping.set("status=State").unwrap();
+        assert!(mapping.set("owner=Assignee").is_err());
+
+        let input = "Key,Summary,Details,State,Extra\n\
+                     ,Login fails,\"Says \"\"no\"\"\",in progress,x\n\
+                     ,,Empty title,,\n";
+        let parsed = transfer::parse(input.as_bytes(), Format::Csv, &mapping).unwrap();
+        assert_eq!(parsed.rows.len(), 1);
+        assert_eq!(parsed.rows[0].title.0, "Login fails");
+        assert_eq!(parsed.rows[0].description.as_str(), "Says \"no\"");
+        assert_eq!(parsed.rows[0].status, Status::InProgress);
+        assert_eq!(
+            parsed.errors[0].to_string(),
+            "line 3, title: The title cannot be empty"
+        );
+
+        let error =
+            transfer::parse(input.as_bytes(), Format::Csv, &CsvMapping::default()).unwrap_err();
+        assert_eq!(error.to_string(), "The CSV header has no `title` column");
+
+        let mut out = vec![];
+        transfer::export(tickets(&store()), Format::Csv, &mapping, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("id,Summary,Details,State,reporter\n"));
+    }
+}
@@ -1,22 +1,94 @@
 use serde::{Deserialize, Serialize};
 
+use crate::policy::{Policy, Violations};
+use crate::text::{self, Problem};
+
+/// A ticket description: at most `MAX_GRAPHEMES` user-perceived characters,
+/// NFC-normalized and trimmed. Newlines and tabs are allowed, with `\r\n`
+/// line endings turned into `\n`.
 #[derive(Debug, PartialEq, Clone, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String")]
 pub struct TicketDescription(pub String);
 
-#[derive(Debug, thiserror::Error)]
+impl TicketDescription {
+    pub const MAX_GRAPHEMES: usize = 500;
+
+    /// Checks `value` against `policy`, reporting every rule it breaks.
+    /// `TryFrom` only checks the built-in rules, and reports the first broken.
+    pub fn validate_with(
+        value: &str,
+        policy: &Policy,
+    ) -> Result<Self, Violations<TicketDescriptionError>> {
+        let description = text::normalize(value, true);
+        let problems = text::check(
+            value,
+            &description,
+            &policy.description,
+            Self::MAX_GRAPHEMES,
+            true,
+        );
+        if problems.is_empty() {
+            Ok(Self(description))
+        } else {
+            Err(Violations(
+                problems
+                    .into_iter()
+                    .map(TicketDescriptionError::from)
+                    .collect(),
+            ))
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
 pub enum TicketDescriptionError {
     #[error("The description cannot be empty")]
     Empty,
-    #[error("The description cannot be longer than 500 bytes")]
-    TooLong,
+    #[error("The description cannot be only whitespace")]
+    Blank,
+    #[error("The description cannot be longer than {max} characters, but it has {length}")]
+    TooLong { max: usize, length: usize },
+    #[error("The description cannot contain control characters, but has {character:?} at character {position}")]
+    ControlCharacter { position: usize, character: char },
+    #[error("The description cannot contain bidirectional overrides, but has {character:?} at character {position}")]
+    BidiOverride { position: usize, character: char },
+    #[error("The description must start with one of {}", .expected.join(", "))]
+    MissingPrefix { expected: Vec<String> },
+    #[error("The description cannot contain {word:?}, but has it at character {position}")]
+    BannedWord { word: String, position: usize },
+}
+
+impl From<Problem> for TicketDescriptionError {
+    fn from(problem: Problem) -> Self {
+        match problem {
+            Problem::Empty => Self::Empty,
+            Problem::Blank => Self::Blank,
+            Problem::TooLong { max, length } => Self::TooLong { max, length },
+            Problem::Control {
+                position,
+                character,
+            } => Self::ControlCharacter {
+                position,
+                character,
+            },
+            Problem::BidiOverride {
+                position,
+                character,
+            } => Self::BidiOverride {
+                position,
+                character,
+            },
+            Problem::MissingPrefix { expected } => Self::MissingPrefix { expected },
+            Problem::BannedWord { word, position } => Self::BannedWord { word, position },
+        }
+    }
 }
 
 impl TryFrom<String> for TicketDescription {
     type Error = TicketDescriptionError;
 
     fn try_from(value: String) -> Result<Self, Self::Error> {
-        validate(&value)?;
-        Ok(Self(value))
+        Self::try_from(value.as_str())
     }
 }
 
@@ -24,24 +96,14 @@ impl TryFrom<&str> for TicketDescription {
     type Error = TicketDescriptionError;
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
-        validate(value)?;
-        Ok(Self(value.to_string()))
-    }
-}
-
-fn validate(description: &str) -> Result<(), TicketDescriptionError> {
-    if description.is_empty() {
-        Err(TicketDescriptionError::Empty)
-    } else if description.len() > 500 {
-        Err(TicketDescriptionError::TooLong)
-    } else {
-        Ok(())
+        Self::validate_with(value, &Policy::default()).map_err(Violations::first)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::FieldPolicy;
     use common::{overly_long_description, valid_description};
     use std::convert::TryFrom;
 
@@ -63,13 +125,82 @@ mod tests {
         let err = TicketDescription::try_from(overly_long_description()).unwrap_err();
         assert_eq!(
             err.to_string(),
-            "The description cannot be longer than 500 bytes"
+            "The description cannot be longer than 500 characters, but it has 844"
         );
     }
 
+    #[test]
+    fn test_deserialize_validates() {
+        let json = serde_json::to_string(&overly_long_description()).unwrap();
+        assert!(serde_json::from_str::<TicketDescription>(&json).is_err());
+        assert!(serde_json::from_str::<TicketDescription>("\"\"").is_err());
+    }
+
     #[test]
     fn test_try_from_str() {
         let description = TicketDescription::try_from("A description").unwrap();
         assert_eq!(description.0, "A description");
     }
+
+    #[test]
+    fn test_multiline() {
+        let description = TicketDescription::try_from("\n First line\r\n\tSecond line \n").unwrap();
+        assert_eq!(description.0, "First line\n\tSecond line");
+
+        let err = TicketDescription::try_from("Tab\tok, escape \u{1b}[31m not").unwrap_err();
+        assert_eq!(
+            err,
+            TicketDescriptionError::ControlCharacter {
+                position: 16,
+                character: '\u{1b}'
+            }
+        );
+    }
+
+    #[test]
+    fn test_counts_graphemes_not_bytes() {
+        let emoji = "🎉".repeat(500);
+        let policy = Policy {
+            description: FieldPolicy {
+                max_graphemes: Some(10),
+                ..FieldPolicy::default()
+            },
+            ..Policy::default()
+        };
+        assert!(TicketDescription::try_from(emoji.as_str()).is_ok());
+        assert_eq!(
+            TicketDescription::validate_with(&emoji, &policy).unwrap_err(),
+            Violations(vec![TicketDescriptionError::TooLong {
+                max: 10,
+                length: 500
+            }])
+        );
+    }
+
+    #[test]
+    fn test_policy_limit_is_capped() {
+        // Fine for Markdown descriptions, but over the plain limit.
+        let policy: Policy =
+            serde_json::from_str(r#"{"description": {"max_graphemes": 1000}}"#).unwrap();
+        let long = "a".repeat(600);
+        assert!(TicketDescription::validate_with(&long, &policy).is_err());
+        let description = TicketDescription::validate_with(&"a".repeat(500), &policy).unwrap();
+        let json = serde_json::to_string(&description).unwrap();
+        assert_eq!(
+            serde_json::from_str::<TicketDescription>(&json).unwrap(),
+            description
+        );
+    }
+
+    #[test]
+    fn test_rejects_blank_and_bidi() {
+        assert_eq!(
+            TicketDescription::try_from("\n\n").unwrap_err(),
+            TicketDescriptionError::Blank
+        );
+        assert!(matches!(
+            TicketDescription::try_from("x\u{2067}y").unwrap_err(),
+            TicketDescriptionError::BidiOverride { position: 2, .. }
+        ));
+    }
 }
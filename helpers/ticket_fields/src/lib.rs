@@ -1,6 +1,11 @@
 mod description;
+mod markdown;
+mod policy;
 pub mod test_helpers;
+mod text;
 mod title;
 
-pub use description::TicketDescription;
-pub use title::TicketTitle;
+pub use description::{TicketDescription, TicketDescriptionError};
+pub use markdown::{Link, MarkdownDescription};
+pub use policy::{FieldPolicy, Policy, Violations};
+pub use title::{TicketTitle, TicketTitleError};
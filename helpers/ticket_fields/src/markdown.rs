@@ -0,0 +1,364 @@
+use pulldown_cmark::{html, CowStr, Event, LinkType, Options, Parser, Tag, TagEnd};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::policy::{Policy, Violations};
+use crate::text;
+use crate::{TicketDescription, TicketDescriptionError};
+
+/// A ticket description written in Markdown (CommonMark, plus tables,
+/// strikethrough and task lists).
+///
+/// It's checked like a `TicketDescription`, with a higher limit, and parsed
+/// once on construction to find its links and `#123`-style ticket references.
+/// It serializes as its Markdown source.
+#[derive(Debug, PartialEq, Clone, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct MarkdownDescription {
+    source: String,
+    links: Vec<Link>,
+    references: Vec<u64>,
+}
+
+/// A link in a description, e.g. `[the docs](https://example.com)`.
+#[derive(Debug, PartialEq, Clone, Eq)]
+pub struct Link {
+    pub url: String,
+    /// The text shown for the link, as plain text.
+    pub text: String,
+}
+
+impl MarkdownDescription {
+    pub const MAX_GRAPHEMES: usize = 5000;
+
+    /// Checks `value` against `policy`, whose description settings apply,
+    /// reporting every rule it breaks.
+    pub fn validate_with(
+        value: &str,
+        policy: &Policy,
+    ) -> Result<Self, Violations<TicketDescriptionError>> {
+        let source = text::normalize(value, true);
+        let problems = text::check(
+            value,
+            &source,
+            &policy.description,
+            Self::MAX_GRAPHEMES,
+            true,
+        );
+        if !problems.is_empty() {
+            return Err(Violations(
+                problems
+                    .into_iter()
+                    .map(TicketDescriptionError::from)
+                    .collect(),
+            ));
+        }
+
+        let (links, references) = scan(&source);
+        Ok(Self {
+            source,
+            links,
+            references,
+        })
+    }
+
+    /// The Markdown source.
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// Every link, in order of appearance.
+    pub fn links(&self) -> &[Link] {
+        &self.links
+    }
+
+    /// Ids of the tickets mentioned as `#123`, in order of first appearance.
+    /// Mentions in code are ignored.
+    pub fn references(&self) -> &[u64] {
+        &self.references
+    }
+
+    /// Renders the description as HTML that's safe to embed in a page:
+    /// raw HTML in the source is escaped, and links and images only keep
+    /// `http`, `https`, `mailto` and relative URLs.
+    pub fn to_html(&self) -> String {
+        let events = parser(&self.source).filter_map(|event| match event {
+            Event::Html(html) | Event::InlineHtml(html) => Some(Event::Text(html)),
+            Event::Start(Tag::Link {
+                link_type,
+                dest_url,
+                title,
+                id,
+            }) => Some(Event::Start(Tag::Link {
+                link_type,
+                dest_url: safe_url(dest_url),
+                title,
+                id,
+            })),
+            Event::Start(Tag::Image {
+                link_type,
+                dest_url,
+                title,
+                id,
+            }) => Some(Event::Start(Tag::Image {
+                link_type,
+                dest_url: safe_url(dest_url),
+                title,
+                id,
+            })),
+            Event::Start(Tag::HtmlBlock) | Event::End(TagEnd::HtmlBlock) => None,
+            event => Some(event),
+        });
+        let mut out = String::new();
+        html::push_html(&mut out, events);
+        out
+    }
+
+    /// The text without any formatting, e.g. for search.
+    /// Blocks are separated by newlines.
+    pub fn to_plain_text(&self) -> String {
+        let mut out = String::new();
+        for event in parser(&self.source) {
+            match event {
+                Event::Text(text) | Event::Code(text) => out.push_str(&text),
+                Event::Html(html) | Event::InlineHtml(html) => out.push_str(&html),
+                Event::SoftBreak => out.push(' '),
+                Event::HardBreak => out.push('\n'),
+                Event::End(
+                    TagEnd::Paragraph
+                    | TagEnd::Heading(_)
+                    | TagEnd::Item
+                    | TagEnd::CodeBlock
+                    | TagEnd::TableRow
+                    | TagEnd::TableHead,
+                ) if !out.ends_with('\n') => out.push('\n'),
+                Event::End(TagEnd::TableCell) => out.push(' '),
+                _ => {}
+            }
+        }
+        out.trim_end().to_string()
+    }
+}
+
+fn parser(source: &str) -> Parser<'_> {
+    let options =
+        Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS;
+    Parser::new_ext(source, options)
+}
+
+/// Finds the links and ticket references in `source`.
+fn scan(source: &str) -> (Vec<Link>, Vec<u64>) {
+    let mut links = vec![];
+    let mut references = vec![];
+    // The link being read, if any.
+    let mut link: Option<Link> = None;
+    let mut in_code_block = false;
+
+    for event in parser(source) {
+        match event {
+            Event::Start(Tag::Link {
+                link_type,
+                dest_url,
+                ..
+            }) => {
+                let url = match link_type {
+                    LinkType::Email => format!("mailto:{}", dest_url),
+                    _ => dest_url.to_string(),
+                };
+                link = Some(Link {
+                    url,
+                    text: String::new(),
+                });
+            }
+            Event::End(TagEnd::Link) => links.extend(link.take()),
+            Event::Start(Tag::CodeBlock(_)) => in_code_block = true,
+            Event::End(TagEnd::CodeBlock) => in_code_block = false,
+            Event::Code(code) => {
+                if let Some(link) = &mut link {
+                    link.text.push_str(&code);
+                }
+            }
+            Event::Text(text) => {
+                if let Some(link) = &mut link {
+                    link.text.push_str(&text);
+                }
+                if !in_code_block {
+                    for id in ticket_references(&text) {
+                        if !references.contains(&id) {
+                            references.push(id);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    (links, references)
+}
+
+/// `#` followed by digits, not touching other letters or digits:
+/// `see #12.` mentions 12, `a#12` and `#12a` don't.
+fn ticket_references(text: &str) -> impl Iterator<Item = u64> + '_ {
+    text.match_indices('#').filter_map(move |(start, _)| {
+        let before = text[..start].chars().next_back();
+        if before.is_some_and(|c| c.is_alphanumeric() || c == '#') {
+            return None;
+        }
+        let digits = &text[start + 1..];
+        let len = digits
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(digits.len());
+        let after = digits[len..].chars().next();
+        if len == 0 || after.is_some_and(|c| c.is_alphanumeric() || c == '_') {
+            return None;
+        }
+        digits[..len].parse().ok()
+    })
+}
+
+/// `url` if it can't run code when followed, or an empty URL otherwise.
+fn safe_url(url: CowStr<'_>) -> CowStr<'_> {
+    let scheme = url
+        .split_once(':')
+        .map(|(scheme, _)| scheme)
+        .filter(|scheme| !scheme.contains(['/', '?', '#']));
+    match scheme {
+        None => url,
+        Some(scheme)
+            if ["http", "https", "mailto"]
+                .iter()
+                .any(|safe| scheme.eq_ignore_ascii_case(safe)) =>
+        {
+            url
+        }
+        Some(_) => CowStr::Borrowed(""),
+    }
+}
+
+impl TryFrom<String> for MarkdownDescription {
+    type Error = TicketDescriptionError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::try_from(value.as_str())
+    }
+}
+
+impl TryFrom<&str> for MarkdownDescription {
+    type Error = TicketDescriptionError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Self::validate_with(value, &Policy::default()).map_err(Violations::first)
+    }
+}
+
+/// Plain descriptions are valid Markdown, and always within the limit.
+impl From<TicketDescription> for MarkdownDescription {
+    fn from(description: TicketDescription) -> Self {
+        let (links, references) = scan(&description.0);
+        Self {
+            source: description.0,
+            links,
+            references,
+        }
+    }
+}
+
+impl From<MarkdownDescription> for String {
+    fn from(description: MarkdownDescription) -> Self {
+        description.source
+    }
+}
+
+impl fmt::Display for MarkdownDescription {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn markdown(source: &str) -> MarkdownDescription {
+        MarkdownDescription::try_from(source).unwrap()
+    }
+
+    #[test]
+    fn test_higher_limit() {
+        let long = "a".repeat(MarkdownDescription::MAX_GRAPHEMES);
+        assert!(MarkdownDescription::try_from(long.as_str()).is_ok());
+        let err = MarkdownDescription::try_from(format!("{}a", long)).unwrap_err();
+        assert!(matches!(err, TicketDescriptionError::TooLong { .. }));
+        assert_eq!(
+            MarkdownDescription::try_from(" ").unwrap_err(),
+            TicketDescriptionError::Blank
+        );
+    }
+
+    #[test]
+    fn test_render_html() {
+        let description = markdown("# Crash\n\nSteps:\n\n1. Open **the app**\n2. See `panic!`");
+        assert_eq!(
+            description.to_html(),
+            "<h1>Crash</h1>\n<p>Steps:</p>\n<ol>\n<li>Open <strong>the app</strong></li>\n\
+             <li>See <code>panic!</code></li>\n</ol>\n"
+        );
+    }
+
+    #[test]
+    fn test_html_is_sanitized() {
+        let description = markdown(
+            "<script>alert(1)</script>\n\n\
+             Hi <img src=x onerror=alert(1)> \
+             [click](javascript:alert(1)) [ok](https://example.com) [rel](/tickets/1)",
+        );
+        let html = description.to_html();
+        assert!(!html.contains("<script"), "{}", html);
+        assert!(!html.contains("<img"), "{}", html);
+        assert!(!html.contains("javascript:"), "{}", html);
+        assert!(html.contains("&lt;script&gt;"), "{}", html);
+        assert!(html.contains(r#"<a href="https://example.com">ok</a>"#));
+        assert!(html.contains(r#"<a href="/tickets/1">rel</a>"#));
+    }
+
+    #[test]
+    fn test_plain_text() {
+        let description = markdown("# Title\n\nSome *emphasis*\nand `code`.\n\n- one\n- two");
+        assert_eq!(
+            description.to_plain_text(),
+            "Title\nSome emphasis and code.\none\ntwo"
+        );
+    }
+
+    #[test]
+    fn test_links_and_references() {
+        let description = markdown(
+            "Duplicate of #12, see [the *docs*](https://example.com/docs) and <me@example.com>.\n\n\
+             Also #7 and #12 again, but not a#3, #4b, `#5` or:\n\n    #6\n\n## Heading",
+        );
+        assert_eq!(description.references(), [12, 7]);
+        assert_eq!(
+            description.links(),
+            [
+                Link {
+                    url: "https://example.com/docs".into(),
+                    text: "the docs".into()
+                },
+                Link {
+                    url: "mailto:me@example.com".into(),
+                    text: "me@example.com".into()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_serializes_as_source() {
+        let description = markdown("See #1");
+        let json = serde_json::to_string(&description).unwrap();
+        assert_eq!(json, "\"See #1\"");
+        let parsed: MarkdownDescription = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.references(), [1]);
+        assert!(serde_json::from_str::<MarkdownDescription>("\"\"").is_err());
+    }
+}
@@ -0,0 +1,105 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::{MarkdownDescription, TicketTitle};
+
+/// Rules for titles and descriptions on top of the built-in ones, which are
+/// always enforced: no empty or whitespace-only text, control characters or
+/// bidirectional overrides.
+///
+/// Policies only apply through `validate_with`, to new input: `TryFrom` and
+/// deserialization check the built-in rules alone, so text that was saved
+/// before a policy got stricter still loads.
+///
+/// Policies can be read from config with serde, e.g. from JSON:
+///
+/// ```
+/// # use ticket_fields::Policy;
+/// let policy: Policy = serde_json::from_str(r#"{
+///     "title": { "max_graphemes": 40, "required_prefixes": ["[BUG]", "[FEATURE]"] },
+///     "description": { "banned_words": ["asap"] }
+/// }"#).unwrap();
+/// assert_eq!(policy.title.max_graphemes, Some(40));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "Fields")]
+pub struct Policy {
+    pub title: FieldPolicy,
+    pub description: FieldPolicy,
+}
+
+/// A `Policy` as it's written, before its limits are checked.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Fields {
+    title: FieldPolicy,
+    description: FieldPolicy,
+}
+
+impl TryFrom<Fields> for Policy {
+    type Error = String;
+
+    fn try_from(fields: Fields) -> Result<Self, Self::Error> {
+        let limits = [
+            ("title", &fields.title, TicketTitle::MAX_GRAPHEMES),
+            (
+                "description",
+                &fields.description,
+                MarkdownDescription::MAX_GRAPHEMES,
+            ),
+        ];
+        for (name, field, max) in limits {
+            if field.max_graphemes.is_some_and(|limit| limit > max) {
+                return Err(format!(
+                    "The {} limit can't be raised above {} characters",
+                    name, max
+                ));
+            }
+        }
+        Ok(Self {
+            title: fields.title,
+            description: fields.description,
+        })
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FieldPolicy {
+    /// Limit on user-perceived characters. It can only lower the field's
+    /// `MAX_GRAPHEMES`, since loading saved text only checks that one: higher
+    /// limits are refused when parsing a policy, and capped otherwise.
+    /// `None` keeps `MAX_GRAPHEMES`.
+    pub max_graphemes: Option<usize>,
+    /// If not empty, the text must start with one of these, e.g. `[BUG]`.
+    pub required_prefixes: Vec<String>,
+    /// Words the text can't contain, matched as whole words, ignoring case.
+    pub banned_words: Vec<String>,
+}
+
+/// Every rule a value broke, in the order they were checked. Never empty.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Violations<E>(pub Vec<E>);
+
+impl<E> Violations<E> {
+    pub fn first(self) -> E {
+        self.0
+            .into_iter()
+            .next()
+            .expect("Violations are never empty")
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for Violations<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, violation) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str("; ")?;
+            }
+            write!(f, "{}", violation)?;
+        }
+        Ok(())
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> std::error::Error for Violations<E> {}
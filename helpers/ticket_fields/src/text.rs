@@ -0,0 +1,110 @@
+use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::policy::FieldPolicy;
+
+/// What's wrong with a piece of text. Positions count user-perceived
+/// characters (grapheme clusters), starting from 1, in the normalized text.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Problem {
+    Empty,
+    Blank,
+    TooLong { max: usize, length: usize },
+    Control { position: usize, character: char },
+    BidiOverride { position: usize, character: char },
+    MissingPrefix { expected: Vec<String> },
+    BannedWord { word: String, position: usize },
+}
+
+/// NFC-normalizes `text` and trims whitespace at both ends.
+/// Multi-line text also gets its line endings turned into `\n`.
+pub(crate) fn normalize(text: &str, multiline: bool) -> String {
+    let text: String = text.trim().nfc().collect();
+    if multiline {
+        text.replace("\r\n", "\n")
+    } else {
+        text
+    }
+}
+
+/// Checks already normalized text against the built-in rules and `policy`,
+/// returning every problem found. `original` is the text before normalization,
+/// used to tell empty input from whitespace-only input.
+pub(crate) fn check(
+    original: &str,
+    text: &str,
+    policy: &FieldPolicy,
+    max_graphemes: usize,
+    multiline: bool,
+) -> Vec<Problem> {
+    if text.is_empty() {
+        return vec![if original.is_empty() {
+            Problem::Empty
+        } else {
+            Problem::Blank
+        }];
+    }
+
+    let mut problems = vec![];
+    let mut length = 0;
+    for (index, grapheme) in text.graphemes(true).enumerate() {
+        length += 1;
+        let position = index + 1;
+        for character in grapheme.chars() {
+            if is_bidi_override(character) {
+                problems.push(Problem::BidiOverride {
+                    position,
+                    character,
+                });
+            }
+            let allowed = multiline && matches!(character, '\n' | '\t');
+            if character.is_control() && !allowed {
+                problems.push(Problem::Control {
+                    position,
+                    character,
+                });
+            }
+        }
+    }
+
+    let max = policy
+        .max_graphemes
+        .map_or(max_graphemes, |max| max.min(max_graphemes));
+    if length > max {
+        problems.push(Problem::TooLong { max, length });
+    }
+
+    let prefixes = &policy.required_prefixes;
+    if !prefixes.is_empty()
+        && !prefixes
+            .iter()
+            .any(|prefix| text.starts_with(prefix.as_str()))
+    {
+        problems.push(Problem::MissingPrefix {
+            expected: prefixes.clone(),
+        });
+    }
+
+    if !policy.banned_words.is_empty() {
+        for (offset, word) in text.unicode_word_indices() {
+            let banned = policy
+                .banned_words
+                .iter()
+                .any(|banned| banned.to_lowercase() == word.to_lowercase());
+            if banned {
+                problems.push(Problem::BannedWord {
+                    word: word.to_string(),
+                    position: text[..offset].graphemes(true).count() + 1,
+                });
+            }
+        }
+    }
+
+    problems
+}
+
+/// Characters that change the direction of the text around them, which can
+/// make text display differently from how it reads.
+fn is_bidi_override(character: char) -> bool {
+    matches!(character, '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}')
+}
@@ -1,23 +1,86 @@
 use std::convert::TryFrom;
 use serde::{Deserialize, Serialize};
 
+use crate::policy::{Policy, Violations};
+use crate::text::{self, Problem};
+
+/// A ticket title: one line of at most `MAX_GRAPHEMES` user-perceived characters,
+/// NFC-normalized and trimmed.
 #[derive(Debug, PartialEq, Clone, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String")]
 pub struct TicketTitle(pub String);
 
-#[derive(Debug, thiserror::Error)]
+impl TicketTitle {
+    /// Limit on grapheme clusters, so `日本語` counts as 3 and `👩‍👩‍👧` as 1.
+    pub const MAX_GRAPHEMES: usize = 50;
+
+    /// Checks `value` against `policy`, reporting every rule it breaks.
+    /// `TryFrom` only checks the built-in rules, and reports the first broken.
+    pub fn validate_with(
+        value: &str,
+        policy: &Policy,
+    ) -> Result<Self, Violations<TicketTitleError>> {
+        let title = text::normalize(value, false);
+        let problems = text::check(value, &title, &policy.title, Self::MAX_GRAPHEMES, false);
+        if problems.is_empty() {
+            Ok(Self(title))
+        } else {
+            Err(Violations(
+                problems.into_iter().map(TicketTitleError::from).collect(),
+            ))
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
 pub enum TicketTitleError {
     #[error("The title cannot be empty")]
     Empty,
-    #[error("The title cannot be longer than 50 bytes")]
-    TooLong,
+    #[error("The title cannot be only whitespace")]
+    Blank,
+    #[error("The title cannot be longer than {max} characters, but it has {length}")]
+    TooLong { max: usize, length: usize },
+    #[error("The title cannot contain control characters, but has {character:?} at character {position}")]
+    ControlCharacter { position: usize, character: char },
+    #[error("The title cannot contain bidirectional overrides, but has {character:?} at character {position}")]
+    BidiOverride { position: usize, character: char },
+    #[error("The title must start with one of {}", .expected.join(", "))]
+    MissingPrefix { expected: Vec<String> },
+    #[error("The title cannot contain {word:?}, but has it at character {position}")]
+    BannedWord { word: String, position: usize },
+}
+
+impl From<Problem> for TicketTitleError {
+    fn from(problem: Problem) -> Self {
+        match problem {
+            Problem::Empty => Self::Empty,
+            Problem::Blank => Self::Blank,
+            Problem::TooLong { max, length } => Self::TooLong { max, length },
+            Problem::Control {
+                position,
+                character,
+            } => Self::ControlCharacter {
+                position,
+                character,
+            },
+            Problem::BidiOverride {
+                position,
+                character,
+            } => Self::BidiOverride {
+                position,
+                character,
+            },
+            Problem::MissingPrefix { expected } => Self::MissingPrefix { expected },
+            Problem::BannedWord { word, position } => Self::BannedWord { word, position },
+        }
+    }
 }
 
 impl TryFrom<String> for TicketTitle {
     type Error = TicketTitleError;
 
     fn try_from(value: String) -> Result<Self, Self::Error> {
-        validate(&value)?;
-        Ok(Self(value))
+        Self::try_from(value.as_str())
     }
 }
 
@@ -25,24 +88,14 @@ impl TryFrom<&str> for TicketTitle {
     type Error = TicketTitleError;
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
-        validate(value)?;
-        Ok(Self(value.to_string()))
-    }
-}
-
-fn validate(title: &str) -> Result<(), TicketTitleError> {
-    if title.is_empty() {
-        Err(TicketTitleError::Empty)
-    } else if title.len() > 50 {
-        Err(TicketTitleError::TooLong)
-    } else {
-        Ok(())
+        Self::validate_with(value, &Policy::default()).map_err(Violations::first)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::FieldPolicy;
     use common::{overly_long_title, valid_title};
     use std::convert::TryFrom;
 
@@ -62,7 +115,17 @@ mod tests {
     #[test]
     fn test_try_from_long_string() {
         let err = TicketTitle::try_from(overly_long_title()).unwrap_err();
-        assert_eq!(err.to_string(), "The title cannot be longer than 50 bytes");
+        assert_eq!(
+            err.to_string(),
+            "The title cannot be longer than 50 characters, but it has 84"
+        );
+    }
+
+    #[test]
+    fn test_deserialize_validates() {
+        let json = serde_json::to_string(&overly_long_title()).unwrap();
+        assert!(serde_json::from_str::<TicketTitle>(&json).is_err());
+        assert!(serde_json::from_str::<TicketTitle>("\"\"").is_err());
     }
 
     #[test]
@@ -70,4 +133,145 @@ mod tests {
         let title = TicketTitle::try_from("A title").unwrap();
         assert_eq!(title.0, "A title");
     }
+
+    #[test]
+    fn test_counts_graphemes_not_bytes() {
+        let japanese = "日本語のタイトル".repeat(6);
+        assert_eq!(
+            TicketTitle::try_from(japanese.as_str()).unwrap().0,
+            japanese
+        );
+
+        let family = "👩‍👩‍👧".repeat(50);
+        assert!(TicketTitle::try_from(family.as_str()).is_ok());
+        let err = TicketTitle::try_from(format!("{}!", family)).unwrap_err();
+        assert_eq!(
+            err,
+            TicketTitleError::TooLong {
+                max: 50,
+                length: 51
+            }
+        );
+    }
+
+    fn policy() -> Policy {
+        serde_json::from_str(
+            r#"{"title": {
+                "max_graphemes": 20,
+                "required_prefixes": ["[BUG]", "[FEATURE]"],
+                "banned_words": ["ASAP"]
+            }}"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_policy() {
+        let title = TicketTitle::validate_with("[BUG] Crash on start", &policy()).unwrap();
+        assert_eq!(title.0, "[BUG] Crash on start");
+        // Banned words only match whole words.
+        assert!(TicketTitle::validate_with("[BUG] Wasap crash", &policy()).is_ok());
+    }
+
+    #[test]
+    fn test_policy_collects_every_violation() {
+        let err =
+            TicketTitle::validate_with("Fix it asap, it\u{7}s urgent", &policy()).unwrap_err();
+        assert_eq!(
+            err.0,
+            [
+                TicketTitleError::ControlCharacter {
+                    position: 16,
+                    character: '\u{7}'
+                },
+                TicketTitleError::TooLong {
+                    max: 20,
+                    length: 24
+                },
+                TicketTitleError::MissingPrefix {
+                    expected: vec!["[BUG]".into(), "[FEATURE]".into()]
+                },
+                TicketTitleError::BannedWord {
+                    word: "asap".into(),
+                    position: 8
+                },
+            ]
+        );
+        assert!(err
+            .to_string()
+            .contains("; The title must start with one of [BUG], [FEATURE]; "));
+    }
+
+    #[test]
+    fn test_policy_rejects_unknown_settings() {
+        assert!(serde_json::from_str::<Policy>(r#"{"title": {"max_bytes": 5}}"#).is_err());
+    }
+
+    #[test]
+    fn test_policy_cant_raise_the_limit() {
+        let err = serde_json::from_str::<Policy>(r#"{"title": {"max_graphemes": 80}}"#)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("The title limit can't be raised above 50 characters"),
+            "{}",
+            err
+        );
+
+        // Built by hand, it's capped, so whatever it accepts loads again.
+        let policy = Policy {
+            title: FieldPolicy {
+                max_graphemes: Some(80),
+                ..FieldPolicy::default()
+            },
+            ..Policy::default()
+        };
+        let long = "a".repeat(70);
+        assert_eq!(
+            TicketTitle::validate_with(&long, &policy)
+                .unwrap_err()
+                .first(),
+            TicketTitleError::TooLong {
+                max: 50,
+                length: 70
+            }
+        );
+        let title = TicketTitle::validate_with(&"a".repeat(50), &policy).unwrap();
+        let json = serde_json::to_string(&title).unwrap();
+        assert_eq!(serde_json::from_str::<TicketTitle>(&json).unwrap(), title);
+    }
+
+    #[test]
+    fn test_normalizes_and_trims() {
+        // `e` followed by a combining acute accent becomes a single `é`.
+        let title = TicketTitle::try_from("  Cafe\u{301} menu\t").unwrap();
+        assert_eq!(title.0, "Caf\u{e9} menu");
+    }
+
+    #[test]
+    fn test_rejects_blank_control_and_bidi() {
+        assert_eq!(
+            TicketTitle::try_from(" \t ").unwrap_err(),
+            TicketTitleError::Blank
+        );
+        assert_eq!(
+            TicketTitle::try_from("Two\nlines").unwrap_err(),
+            TicketTitleError::ControlCharacter {
+                position: 4,
+                character: '\n'
+            }
+        );
+        let err = TicketTitle::try_from("Bell \u{7}").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "The title cannot contain control characters, but has '\\u{7}' at character 6"
+        );
+        assert_eq!(
+            TicketTitle::try_from("abc\u{202E}fed").unwrap_err(),
+            TicketTitleError::BidiOverride {
+                position: 4,
+                character: '\u{202E}'
+            }
+        );
+    }
 }
@@ -0,0 +1,110 @@
+// Invariants of the fields over arbitrary input, rather than the handful of
+// fixed strings in `helpers/common`.
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+    use ticket_fields::{
+        MarkdownDescription, TicketDescription, TicketDescriptionError, TicketTitle,
+        TicketTitleError,
+    };
+    use unicode_segmentation::UnicodeSegmentation;
+
+    /// Mostly printable text, with the characters the rules care about
+    /// thrown in: whitespace, line endings, controls, bidi overrides and
+    /// combining marks.
+    fn text() -> impl Strategy<Value = String> {
+        let character = prop_oneof![
+            8 => any::<char>(),
+            4 => prop::char::range('a', 'z'),
+            2 => Just(' '),
+            1 => prop::sample::select(vec!['\n', '\r', '\t', '\u{0}', '\u{1b}', '\u{202e}', '\u{301}', '\u{a0}']),
+        ];
+        prop::collection::vec(character, 0..80).prop_map(|chars| chars.into_iter().collect())
+    }
+
+    proptest! {
+        #[test]
+        fn test_valid_titles_are_normalized(input in text()) {
+            let Ok(title) = TicketTitle::try_from(input.as_str()) else {
+                return Ok(());
+            };
+            prop_assert!(!title.0.is_empty());
+            prop_assert_eq!(title.0.trim(), title.0.as_str());
+            prop_assert!(title.0.graphemes(true).count() <= TicketTitle::MAX_GRAPHEMES);
+            prop_assert!(!title.0.chars().any(char::is_control));
+            // Validating again changes nothing.
+            prop_assert_eq!(TicketTitle::try_from(title.0.clone()), Ok(title.clone()));
+            let json = serde_json::to_string(&title).unwrap();
+            prop_assert_eq!(serde_json::from_str::<TicketTitle>(&json).unwrap(), title);
+        }
+
+        #[test]
+        fn test_title_errors_point_into_the_title(input in text()) {
+            let Err(err) = TicketTitle::try_from(input.as_str()) else {
+                return Ok(());
+            };
+            let length = input.trim().graphemes(true).count();
+            match err {
+                TicketTitleError::Empty => prop_assert!(input.is_empty()),
+                TicketTitleError::Blank => prop_assert!(input.trim().is_empty()),
+                TicketTitleError::ControlCharacter { position, character }
+                | TicketTitleError::BidiOverride { position, character } => {
+                    prop_assert!((1..=length).contains(&position));
+                    prop_assert!(input.contains(character));
+                }
+                TicketTitleError::TooLong { max, length } => prop_assert!(length > max),
+                err => prop_assert!(false, "no policy, but got {:?}", err),
+            }
+        }
+
+        #[test]
+        fn test_titles_of_plain_words_are_kept(input in "[A-Za-z0-9]{1,15}( [A-Za-z0-9]{1,15}){0,2}") {
+            prop_assert_eq!(TicketTitle::try_from(input.as_str()).unwrap().0, input);
+        }
+
+        #[test]
+        fn test_valid_descriptions_are_normalized(input in text()) {
+            let Ok(description) = TicketDescription::try_from(input.as_str()) else {
+                return Ok(());
+            };
+            prop_assert!(!description.0.is_empty());
+            prop_assert!(!description.0.contains('\r'));
+            prop_assert_eq!(
+                TicketDescription::try_from(description.0.clone()),
+                Ok(description.clone())
+            );
+            // Windows line endings make no difference.
+            if !input.contains('\r') {
+                prop_assert_eq!(
+                    TicketDescription::try_from(input.replace('\n', "\r\n")),
+                    Ok(description.clone())
+                );
+            }
+            let markdown = MarkdownDescription::from(description.clone());
+            prop_assert_eq!(markdown.as_str(), description.0.as_str());
+        }
+
+        #[test]
+        fn test_descriptions_over_the_limit_are_rejected(word in "[a-z]{1,9}", extra in 1..100usize) {
+            let input = format!("{} ", word).repeat(TicketDescription::MAX_GRAPHEMES) + &"x".repeat(extra);
+            let is_too_long = matches!(
+                TicketDescription::try_from(input),
+                Err(TicketDescriptionError::TooLong { max: TicketDescription::MAX_GRAPHEMES, .. })
+            );
+            prop_assert!(is_too_long);
+        }
+
+        #[test]
+        fn test_markdown_round_trips(input in text()) {
+            let Ok(markdown) = MarkdownDescription::try_from(input.as_str()) else {
+                return Ok(());
+            };
+            prop_assert_eq!(MarkdownDescription::try_from(markdown.as_str()), Ok(markdown.clone()));
+            let json = serde_json::to_string(&markdown).unwrap();
+            prop_assert_eq!(serde_json::from_str::<MarkdownDescription>(&json).unwrap(), markdown.clone());
+            // Rendering never fails, whatever the source.
+            markdown.to_html();
+            markdown.to_plain_text();
+        }
+    }
+}